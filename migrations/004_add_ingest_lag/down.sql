@@ -0,0 +1,5 @@
+alter table if exists weather_measurements
+    drop column if exists ingest_lag_secs;
+
+alter table if exists climate_measurements
+    drop column if exists ingest_lag_secs;
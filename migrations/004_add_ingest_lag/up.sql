@@ -0,0 +1,6 @@
+-- Seconds between a realtime reading's timestamp and the moment it was written (optional observability metric)
+alter table if exists climate_measurements
+    add column if not exists ingest_lag_secs double precision;
+
+alter table if exists weather_measurements
+    add column if not exists ingest_lag_secs double precision;
@@ -0,0 +1,11 @@
+-- Heartbeats of running collector processes, used to spot accidental concurrent deployments
+create table if not exists collector_instances (
+    id              bigserial primary key,
+    hostname        text not null,
+    pid             integer not null,
+    started_at      timestamptz not null,
+    last_seen       timestamptz not null
+);
+
+create unique index if not exists collector_instances_identity_uq
+    on collector_instances (hostname, pid, started_at);
@@ -0,0 +1 @@
+drop table if exists zone_type_history;
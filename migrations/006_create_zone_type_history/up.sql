@@ -0,0 +1,11 @@
+-- Zone type changes observed during reference sync; the type decides which columns are meaningful for a zone
+create table if not exists zone_type_history (
+    id              bigserial primary key,
+    zone_id         bigint not null references zones(id) on delete cascade,
+    changed_at      timestamptz not null default now(),
+    previous_type   text,
+    new_type        text
+);
+
+create index if not exists zone_type_history_zone_time_idx
+    on zone_type_history (zone_id, changed_at desc);
@@ -0,0 +1,5 @@
+alter table if exists homes
+    drop column if exists incident_detection_enabled;
+
+alter table if exists homes
+    drop column if exists away_radius_m;
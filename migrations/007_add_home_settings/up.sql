@@ -0,0 +1,6 @@
+-- Home-level settings users occasionally change; changes are also emitted as events
+alter table if exists homes
+    add column if not exists away_radius_m double precision;
+
+alter table if exists homes
+    add column if not exists incident_detection_enabled boolean;
@@ -0,0 +1,2 @@
+alter table if exists climate_measurements
+    drop column if exists inside_temp_precision_c;
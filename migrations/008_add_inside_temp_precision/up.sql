@@ -0,0 +1,3 @@
+-- Reporting step of the inside temperature (e.g. 0.1 means 20.0 is 20.0 +/- 0.05); only realtime readings carry it
+alter table if exists climate_measurements
+    add column if not exists inside_temp_precision_c double precision;
@@ -0,0 +1,2 @@
+alter table if exists zones
+    drop column if exists capabilities;
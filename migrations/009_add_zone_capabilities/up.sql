@@ -0,0 +1,3 @@
+-- Last seen zone capabilities (setpoint ranges, AC modes); only populated when capability tracking is enabled
+alter table if exists zones
+    add column if not exists capabilities jsonb;
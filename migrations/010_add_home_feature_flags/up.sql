@@ -0,0 +1,3 @@
+-- Small boolean home toggles (christmas mode, auto-assist reminders, ...) kept together instead of a column each
+alter table if exists homes
+    add column if not exists feature_flags jsonb;
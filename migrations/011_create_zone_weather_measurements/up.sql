@@ -0,0 +1,19 @@
+-- Home weather copied onto each zone (WEATHER_PER_ZONE) so zone-level dashboards can join on zone_id alone
+create table if not exists zone_weather_measurements (
+    id                      bigserial not null,
+    time                    timestamptz not null,
+    home_id                 bigint not null references homes(id) on delete cascade,
+    zone_id                 bigint not null references zones(id) on delete cascade,
+    source                  text not null check (source in ('realtime','historical','derived')),
+    outside_temp_c          double precision,
+    solar_intensity_pct     double precision,
+    weather_state           text,
+    primary key (id, time)
+);
+
+create unique index if not exists zone_weather_measurements_dedupe_uq
+    on zone_weather_measurements (zone_id, time, source);
+create index if not exists zone_weather_measurements_zone_time_idx
+    on zone_weather_measurements (zone_id, time desc);
+
+select create_hypertable('zone_weather_measurements', 'time', if_not_exists => true, chunk_time_interval => interval '7 days');
@@ -0,0 +1,7 @@
+-- Last day each zone's historical backfill fully processed, so restarts resume after it instead of rescanning
+create table if not exists backfill_progress (
+    zone_id                 bigint primary key references zones(id) on delete cascade,
+    home_id                 bigint not null references homes(id) on delete cascade,
+    last_completed_day      date not null,
+    updated_at              timestamptz not null default now()
+);
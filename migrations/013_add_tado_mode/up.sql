@@ -0,0 +1,3 @@
+-- Home/Away mode the zone was in when the reading was taken (HOME or AWAY), for segmenting data by occupancy
+alter table if exists climate_measurements
+    add column if not exists tado_mode text;
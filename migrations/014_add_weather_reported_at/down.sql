@@ -0,0 +1,2 @@
+alter table if exists weather_measurements
+    drop column if exists reported_at;
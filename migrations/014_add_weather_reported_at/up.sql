@@ -0,0 +1,3 @@
+-- Timestamp Tado attached to the reading; NULL when the API sent none and `time` fell back to the collection time
+alter table if exists weather_measurements
+    add column if not exists reported_at timestamptz;
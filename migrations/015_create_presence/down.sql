@@ -0,0 +1,2 @@
+drop table if exists presence_measurements;
+drop table if exists mobile_devices;
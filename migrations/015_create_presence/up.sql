@@ -0,0 +1,33 @@
+-- Mobile devices (phones) of the home's users, as reported by /homes/{id}/mobileDevices
+create table if not exists mobile_devices (
+    id                      bigserial primary key,
+    home_id                 bigint not null references homes(id) on delete cascade,
+    tado_mobile_device_id   bigint not null,
+    name                    text,
+    platform                text,
+    model                   text,
+    geo_tracking_enabled    boolean,
+    created_at              timestamptz not null default now(),
+    updated_at              timestamptz not null default now()
+);
+
+create unique index if not exists mobile_devices_home_device_uq on mobile_devices (home_id, tado_mobile_device_id);
+
+-- Geofencing state of each geo-tracked mobile device over time
+create table if not exists presence_measurements (
+    id                                  bigserial not null,
+    time                                timestamptz not null,
+    home_id                             bigint not null references homes(id) on delete cascade,
+    mobile_device_id                    bigint not null references mobile_devices(id) on delete cascade,
+    at_home                             boolean,
+    stale                               boolean,
+    relative_distance_from_home_fence   double precision,
+    primary key (id, time)
+);
+
+create unique index if not exists presence_measurements_dedupe_uq
+    on presence_measurements (mobile_device_id, time);
+create index if not exists presence_measurements_home_time_idx
+    on presence_measurements (home_id, time desc);
+
+select create_hypertable('presence_measurements', 'time', if_not_exists => true, chunk_time_interval => interval '7 days');
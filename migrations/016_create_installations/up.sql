@@ -0,0 +1,13 @@
+-- Last seen hardware installations per home; only populated when installation tracking is enabled
+create table if not exists installations (
+    id                      bigserial primary key,
+    home_id                 bigint not null references homes(id) on delete cascade,
+    tado_installation_id    bigint not null,
+    installation_type       text,
+    state                   text,
+    revision                bigint,
+    created_at              timestamptz not null default now(),
+    updated_at              timestamptz not null default now()
+);
+
+create unique index if not exists installations_home_installation_uq on installations (home_id, tado_installation_id);
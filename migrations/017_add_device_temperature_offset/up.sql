@@ -0,0 +1,3 @@
+-- Calibration offset configured on temperature-measuring devices, in degrees Celsius
+alter table if exists devices
+    add column if not exists temperature_offset_c double precision;
@@ -0,0 +1 @@
+drop table if exists flow_temperature_optimization;
@@ -0,0 +1,11 @@
+-- Latest flow temperature optimization settings per home (heat pumps and OpenTherm boilers)
+create table if not exists flow_temperature_optimization (
+    home_id                             bigint primary key references homes(id) on delete cascade,
+    max_flow_temperature                bigint,
+    auto_adaptation_enabled             boolean,
+    auto_adaptation_max_flow_temperature bigint,
+    max_flow_temperature_min            bigint,
+    max_flow_temperature_max            bigint,
+    open_therm_device_serial_number     text,
+    updated_at                          timestamptz not null default now()
+);
@@ -0,0 +1,2 @@
+alter table if exists homes
+    drop column if exists incident_detection_supported;
@@ -0,0 +1,3 @@
+-- Whether the home can use incident detection at all, next to whether it is enabled
+alter table if exists homes
+    add column if not exists incident_detection_supported boolean;
@@ -0,0 +1 @@
+drop table if exists air_comfort_measurements;
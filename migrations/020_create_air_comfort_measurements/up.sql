@@ -0,0 +1,18 @@
+-- Per-zone comfort levels from /homes/{id}/airComfort, with the home's air freshness at the time
+create table if not exists air_comfort_measurements (
+    id                  bigserial not null,
+    time                timestamptz not null,
+    home_id             bigint not null references homes(id) on delete cascade,
+    zone_id             bigint not null references zones(id) on delete cascade,
+    temperature_level   text,
+    humidity_level      text,
+    freshness           text,
+    primary key (id, time)
+);
+
+create unique index if not exists air_comfort_measurements_dedupe_uq
+    on air_comfort_measurements (zone_id, time);
+create index if not exists air_comfort_measurements_home_time_idx
+    on air_comfort_measurements (home_id, time desc);
+
+select create_hypertable('air_comfort_measurements', 'time', if_not_exists => true, chunk_time_interval => interval '7 days');
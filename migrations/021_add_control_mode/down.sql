@@ -0,0 +1,2 @@
+alter table if exists climate_measurements
+    drop column if exists control_mode;
@@ -0,0 +1,3 @@
+-- Why the zone's setting was active (schedule, manual or away), from day report stripes; historical rows only
+alter table if exists climate_measurements
+    add column if not exists control_mode text;
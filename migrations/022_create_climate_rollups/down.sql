@@ -0,0 +1,2 @@
+drop materialized view if exists climate_measurements_daily;
+drop materialized view if exists climate_measurements_hourly;
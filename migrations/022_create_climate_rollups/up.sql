@@ -0,0 +1,56 @@
+-- Hourly and daily climate rollups as TimescaleDB continuous aggregates, so long dashboard ranges read one row
+-- per zone and bucket instead of every 15-minute reading. Only zone-level rows (no device) are rolled up, with
+-- realtime and historical readings of a bucket averaged together.
+-- The refresh policies have no start offset: backfilled history invalidates old buckets, and those must be
+-- re-materialized too. Refreshes only recompute invalidated buckets, so this stays cheap after the first run.
+create materialized view if not exists climate_measurements_hourly
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select time_bucket(interval '1 hour', time) as bucket,
+       home_id,
+       zone_id,
+       avg(inside_temp_c)       as avg_inside_temp_c,
+       min(inside_temp_c)       as min_inside_temp_c,
+       max(inside_temp_c)       as max_inside_temp_c,
+       avg(humidity_pct)        as avg_humidity_pct,
+       min(humidity_pct)        as min_humidity_pct,
+       max(humidity_pct)        as max_humidity_pct,
+       avg(setpoint_temp_c)     as avg_setpoint_temp_c,
+       min(setpoint_temp_c)     as min_setpoint_temp_c,
+       max(setpoint_temp_c)     as max_setpoint_temp_c,
+       avg(heating_power_pct)   as avg_heating_power_pct
+from climate_measurements
+where zone_id is not null and device_id is null
+group by bucket, home_id, zone_id
+with no data;
+
+select add_continuous_aggregate_policy('climate_measurements_hourly',
+    start_offset => null,
+    end_offset => interval '1 hour',
+    schedule_interval => interval '30 minutes',
+    if_not_exists => true);
+
+create materialized view if not exists climate_measurements_daily
+with (timescaledb.continuous, timescaledb.materialized_only = false) as
+select time_bucket(interval '1 day', time) as bucket,
+       home_id,
+       zone_id,
+       avg(inside_temp_c)       as avg_inside_temp_c,
+       min(inside_temp_c)       as min_inside_temp_c,
+       max(inside_temp_c)       as max_inside_temp_c,
+       avg(humidity_pct)        as avg_humidity_pct,
+       min(humidity_pct)        as min_humidity_pct,
+       max(humidity_pct)        as max_humidity_pct,
+       avg(setpoint_temp_c)     as avg_setpoint_temp_c,
+       min(setpoint_temp_c)     as min_setpoint_temp_c,
+       max(setpoint_temp_c)     as max_setpoint_temp_c,
+       avg(heating_power_pct)   as avg_heating_power_pct
+from climate_measurements
+where zone_id is not null and device_id is null
+group by bucket, home_id, zone_id
+with no data;
+
+select add_continuous_aggregate_policy('climate_measurements_daily',
+    start_offset => null,
+    end_offset => interval '1 day',
+    schedule_interval => interval '6 hours',
+    if_not_exists => true);
@@ -0,0 +1,10 @@
+select tado_set_compression_policies(null, null, null);
+drop function if exists tado_set_compression_policies(interval, interval, interval);
+
+select decompress_chunk(c, if_compressed => true) from show_chunks('climate_measurements') c;
+select decompress_chunk(c, if_compressed => true) from show_chunks('weather_measurements') c;
+select decompress_chunk(c, if_compressed => true) from show_chunks('events') c;
+
+alter table climate_measurements set (timescaledb.compress = false);
+alter table weather_measurements set (timescaledb.compress = false);
+alter table events set (timescaledb.compress = false);
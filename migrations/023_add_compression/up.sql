@@ -0,0 +1,52 @@
+-- Native compression for the measurement and event hypertables. Rows are segmented per home (and zone) and kept
+-- in time order inside a segment; `id` is part of the primary key, so it has to be part of the ordering too.
+-- Every column of a table's dedupe unique index is in its segmentby or orderby, so the on-conflict upserts of
+-- backfill and realtime can still check uniqueness against compressed chunks.
+-- Inserts, upserts and deletes into compressed chunks (backfill, retention) need TimescaleDB 2.11 or newer.
+alter table climate_measurements set (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'home_id, zone_id, source, device_id',
+    timescaledb.compress_orderby = 'time desc, id'
+);
+
+alter table weather_measurements set (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'home_id',
+    timescaledb.compress_orderby = 'time desc, source, id'
+);
+
+alter table events set (
+    timescaledb.compress,
+    timescaledb.compress_segmentby = 'home_id, zone_id',
+    timescaledb.compress_orderby = 'time desc, id'
+);
+
+-- (Re)sets how old a chunk must be before it is compressed, per hypertable; null removes that table's policy.
+-- Call it again to change the defaults, e.g. `select tado_set_compression_policies(interval '30 days');`
+create or replace function tado_set_compression_policies(
+    climate_after interval default interval '7 days',
+    weather_after interval default interval '7 days',
+    events_after  interval default interval '7 days'
+) returns void
+language plpgsql
+as $$
+declare
+    target record;
+begin
+    for target in
+        select *
+        from (values
+            ('climate_measurements'::regclass, climate_after),
+            ('weather_measurements'::regclass, weather_after),
+            ('events'::regclass, events_after)
+        ) as t(hypertable, compress_after)
+    loop
+        perform remove_compression_policy(target.hypertable, if_exists => true);
+        if target.compress_after is not null then
+            perform add_compression_policy(target.hypertable, target.compress_after);
+        end if;
+    end loop;
+end;
+$$;
+
+select tado_set_compression_policies();
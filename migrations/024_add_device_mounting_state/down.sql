@@ -0,0 +1,2 @@
+alter table if exists devices
+    drop column if exists mounting_state;
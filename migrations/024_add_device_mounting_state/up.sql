@@ -0,0 +1,3 @@
+-- Mounting state reported for valves and thermostats (e.g. COMPLETED, CALIBRATION_ERROR)
+alter table if exists devices
+    add column if not exists mounting_state text;
@@ -7,35 +7,75 @@
 //!   - Skips GET endpoints that return a single entity also available via a list endpoint
 //!   - Skips all endpoints under invitations
 //!
+//! Transport
+//! - `ureq` only speaks HTTP/1.1, so there is no HTTP version to pin.
+//! - Without `TADO_MIN_TLS` the agent offers TLS 1.2 and 1.3 and takes whatever the server picks;
+//!   `1.3` drops every TLS 1.2 cipher suite from the rustls provider so 1.2 can no longer be negotiated.
+//! - `TADO_DANGER_ACCEPT_INVALID_CERTS` turns off certificate verification for local mock servers and
+//!   logs an error-level warning every time such an agent is built.
+//!
+//! - Every request and every JSON response byte read is counted in `TrafficCounters`; `run` logs the totals.
+//! - `TADO_GLOBAL_RPS` spaces out every request of a client, token refreshes included; per-caller pacing such as
+//!   the backfill's `BACKFILL_REQUESTS_PER_SECOND` comes on top of it.
+//!
 //! Authentication
 //! - Uses a browser-derived OAuth2 refresh token and rotates it in-memory.
+//! - OAuth state sits behind a `Mutex`, so one client can be shared by the per-home collection threads.
 //! - Mimics browser headers for both token refresh and API requests.
 
+use crate::config::{TlsVersion, is_plausible_token};
 use crate::models::tado::*;
-use chrono::NaiveDate;
+use crate::services::metrics;
+use chrono::{DateTime, NaiveDate, Utc};
 use log::{debug, error, info, warn};
 use serde::de::DeserializeOwned;
-use std::cell::RefCell;
+use std::cell::Cell;
 use std::num::NonZeroU32;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime};
 
 const BASE_URL: &str = "https://my.tado.com/api/v2";
-// Matches the browser refresh endpoint observed in the app
-const OAUTH_TOKEN_URL: &str = "https://login.tado.com/oauth2/token?ngsw-bypass=true";
-// Public browser client id used by app.tado.com
-const OAUTH_CLIENT_ID: &str = "af44f89e-ae86-4ebe-905f-6bf759cf6473";
+// Matches the browser refresh endpoint observed in the app; `TADO_OAUTH_TOKEN_URL` overrides it
+pub const OAUTH_TOKEN_URL: &str = "https://login.tado.com/oauth2/token?ngsw-bypass=true";
+// Public browser client id used by app.tado.com; `TADO_OAUTH_CLIENT_ID` overrides it
+pub const OAUTH_CLIENT_ID: &str = "af44f89e-ae86-4ebe-905f-6bf759cf6473";
 
 const JSON_BODY_MAX: u64 = 10 * 1024 * 1024;
+// Longest total time one request may spend honoring `Retry-After` before the 429 is surfaced
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(300);
+// How long before expiry the background refresher renews the access token; foreground requests only
+// refresh in their last 30 seconds, so this one normally wins
+const BACKGROUND_REFRESH_LEAD: Duration = Duration::from_secs(60);
+const BACKGROUND_REFRESH_RETRY: Duration = Duration::from_secs(30);
+// How often a sleeping background refresher checks whether it should stop
+const BACKGROUND_REFRESH_POLL: Duration = Duration::from_millis(200);
 type HttpResponse = http::Response<ureq::Body>;
 
 #[derive(Debug)]
 pub enum TadoClientError {
     MissingAuth,
     Transport(String),
-    Http { status: u16, message: String },
+    Http {
+        status: u16,
+        message: String,
+    },
+    /// HTTP 429; `retry_after` is the server's `Retry-After`, if it sent a usable one.
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    /// HTTP 503, e.g. during Tado maintenance; `retry_after` as for `RateLimited`.
+    Unavailable {
+        retry_after: Option<Duration>,
+    },
     Json(serde_json::Error),
     Auth(String),
+    /// The token endpoint answered `invalid_grant`: the refresh token is expired or revoked and only a new browser
+    /// login helps. `description` is Tado's `error_description`, if any.
+    RefreshTokenRejected {
+        description: Option<String>,
+    },
 }
 
 impl core::fmt::Display for TadoClientError {
@@ -44,8 +84,26 @@ impl core::fmt::Display for TadoClientError {
             TadoClientError::MissingAuth => write!(f, "missing bearer token for authenticated endpoint"),
             TadoClientError::Transport(s) => write!(f, "transport error: {}", s),
             TadoClientError::Http { status, message } => write!(f, "http {}: {}", status, message),
+            TadoClientError::RateLimited {
+                retry_after: Some(wait),
+            } => {
+                write!(f, "http 429: rate limited (retry after {}s)", wait.as_secs())
+            }
+            TadoClientError::RateLimited { retry_after: None } => write!(f, "http 429: rate limited"),
+            TadoClientError::Unavailable {
+                retry_after: Some(wait),
+            } => {
+                write!(f, "http 503: service unavailable (retry after {}s)", wait.as_secs())
+            }
+            TadoClientError::Unavailable { retry_after: None } => write!(f, "http 503: service unavailable"),
             TadoClientError::Json(e) => write!(f, "json error: {}", e),
             TadoClientError::Auth(e) => write!(f, "auth error: {}", e),
+            TadoClientError::RefreshTokenRejected {
+                description: Some(description),
+            } => write!(f, "refresh token rejected (invalid_grant): {}", description),
+            TadoClientError::RefreshTokenRejected { description: None } => {
+                write!(f, "refresh token rejected (invalid_grant)")
+            }
         }
     }
 }
@@ -58,6 +116,100 @@ impl From<serde_json::Error> for TadoClientError {
     }
 }
 
+/// Exponential backoff between retried requests: the nth retry waits `base * 2^(n-1)`, capped at `max`,
+/// plus up to 10% random jitter so concurrent retries do not line up.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryBackoff {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl RetryBackoff {
+    /// Delay before retry number `retry` (1-based), without jitter.
+    fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.checked_pow(retry.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.base.checked_mul(factor).unwrap_or(self.max).min(self.max)
+    }
+
+    fn jittered_delay(&self, retry: u32) -> Duration {
+        let delay = self.delay(retry);
+        delay + delay.mul_f64(rand::random::<f64>() * 0.1)
+    }
+}
+
+/// Process-wide network footprint: requests sent and JSON response bytes read, shared via `Arc`.
+#[derive(Debug, Default)]
+pub struct TrafficCounters {
+    requests: AtomicU64,
+    response_bytes: AtomicU64,
+}
+
+impl TrafficCounters {
+    fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_response_bytes(&self, bytes: usize) {
+        self.response_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn response_bytes(&self) -> u64 {
+        self.response_bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// Source of the ids `get_json` gives each API call, so the request log and what its caller wrote can be matched.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static LAST_REQUEST_ID: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Runs `f` and also returns the id of the last Tado API request it sent, so callers can log what they wrote
+/// against it. `None` when `f` answered without the API, e.g. from a cached day report.
+pub fn with_request_id<T>(f: impl FnOnce() -> T) -> (T, Option<u64>) {
+    LAST_REQUEST_ID.set(None);
+    let value = f();
+    (value, LAST_REQUEST_ID.take())
+}
+
+/// Token bucket holding a single token, refilled at `rps`: each request waits until its slot, so requests are
+/// never closer together than `1/rps`, whichever thread sends them. Slots are reserved under the lock and slept
+/// outside it, so waiting callers queue up in order without blocking one another's bookkeeping.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rps: NonZeroU32,
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(rps: NonZeroU32) -> Self {
+        RateLimiter {
+            rps,
+            interval: Duration::from_secs_f64(1.0 / rps.get() as f64),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn acquire(&self) {
+        let wait = {
+            let mut next_slot = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.interval;
+            slot - now
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 struct AccessToken {
     access_token: String,
@@ -68,14 +220,25 @@ struct AccessToken {
 struct OAuthState {
     token: Option<AccessToken>,
     refresh_token: String,
+    /// Modification time of the persistence file when this process last loaded or wrote it; a file unchanged
+    /// since then holds nothing newer than `refresh_token` (and may be a seed list or corrupt content that
+    /// config already replaced).
+    token_file_modified: Option<SystemTime>,
 }
 
 pub struct TadoClient {
     agent: ureq::Agent,
-    oauth: RefCell<OAuthState>,
+    base_url: String,
+    token_url: String,
+    client_id: String,
+    oauth: Mutex<OAuthState>,
     user_agent: String,
     refresh_token_path: PathBuf,
     max_server_error_retries: NonZeroU32,
+    retry_backoff: RetryBackoff,
+    traffic: Arc<TrafficCounters>,
+    /// `TADO_GLOBAL_RPS`; applies to every request, token refreshes included.
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl TadoClient {
@@ -98,58 +261,135 @@ impl TadoClient {
             ("Cache-Control", "no-cache".to_string()),
         ]
     }
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         initial_refresh_token: impl Into<String>,
         user_agent: impl Into<String>,
+        oauth_client_id: impl Into<String>,
+        oauth_token_url: impl Into<String>,
         refresh_token_path: impl Into<PathBuf>,
         max_server_error_retries: NonZeroU32,
+        retry_backoff: RetryBackoff,
+        transport: TransportOptions,
+        global_rps: Option<NonZeroU32>,
     ) -> Result<Self, TadoClientError> {
-        let agent = ureq::agent();
+        let agent = build_agent(transport)?;
+        let refresh_token_path = refresh_token_path.into();
+        let traffic = Arc::new(TrafficCounters::default());
+        metrics::register_traffic(Arc::clone(&traffic));
 
         let client = TadoClient {
             agent,
-            oauth: RefCell::new(OAuthState {
+            base_url: BASE_URL.to_string(),
+            token_url: oauth_token_url.into(),
+            client_id: oauth_client_id.into(),
+            oauth: Mutex::new(OAuthState {
                 token: None,
                 refresh_token: initial_refresh_token.into(),
+                token_file_modified: file_modified(&refresh_token_path),
             }),
             user_agent: user_agent.into(),
-            refresh_token_path: refresh_token_path.into(),
+            refresh_token_path,
             max_server_error_retries,
+            retry_backoff,
+            traffic,
+            rate_limiter: global_rps.map(RateLimiter::new),
         };
 
         // Fetch initial access token using the provided refresh token
-        let _ = client.get_bearer()?;
+        retry_transient_errors(
+            client.max_server_error_retries,
+            "POST /oauth2/token",
+            client.retry_backoff,
+            || client.get_bearer(),
+        )?;
         info!("Tado OAuth: initial access token acquired via refresh grant");
 
         Ok(client)
     }
 
-    fn url(path: &str) -> String {
+    /// Requests per second this client is limited to across all endpoints; `None` when unlimited.
+    pub fn effective_rps(&self) -> Option<NonZeroU32> {
+        self.rate_limiter.as_ref().map(|limiter| limiter.rps)
+    }
+
+    /// Waits for the global rate limiter, if any; called right before every request goes out.
+    fn throttle(&self) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.acquire();
+        }
+    }
+
+    /// Request and byte totals accumulated by this client since it was created.
+    pub fn traffic(&self) -> Arc<TrafficCounters> {
+        Arc::clone(&self.traffic)
+    }
+
+    fn url(&self, path: &str) -> String {
         if path.starts_with('/') {
-            format!("{}{}", BASE_URL, path)
+            format!("{}{}", self.base_url, path)
         } else {
-            format!("{}/{}", BASE_URL, path)
+            format!("{}/{}", self.base_url, path)
         }
     }
 
+    /// A panic mid-refresh leaves the previous token in place, which is still usable, so poisoning is ignored.
+    fn oauth_state(&self) -> MutexGuard<'_, OAuthState> {
+        self.oauth.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs one refresh grant. Maintenance responses (503) and transport errors come back as transient errors for
+    /// the caller's retry loop, which sleeps only after the OAuth lock is released. A rejected grant
+    /// (`invalid_grant` and other 4xx) is terminal.
     fn oauth_refresh_grant(&self, refresh: &str) -> Result<(AccessToken, Option<String>), TadoClientError> {
-        let _ = refresh; // never log refresh token
         info!("Tado OAuth: refreshing access token (browser flow)");
-        let mut req = self.agent.post(OAUTH_TOKEN_URL);
+        self.throttle();
+        self.traffic.record_request();
+        let mut req = self.agent.post(&self.token_url);
         for (k, v) in self.browser_headers() {
             req = req.header(k, &v);
         }
+        let started = Instant::now();
         let resp = req
             .header("Content-Type", "application/x-www-form-urlencoded")
             .config()
             .http_status_as_error(false)
             .build()
             .send_form([
-                ("client_id", OAUTH_CLIENT_ID),
+                ("client_id", self.client_id.as_str()),
                 ("grant_type", "refresh_token"),
                 ("refresh_token", refresh),
             ]);
-        Self::parse_token_response(resp)
+        metrics::record_request(
+            &self.token_url,
+            resp.as_ref().ok().map(|r| r.status().as_u16()),
+            started.elapsed(),
+        );
+        self.parse_token_response(resp)
+    }
+
+    /// Picks up a refresh token that another process sharing the persistence file (a `--once` run, say)
+    /// rotated since this one last refreshed; Tado only accepts the latest one. Only a file written after this
+    /// process loaded or wrote it counts, and only when it holds a single plausible token.
+    fn adopt_persisted_refresh_token(&self, state: &mut OAuthState) {
+        let modified = file_modified(&self.refresh_token_path);
+        if modified.is_none() || modified == state.token_file_modified {
+            return;
+        }
+        let Ok(persisted) = std::fs::read_to_string(&self.refresh_token_path) else {
+            return;
+        };
+        state.token_file_modified = modified;
+        let persisted = persisted.trim();
+        if !is_plausible_token(persisted) {
+            warn!(
+                "Tado OAuth: ignoring malformed refresh token written to {}",
+                self.refresh_token_path.display()
+            );
+        } else if persisted != state.refresh_token {
+            info!("Tado OAuth: using the refresh token rotated by another process");
+            state.refresh_token = persisted.to_string();
+        }
     }
 
     fn persist_refresh_token(&self, token: &str) {
@@ -166,7 +406,7 @@ impl TadoClient {
             return;
         }
 
-        if let Err(e) = std::fs::write(&self.refresh_token_path, token) {
+        if let Err(e) = write_atomically(&self.refresh_token_path, token) {
             warn!(
                 "Tado OAuth: failed to persist rotated refresh token to {}: {}",
                 self.refresh_token_path.display(),
@@ -181,6 +421,7 @@ impl TadoClient {
     }
 
     fn parse_token_response(
+        &self,
         resp: Result<HttpResponse, ureq::Error>,
     ) -> Result<(AccessToken, Option<String>), TadoClientError> {
         #[derive(serde::Deserialize)]
@@ -197,7 +438,7 @@ impl TadoClient {
                         access_token,
                         expires_in,
                         refresh_token,
-                    } = read_json_body::<R>(&mut r, OAUTH_TOKEN_URL)?;
+                    } = read_json_body::<R>(&mut r, &self.token_url, &self.traffic)?;
                     let expires_at = Instant::now() + Duration::from_secs(expires_in);
                     let tok = AccessToken {
                         access_token,
@@ -205,36 +446,108 @@ impl TadoClient {
                     };
                     debug!("Tado OAuth: token parsed; expires_in_secs ~{}", expires_in);
                     Ok((tok, refresh_token))
+                } else if r.status().as_u16() == 503 {
+                    // Maintenance, not a verdict on the refresh token; retried like API calls
+                    Err(TadoClientError::Unavailable {
+                        retry_after: r
+                            .headers()
+                            .get("retry-after")
+                            .and_then(|v| v.to_str().ok())
+                            .and_then(|v| parse_retry_after(v, Utc::now())),
+                    })
+                } else if r.status().is_server_error() {
+                    let status = r.status().as_u16();
+                    let message = read_body_text(&mut r);
+                    Err(TadoClientError::Http { status, message })
                 } else {
-                    let status = r.status();
+                    let status = r.status().as_u16();
                     let body = read_body_text(&mut r);
-                    Err(TadoClientError::Auth(format!("http {}: {}", status, body)))
+                    Err(token_error_from_body(status, &body))
                 }
             }
             Err(e) => Err(TadoClientError::Transport(e.to_string())),
         }
     }
 
+    /// Holding the lock across the refresh makes concurrent callers wait for one grant instead of racing
+    /// their own (which would also rotate the refresh token out from under each other). A failed grant returns
+    /// with the lock released, so callers back off without blocking each other.
     fn get_bearer(&self) -> Result<String, TadoClientError> {
-        let mut s = self.oauth.borrow_mut();
+        let mut s = self.oauth_state();
         let needs_refresh = match &s.token {
             None => true,
             Some(t) => Instant::now() + Duration::from_secs(30) >= t.expires_at,
         };
         if needs_refresh {
             info!("Tado OAuth: access token missing/expired; using refresh grant");
-            let (new_access, new_refresh) = self.oauth_refresh_grant(&s.refresh_token)?;
-            if let Some(r) = new_refresh {
-                s.refresh_token = r;
-                // Persist the rotated refresh token for future runs.
-                self.persist_refresh_token(&s.refresh_token);
-            }
-            s.token = Some(new_access);
+            self.refresh_access_token(&mut s)?;
         }
         Ok(s.token.as_ref().unwrap().access_token.clone())
     }
 
+    /// Replaces the access token via the refresh grant; callers hold the OAuth lock for the whole exchange.
+    fn refresh_access_token(&self, s: &mut OAuthState) -> Result<(), TadoClientError> {
+        self.adopt_persisted_refresh_token(s);
+        let (new_access, new_refresh) = self.oauth_refresh_grant(&s.refresh_token)?;
+        if let Some(r) = new_refresh {
+            s.refresh_token = r;
+            // Persist the rotated refresh token for future runs.
+            self.persist_refresh_token(&s.refresh_token);
+            s.token_file_modified = file_modified(&self.refresh_token_path);
+        }
+        s.token = Some(new_access);
+        Ok(())
+    }
+
+    /// Keeps the access token fresh from a dedicated thread: wakes `BACKGROUND_REFRESH_LEAD` before it expires and
+    /// refreshes it, so foreground requests rarely wait on a grant. A failed refresh is retried after
+    /// `BACKGROUND_REFRESH_RETRY`; `get_bearer` still refreshes on demand if it never succeeds. Returns once `stop`
+    /// is set.
+    pub fn refresh_in_background(&self, stop: &AtomicBool) {
+        let mut next_attempt = self.background_refresh_due();
+        while !stop.load(Ordering::SeqCst) {
+            let remaining = next_attempt.saturating_duration_since(Instant::now());
+            if !remaining.is_zero() {
+                std::thread::sleep(remaining.min(BACKGROUND_REFRESH_POLL));
+                continue;
+            }
+            {
+                let mut s = self.oauth_state();
+                // A foreground refresh may have happened while this thread slept
+                let due = s
+                    .token
+                    .as_ref()
+                    .is_none_or(|t| Instant::now() + BACKGROUND_REFRESH_LEAD >= t.expires_at);
+                if due {
+                    debug!("Tado OAuth: refreshing access token ahead of expiry");
+                    if let Err(e) = self.refresh_access_token(&mut s) {
+                        warn!(
+                            "Tado OAuth: background token refresh failed, retrying in {}s: {}",
+                            BACKGROUND_REFRESH_RETRY.as_secs(),
+                            e
+                        );
+                        next_attempt = Instant::now() + BACKGROUND_REFRESH_RETRY;
+                        continue;
+                    }
+                }
+            }
+            next_attempt = self.background_refresh_due();
+        }
+    }
+
+    fn background_refresh_due(&self) -> Instant {
+        match &self.oauth_state().token {
+            Some(t) => t
+                .expires_at
+                .checked_sub(BACKGROUND_REFRESH_LEAD)
+                .unwrap_or_else(Instant::now),
+            None => Instant::now(),
+        }
+    }
+
     fn call_get(&self, url: &str, query: &[(&str, String)], bearer: &str) -> Result<HttpResponse, ureq::Error> {
+        self.throttle();
+        self.traffic.record_request();
         let mut req = self.agent.get(url);
         for (k, v) in self.browser_headers() {
             req = req.header(k, &v);
@@ -243,29 +556,32 @@ impl TadoClient {
             req = req.query(k, v);
         }
         req = req.header("Authorization", &format!("Bearer {}", bearer));
-        req.config().http_status_as_error(false).build().call()
+        let started = Instant::now();
+        let res = req.config().http_status_as_error(false).build().call();
+        metrics::record_request(url, res.as_ref().ok().map(|r| r.status().as_u16()), started.elapsed());
+        res
     }
 
     fn retry_after_refresh<T: DeserializeOwned>(
         &self,
         url: &str,
         query: &[(&str, String)],
+        request: &str,
+        rejected_token: &str,
     ) -> Result<T, TadoClientError> {
         {
-            let mut s = self.oauth.borrow_mut();
-            let (new_access, new_refresh) = self.oauth_refresh_grant(&s.refresh_token)?;
-            if let Some(r) = new_refresh {
-                s.refresh_token = r;
-                // Persist the rotated refresh token for future runs.
-                self.persist_refresh_token(&s.refresh_token);
+            let mut s = self.oauth_state();
+            // Another thread may have replaced the rejected token while this one waited for the lock.
+            let already_refreshed = s.token.as_ref().is_some_and(|t| t.access_token != rejected_token);
+            if !already_refreshed {
+                self.refresh_access_token(&mut s)?;
             }
-            s.token = Some(new_access);
         }
         let token2 = self.get_bearer()?;
         // Log the retried request at info level so non-auth calls are visible
-        info!("Tado API GET {}{} [after refresh]", url, format_query_params(query));
+        info!("Tado API {} [after refresh]", request);
         match self.call_get(url, query, &token2) {
-            Ok(mut res2) if res2.status().is_success() => read_json_body::<T>(&mut res2, url),
+            Ok(mut res2) if res2.status().is_success() => read_json_body::<T>(&mut res2, url, &self.traffic),
             Ok(mut res2) => {
                 let status = res2.status().as_u16();
                 let msg = read_body_text(&mut res2);
@@ -276,46 +592,35 @@ impl TadoClient {
     }
 
     fn get_json<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T, TadoClientError> {
-        let url = Self::url(path);
+        let url = self.url(path);
         let query_suffix = format_query_params(query);
-        let mut retries_attempted: u32 = 0;
+        let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+        LAST_REQUEST_ID.set(Some(request_id));
+        let request = format!("request {} GET {}{}", request_id, path, query_suffix);
 
-        loop {
+        retry_transient_errors(self.max_server_error_retries, &request, self.retry_backoff, || {
             let token = self.get_bearer()?;
             // Log every non-auth endpoint call at info level
-            info!("Tado API GET {}{}", path, query_suffix);
+            info!("Tado API {}", request);
 
-            let result = match self.call_get(&url, query, &token) {
-                Ok(res) if res.status().as_u16() == 401 => self.retry_after_refresh::<T>(&url, query),
-                Ok(mut res) if res.status().is_success() => return read_json_body::<T>(&mut res, path),
+            match self.call_get(&url, query, &token) {
+                Ok(res) if res.status().as_u16() == 401 => self.retry_after_refresh::<T>(&url, query, &request, &token),
+                Ok(res) if res.status().as_u16() == 429 => Err(TadoClientError::RateLimited {
+                    retry_after: res
+                        .headers()
+                        .get("retry-after")
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| parse_retry_after(v, Utc::now())),
+                }),
+                Ok(mut res) if res.status().is_success() => read_json_body::<T>(&mut res, path, &self.traffic),
                 Ok(mut res) => {
                     let status = res.status().as_u16();
                     let msg = read_body_text(&mut res);
                     Err(TadoClientError::Http { status, message: msg })
                 }
                 Err(e) => Err(TadoClientError::Transport(e.to_string())),
-            };
-
-            match result {
-                Ok(value) => return Ok(value),
-                Err(TadoClientError::Http { status, ref message })
-                    if (500..=599).contains(&status) && retries_attempted < self.max_server_error_retries.get() =>
-                {
-                    retries_attempted += 1;
-                    warn!(
-                        "Tado API GET {}{} -> server error {} (attempt {} of {}), retrying",
-                        path,
-                        query_suffix,
-                        status,
-                        retries_attempted,
-                        self.max_server_error_retries.get()
-                    );
-                    debug!("Server error body: {}", message);
-                    continue;
-                }
-                Err(err) => return Err(err),
             }
-        }
+        })
     }
 
     pub fn get_me(&self) -> Result<User, TadoClientError> {
@@ -389,6 +694,10 @@ impl TadoClient {
         self.get_json(&format!("/homes/{}/deviceList", home_id.0), &[])
     }
 
+    pub fn get_mobile_devices(&self, home_id: HomeId) -> Result<Vec<MobileDevice>, TadoClientError> {
+        self.get_json(&format!("/homes/{}/mobileDevices", home_id.0), &[])
+    }
+
     pub fn get_installations(&self, home_id: HomeId) -> Result<Vec<Installation>, TadoClientError> {
         self.get_json(&format!("/homes/{}/installations", home_id.0), &[])
     }
@@ -411,6 +720,62 @@ impl TadoClient {
     }
 }
 
+/// Transport-level knobs for the agent, all driven by config.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransportOptions {
+    pub min_tls: Option<TlsVersion>,
+    /// Disables TLS certificate verification. Only for local testing against self-signed mock servers.
+    pub danger_accept_invalid_certs: bool,
+    /// Connect and whole-request timeout; `None` keeps ureq's defaults, which never time out a stalled read.
+    pub timeout: Option<Duration>,
+}
+
+const INSECURE_TLS_WARNING: &str = "TADO_DANGER_ACCEPT_INVALID_CERTS is enabled: TLS certificate verification is OFF. \
+     Any network attacker can read and alter Tado traffic, including OAuth tokens. Never use this in production.";
+
+/// Builds the shared agent, applying the optional HTTP/TLS settings from the config.
+fn build_agent(options: TransportOptions) -> Result<ureq::Agent, TadoClientError> {
+    // A timeout surfaces as `ureq::Error::Timeout`, i.e. a retryable `TadoClientError::Transport`.
+    let mut config = ureq::Agent::config_builder()
+        .timeout_connect(options.timeout)
+        .timeout_global(options.timeout);
+
+    if options.min_tls.is_some() || options.danger_accept_invalid_certs {
+        let mut tls = ureq::tls::TlsConfig::builder().provider(ureq::tls::TlsProvider::Rustls);
+        if options.danger_accept_invalid_certs {
+            // Logged at error level so it survives any sensible RUST_LOG filter.
+            error!("{}", INSECURE_TLS_WARNING);
+            tls = tls.disable_verification(true);
+        }
+        if let Some(min_tls) = options.min_tls {
+            tls = pin_min_tls(tls, min_tls)?;
+        }
+        config = config.tls_config(tls.build());
+    }
+
+    Ok(config.build().into())
+}
+
+fn pin_min_tls(
+    mut tls: ureq::tls::TlsConfigBuilder,
+    min_tls: TlsVersion,
+) -> Result<ureq::tls::TlsConfigBuilder, TadoClientError> {
+    if min_tls == TlsVersion::Tls13 {
+        let mut provider = rustls::crypto::ring::default_provider();
+        provider
+            .cipher_suites
+            .retain(|suite| matches!(suite, rustls::SupportedCipherSuite::Tls13(_)));
+        if provider.cipher_suites.is_empty() {
+            return Err(TadoClientError::Transport(
+                "TADO_MIN_TLS=1.3 requested but no TLS 1.3 cipher suites are available".to_string(),
+            ));
+        }
+        tls = tls.unversioned_rustls_crypto_provider(Arc::new(provider));
+    }
+    info!("Tado transport: minimum {} pinned", min_tls);
+    Ok(tls)
+}
+
 fn format_query_params(query: &[(&str, String)]) -> String {
     if query.is_empty() {
         "".to_string()
@@ -424,7 +789,11 @@ fn format_query_params(query: &[(&str, String)]) -> String {
     }
 }
 
-fn read_json_body<T: DeserializeOwned>(res: &mut HttpResponse, context: &str) -> Result<T, TadoClientError> {
+fn read_json_body<T: DeserializeOwned>(
+    res: &mut HttpResponse,
+    context: &str,
+    traffic: &TrafficCounters,
+) -> Result<T, TadoClientError> {
     // Read the (potentially compressed) body with a hard size limit, then deserialize.
     // On failure, log detailed error with precise JSON path and full body (except for sensitive endpoints).
     use std::io::Read as _;
@@ -434,6 +803,7 @@ fn read_json_body<T: DeserializeOwned>(res: &mut HttpResponse, context: &str) ->
     if let Err(e) = reader.read_to_end(&mut buf) {
         return Err(TadoClientError::Transport(format!("failed to read body: {}", e)));
     }
+    traffic.record_response_bytes(buf.len());
 
     // Use serde_path_to_error to capture the exact path where deserialization fails.
     let mut de = serde_json::Deserializer::from_slice(&buf);
@@ -478,3 +848,630 @@ fn read_body_text(res: &mut HttpResponse) -> String {
         .read_to_string()
         .unwrap_or_else(|_| String::from("<no body>"))
 }
+
+/// Runs `attempt` until it succeeds, fails with a non-retryable error, or `max_retries` retries are used up.
+///
+/// Transport errors and HTTP 5xx are retried after an exponentially growing `backoff`. HTTP 429 and 503 wait
+/// for `Retry-After` (or the backoff when absent), up to `MAX_RATE_LIMIT_WAIT` in total. Other 4xx responses are
+/// returned immediately (401 is already handled inside `attempt` by refreshing the token).
+fn retry_transient_errors<T>(
+    max_retries: NonZeroU32,
+    request: &str,
+    backoff: RetryBackoff,
+    mut attempt: impl FnMut() -> Result<T, TadoClientError>,
+) -> Result<T, TadoClientError> {
+    let mut retries_attempted: u32 = 0;
+    let mut rate_limit_waited = Duration::ZERO;
+    loop {
+        let err = match attempt() {
+            Err(err) if is_transient(&err) && retries_attempted < max_retries.get() => err,
+            result => return result,
+        };
+        retries_attempted += 1;
+
+        let delay = match &err {
+            TadoClientError::RateLimited {
+                retry_after: Some(wait),
+            }
+            | TadoClientError::Unavailable {
+                retry_after: Some(wait),
+            } => {
+                if rate_limit_waited + *wait > MAX_RATE_LIMIT_WAIT {
+                    let (status, reason) = match err {
+                        TadoClientError::RateLimited { .. } => (429, "rate limited"),
+                        _ => (503, "service unavailable"),
+                    };
+                    return Err(TadoClientError::Http {
+                        status,
+                        message: format!(
+                            "{}; Retry-After of {}s would exceed the {}s total wait cap",
+                            reason,
+                            wait.as_secs(),
+                            MAX_RATE_LIMIT_WAIT.as_secs()
+                        ),
+                    });
+                }
+                rate_limit_waited += *wait;
+                *wait
+            }
+            _ => backoff.jittered_delay(retries_attempted),
+        };
+        warn!(
+            "Tado API {} -> {} (attempt {} of {}), retrying",
+            request,
+            match &err {
+                TadoClientError::Http { status, .. } => format!("server error {}", status),
+                TadoClientError::RateLimited { .. } => "rate limited".to_string(),
+                TadoClientError::Unavailable { .. } => "service unavailable".to_string(),
+                _ => "transport error".to_string(),
+            },
+            retries_attempted,
+            max_retries.get()
+        );
+        debug!("Retried request error: {}", err);
+        debug!(
+            "Tado API {} backing off {} ms before retry {}",
+            request,
+            delay.as_millis(),
+            retries_attempted
+        );
+        std::thread::sleep(delay);
+    }
+}
+
+/// Classifies a rejected token request: `invalid_grant` becomes `RefreshTokenRejected`, anything else stays a
+/// generic `Auth` error carrying the status and body.
+fn token_error_from_body(status: u16, body: &str) -> TadoClientError {
+    #[derive(serde::Deserialize)]
+    struct OAuthError {
+        error: String,
+        #[serde(default)]
+        error_description: Option<String>,
+    }
+    match serde_json::from_str::<OAuthError>(body) {
+        Ok(e) if e.error == "invalid_grant" => TadoClientError::RefreshTokenRejected {
+            description: e.error_description,
+        },
+        _ => TadoClientError::Auth(format!("http {}: {}", status, body)),
+    }
+}
+
+fn is_transient(err: &TadoClientError) -> bool {
+    match err {
+        TadoClientError::Http { status, .. } => (500..=599).contains(status),
+        TadoClientError::RateLimited { .. } | TadoClientError::Unavailable { .. } | TadoClientError::Transport(_) => {
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Parses a `Retry-After` header in either delay-seconds or HTTP-date form; past dates mean "now".
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?.with_timezone(&Utc);
+    Some((at - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Modification time of the file at `path`, or `None` when it is missing or unreadable.
+fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Writes `contents` to a sibling temp file and renames it over `path`, so a crash mid-write never leaves
+/// a truncated file behind: readers see either the old contents or the new ones.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write as _;
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = std::fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&tmp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use log::{Level, Log, Metadata, Record};
+
+    /// Minimal logger that keeps error-level messages so tests can assert on them.
+    struct CapturingLogger {
+        errors: Mutex<Vec<String>>,
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Error
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.errors.lock().expect("logger lock").push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger {
+        errors: Mutex::new(Vec::new()),
+    };
+
+    #[test]
+    fn accepting_invalid_certs_logs_warning() {
+        log::set_logger(&LOGGER).expect("no other logger installed in tests");
+        log::set_max_level(log::LevelFilter::Error);
+
+        build_agent(TransportOptions {
+            danger_accept_invalid_certs: true,
+            ..TransportOptions::default()
+        })
+        .expect("agent builds");
+
+        let errors = LOGGER.errors.lock().expect("logger lock");
+        assert!(errors.iter().any(|m| m == INSECURE_TLS_WARNING));
+    }
+
+    #[test]
+    fn token_write_replaces_file_without_leftovers() {
+        let dir = std::env::temp_dir().join(format!("tado-timescale-token-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let path = dir.join("token.txt");
+        std::fs::write(&path, "old-token").expect("seed token");
+
+        write_atomically(&path, "new-token").expect("atomic write");
+
+        assert_eq!(std::fs::read_to_string(&path).expect("read token"), "new-token");
+        let entries: Vec<_> = std::fs::read_dir(&dir).expect("list dir").collect();
+        assert_eq!(entries.len(), 1, "temp file must be renamed away");
+        std::fs::remove_dir_all(&dir).expect("cleanup");
+    }
+
+    #[test]
+    fn parsed_response_adds_body_size_to_byte_counter() {
+        let traffic = TrafficCounters::default();
+        let body = r#"{"id": 42, "name": "Home"}"#;
+        let mut res = http::Response::new(ureq::Body::builder().data(body));
+
+        let parsed: serde_json::Value = read_json_body(&mut res, "/homes/42", &traffic).expect("parse body");
+
+        assert_eq!(parsed["id"], 42);
+        assert_eq!(traffic.response_bytes(), body.len() as u64);
+        assert_eq!(traffic.requests(), 0);
+    }
+
+    const NO_BACKOFF: RetryBackoff = RetryBackoff {
+        base: Duration::ZERO,
+        max: Duration::ZERO,
+    };
+
+    #[test]
+    fn server_errors_are_retried_until_success() {
+        let mut responses = vec![
+            Err(TadoClientError::Http {
+                status: 503,
+                message: "unavailable".to_string(),
+            }),
+            Err(TadoClientError::Http {
+                status: 503,
+                message: "unavailable".to_string(),
+            }),
+            Ok(200),
+        ]
+        .into_iter();
+        let mut calls = 0;
+
+        let result = retry_transient_errors(NonZeroU32::new(3).unwrap(), "GET /me", NO_BACKOFF, || {
+            calls += 1;
+            responses.next().expect("no more responses")
+        });
+
+        assert_eq!(result.expect("eventually succeeds"), 200);
+        assert_eq!(calls, 3);
+
+        let mut client_error_calls = 0;
+        let not_found = retry_transient_errors(NonZeroU32::new(3).unwrap(), "GET /me", NO_BACKOFF, || {
+            client_error_calls += 1;
+            Err::<(), _>(TadoClientError::Http {
+                status: 404,
+                message: "not found".to_string(),
+            })
+        });
+        assert!(matches!(not_found, Err(TadoClientError::Http { status: 404, .. })));
+        assert_eq!(client_error_calls, 1);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        let backoff = RetryBackoff {
+            base: Duration::from_millis(500),
+            max: Duration::from_secs(5),
+        };
+
+        let delays: Vec<Duration> = (1..=8).map(|n| backoff.delay(n)).collect();
+
+        assert_eq!(delays[0], Duration::from_millis(500));
+        assert_eq!(delays[3], Duration::from_secs(4));
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]), "{delays:?}");
+        assert!(delays[4..].iter().all(|d| *d == backoff.max), "{delays:?}");
+        assert_eq!(backoff.delay(64), backoff.max);
+
+        let jittered = backoff.jittered_delay(2);
+        assert!(jittered >= backoff.delay(2) && jittered <= backoff.delay(2).mul_f64(1.1));
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 28, 0).unwrap();
+
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn retry_after_beyond_cap_is_surfaced() {
+        let mut calls = 0;
+        let result = retry_transient_errors(NonZeroU32::new(3).unwrap(), "GET /me", NO_BACKOFF, || {
+            calls += 1;
+            Err::<(), _>(TadoClientError::RateLimited {
+                retry_after: Some(Duration::from_secs(3 * 60 * 60)),
+            })
+        });
+
+        match result {
+            Err(TadoClientError::Http { status: 429, message }) => assert!(message.contains("wait cap"), "{message}"),
+            other => panic!("expected capped 429, got {other:?}"),
+        }
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn stalled_server_times_out_instead_of_hanging() {
+        // The kernel completes the handshake from the backlog, but nothing ever answers the request.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let url = format!("http://{}/homes/1", listener.local_addr().expect("local addr"));
+        let agent = build_agent(TransportOptions {
+            timeout: Some(Duration::from_millis(200)),
+            ..TransportOptions::default()
+        })
+        .expect("agent builds");
+
+        let started = Instant::now();
+        let result = agent.get(&url).call();
+
+        assert!(matches!(result, Err(ureq::Error::Timeout(_))), "{result:?}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Plain HTTP/1.1 mock of the API answering every request with `respond(request_line)` as
+    /// `(status line, extra headers, body)`. Every connection gets its own thread and may carry several requests.
+    fn spawn_mock_api(respond: impl Fn(&str) -> (&'static str, String, String) + Send + Sync + 'static) -> String {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let respond = Arc::new(respond);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind mock api");
+        let base = format!("http://{}", listener.local_addr().expect("local addr"));
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let respond = Arc::clone(&respond);
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+                    let mut stream = stream;
+                    loop {
+                        let mut request_line = String::new();
+                        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let mut content_length = 0;
+                        loop {
+                            let mut header = String::new();
+                            reader.read_line(&mut header).expect("read header");
+                            if header.trim().is_empty() {
+                                break;
+                            }
+                            if let Some((name, value)) = header.split_once(':')
+                                && name.eq_ignore_ascii_case("content-length")
+                            {
+                                content_length = value.trim().parse().expect("content length");
+                            }
+                        }
+                        let mut body = vec![0; content_length];
+                        reader.read_exact(&mut body).expect("read body");
+
+                        let (status, headers, response_body) = respond(&request_line);
+                        write!(
+                            stream,
+                            "HTTP/1.1 {}\r\nContent-Type: application/json\r\n{}Content-Length: {}\r\n\r\n{}",
+                            status,
+                            headers,
+                            response_body.len(),
+                            response_body
+                        )
+                        .expect("write response");
+                    }
+                });
+            }
+        });
+        base
+    }
+
+    fn mock_client(base: &str) -> TadoClient {
+        TadoClient {
+            agent: build_agent(TransportOptions::default()).expect("agent builds"),
+            base_url: base.to_string(),
+            token_url: format!("{}/oauth2/token", base),
+            client_id: OAUTH_CLIENT_ID.to_string(),
+            oauth: Mutex::new(OAuthState {
+                token: None,
+                refresh_token: "refresh-1".to_string(),
+                token_file_modified: None,
+            }),
+            user_agent: "test".to_string(),
+            refresh_token_path: std::env::temp_dir().join("tado-timescale-unused-token"),
+            max_server_error_retries: NonZeroU32::new(2).unwrap(),
+            retry_backoff: NO_BACKOFF,
+            traffic: Arc::new(TrafficCounters::default()),
+            rate_limiter: None,
+        }
+    }
+
+    const TOKEN_BODY: &str = r#"{"access_token": "access-1", "expires_in": 600}"#;
+
+    #[test]
+    fn concurrent_requests_share_one_token_refresh() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<TadoClient>();
+
+        let token_grants = Arc::new(AtomicU64::new(0));
+        let grants = Arc::clone(&token_grants);
+        let base = spawn_mock_api(move |request_line| {
+            if request_line.contains("/oauth2/token") {
+                grants.fetch_add(1, Ordering::SeqCst);
+                // Slow enough that both callers are waiting on the grant at once
+                std::thread::sleep(Duration::from_millis(200));
+                ("200 OK", String::new(), TOKEN_BODY.to_string())
+            } else {
+                ("200 OK", String::new(), r#"{"id": "user-1", "homes": []}"#.to_string())
+            }
+        });
+        let client = Arc::new(mock_client(&base));
+
+        let callers: Vec<_> = (0..2)
+            .map(|_| {
+                let client = Arc::clone(&client);
+                std::thread::spawn(move || client.get_me())
+            })
+            .collect();
+        for caller in callers {
+            let me = caller.join().expect("caller thread").expect("get_me succeeds");
+            assert_eq!(me.id.as_deref(), Some("user-1"));
+        }
+
+        assert_eq!(token_grants.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn token_endpoint_maintenance_is_retried_by_the_request_after_retry_after() {
+        let token_attempts = Arc::new(AtomicU64::new(0));
+        let attempts = Arc::clone(&token_attempts);
+        let base = spawn_mock_api(move |request_line| {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                (
+                    "503 Service Unavailable",
+                    "Retry-After: 1\r\n".to_string(),
+                    "maintenance".to_string(),
+                )
+            } else if request_line.contains("/oauth2/token") {
+                ("200 OK", String::new(), TOKEN_BODY.to_string())
+            } else {
+                ("200 OK", String::new(), r#"{"id": "user-1", "homes": []}"#.to_string())
+            }
+        });
+        let client = mock_client(&base);
+
+        // One grant attempt per call; the retry belongs to the request's own loop
+        assert!(matches!(
+            client.get_bearer(),
+            Err(TadoClientError::Unavailable { retry_after: Some(_) })
+        ));
+        assert!(client.oauth.try_lock().is_ok(), "lock released before any backoff");
+        assert_eq!(token_attempts.load(Ordering::SeqCst), 1);
+
+        token_attempts.store(0, Ordering::SeqCst);
+        let started = Instant::now();
+        let me = client.get_me().expect("request after token maintenance");
+        assert_eq!(me.id.as_deref(), Some("user-1"));
+        // 503 grant, then the successful grant and the request itself
+        assert_eq!(token_attempts.load(Ordering::SeqCst), 3);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+
+        // A rejected refresh token is still terminal
+        let rejected = mock_client(&spawn_mock_api(|_| {
+            (
+                "400 Bad Request",
+                String::new(),
+                r#"{"error": "invalid_grant"}"#.to_string(),
+            )
+        }));
+        assert!(matches!(
+            rejected.get_bearer(),
+            Err(TadoClientError::RefreshTokenRejected { .. })
+        ));
+    }
+
+    #[test]
+    fn refresh_token_rotated_by_another_process_is_adopted() {
+        let dir = std::env::temp_dir().join(format!("tado-timescale-shared-token-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let mut client = mock_client("http://127.0.0.1:9");
+        client.refresh_token_path = dir.join("token.txt");
+        let mut state = OAuthState {
+            token: None,
+            refresh_token: "refresh-1".to_string(),
+            token_file_modified: None,
+        };
+
+        // No file yet, then an empty one: the in-memory token stays
+        client.adopt_persisted_refresh_token(&mut state);
+        std::fs::write(&client.refresh_token_path, "\n").expect("write empty token");
+        client.adopt_persisted_refresh_token(&mut state);
+        assert_eq!(state.refresh_token, "refresh-1");
+
+        std::fs::write(&client.refresh_token_path, "refresh-2\n").expect("write rotated token");
+        state.token_file_modified = None;
+        client.adopt_persisted_refresh_token(&mut state);
+        std::fs::remove_dir_all(&dir).expect("cleanup");
+        assert_eq!(state.refresh_token, "refresh-2");
+    }
+
+    #[test]
+    fn token_file_present_at_startup_is_not_adopted() {
+        let dir = std::env::temp_dir().join(format!("tado-timescale-startup-token-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let mut client = mock_client("http://127.0.0.1:9");
+        client.refresh_token_path = dir.join("token.txt");
+        // What config found at startup, and the token it resolved instead
+        let loaded = |contents: &str, resolved: &str| {
+            std::fs::write(&client.refresh_token_path, contents).expect("write token file");
+            OAuthState {
+                token: None,
+                refresh_token: resolved.to_string(),
+                token_file_modified: file_modified(&client.refresh_token_path),
+            }
+        };
+
+        // Corrupt file: config fell back to INITIAL_TADO_REFRESH_TOKEN
+        let mut state = loaded("not a token\u{1}", "initial");
+        client.adopt_persisted_refresh_token(&mut state);
+        assert_eq!(state.refresh_token, "initial");
+
+        // Seed list in the primary file: account 0 got its first entry
+        let mut state = loaded("first\nsecond\n", "first");
+        client.adopt_persisted_refresh_token(&mut state);
+        assert_eq!(state.refresh_token, "first");
+
+        // Rewritten later, but still not a single token
+        state.token_file_modified = None;
+        client.adopt_persisted_refresh_token(&mut state);
+        std::fs::remove_dir_all(&dir).expect("cleanup");
+        assert_eq!(state.refresh_token, "first");
+    }
+
+    #[test]
+    fn background_refresh_renews_the_token_before_it_expires_and_stops_on_request() {
+        let token_grants = Arc::new(AtomicU64::new(0));
+        let grants = Arc::clone(&token_grants);
+        let base = spawn_mock_api(move |_| {
+            grants.fetch_add(1, Ordering::SeqCst);
+            ("200 OK", String::new(), TOKEN_BODY.to_string())
+        });
+        let client = mock_client(&base);
+        client.oauth_state().token = Some(AccessToken {
+            access_token: "access-0".to_string(),
+            expires_at: Instant::now() + BACKGROUND_REFRESH_LEAD + Duration::from_millis(300),
+        });
+
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            let refresher = scope.spawn(|| client.refresh_in_background(&stop));
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while token_grants.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            stop.store(true, Ordering::SeqCst);
+            refresher.join().expect("refresher thread");
+        });
+
+        // One grant, ahead of the old token's expiry; the new token is not due for minutes
+        assert_eq!(token_grants.load(Ordering::SeqCst), 1);
+        let token = client.oauth_state().token.clone().expect("token");
+        assert_eq!(token.access_token, "access-1");
+        assert!(token.expires_at > Instant::now() + BACKGROUND_REFRESH_LEAD);
+    }
+
+    #[test]
+    fn request_ids_are_unique_and_only_reported_for_api_calls() {
+        let base = spawn_mock_api(|request_line| {
+            if request_line.contains("/oauth2/token") {
+                ("200 OK", String::new(), TOKEN_BODY.to_string())
+            } else {
+                ("200 OK", String::new(), r#"{"id": "user-1", "homes": []}"#.to_string())
+            }
+        });
+        let client = mock_client(&base);
+
+        let (first, first_id) = with_request_id(|| client.get_me());
+        let (second, second_id) = with_request_id(|| client.get_me());
+        assert!(first.is_ok() && second.is_ok());
+        let (first_id, second_id) = (first_id.expect("first id"), second_id.expect("second id"));
+        assert!(second_id > first_id);
+
+        // An answer that never reached the API reports no request, not the previous one
+        assert_eq!(with_request_id(|| "cached").1, None);
+    }
+
+    #[test]
+    fn invalid_grant_is_told_apart_from_other_token_errors() {
+        let expired = token_error_from_body(
+            400,
+            r#"{"error": "invalid_grant", "error_description": "Refresh token is expired or revoked"}"#,
+        );
+        assert!(matches!(
+            &expired,
+            TadoClientError::RefreshTokenRejected { description: Some(d) } if d == "Refresh token is expired or revoked"
+        ));
+        assert_eq!(
+            expired.to_string(),
+            "refresh token rejected (invalid_grant): Refresh token is expired or revoked"
+        );
+
+        assert!(matches!(
+            token_error_from_body(400, r#"{"error": "invalid_grant"}"#),
+            TadoClientError::RefreshTokenRejected { description: None }
+        ));
+        assert!(matches!(
+            token_error_from_body(401, r#"{"error": "invalid_client"}"#),
+            TadoClientError::Auth(message) if message == r#"http 401: {"error": "invalid_client"}"#
+        ));
+        assert!(matches!(
+            token_error_from_body(403, "<html>Forbidden</html>"),
+            TadoClientError::Auth(_)
+        ));
+    }
+
+    #[test]
+    fn rate_limiter_spaces_requests_by_the_configured_rate() {
+        let rps = NonZeroU32::new(20).unwrap();
+        let limiter = RateLimiter::new(rps);
+        let requests = 5;
+
+        let started = Instant::now();
+        for _ in 0..requests {
+            limiter.acquire();
+        }
+        let min_elapsed = Duration::from_secs_f64((requests - 1) as f64 / rps.get() as f64);
+        assert!(started.elapsed() >= min_elapsed, "took {:?}", started.elapsed());
+    }
+}
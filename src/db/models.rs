@@ -1,9 +1,9 @@
 //! Diesel model structs representing application entities and time-series data.
 //!
 //! Important: Migrations will set up TimescaleDB hypertables for
-//! `climate_measurements`, `weather_measurements`, and `events`.
+//! `climate_measurements`, `weather_measurements`, `zone_weather_measurements`, and `events`.
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -30,6 +30,38 @@ pub mod event_types {
     pub const DEVICE_REMOVED: &str = "DEVICE_REMOVED";
     pub const DEVICE_TEMPERATURE_OFFSET_CHANGED: &str = "DEVICE_TEMPERATURE_OFFSET_CHANGED";
     pub const DEVICE_MOUNTING_STATE_CHANGED: &str = "DEVICE_MOUNTING_STATE_CHANGED";
+    pub const DEVICE_CHARACTERISTICS_CHANGED: &str = "DEVICE_CHARACTERISTICS_CHANGED";
+
+    // Manual presence override affecting a zone
+    pub const GEO_OVERRIDE_ON: &str = "GEO_OVERRIDE_ON";
+    pub const GEO_OVERRIDE_OFF: &str = "GEO_OVERRIDE_OFF";
+
+    // Home configuration
+    pub const AWAY_RADIUS_CHANGED: &str = "AWAY_RADIUS_CHANGED";
+    pub const INCIDENT_DETECTION_CHANGED: &str = "INCIDENT_DETECTION_CHANGED";
+    pub const HOME_FLAGS_CHANGED: &str = "HOME_FLAGS_CHANGED";
+
+    // Zone configuration
+    pub const ZONE_TYPE_CHANGED: &str = "ZONE_TYPE_CHANGED";
+    pub const ZONE_CAPABILITIES_CHANGED: &str = "ZONE_CAPABILITIES_CHANGED";
+
+    // Hardware installation lifecycle (commissioning, pushed updates)
+    pub const INSTALLATION_STATE_CHANGED: &str = "INSTALLATION_STATE_CHANGED";
+
+    // Realtime reading older than the zone's latest one seen (clock skew or API anomaly)
+    pub const OUT_OF_ORDER_MEASUREMENT: &str = "OUT_OF_ORDER_MEASUREMENT";
+
+    // Schedule-driven setting change observed away from the predicted block start
+    pub const SCHEDULE_TRANSITION_DELAYED: &str = "SCHEDULE_TRANSITION_DELAYED";
+
+    // Zone temperature sensor now reports at a different precision (e.g. 0.1 °C -> 0.5 °C steps)
+    pub const SENSOR_PRECISION_CHANGED: &str = "SENSOR_PRECISION_CHANGED";
+
+    // Derived rollups
+    pub const DAILY_HEATING_RUNTIME: &str = "DAILY_HEATING_RUNTIME";
+
+    // Marker written once a zone hits EVENTS_MAX_PER_ZONE_PER_DAY for an event type
+    pub const EVENTS_THROTTLED: &str = "EVENTS_THROTTLED";
 }
 
 pub mod event_source {
@@ -38,6 +70,13 @@ pub mod event_source {
     pub const DERIVED: &str = "derived";
 }
 
+/// Values of `climate_measurements.control_mode`: what made the zone's setting active.
+pub mod control_mode {
+    pub const SCHEDULE: &str = "schedule";
+    pub const MANUAL: &str = "manual";
+    pub const AWAY: &str = "away";
+}
+
 #[derive(Debug, Clone, Queryable, Identifiable, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = schema::users)]
 pub struct User {
@@ -79,6 +118,10 @@ pub struct Home {
     pub longitude: Option<f64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub away_radius_m: Option<f64>,
+    pub incident_detection_enabled: Option<bool>,
+    pub feature_flags: Option<serde_json::Value>,
+    pub incident_detection_supported: Option<bool>,
 }
 
 #[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
@@ -96,6 +139,10 @@ pub struct NewHome {
     pub country: Option<String>,
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    pub away_radius_m: Option<f64>,
+    pub incident_detection_enabled: Option<bool>,
+    pub feature_flags: Option<serde_json::Value>,
+    pub incident_detection_supported: Option<bool>,
 }
 
 #[derive(Debug, Clone, Queryable, Identifiable, Associations, Selectable, Serialize, Deserialize)]
@@ -131,6 +178,7 @@ pub struct Zone {
     pub date_created: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub capabilities: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
@@ -159,6 +207,8 @@ pub struct Device {
     pub characteristics: Option<serde_json::Value>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub temperature_offset_c: Option<f64>,
+    pub mounting_state: Option<String>,
 }
 
 #[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
@@ -173,6 +223,8 @@ pub struct NewDevice {
     pub orientation: Option<String>,
     pub battery_state: Option<String>,
     pub characteristics: Option<serde_json::Value>,
+    pub temperature_offset_c: Option<f64>,
+    pub mounting_state: Option<String>,
 }
 
 #[derive(Debug, Clone, Queryable, Identifiable, Associations, Selectable, Serialize, Deserialize)]
@@ -217,6 +269,10 @@ pub struct ClimateMeasurement {
     pub window_open: Option<bool>,
     pub battery_low: Option<bool>,
     pub connection_up: Option<bool>,
+    pub ingest_lag_secs: Option<f64>,
+    pub inside_temp_precision_c: Option<f64>,
+    pub tado_mode: Option<String>,
+    pub control_mode: Option<String>,
 }
 
 #[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
@@ -236,6 +292,10 @@ pub struct NewClimateMeasurement {
     pub window_open: Option<bool>,
     pub battery_low: Option<bool>,
     pub connection_up: Option<bool>,
+    pub ingest_lag_secs: Option<f64>,
+    pub inside_temp_precision_c: Option<f64>,
+    pub tado_mode: Option<String>,
+    pub control_mode: Option<String>,
 }
 
 impl NewClimateMeasurement {
@@ -261,10 +321,57 @@ impl NewClimateMeasurement {
             window_open: None,
             battery_low: None,
             connection_up: None,
+            ingest_lag_secs: None,
+            inside_temp_precision_c: None,
+            tado_mode: None,
+            control_mode: None,
         }
     }
 }
 
+// Continuous aggregate: climate_measurements_hourly
+/// Zone climate per 1-hour bucket (`time_bucket('1 hour', time)`, UTC), over zone-level rows of every source.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = schema::climate_measurements_hourly)]
+pub struct ClimateMeasurementHourly {
+    /// Start of the 1-hour bucket.
+    pub bucket: DateTime<Utc>,
+    pub home_id: i64,
+    pub zone_id: i64,
+    pub avg_inside_temp_c: Option<f64>,
+    pub min_inside_temp_c: Option<f64>,
+    pub max_inside_temp_c: Option<f64>,
+    pub avg_humidity_pct: Option<f64>,
+    pub min_humidity_pct: Option<f64>,
+    pub max_humidity_pct: Option<f64>,
+    pub avg_setpoint_temp_c: Option<f64>,
+    pub min_setpoint_temp_c: Option<f64>,
+    pub max_setpoint_temp_c: Option<f64>,
+    pub avg_heating_power_pct: Option<f64>,
+}
+
+// Continuous aggregate: climate_measurements_daily
+/// Zone climate per 1-day bucket (`time_bucket('1 day', time)`, i.e. UTC midnight to midnight), computed from the
+/// raw rows rather than the hourly view so the daily averages are not averages of averages.
+#[derive(Debug, Clone, Queryable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = schema::climate_measurements_daily)]
+pub struct ClimateMeasurementDaily {
+    /// Start of the 1-day bucket.
+    pub bucket: DateTime<Utc>,
+    pub home_id: i64,
+    pub zone_id: i64,
+    pub avg_inside_temp_c: Option<f64>,
+    pub min_inside_temp_c: Option<f64>,
+    pub max_inside_temp_c: Option<f64>,
+    pub avg_humidity_pct: Option<f64>,
+    pub min_humidity_pct: Option<f64>,
+    pub max_humidity_pct: Option<f64>,
+    pub avg_setpoint_temp_c: Option<f64>,
+    pub min_setpoint_temp_c: Option<f64>,
+    pub max_setpoint_temp_c: Option<f64>,
+    pub avg_heating_power_pct: Option<f64>,
+}
+
 // Hypertable: weather_measurements
 #[derive(Debug, Clone, Queryable, Identifiable, Associations, Selectable, Serialize, Deserialize)]
 #[diesel(table_name = schema::weather_measurements)]
@@ -278,6 +385,9 @@ pub struct WeatherMeasurement {
     pub outside_temp_c: Option<f64>,
     pub solar_intensity_pct: Option<f64>,
     pub weather_state: Option<String>,
+    pub ingest_lag_secs: Option<f64>,
+    /// Timestamp the API attached to the reading; `None` when `time` is a fallback to the collection time.
+    pub reported_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
@@ -289,6 +399,9 @@ pub struct NewWeatherMeasurement {
     pub outside_temp_c: Option<f64>,
     pub solar_intensity_pct: Option<f64>,
     pub weather_state: Option<String>,
+    pub ingest_lag_secs: Option<f64>,
+    /// Timestamp the API attached to the reading; `None` when `time` is a fallback to the collection time.
+    pub reported_at: Option<DateTime<Utc>>,
 }
 
 impl NewWeatherMeasurement {
@@ -300,6 +413,36 @@ impl NewWeatherMeasurement {
             outside_temp_c: None,
             solar_intensity_pct: None,
             weather_state: None,
+            ingest_lag_secs: None,
+            reported_at: None,
+        }
+    }
+}
+
+// Hypertable: zone_weather_measurements (home weather copied per zone, opt-in)
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::zone_weather_measurements)]
+pub struct NewZoneWeatherMeasurement {
+    pub time: DateTime<Utc>,
+    pub home_id: i64,
+    pub zone_id: i64,
+    pub source: String,
+    pub outside_temp_c: Option<f64>,
+    pub solar_intensity_pct: Option<f64>,
+    pub weather_state: Option<String>,
+}
+
+impl NewZoneWeatherMeasurement {
+    /// The home's weather reading as seen from one of its zones.
+    pub fn from_home(row: &NewWeatherMeasurement, zone_id: i64) -> Self {
+        Self {
+            time: row.time,
+            home_id: row.home_id,
+            zone_id,
+            source: row.source.clone(),
+            outside_temp_c: row.outside_temp_c,
+            solar_intensity_pct: row.solar_intensity_pct,
+            weather_state: row.weather_state.clone(),
         }
     }
 }
@@ -333,3 +476,98 @@ pub struct NewEvent {
     pub event_type: String,
     pub payload: Option<serde_json::Value>,
 }
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::zone_type_history)]
+pub struct NewZoneTypeChange {
+    pub zone_id: i64,
+    pub changed_at: DateTime<Utc>,
+    pub previous_type: Option<String>,
+    pub new_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Queryable, Identifiable, Selectable, Serialize, Deserialize)]
+#[diesel(table_name = schema::collector_instances)]
+pub struct CollectorInstance {
+    pub id: i64,
+    pub hostname: String,
+    pub pid: i32,
+    pub started_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::collector_instances)]
+pub struct NewCollectorInstance {
+    pub hostname: String,
+    pub pid: i32,
+    pub started_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::backfill_progress)]
+pub struct NewBackfillProgress {
+    pub zone_id: i64,
+    pub home_id: i64,
+    pub last_completed_day: NaiveDate,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::flow_temperature_optimization)]
+pub struct NewFlowTemperatureOptimization {
+    pub home_id: i64,
+    pub max_flow_temperature: Option<i64>,
+    pub auto_adaptation_enabled: Option<bool>,
+    pub auto_adaptation_max_flow_temperature: Option<i64>,
+    pub max_flow_temperature_min: Option<i64>,
+    pub max_flow_temperature_max: Option<i64>,
+    pub open_therm_device_serial_number: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::installations)]
+pub struct NewInstallation {
+    pub home_id: i64,
+    pub tado_installation_id: i64,
+    pub installation_type: Option<String>,
+    pub state: Option<String>,
+    pub revision: Option<i64>,
+}
+
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::mobile_devices)]
+pub struct NewMobileDevice {
+    pub home_id: i64,
+    pub tado_mobile_device_id: i64,
+    pub name: Option<String>,
+    pub platform: Option<String>,
+    pub model: Option<String>,
+    pub geo_tracking_enabled: Option<bool>,
+}
+
+// Hypertable: presence_measurements
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::presence_measurements)]
+pub struct NewPresenceMeasurement {
+    pub time: DateTime<Utc>,
+    pub home_id: i64,
+    pub mobile_device_id: i64,
+    pub at_home: Option<bool>,
+    pub stale: Option<bool>,
+    pub relative_distance_from_home_fence: Option<f64>,
+}
+
+// Hypertable: air_comfort_measurements
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[diesel(table_name = schema::air_comfort_measurements)]
+pub struct NewAirComfortMeasurement {
+    pub time: DateTime<Utc>,
+    pub home_id: i64,
+    pub zone_id: i64,
+    pub temperature_level: Option<String>,
+    pub humidity_level: Option<String>,
+    pub freshness: Option<String>,
+}
@@ -0,0 +1,138 @@
+//! Just enough of the system `libsqlite3` for `--export-sqlite` to write a database file.
+//!
+//! Diesel's SQLite backend would need a second driver crate next to libpq for a single write-only export, so the
+//! handful of C calls it uses are declared here instead. Statements are passed as SQL text with their values
+//! inlined as literals; the export escapes them.
+
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::path::Path;
+use std::ptr;
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_OPEN_READWRITE: c_int = 0x0000_0002;
+const SQLITE_OPEN_CREATE: c_int = 0x0000_0004;
+
+type ExecCallback = unsafe extern "C" fn(*mut c_void, c_int, *mut *mut c_char, *mut *mut c_char) -> c_int;
+
+#[link(name = "sqlite3")]
+unsafe extern "C" {
+    fn sqlite3_open_v2(filename: *const c_char, db: *mut *mut c_void, flags: c_int, vfs: *const c_char) -> c_int;
+    fn sqlite3_close(db: *mut c_void) -> c_int;
+    fn sqlite3_errmsg(db: *mut c_void) -> *const c_char;
+    fn sqlite3_exec(
+        db: *mut c_void,
+        sql: *const c_char,
+        callback: Option<ExecCallback>,
+        arg: *mut c_void,
+        errmsg: *mut *mut c_char,
+    ) -> c_int;
+    fn sqlite3_free(ptr: *mut c_void);
+}
+
+/// An open SQLite database file; closed on drop.
+pub struct Sqlite {
+    db: *mut c_void,
+}
+
+impl Sqlite {
+    /// Creates a fresh database at `path`, replacing any file already there.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        match std::fs::remove_file(path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("replace {} failed: {}", path.display(), e)),
+        }
+        Self::open_with(path, SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE)
+    }
+
+    /// Opens an existing database at `path`.
+    #[cfg(test)]
+    pub fn open(path: &Path) -> Result<Self, String> {
+        Self::open_with(path, SQLITE_OPEN_READWRITE)
+    }
+
+    fn open_with(path: &Path, flags: c_int) -> Result<Self, String> {
+        let filename = path
+            .to_str()
+            .and_then(|p| CString::new(p).ok())
+            .ok_or_else(|| format!("{} is not a valid SQLite file name", path.display()))?;
+        let mut db = ptr::null_mut();
+        // SAFETY: `filename` is a valid C string and `db` receives the handle, which `Sqlite` then owns.
+        let rc = unsafe { sqlite3_open_v2(filename.as_ptr(), &mut db, flags, ptr::null()) };
+        // Even a failed open allocates a handle (unless out of memory); dropping it closes that one too
+        let sqlite = Sqlite { db };
+        if rc != SQLITE_OK {
+            return Err(format!("open {} failed: {}", path.display(), sqlite.last_error()));
+        }
+        Ok(sqlite)
+    }
+
+    /// Runs one or more statements that return no rows.
+    pub fn execute(&mut self, sql: &str) -> Result<(), String> {
+        self.exec(sql, None, ptr::null_mut())
+    }
+
+    /// Runs `sql` and returns every row as text values (`None` for NULL).
+    #[cfg(test)]
+    pub fn query(&mut self, sql: &str) -> Result<Vec<Vec<Option<String>>>, String> {
+        unsafe extern "C" fn collect(
+            rows: *mut c_void,
+            columns: c_int,
+            values: *mut *mut c_char,
+            _names: *mut *mut c_char,
+        ) -> c_int {
+            // SAFETY: `rows` is the `Vec` passed to `exec` below, and SQLite hands over `columns` values.
+            let (rows, values) = unsafe {
+                (
+                    &mut *(rows as *mut Vec<Vec<Option<String>>>),
+                    std::slice::from_raw_parts(values, columns as usize),
+                )
+            };
+            rows.push(
+                values
+                    .iter()
+                    .map(|v| (!v.is_null()).then(|| unsafe { CStr::from_ptr(*v) }.to_string_lossy().into_owned()))
+                    .collect(),
+            );
+            0
+        }
+
+        let mut rows: Vec<Vec<Option<String>>> = Vec::new();
+        self.exec(sql, Some(collect), &mut rows as *mut _ as *mut c_void)?;
+        Ok(rows)
+    }
+
+    fn exec(&mut self, sql: &str, callback: Option<ExecCallback>, arg: *mut c_void) -> Result<(), String> {
+        let sql = CString::new(sql).map_err(|_| "SQL contains a NUL byte".to_string())?;
+        let mut errmsg: *mut c_char = ptr::null_mut();
+        // SAFETY: the handle is open, `sql` is a valid C string and `callback` matches what `arg` points to.
+        let rc = unsafe { sqlite3_exec(self.db, sql.as_ptr(), callback, arg, &mut errmsg) };
+        if rc == SQLITE_OK {
+            return Ok(());
+        }
+        if errmsg.is_null() {
+            return Err(self.last_error());
+        }
+        // SAFETY: SQLite allocated `errmsg` for us to read once and free.
+        let message = unsafe { CStr::from_ptr(errmsg) }.to_string_lossy().into_owned();
+        unsafe { sqlite3_free(errmsg as *mut c_void) };
+        Err(message)
+    }
+
+    fn last_error(&self) -> String {
+        if self.db.is_null() {
+            return "out of memory".to_string();
+        }
+        // SAFETY: the handle is valid; the message is owned by SQLite and copied before the next call.
+        unsafe { CStr::from_ptr(sqlite3_errmsg(self.db)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for Sqlite {
+    fn drop(&mut self) {
+        // SAFETY: closing a null handle is a no-op; otherwise this is the handle's only owner.
+        unsafe { sqlite3_close(self.db) };
+    }
+}
@@ -6,26 +6,57 @@ pub mod client;
 pub mod config;
 pub mod db {
     pub mod models;
+    pub mod sqlite;
 }
 pub mod schema;
 pub mod utils;
 pub mod services {
+    pub mod accounts;
+    pub mod air_comfort;
+    pub mod api;
     pub mod backfill;
+    pub mod export;
     pub mod fake_data;
+    pub mod heartbeat;
+    pub mod import;
+    pub mod influx;
     pub mod ingest;
+    pub mod metrics;
+    pub mod parse_check;
+    pub mod presence;
+    pub mod query;
     pub mod realtime;
     pub mod refs;
+    pub mod remote_write;
+    pub mod report_cache;
+    pub mod retention;
+    pub mod rollup;
+    pub mod shutdown;
+    pub mod webhook;
 }
 
-use crate::client::TadoClient;
-use crate::config::Config;
+use crate::client::{RetryBackoff, TadoClient, TadoClientError, TransportOptions};
+use crate::config::{Config, DisabledWeatherFields, LogFormat};
 use crate::models::tado::HomeId;
-use crate::services::{backfill, fake_data, realtime, refs};
-use diesel::prelude::*;
+use crate::services::accounts::Accounts;
+use crate::services::heartbeat::{self, Heartbeat};
+use crate::services::influx::InfluxSink;
+use crate::services::retention::RetentionPolicy;
+use crate::services::webhook::WeatherWebhook;
+use crate::services::{
+    api, backfill, export, fake_data, import, metrics, parse_check, realtime, refs, remote_write, shutdown,
+};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
 use diesel::PgConnection;
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use log::{error, info};
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use log::{error, info, warn};
+use std::io::Write;
+use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 #[derive(Debug)]
 struct LoadedEnvFile {
@@ -33,6 +64,52 @@ struct LoadedEnvFile {
     explicit: bool,
 }
 
+/// One-off `--export-sqlite` request; replaces the normal collector run.
+#[derive(Debug)]
+struct SqliteExport {
+    path: PathBuf,
+    days: NonZeroU32,
+}
+
+const DEFAULT_EXPORT_DAYS: u32 = 7;
+
+/// One-off `--export <path>` measurement dump; replaces the normal collector run.
+#[derive(Debug)]
+struct MeasurementExport {
+    path: PathBuf,
+    format: export::MeasurementFormat,
+    range: export::DateRange,
+}
+
+/// One-off `--import <path>` measurement restore; replaces the normal collector run.
+#[derive(Debug)]
+struct MeasurementImport {
+    path: PathBuf,
+    source: &'static str,
+}
+
+/// One-off `--parse-file <path> --as <TypeName>` request; replaces the normal collector run.
+#[derive(Debug)]
+struct ParseCheck {
+    path: PathBuf,
+    type_name: String,
+}
+
+#[derive(Debug)]
+struct CliArgs {
+    loaded_env: Option<LoadedEnvFile>,
+    export: Option<SqliteExport>,
+    measurement_export: Option<MeasurementExport>,
+    measurement_import: Option<MeasurementImport>,
+    /// `--once`: run a single realtime collection pass instead of the loop.
+    once: bool,
+    parse_check: Option<ParseCheck>,
+    /// `--generate-fake-data --i-understand`: fill the database with synthetic data and exit.
+    generate_fake_data: bool,
+    /// `--healthcheck`: check that the database and the Tado API are reachable, then exit.
+    healthcheck: bool,
+}
+
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 fn apply_database_migrations(conn: &mut PgConnection) -> Result<(), String> {
@@ -50,11 +127,11 @@ fn apply_database_migrations(conn: &mut PgConnection) -> Result<(), String> {
     }
 }
 
-pub fn run() -> Result<(), String> {
+pub fn run(once: bool) -> Result<(), String> {
     // 1) Load config
     let cfg = Config::from_env()?;
     info!(
-        "Config loaded (realtime_interval={}s, realtime_enabled={}, backfill_enabled={}, backfill_from={}, backfill_rps={}, backfill_sample_rate={}, backfill_min_gap={}min, max_request_retries={}, fake_data_mode={})",
+        "Config loaded (realtime_interval={}s, realtime_enabled={}, backfill_enabled={}, backfill_from={}, backfill_rps={}, backfill_sample_rate={}, backfill_min_gap={}min, max_request_retries={}, fake_data_mode={}, dry_run={})",
         cfg.realtime_interval.as_secs(),
         cfg.realtime_enabled,
         cfg.backfill_enabled,
@@ -69,9 +146,17 @@ pub fn run() -> Result<(), String> {
             .unwrap_or_else(|| "-".to_string()),
         cfg.backfill_min_gap.num_minutes(),
         cfg.max_request_retries.get(),
-        cfg.fake_data_mode
+        cfg.fake_data_mode,
+        cfg.dry_run
     );
 
+    if let Some(addr) = cfg.metrics_listen_addr.as_deref() {
+        metrics::serve(addr)?;
+    }
+    if let Some(url) = cfg.prom_remote_write_url.as_deref() {
+        remote_write::start(url, cfg.prom_remote_write_interval)?;
+    }
+
     // 2) Connect DB
     let mut conn = PgConnection::establish(&cfg.database_url).map_err(|e| format!("DB connection failed: {}", e))?;
     info!("Connected to database");
@@ -79,55 +164,99 @@ pub fn run() -> Result<(), String> {
     // 3) Apply pending database migrations
     apply_database_migrations(&mut conn)?;
 
+    if let Some(addr) = cfg.api_listen_addr.as_deref() {
+        api::serve(addr, &cfg.database_url)?;
+    }
+
     if cfg.fake_data_mode {
         info!("Fake data mode enabled; generating synthetic dataset");
-        fake_data::run(&mut conn)?;
+        fake_data::run(
+            &mut conn,
+            cfg.weather_disabled_fields,
+            &config::fake_data_config_from_env()?,
+            cfg.dry_run,
+        )?;
         return Ok(());
     }
 
-    // 4) Init Tado client
-    let client = TadoClient::new(
-        &cfg.tado_refresh_token,
-        &cfg.tado_client_user_agent,
-        cfg.tado_refresh_token_file.clone(),
-        cfg.max_request_retries,
-    )
-    .map_err(|e| format!("Tado auth failed (refresh token invalid/expired?): {}", e))?;
-    info!("Authenticated to Tado API");
-
-    // 5) Discover homes
-    let me = client.get_me().map_err(|e| format!("get_me failed: {}", e))?;
-    let mut target_homes = me
-        .homes
-        .as_deref()
-        .unwrap_or(&[])
-        .iter()
-        .filter_map(|hb| hb.id.map(|id| id.0))
-        .collect::<Vec<_>>();
-    target_homes.sort_unstable();
-    target_homes.dedup();
-    if target_homes.is_empty() {
-        return Err("No homes found; ensure the account has homes".into());
+    // Announce this process and warn about overlapping deployments
+    let heartbeat = Heartbeat::new(cfg.realtime_interval, cfg.dry_run);
+    if let Err(e) = heartbeat.register(&mut conn) {
+        warn!("Collector heartbeat registration failed: {}", e);
+    }
+    // Keeps beating through the reference sync, backfill and realtime loop; a dry run never writes one
+    if !cfg.dry_run {
+        heartbeat.spawn_background(&cfg.database_url, cfg.maintenance_window);
     }
+
+    // 4) Init one Tado client per account
+    let mut clients = Vec::with_capacity(cfg.tado_accounts.len());
+    for (index, account) in cfg.tado_accounts.iter().enumerate() {
+        let client = TadoClient::new(
+            &account.refresh_token,
+            &cfg.tado_client_user_agent,
+            &cfg.tado_oauth_client_id,
+            &cfg.tado_oauth_token_url,
+            account.refresh_token_file.clone(),
+            cfg.max_request_retries,
+            RetryBackoff {
+                base: cfg.retry_backoff_base,
+                max: cfg.retry_backoff_max,
+            },
+            TransportOptions {
+                min_tls: cfg.tado_min_tls,
+                danger_accept_invalid_certs: cfg.tado_danger_accept_invalid_certs,
+                timeout: Some(cfg.http_timeout),
+            },
+            cfg.tado_global_rps,
+        )
+        .map_err(|e| auth_failure_message(index + 1, &account.refresh_token_file, &e))?;
+        clients.push(client);
+    }
+    info!("Authenticated to Tado API ({} account(s))", clients.len());
+
+    // 5) Discover homes across all accounts
+    let mut accounts = Accounts::discover(clients)?;
+    let target_homes = accounts.select_homes(cfg.tado_home_ids.as_ref())?;
     info!("Discovered {} home(s)", target_homes.len());
 
     // 6) Sync reference data (users/homes/zones/devices/links)
     info!("Syncing reference data");
-    refs::sync_all(&mut conn, &client, &me, &target_homes)?;
+    let sync_options = refs::SyncOptions {
+        track_zone_type_changes: cfg.track_zone_type_changes,
+        track_device_characteristics: cfg.track_device_characteristics,
+        track_zone_capabilities: cfg.track_zone_capabilities,
+        track_installations: cfg.track_installations,
+        threads: cfg.refs_sync_threads.get() as usize,
+        dry_run: cfg.dry_run,
+    };
+    accounts.sync_refs(&mut conn, &cfg.database_url, sync_options)?;
     info!("Reference data sync complete");
 
     // 7) Historical backfill
     if cfg.backfill_enabled {
         info!("Starting historical backfill for {} home(s)", target_homes.len());
         for home_id in &target_homes {
+            let Some(client) = accounts.client_for(*home_id) else {
+                continue;
+            };
             backfill::run_for_home(
                 &mut conn,
-                &client,
+                client,
                 HomeId(*home_id),
                 cfg.backfill_from_date,
                 cfg.backfill_requests_per_second,
                 cfg.backfill_sample_rate,
                 cfg.backfill_min_gap,
+                cfg.weather_disabled_fields,
+                cfg.weather_per_zone,
+                cfg.backfill_verify,
+                cfg.backfill_ignore_progress,
+                cfg.backfill_trim_leading_bogus,
+                cfg.backfill_persist_reports_dir.as_deref(),
+                &cfg.database_url,
+                cfg.backfill_concurrency.get() as usize,
+                cfg.dry_run,
             )?;
             info!("Backfill completed for home {}", home_id);
         }
@@ -138,26 +267,232 @@ pub fn run() -> Result<(), String> {
         );
     }
 
-    // 8) Realtime loop (steady cadence)
-    if cfg.realtime_enabled {
+    // 8) Realtime collection: a single pass with `--once`, otherwise the loop (steady cadence)
+    let weather_webhook = cfg
+        .weather_webhook_url
+        .as_deref()
+        .map(|url| WeatherWebhook::new(url, cfg.weather_webhook_every_ticks));
+    let influx = cfg
+        .influxdb_url
+        .as_deref()
+        .map(|url| {
+            InfluxSink::new(
+                url,
+                cfg.influxdb_bucket.as_deref(),
+                cfg.influxdb_org.as_deref(),
+                cfg.influxdb_token.as_deref(),
+            )
+        })
+        .transpose()?;
+    let realtime_options = realtime::RealtimeOptions {
+        store_ingest_lag: cfg.store_ingest_lag,
+        dry_run: cfg.dry_run,
+        trim_leading_bogus: cfg.backfill_trim_leading_bogus,
+        events_max_per_zone_per_day: cfg.events_max_per_zone_per_day,
+        maintenance_window: cfg.maintenance_window,
+        daily_runtime_rollup: cfg.daily_runtime_rollup,
+        retention: RetentionPolicy {
+            realtime_days: cfg.retention_realtime_days,
+            historical_days: cfg.retention_historical_days,
+        },
+        track_geolocation_override: cfg.track_geolocation_override,
+        track_sensor_precision: cfg.track_sensor_precision,
+        collect_weather: cfg.realtime_collect_weather,
+        collect_zones: cfg.realtime_collect_zones,
+        collect_devices: cfg.realtime_collect_devices,
+        collect_presence: cfg.collect_presence,
+        collect_air_comfort: cfg.collect_air_comfort,
+        battery_event_debounce: cfg.battery_event_debounce,
+        out_of_order_check: cfg.out_of_order_check,
+        schedule_transition_tolerance: cfg.schedule_transition_tolerance,
+        max_catchup_ticks: cfg.realtime_max_catchup_ticks,
+        max_consecutive_failures: cfg.realtime_max_consecutive_failures.get(),
+        startup_catchup: cfg.realtime_startup_catchup,
+        refs_sync_every: cfg.refs_sync_every,
+        refs_sync_options: sync_options,
+        weather_disabled_fields: cfg.weather_disabled_fields,
+        weather_per_zone: cfg.weather_per_zone,
+        tx_per_tick: cfg.realtime_tx_per_tick,
+        // Half the minimum backfill gap, so even a late tick stores a row before gap detection would see a hole
+        dedup: cfg.realtime_dedup.then(|| cfg.backfill_min_gap / 2),
+        db_reconnect_max_retries: cfg.db_reconnect_max_retries.get(),
+    };
+    if once {
+        info!(
+            "Running a single realtime collection (--once) for {} home(s)",
+            target_homes.len()
+        );
+        realtime::run_single(
+            &mut conn,
+            &accounts,
+            weather_webhook.as_ref(),
+            influx.as_ref(),
+            realtime_options,
+        )?;
+    } else if cfg.realtime_enabled {
         info!(
             "Starting realtime loop: homes={}, interval={}s",
             target_homes.len(),
             cfg.realtime_interval.as_secs()
         );
-        realtime::run_loop(&mut conn, &client, &target_homes, cfg.realtime_interval)?;
+        shutdown::install_handlers()?;
+        let stop_token_refresh = AtomicBool::new(false);
+        std::thread::scope(|scope| {
+            if cfg.tado_background_token_refresh {
+                info!("Refreshing Tado access tokens in the background");
+                for client in accounts.clients() {
+                    scope.spawn(|| client.refresh_in_background(&stop_token_refresh));
+                }
+            }
+            // The scope joins the refreshers, so they have to stop however the loop ends: shutdown, error or panic
+            let _stop_refreshers = SetOnDrop(&stop_token_refresh);
+            realtime::run_loop(
+                &mut conn,
+                &cfg.database_url,
+                &accounts,
+                cfg.realtime_interval,
+                weather_webhook.as_ref(),
+                influx.as_ref(),
+                realtime_options,
+            )
+        })?;
     } else {
         info!("Realtime loop disabled via REALTIME_ENABLED={}", cfg.realtime_enabled);
     }
 
+    let (requests, response_bytes) = accounts.clients().iter().fold((0, 0), |(requests, bytes), client| {
+        let traffic = client.traffic();
+        (requests + traffic.requests(), bytes + traffic.response_bytes())
+    });
+    info!(
+        "Tado API traffic this run: {} request(s), {} response byte(s)",
+        requests, response_bytes
+    );
+    remote_write::flush();
+
+    Ok(())
+}
+
+/// Sets the flag when dropped, including while unwinding from a panic.
+struct SetOnDrop<'a>(&'a AtomicBool);
+
+impl Drop for SetOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Startup error for a failed initial token grant. A rejected refresh token cannot be fixed by retrying, so that
+/// case spells out how to replace it; the token itself is never included.
+fn auth_failure_message(account_number: usize, token_file: &Path, err: &TadoClientError) -> String {
+    match err {
+        TadoClientError::RefreshTokenRejected { .. } => format!(
+            "Tado rejected the refresh token of account {} ({}). It has expired or was revoked, e.g. by logging in \
+             elsewhere with it. Obtain a new one in the browser as described under \"Authentication\" in the README, \
+             then replace the contents of {} with it (or delete that file and set INITIAL_TADO_REFRESH_TOKEN)",
+            account_number,
+            err,
+            token_file.display()
+        ),
+        _ => format!("Tado auth failed for account {}: {}", account_number, err),
+    }
+}
+
+/// Read-only snapshot export; needs only the database, so no token or migrations are involved.
+fn run_export(export: &SqliteExport) -> Result<(), String> {
+    let database_url = config::database_url_from_env()?;
+    let mut conn = PgConnection::establish(&database_url).map_err(|e| format!("DB connection failed: {}", e))?;
+    info!("Connected to database");
+    export::run(&mut conn, &export.path, export.days)
+}
+
+/// Read-only measurement dump for the homes in `TADO_HOME_IDS` (all stored homes when unset).
+fn run_measurement_export(export: &MeasurementExport) -> Result<(), String> {
+    let database_url = config::database_url_from_env()?;
+    let home_ids = config::home_ids_from_env()?;
+    let mut conn = PgConnection::establish(&database_url).map_err(|e| format!("DB connection failed: {}", e))?;
+    info!("Connected to database");
+    export::run_measurements(&mut conn, &export.path, export.format, home_ids.as_ref(), export.range)
+}
+
+/// Measurement restore from an `--export` dump or another tool's NDJSON; migrates first since it writes.
+fn run_measurement_import(import: &MeasurementImport) -> Result<(), String> {
+    let database_url = config::database_url_from_env()?;
+    let dry_run = config::dry_run_from_env()?;
+    let mut conn = PgConnection::establish(&database_url).map_err(|e| format!("DB connection failed: {}", e))?;
+    info!("Connected to database");
+    apply_database_migrations(&mut conn)?;
+    import::run(&mut conn, &import.path, import.source, dry_run)
+}
+
+/// Synthetic dataset on demand; needs only the database, so no token is involved. The tables are migrated first
+/// because, unlike the export, this writes to them.
+fn run_generate_fake_data() -> Result<(), String> {
+    let database_url = config::database_url_from_env()?;
+    let weather_disabled_fields = DisabledWeatherFields::from_env()?;
+    let fake_data_config = config::fake_data_config_from_env()?;
+    let dry_run = config::dry_run_from_env()?;
+    let mut conn = PgConnection::establish(&database_url).map_err(|e| format!("DB connection failed: {}", e))?;
+    info!("Connected to database");
+    apply_database_migrations(&mut conn)?;
+    info!("Generating synthetic dataset (--generate-fake-data)");
+    fake_data::run(&mut conn, weather_disabled_fields, &fake_data_config, dry_run)
+}
+
+/// Upper bound for each step of `--healthcheck`, so a hung dependency fails the probe instead of stalling it.
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Liveness probe: `select 1` on the database, then a fresh heartbeat from the collector on this host in
+/// `collector_instances`, with short timeouts. Nothing is migrated or written and Tado is never contacted, so the
+/// probe cannot rotate the refresh token out from under the collector. The heartbeat is not checked under
+/// `DRY_RUN`, which never writes one, or during the maintenance window, which pauses it.
+fn run_healthcheck() -> Result<(), String> {
+    let cfg = Config::from_env()?;
+
+    let database_url = config::with_connect_timeout(&cfg.database_url, HEALTHCHECK_TIMEOUT.as_secs());
+    let mut conn = PgConnection::establish(&database_url).map_err(|e| format!("DB connection failed: {}", e))?;
+    conn.batch_execute("select 1")
+        .map_err(|e| format!("DB query failed: {}", e))?;
+
+    if cfg.dry_run {
+        info!("Healthcheck passed: database reachable; heartbeat not checked under DRY_RUN");
+        return Ok(());
+    }
+    if let Some(window) = cfg.maintenance_window.filter(|w| w.contains(Utc::now())) {
+        info!(
+            "Healthcheck passed: database reachable; heartbeat paused for maintenance window {}",
+            window
+        );
+        return Ok(());
+    }
+    let last_seen = heartbeat::check_fresh(&mut conn, cfg.realtime_interval)?;
+
+    info!(
+        "Healthcheck passed: database reachable, collector heartbeat at {}",
+        last_seen
+    );
     Ok(())
 }
 
-fn configure_env_from_cli() -> Result<Option<LoadedEnvFile>, String> {
+fn configure_env_from_cli() -> Result<CliArgs, String> {
     let mut args = std::env::args_os();
     args.next(); // skip program name
 
     let mut env_file: Option<PathBuf> = None;
+    let mut export_path: Option<PathBuf> = None;
+    let mut export_days: Option<NonZeroU32> = None;
+    let mut measurement_path: Option<PathBuf> = None;
+    let mut measurement_format: Option<export::MeasurementFormat> = None;
+    let mut export_from: Option<NaiveDate> = None;
+    let mut export_to: Option<NaiveDate> = None;
+    let mut import_path: Option<PathBuf> = None;
+    let mut import_source: Option<&'static str> = None;
+    let mut once = false;
+    let mut parse_path: Option<PathBuf> = None;
+    let mut parse_type: Option<String> = None;
+    let mut generate_fake_data = false;
+    let mut i_understand = false;
+    let mut healthcheck = false;
 
     while let Some(arg) = args.next() {
         match arg.to_str() {
@@ -180,31 +515,249 @@ fn configure_env_from_cli() -> Result<Option<LoadedEnvFile>, String> {
                 }
                 env_file = Some(PathBuf::from(path_str));
             }
+            Some("--export-sqlite") => {
+                if export_path.is_some() {
+                    return Err("`--export-sqlite` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .ok_or_else(|| "`--export-sqlite` requires a path argument".to_string())?;
+                export_path = Some(PathBuf::from(value));
+            }
+            Some("--days") => {
+                if export_days.is_some() {
+                    return Err("`--days` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .and_then(|v| v.to_str().and_then(|v| v.trim().parse::<NonZeroU32>().ok()))
+                    .ok_or_else(|| "`--days` requires a positive integer".to_string())?;
+                export_days = Some(value);
+            }
+            Some("--export") => {
+                if measurement_path.is_some() {
+                    return Err("`--export` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .ok_or_else(|| "`--export` requires a path argument".to_string())?;
+                measurement_path = Some(PathBuf::from(value));
+            }
+            Some("--export-format") => {
+                if measurement_format.is_some() {
+                    return Err("`--export-format` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .and_then(|v| v.into_string().ok())
+                    .ok_or_else(|| "`--export-format` requires ndjson or csv".to_string())?;
+                measurement_format = Some(export::MeasurementFormat::parse(value.trim())?);
+            }
+            Some(flag @ ("--export-from" | "--export-to")) => {
+                let slot = if flag == "--export-from" {
+                    &mut export_from
+                } else {
+                    &mut export_to
+                };
+                if slot.is_some() {
+                    return Err(format!("`{}` provided more than once", flag));
+                }
+                let value = args
+                    .next()
+                    .and_then(|v| {
+                        v.to_str()
+                            .and_then(|v| NaiveDate::parse_from_str(v.trim(), "%Y-%m-%d").ok())
+                    })
+                    .ok_or_else(|| format!("`{}` requires a date (YYYY-MM-DD)", flag))?;
+                *slot = Some(value);
+            }
+            Some("--import") => {
+                if import_path.is_some() {
+                    return Err("`--import` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .ok_or_else(|| "`--import` requires a path argument".to_string())?;
+                import_path = Some(PathBuf::from(value));
+            }
+            Some("--source") => {
+                if import_source.is_some() {
+                    return Err("`--source` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .and_then(|v| v.into_string().ok())
+                    .ok_or_else(|| format!("`--source` requires one of {}", import::SOURCES.join(", ")))?;
+                import_source = Some(import::parse_source(value.trim())?);
+            }
+            Some("--parse-file") => {
+                if parse_path.is_some() {
+                    return Err("`--parse-file` provided more than once".to_string());
+                }
+                let value = args
+                    .next()
+                    .ok_or_else(|| "`--parse-file` requires a path argument".to_string())?;
+                parse_path = Some(PathBuf::from(value));
+            }
+            Some("--as") => {
+                if parse_type.is_some() {
+                    return Err("`--as` provided more than once".to_string());
+                }
+                let value = args.next().and_then(|v| v.into_string().ok()).ok_or_else(|| {
+                    format!(
+                        "`--as` requires a type name ({})",
+                        parse_check::SUPPORTED_TYPES.join(", ")
+                    )
+                })?;
+                parse_type = Some(value);
+            }
+            Some("--once") => {
+                if once {
+                    return Err("`--once` provided more than once".to_string());
+                }
+                once = true;
+            }
+            Some("--generate-fake-data") => {
+                if generate_fake_data {
+                    return Err("`--generate-fake-data` provided more than once".to_string());
+                }
+                generate_fake_data = true;
+            }
+            Some("--i-understand") => {
+                if i_understand {
+                    return Err("`--i-understand` provided more than once".to_string());
+                }
+                i_understand = true;
+            }
+            Some("--healthcheck") => {
+                if healthcheck {
+                    return Err("`--healthcheck` provided more than once".to_string());
+                }
+                healthcheck = true;
+            }
             Some("--") => break,
-            Some(other) => return Err(format!("unrecognised argument: {}", other)),
+            Some(other) => {
+                return Err(format!(
+                    "unrecognised argument: {} (expected --env-file <path>, --once, --export-sqlite <path> [--days <n>], --export <path> [--export-format ndjson|csv] [--export-from <date>] [--export-to <date>], --import <path> [--source <source>], --parse-file <path> --as <type>, --generate-fake-data --i-understand, or --healthcheck)",
+                    other
+                ));
+            }
             None => return Err("argument contains invalid UTF-8".to_string()),
         }
     }
 
-    if let Some(path) = env_file {
+    let export = match (export_path, export_days) {
+        (Some(path), days) => Some(SqliteExport {
+            path,
+            days: days.unwrap_or(NonZeroU32::new(DEFAULT_EXPORT_DAYS).expect("non-zero default")),
+        }),
+        (None, Some(_)) => return Err("`--days` is only valid together with `--export-sqlite`".to_string()),
+        (None, None) => None,
+    };
+    let measurement_export = match measurement_path {
+        Some(path) => {
+            let range = export::DateRange {
+                from: export_from,
+                to: export_to,
+            };
+            if let (Some(from), Some(to)) = (range.from, range.to)
+                && from > to
+            {
+                return Err(format!("`--export-from` ({}) is after `--export-to` ({})", from, to));
+            }
+            Some(MeasurementExport {
+                path,
+                format: measurement_format.unwrap_or_default(),
+                range,
+            })
+        }
+        None if measurement_format.is_some() || export_from.is_some() || export_to.is_some() => {
+            return Err(
+                "`--export-format`, `--export-from` and `--export-to` are only valid together with `--export`"
+                    .to_string(),
+            );
+        }
+        None => None,
+    };
+    let measurement_import = match (import_path, import_source) {
+        (Some(path), source) => Some(MeasurementImport {
+            path,
+            source: source.unwrap_or(import::DEFAULT_SOURCE),
+        }),
+        (None, Some(_)) => return Err("`--source` is only valid together with `--import`".to_string()),
+        (None, None) => None,
+    };
+    let parse_check = match (parse_path, parse_type) {
+        (Some(path), Some(type_name)) => Some(ParseCheck { path, type_name }),
+        (Some(_), None) => {
+            return Err(format!(
+                "`--parse-file` requires `--as <type>` ({})",
+                parse_check::SUPPORTED_TYPES.join(", ")
+            ));
+        }
+        (None, Some(_)) => return Err("`--as` is only valid together with `--parse-file`".to_string()),
+        (None, None) => None,
+    };
+    match (generate_fake_data, i_understand) {
+        (true, false) => {
+            return Err(
+                "`--generate-fake-data` writes `FAKE_DATA_HISTORY_DAYS` of synthetic rows into the configured database; add `--i-understand` to confirm"
+                    .to_string(),
+            );
+        }
+        (false, true) => return Err("`--i-understand` is only valid together with `--generate-fake-data`".to_string()),
+        _ => {}
+    }
+    if [
+        once,
+        export.is_some(),
+        measurement_export.is_some(),
+        measurement_import.is_some(),
+        parse_check.is_some(),
+        generate_fake_data,
+        healthcheck,
+    ]
+    .iter()
+    .filter(|set| **set)
+    .count()
+        > 1
+    {
+        return Err(
+            "`--once`, `--export-sqlite`, `--export`, `--import`, `--parse-file`, `--generate-fake-data` and `--healthcheck` are mutually exclusive"
+                .to_string(),
+        );
+    }
+
+    let loaded_env = if let Some(path) = env_file {
         if !path.is_file() {
             return Err(format!("env file not found: {}", path.display()));
         }
         load_env_file(&path)?;
-        Ok(Some(LoadedEnvFile { path, explicit: true }))
+        Some(LoadedEnvFile { path, explicit: true })
     } else {
         let cwd = std::env::current_dir().map_err(|e| format!("unable to read current directory: {}", e))?;
         let default_path = cwd.join(".env");
         if default_path.is_file() {
             load_env_file(&default_path)?;
-            Ok(Some(LoadedEnvFile {
+            Some(LoadedEnvFile {
                 path: default_path,
                 explicit: false,
-            }))
+            })
         } else {
-            Ok(None)
+            None
         }
-    }
+    };
+
+    Ok(CliArgs {
+        loaded_env,
+        export,
+        measurement_export,
+        measurement_import,
+        once,
+        parse_check,
+        generate_fake_data,
+        healthcheck,
+    })
 }
 
 fn load_env_file(path: &Path) -> Result<(), String> {
@@ -345,22 +898,46 @@ fn parse_single_quoted(input: &str) -> Result<String, String> {
     Err("unterminated single-quoted value".to_string())
 }
 
+/// One `LOG_FORMAT=json` line; serde_json takes care of escaping quotes and newlines in the message.
+fn json_log_line(timestamp: DateTime<Utc>, level: log::Level, target: &str, message: &str) -> String {
+    serde_json::json!({
+        "timestamp": timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "level": level.as_str(),
+        "target": target,
+        "message": message,
+    })
+    .to_string()
+}
+
 fn main() {
-    let loaded_env = match configure_env_from_cli() {
-        Ok(info) => info,
+    let cli = match configure_env_from_cli() {
+        Ok(cli) => cli,
         Err(err) => {
             eprintln!("fatal: {}", err);
             std::process::exit(1);
         }
     };
 
-    // Init logging after environment so RUST_LOG from .env is respected.
+    // Init logging after environment so RUST_LOG and LOG_FORMAT from .env are respected.
+    let log_format = match LogFormat::from_env() {
+        Ok(format) => format,
+        Err(err) => {
+            eprintln!("fatal: {}", err);
+            std::process::exit(1);
+        }
+    };
     let default_filter = env_logger::Env::default().default_filter_or("info");
-    env_logger::Builder::from_env(default_filter)
-        .format_timestamp_secs()
-        .init();
+    let mut logger = env_logger::Builder::from_env(default_filter);
+    match log_format {
+        LogFormat::Text => logger.format_timestamp_secs(),
+        LogFormat::Json => logger.format(|buf, record| {
+            let line = json_log_line(Utc::now(), record.level(), record.target(), &record.args().to_string());
+            writeln!(buf, "{}", line)
+        }),
+    };
+    logger.init();
 
-    if let Some(info) = loaded_env.as_ref() {
+    if let Some(info) = cli.loaded_env.as_ref() {
         let origin = if info.explicit { "CLI-specified" } else { "default" };
         info!("Environment loaded from {} .env file: {}", origin, info.path.display());
     }
@@ -370,7 +947,21 @@ fn main() {
         env!("CARGO_PKG_VERSION"),
         env!("BUILD_TIME_GIT_HASH")
     );
-    if let Err(e) = run() {
+    let result = match (
+        cli.export.as_ref(),
+        cli.measurement_export.as_ref(),
+        cli.measurement_import.as_ref(),
+        cli.parse_check.as_ref(),
+    ) {
+        (Some(export), _, _, _) => run_export(export),
+        (None, Some(export), _, _) => run_measurement_export(export),
+        (None, None, Some(import), _) => run_measurement_import(import),
+        (None, None, None, Some(check)) => parse_check::run(&check.path, &check.type_name),
+        (None, None, None, None) if cli.generate_fake_data => run_generate_fake_data(),
+        (None, None, None, None) if cli.healthcheck => run_healthcheck(),
+        (None, None, None, None) => run(cli.once),
+    };
+    if let Err(e) = result {
         error!("fatal: {}", e);
         std::process::exit(1);
     }
@@ -1,5 +1,26 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    air_comfort_measurements (id, time) {
+        id -> Int8,
+        time -> Timestamptz,
+        home_id -> Int8,
+        zone_id -> Int8,
+        temperature_level -> Nullable<Text>,
+        humidity_level -> Nullable<Text>,
+        freshness -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    backfill_progress (zone_id) {
+        zone_id -> Int8,
+        home_id -> Int8,
+        last_completed_day -> Date,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     climate_measurements (id, time) {
         id -> Int8,
@@ -17,6 +38,56 @@ diesel::table! {
         window_open -> Nullable<Bool>,
         battery_low -> Nullable<Bool>,
         connection_up -> Nullable<Bool>,
+        ingest_lag_secs -> Nullable<Float8>,
+        inside_temp_precision_c -> Nullable<Float8>,
+        tado_mode -> Nullable<Text>,
+        control_mode -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    climate_measurements_daily (home_id, zone_id, bucket) {
+        bucket -> Timestamptz,
+        home_id -> Int8,
+        zone_id -> Int8,
+        avg_inside_temp_c -> Nullable<Float8>,
+        min_inside_temp_c -> Nullable<Float8>,
+        max_inside_temp_c -> Nullable<Float8>,
+        avg_humidity_pct -> Nullable<Float8>,
+        min_humidity_pct -> Nullable<Float8>,
+        max_humidity_pct -> Nullable<Float8>,
+        avg_setpoint_temp_c -> Nullable<Float8>,
+        min_setpoint_temp_c -> Nullable<Float8>,
+        max_setpoint_temp_c -> Nullable<Float8>,
+        avg_heating_power_pct -> Nullable<Float8>,
+    }
+}
+
+diesel::table! {
+    climate_measurements_hourly (home_id, zone_id, bucket) {
+        bucket -> Timestamptz,
+        home_id -> Int8,
+        zone_id -> Int8,
+        avg_inside_temp_c -> Nullable<Float8>,
+        min_inside_temp_c -> Nullable<Float8>,
+        max_inside_temp_c -> Nullable<Float8>,
+        avg_humidity_pct -> Nullable<Float8>,
+        min_humidity_pct -> Nullable<Float8>,
+        max_humidity_pct -> Nullable<Float8>,
+        avg_setpoint_temp_c -> Nullable<Float8>,
+        min_setpoint_temp_c -> Nullable<Float8>,
+        max_setpoint_temp_c -> Nullable<Float8>,
+        avg_heating_power_pct -> Nullable<Float8>,
+    }
+}
+
+diesel::table! {
+    collector_instances (id) {
+        id -> Int8,
+        hostname -> Text,
+        pid -> Int4,
+        started_at -> Timestamptz,
+        last_seen -> Timestamptz,
     }
 }
 
@@ -34,6 +105,8 @@ diesel::table! {
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
         device_type_desc -> Nullable<Text>,
+        temperature_offset_c -> Nullable<Float8>,
+        mounting_state -> Nullable<Text>,
     }
 }
 
@@ -50,6 +123,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    flow_temperature_optimization (home_id) {
+        home_id -> Int8,
+        max_flow_temperature -> Nullable<Int8>,
+        auto_adaptation_enabled -> Nullable<Bool>,
+        auto_adaptation_max_flow_temperature -> Nullable<Int8>,
+        max_flow_temperature_min -> Nullable<Int8>,
+        max_flow_temperature_max -> Nullable<Int8>,
+        open_therm_device_serial_number -> Nullable<Text>,
+        updated_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     homes (id) {
         id -> Int8,
@@ -67,6 +153,49 @@ diesel::table! {
         longitude -> Nullable<Float8>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        away_radius_m -> Nullable<Float8>,
+        incident_detection_enabled -> Nullable<Bool>,
+        feature_flags -> Nullable<Jsonb>,
+        incident_detection_supported -> Nullable<Bool>,
+    }
+}
+
+diesel::table! {
+    installations (id) {
+        id -> Int8,
+        home_id -> Int8,
+        tado_installation_id -> Int8,
+        installation_type -> Nullable<Text>,
+        state -> Nullable<Text>,
+        revision -> Nullable<Int8>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    mobile_devices (id) {
+        id -> Int8,
+        home_id -> Int8,
+        tado_mobile_device_id -> Int8,
+        name -> Nullable<Text>,
+        platform -> Nullable<Text>,
+        model -> Nullable<Text>,
+        geo_tracking_enabled -> Nullable<Bool>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    presence_measurements (id, time) {
+        id -> Int8,
+        time -> Timestamptz,
+        home_id -> Int8,
+        mobile_device_id -> Int8,
+        at_home -> Nullable<Bool>,
+        stale -> Nullable<Bool>,
+        relative_distance_from_home_fence -> Nullable<Float8>,
     }
 }
 
@@ -101,6 +230,8 @@ diesel::table! {
         outside_temp_c -> Nullable<Float8>,
         solar_intensity_pct -> Nullable<Float8>,
         weather_state -> Nullable<Text>,
+        ingest_lag_secs -> Nullable<Float8>,
+        reported_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -112,6 +243,29 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    zone_type_history (id) {
+        id -> Int8,
+        zone_id -> Int8,
+        changed_at -> Timestamptz,
+        previous_type -> Nullable<Text>,
+        new_type -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    zone_weather_measurements (id, time) {
+        id -> Int8,
+        time -> Timestamptz,
+        home_id -> Int8,
+        zone_id -> Int8,
+        source -> Text,
+        outside_temp_c -> Nullable<Float8>,
+        solar_intensity_pct -> Nullable<Float8>,
+        weather_state -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     zones (id) {
         id -> Int8,
@@ -122,31 +276,59 @@ diesel::table! {
         date_created -> Nullable<Timestamptz>,
         created_at -> Timestamptz,
         updated_at -> Timestamptz,
+        capabilities -> Nullable<Jsonb>,
     }
 }
 
+diesel::joinable!(air_comfort_measurements -> homes (home_id));
+diesel::joinable!(air_comfort_measurements -> zones (zone_id));
+diesel::joinable!(backfill_progress -> homes (home_id));
+diesel::joinable!(backfill_progress -> zones (zone_id));
 diesel::joinable!(climate_measurements -> devices (device_id));
 diesel::joinable!(climate_measurements -> homes (home_id));
 diesel::joinable!(climate_measurements -> zones (zone_id));
+diesel::joinable!(climate_measurements_daily -> homes (home_id));
+diesel::joinable!(climate_measurements_daily -> zones (zone_id));
+diesel::joinable!(climate_measurements_hourly -> homes (home_id));
+diesel::joinable!(climate_measurements_hourly -> zones (zone_id));
 diesel::joinable!(devices -> homes (home_id));
 diesel::joinable!(events -> devices (device_id));
 diesel::joinable!(events -> homes (home_id));
 diesel::joinable!(events -> zones (zone_id));
+diesel::joinable!(flow_temperature_optimization -> homes (home_id));
+diesel::joinable!(installations -> homes (home_id));
+diesel::joinable!(mobile_devices -> homes (home_id));
+diesel::joinable!(presence_measurements -> homes (home_id));
+diesel::joinable!(presence_measurements -> mobile_devices (mobile_device_id));
 diesel::joinable!(user_homes -> homes (home_id));
 diesel::joinable!(user_homes -> users (user_id));
 diesel::joinable!(weather_measurements -> homes (home_id));
 diesel::joinable!(zone_devices -> devices (device_id));
 diesel::joinable!(zone_devices -> zones (zone_id));
+diesel::joinable!(zone_type_history -> zones (zone_id));
+diesel::joinable!(zone_weather_measurements -> homes (home_id));
+diesel::joinable!(zone_weather_measurements -> zones (zone_id));
 diesel::joinable!(zones -> homes (home_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    air_comfort_measurements,
+    backfill_progress,
     climate_measurements,
+    climate_measurements_daily,
+    climate_measurements_hourly,
+    collector_instances,
     devices,
     events,
+    flow_temperature_optimization,
     homes,
+    installations,
+    mobile_devices,
+    presence_measurements,
     user_homes,
     users,
     weather_measurements,
     zone_devices,
+    zone_type_history,
+    zone_weather_measurements,
     zones,
 );
@@ -0,0 +1,179 @@
+use crate::client::TadoClient;
+use crate::models::tado;
+use crate::services::refs;
+use diesel::PgConnection;
+use log::{info, warn};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The Tado clients of every configured account and the account that owns each discovered home.
+///
+/// A home visible to several accounts (e.g. a shared household) is collected once, through the first account
+/// that lists it.
+pub struct Accounts {
+    clients: Vec<TadoClient>,
+    /// Tado home id -> index into `clients`.
+    owners: BTreeMap<i64, usize>,
+}
+
+impl Accounts {
+    /// Discovers each account's homes via `get_me`.
+    pub fn discover(clients: Vec<TadoClient>) -> Result<Self, String> {
+        let mut homes_per_account = Vec::with_capacity(clients.len());
+        for (index, client) in clients.iter().enumerate() {
+            let me = client
+                .get_me()
+                .map_err(|e| format!("get_me failed for account {}: {}", index + 1, e))?;
+            let home_ids = home_ids_of(&me);
+            info!("Account {}: discovered {} home(s)", index + 1, home_ids.len());
+            homes_per_account.push(home_ids);
+        }
+        Ok(Accounts {
+            clients,
+            owners: assign_owners(&homes_per_account),
+        })
+    }
+
+    /// Narrows the discovered homes to `requested` (`TADO_HOME_IDS`), or keeps all when unset, and returns the
+    /// homes to collect. Errors when nothing is left, naming what was requested and what exists.
+    pub fn select_homes(&mut self, requested: Option<&BTreeSet<i64>>) -> Result<Vec<i64>, String> {
+        let selected = select_homes(&self.home_ids(), requested)?;
+        self.owners.retain(|home_id, _| selected.contains(home_id));
+        Ok(selected)
+    }
+
+    /// Every discovered home across all accounts, in ascending order.
+    pub fn home_ids(&self) -> Vec<i64> {
+        self.owners.keys().copied().collect()
+    }
+
+    pub fn client_for(&self, home_id: i64) -> Option<&TadoClient> {
+        self.owners.get(&home_id).map(|index| &self.clients[*index])
+    }
+
+    pub fn clients(&self) -> &[TadoClient] {
+        &self.clients
+    }
+
+    /// Runs the reference sync once per account over the homes it owns, with a fresh `get_me` so membership
+    /// changes are picked up.
+    pub fn sync_refs(
+        &self,
+        conn: &mut PgConnection,
+        database_url: &str,
+        options: refs::SyncOptions,
+    ) -> Result<(), String> {
+        for (index, client) in self.clients.iter().enumerate() {
+            let me = client
+                .get_me()
+                .map_err(|e| format!("get_me failed for account {}: {}", index + 1, e))?;
+            let home_ids: Vec<i64> = self
+                .owners
+                .iter()
+                .filter(|(_, owner)| **owner == index)
+                .map(|(home_id, _)| *home_id)
+                .collect();
+            refs::sync_all(conn, database_url, client, &me, &home_ids, options)?;
+        }
+        Ok(())
+    }
+}
+
+fn home_ids_of(me: &tado::User) -> Vec<i64> {
+    let mut home_ids = me
+        .homes
+        .as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|hb| hb.id.map(|id| id.0))
+        .collect::<Vec<_>>();
+    home_ids.sort_unstable();
+    home_ids.dedup();
+    home_ids
+}
+
+fn select_homes(discovered: &[i64], requested: Option<&BTreeSet<i64>>) -> Result<Vec<i64>, String> {
+    if discovered.is_empty() {
+        return Err(match requested {
+            Some(requested) => format!(
+                "No homes found on the Tado account(s) (TADO_HOME_IDS requested {:?}); ensure the account has homes",
+                requested
+            ),
+            None => "No homes found on the Tado account(s); ensure the account has homes".to_string(),
+        });
+    }
+    let Some(requested) = requested else {
+        return Ok(discovered.to_vec());
+    };
+    let selected: Vec<i64> = discovered
+        .iter()
+        .copied()
+        .filter(|home_id| requested.contains(home_id))
+        .collect();
+    if selected.is_empty() {
+        return Err(format!(
+            "TADO_HOME_IDS matched none of the account homes: requested {:?}, available {:?}",
+            requested, discovered
+        ));
+    }
+    let missing: Vec<i64> = requested
+        .iter()
+        .copied()
+        .filter(|home_id| !discovered.contains(home_id))
+        .collect();
+    if !missing.is_empty() {
+        warn!(
+            "TADO_HOME_IDS lists home(s) {:?} that no account can access; available {:?}",
+            missing, discovered
+        );
+    }
+    Ok(selected)
+}
+
+/// Maps each home to the first account (by index) that lists it.
+fn assign_owners(homes_per_account: &[Vec<i64>]) -> BTreeMap<i64, usize> {
+    let mut owners: BTreeMap<i64, usize> = BTreeMap::new();
+    for (index, home_ids) in homes_per_account.iter().enumerate() {
+        for home_id in home_ids {
+            let owner = *owners.entry(*home_id).or_insert(index);
+            if owner != index {
+                warn!(
+                    "Home {} is visible to accounts {} and {}; collecting it through account {}",
+                    home_id,
+                    owner + 1,
+                    index + 1,
+                    owner + 1
+                );
+            }
+        }
+    }
+    owners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_homes_belong_to_the_first_account() {
+        let owners = assign_owners(&[vec![10, 20], vec![20, 30], vec![]]);
+        assert_eq!(owners, BTreeMap::from([(10, 0), (20, 0), (30, 1)]));
+    }
+
+    #[test]
+    fn empty_home_selection_explains_why() {
+        let requested = BTreeSet::from([7, 20]);
+        assert_eq!(select_homes(&[10, 20], None), Ok(vec![10, 20]));
+        assert_eq!(select_homes(&[10, 20], Some(&requested)), Ok(vec![20]));
+
+        let no_homes = select_homes(&[], None).unwrap_err();
+        assert!(no_homes.starts_with("No homes found"), "{no_homes}");
+        let no_homes_requested = select_homes(&[], Some(&requested)).unwrap_err();
+        assert!(no_homes_requested.contains("requested {7, 20}"), "{no_homes_requested}");
+
+        let filtered_out = select_homes(&[10, 30], Some(&requested)).unwrap_err();
+        assert_eq!(
+            filtered_out,
+            "TADO_HOME_IDS matched none of the account homes: requested {7, 20}, available [10, 30]"
+        );
+    }
+}
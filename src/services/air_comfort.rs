@@ -0,0 +1,111 @@
+use crate::client::TadoClient;
+use crate::db::models::NewAirComfortMeasurement;
+use crate::models::tado::{self, HomeId};
+use crate::schema;
+use crate::services::ingest;
+use crate::utils::serde_enum_name;
+use chrono::{DateTime, Utc};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::debug;
+use std::collections::BTreeMap;
+
+/// Records one air comfort row per zone of the home, each carrying the home's air freshness.
+///
+/// Like presence, the endpoint carries no reading time, so rows are stamped with `now`. `comfort` is empty while
+/// the home has no connection; nothing is written then rather than rows of nulls. Returns the number of inserted
+/// rows.
+pub fn collect(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_home_id: i64,
+    home_id: i64,
+    zone_id_map: &BTreeMap<i64, i64>,
+    now: DateTime<Utc>,
+    dry_run: bool,
+) -> Result<usize, String> {
+    use schema::air_comfort_measurements::dsl as A;
+
+    let air_comfort = client
+        .get_air_comfort(HomeId(home_id))
+        .map_err(|e| format!("get_air_comfort({}) failed: {}", home_id, e))?;
+    let rows = air_comfort_rows(&air_comfort, db_home_id, zone_id_map, now);
+    if rows.is_empty() {
+        debug!("Air comfort: no room comfort reported for home {}", home_id);
+        return Ok(0);
+    }
+    if ingest::skip_for_dry_run(dry_run, "air_comfort_measurements", &rows) {
+        return Ok(rows.len());
+    }
+    let inserted = diesel::insert_into(A::air_comfort_measurements)
+        .values(&rows)
+        .on_conflict((A::zone_id, A::time))
+        .do_nothing()
+        .execute(conn)
+        .map_err(|e| format!("insert air comfort rows failed: {}", e))?;
+    debug!("Air comfort: home {} inserted {} row(s)", home_id, inserted);
+    Ok(inserted)
+}
+
+/// Rows for the rooms that map to a known zone; rooms of zones added since the last reference sync are skipped.
+fn air_comfort_rows(
+    air_comfort: &tado::AirComfort,
+    db_home_id: i64,
+    zone_id_map: &BTreeMap<i64, i64>,
+    now: DateTime<Utc>,
+) -> Vec<NewAirComfortMeasurement> {
+    let freshness = air_comfort
+        .freshness
+        .as_ref()
+        .and_then(|f| f.value.as_ref())
+        .and_then(serde_enum_name);
+    air_comfort
+        .comfort
+        .as_deref()
+        .unwrap_or(&[])
+        .iter()
+        .filter_map(|room| {
+            let db_zone_id = room.room_id.and_then(|id| zone_id_map.get(&id.0).copied())?;
+            Some(NewAirComfortMeasurement {
+                time: now,
+                home_id: db_home_id,
+                zone_id: db_zone_id,
+                temperature_level: room.temperature_level.as_ref().and_then(serde_enum_name),
+                humidity_level: room.humidity_level.as_ref().and_then(serde_enum_name),
+                freshness: freshness.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn rooms_map_to_zones_and_empty_comfort_writes_nothing() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let zones = BTreeMap::from([(1, 101), (2, 102)]);
+        let air_comfort: tado::AirComfort = serde_json::from_str(
+            r#"{"freshness": {"value": "FAIR", "lastOpenWindow": "2024-03-01T10:00:00Z"},
+                "comfort": [
+                    {"roomId": 1, "temperatureLevel": "COMFY", "humidityLevel": "DRY",
+                     "coordinate": {"radial": 0.4, "angular": 120}},
+                    {"roomId": 9, "temperatureLevel": "COLD", "humidityLevel": "HUMID"}
+                ]}"#,
+        )
+        .expect("parse air comfort");
+
+        let rows = air_comfort_rows(&air_comfort, 3, &zones, now);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].zone_id, 101);
+        assert_eq!(rows[0].temperature_level.as_deref(), Some("COMFY"));
+        assert_eq!(rows[0].humidity_level.as_deref(), Some("DRY"));
+        assert_eq!(rows[0].freshness.as_deref(), Some("FAIR"));
+
+        let disconnected: tado::AirComfort =
+            serde_json::from_str(r#"{"freshness": {"value": "FRESH"}, "comfort": []}"#).expect("parse air comfort");
+        assert!(air_comfort_rows(&disconnected, 3, &zones, now).is_empty());
+    }
+}
@@ -0,0 +1,548 @@
+//! Minimal read-only JSON API over the collected data (`API_LISTEN_ADDR`), for dashboards and embedders that
+//! would rather not write SQL:
+//!
+//! - `GET /homes`
+//! - `GET /homes/{tado_home_id}/zones`
+//! - `GET /homes/{tado_home_id}/zones/{tado_zone_id}/measurements?from=...&to=...` (RFC 3339, default last 24h,
+//!   at most 31 days)
+//! - `GET /homes/{tado_home_id}/zones/{tado_zone_id}/readings?from=...&to=...` (same range rules)
+//! - `GET /homes/{tado_home_id}/events?type=...&from=...&to=...` (`type` required, e.g. `OPEN_WINDOW_DETECTED`)
+//!
+//! Every list takes `limit` (default 100, at most 1000) and `offset` (at most 1000000), and answers with
+//! `{"items": [...], "limit": .., "offset": .., "next_offset": ..}`; `next_offset` is null on the last page.
+//! Measurements are the merged realtime/historical series of `query::merged_climate`; readings are the stored
+//! zone-level rows of both sources as is (`query::climate_between`). Readings and events are paged in SQL; the
+//! merged series is built for the whole range and paged from memory, which the range cap keeps bounded.
+
+use crate::services::query;
+use chrono::{DateTime, Duration, Utc};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::{debug, info, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+const CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+const MAX_OFFSET: i64 = 1_000_000;
+/// Measurement range when the request gives no `from`.
+const DEFAULT_RANGE: Duration = Duration::hours(24);
+/// Longest range a series request may ask for.
+const MAX_RANGE: Duration = Duration::days(31);
+
+/// A parsed request the API can answer.
+#[derive(Debug, Clone, PartialEq)]
+enum Route {
+    Homes(Page),
+    Zones {
+        home_id: i64,
+        page: Page,
+    },
+    Measurements {
+        home_id: i64,
+        zone_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        page: Page,
+    },
+    Readings {
+        home_id: i64,
+        zone_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        page: Page,
+    },
+    Events {
+        home_id: i64,
+        event_type: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        page: Page,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Page {
+    limit: i64,
+    offset: i64,
+}
+
+/// A response that is not a page of items: status line and error message.
+#[derive(Debug, Clone, PartialEq)]
+struct Failure {
+    status: &'static str,
+    message: String,
+}
+
+impl Failure {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Failure {
+            status: "400 Bad Request",
+            message: message.into(),
+        }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Failure {
+            status: "404 Not Found",
+            message: message.into(),
+        }
+    }
+}
+
+/// Serves the API on `addr` from a background thread with its own database connection.
+pub fn serve(addr: &str, database_url: &str) -> Result<(), String> {
+    let listener = TcpListener::bind(addr).map_err(|e| format!("binding API listener on {} failed: {}", addr, e))?;
+    info!("API: serving read-only JSON on http://{}/homes", addr);
+    let database_url = database_url.to_string();
+    thread::Builder::new()
+        .name("api".to_string())
+        .spawn(move || {
+            let mut conn: Option<PgConnection> = None;
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_connection(stream, &mut conn, &database_url) {
+                            debug!("API: connection failed: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("API: accepting connection failed: {}", e),
+                }
+            }
+        })
+        .map_err(|e| format!("spawning API thread failed: {}", e))?;
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, conn: &mut Option<PgConnection>, database_url: &str) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers so the client sees a clean response instead of a reset
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (status, body) = match respond(method, target, conn, database_url, Utc::now()) {
+        Ok(body) => ("200 OK", body),
+        Err(failure) => (failure.status, json!({ "error": failure.message }).to_string()),
+    };
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn respond(
+    method: &str,
+    target: &str,
+    conn: &mut Option<PgConnection>,
+    database_url: &str,
+    now: DateTime<Utc>,
+) -> Result<String, Failure> {
+    if method != "GET" {
+        return Err(Failure {
+            status: "405 Method Not Allowed",
+            message: "the API is read-only; only GET is supported".to_string(),
+        });
+    }
+    let route = parse_route(target, now)?;
+    if conn.is_none() {
+        *conn = Some(
+            PgConnection::establish(database_url).map_err(|e| unavailable(format!("DB connection failed: {}", e)))?,
+        );
+    }
+    let result = answer(conn.as_mut().expect("connection established above"), &route);
+    if let Err(failure) = &result
+        && failure.status.starts_with("503")
+    {
+        // Reconnect on the next request in case the connection itself broke
+        *conn = None;
+    }
+    result
+}
+
+fn unavailable(message: String) -> Failure {
+    warn!("API: {}", message);
+    Failure {
+        status: "503 Service Unavailable",
+        message,
+    }
+}
+
+fn answer(conn: &mut PgConnection, route: &Route) -> Result<String, Failure> {
+    match *route {
+        Route::Homes(page) => {
+            let homes = query::homes(conn, page.limit, page.offset).map_err(unavailable)?;
+            Ok(page_json(&homes, page, homes.len() as i64 == page.limit))
+        }
+        Route::Zones { home_id, page } => {
+            let home = find_home(conn, home_id)?;
+            let zones = query::zones(conn, home.id, page.limit, page.offset).map_err(unavailable)?;
+            Ok(page_json(&zones, page, zones.len() as i64 == page.limit))
+        }
+        Route::Measurements {
+            home_id,
+            zone_id,
+            from,
+            to,
+            page,
+        } => {
+            let (home, zone) = find_zone(conn, home_id, zone_id)?;
+            let series = query::merged_climate(conn, home.id, zone.id, from, to).map_err(unavailable)?;
+            Ok(slice_json(&series, page))
+        }
+        Route::Readings {
+            home_id,
+            zone_id,
+            from,
+            to,
+            page,
+        } => {
+            let (home, zone) = find_zone(conn, home_id, zone_id)?;
+            let rows = query::climate_between(conn, home.id, zone.id, (from, to), page.limit, page.offset)
+                .map_err(unavailable)?;
+            Ok(page_json(&rows, page, rows.len() as i64 == page.limit))
+        }
+        Route::Events {
+            home_id,
+            ref event_type,
+            from,
+            to,
+            page,
+        } => {
+            let home = find_home(conn, home_id)?;
+            let events = query::events_by_type(conn, home.id, event_type, (from, to), page.limit, page.offset)
+                .map_err(unavailable)?;
+            Ok(page_json(&events, page, events.len() as i64 == page.limit))
+        }
+    }
+}
+
+fn find_home(conn: &mut PgConnection, home_id: i64) -> Result<crate::db::models::Home, Failure> {
+    query::home_by_tado_id(conn, home_id)
+        .map_err(unavailable)?
+        .ok_or_else(|| Failure::not_found(format!("home {} not found", home_id)))
+}
+
+fn find_zone(
+    conn: &mut PgConnection,
+    home_id: i64,
+    zone_id: i64,
+) -> Result<(crate::db::models::Home, crate::db::models::Zone), Failure> {
+    let home = find_home(conn, home_id)?;
+    let zone = query::zone_by_tado_id(conn, home.id, zone_id)
+        .map_err(unavailable)?
+        .ok_or_else(|| Failure::not_found(format!("zone {} of home {} not found", zone_id, home_id)))?;
+    Ok((home, zone))
+}
+
+/// One page of a series loaded whole for its (capped) range, as merging needs all of it, so the page is cut from it.
+fn slice_json<T: Serialize>(series: &[T], page: Page) -> String {
+    let start = (page.offset as usize).min(series.len());
+    let end = start.saturating_add(page.limit as usize).min(series.len());
+    page_json(&series[start..end], page, end < series.len())
+}
+
+fn page_json<T: Serialize>(items: &[T], page: Page, more: bool) -> String {
+    json!({
+        "items": items,
+        "limit": page.limit,
+        "offset": page.offset,
+        "next_offset": more.then(|| page.offset + page.limit),
+    })
+    .to_string()
+}
+
+fn parse_route(target: &str, now: DateTime<Utc>) -> Result<Route, Failure> {
+    let (path, query_string) = target.split_once('?').unwrap_or((target, ""));
+    let params: Vec<(String, String)> = query_string
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+    let param = |name: &str| params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+
+    let page = Page {
+        limit: parse_param(param("limit"), "limit")?.unwrap_or(DEFAULT_LIMIT),
+        offset: parse_param(param("offset"), "offset")?.unwrap_or(0),
+    };
+    if !(1..=MAX_LIMIT).contains(&page.limit) {
+        return Err(Failure::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    if !(0..=MAX_OFFSET).contains(&page.offset) {
+        return Err(Failure::bad_request(format!(
+            "offset must be between 0 and {}",
+            MAX_OFFSET
+        )));
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let id = |segment: &str, what: &str| {
+        segment
+            .parse::<i64>()
+            .map_err(|_| Failure::bad_request(format!("{} id must be an integer, got '{}'", what, segment)))
+    };
+    match segments[..] {
+        ["homes"] => Ok(Route::Homes(page)),
+        ["homes", home_id, "events"] => {
+            let (from, to) = parse_range(param("from"), param("to"), now)?;
+            let event_type = param("type")
+                .filter(|t| !t.is_empty())
+                .ok_or_else(|| Failure::bad_request("type is required"))?;
+            Ok(Route::Events {
+                home_id: id(home_id, "home")?,
+                event_type: event_type.to_string(),
+                from,
+                to,
+                page,
+            })
+        }
+        ["homes", home_id, "zones"] => Ok(Route::Zones {
+            home_id: id(home_id, "home")?,
+            page,
+        }),
+        [
+            "homes",
+            home_id,
+            "zones",
+            zone_id,
+            series @ ("measurements" | "readings"),
+        ] => {
+            let (from, to) = parse_range(param("from"), param("to"), now)?;
+            let (home_id, zone_id) = (id(home_id, "home")?, id(zone_id, "zone")?);
+            Ok(if series == "measurements" {
+                Route::Measurements {
+                    home_id,
+                    zone_id,
+                    from,
+                    to,
+                    page,
+                }
+            } else {
+                Route::Readings {
+                    home_id,
+                    zone_id,
+                    from,
+                    to,
+                    page,
+                }
+            })
+        }
+        _ => Err(Failure::not_found(format!("no such endpoint: {}", path))),
+    }
+}
+
+/// `[from, to)` of a series request: `to` defaults to now and `from` to `DEFAULT_RANGE` before `to`; at most
+/// `MAX_RANGE` long.
+fn parse_range(
+    from: Option<&str>,
+    to: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Failure> {
+    let to = parse_time(to, "to")?.unwrap_or(now);
+    let from = parse_time(from, "from")?.unwrap_or(to - DEFAULT_RANGE);
+    if from >= to {
+        return Err(Failure::bad_request("from must be before to"));
+    }
+    if to - from > MAX_RANGE {
+        return Err(Failure::bad_request(format!(
+            "from and to must be at most {} days apart",
+            MAX_RANGE.num_days()
+        )));
+    }
+    Ok((from, to))
+}
+
+fn parse_param(value: Option<&str>, name: &str) -> Result<Option<i64>, Failure> {
+    value
+        .map(|v| {
+            v.parse::<i64>()
+                .map_err(|_| Failure::bad_request(format!("{} must be an integer, got '{}'", name, v)))
+        })
+        .transpose()
+}
+
+fn parse_time(value: Option<&str>, name: &str) -> Result<Option<DateTime<Utc>>, Failure> {
+    value
+        .map(|v| {
+            DateTime::parse_from_rfc3339(v)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|_| Failure::bad_request(format!("{} must be an RFC 3339 time, got '{}'", name, v)))
+        })
+        .transpose()
+}
+
+/// Decodes `%XX` escapes and `+` (space) in a query string component; malformed escapes are kept as is.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+            (None, b'+') => out.push(b' '),
+            (None, byte) => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn measurements_endpoint_pages_the_merged_series() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let route = parse_route(
+            "/homes/12/zones/3/measurements?from=2024-03-01T12%3A00%3A00%2B00%3A00&to=2024-03-01T13:00:00Z&limit=2",
+            now,
+        )
+        .expect("valid route");
+        let from = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let page = Page { limit: 2, offset: 0 };
+        assert_eq!(
+            route,
+            Route::Measurements {
+                home_id: 12,
+                zone_id: 3,
+                from,
+                to: from + Duration::hours(1),
+                page,
+            }
+        );
+
+        let series: Vec<query::MergedClimate> = (0..3)
+            .map(|i| query::MergedClimate {
+                time: from + Duration::minutes(15 * i),
+                inside_temp_c: Some(21.0 + i as f64),
+                window_open: Some(false),
+                ..Default::default()
+            })
+            .collect();
+        let body: serde_json::Value = serde_json::from_str(&slice_json(&series, page)).expect("json body");
+        assert_eq!(
+            body,
+            json!({
+                "items": [
+                    {"time": "2024-03-01T12:00:00Z", "inside_temp_c": 21.0, "humidity_pct": null,
+                     "setpoint_temp_c": null, "heating_power_pct": null, "ac_power_on": null, "ac_mode": null,
+                     "window_open": false},
+                    {"time": "2024-03-01T12:15:00Z", "inside_temp_c": 22.0, "humidity_pct": null,
+                     "setpoint_temp_c": null, "heating_power_pct": null, "ac_power_on": null, "ac_mode": null,
+                     "window_open": false}
+                ],
+                "limit": 2,
+                "offset": 0,
+                "next_offset": 2
+            })
+        );
+        let last: serde_json::Value =
+            serde_json::from_str(&slice_json(&series, Page { limit: 2, offset: 2 })).expect("json body");
+        assert_eq!(last["items"].as_array().map(Vec::len), Some(1));
+        assert_eq!(last["next_offset"], serde_json::Value::Null);
+
+        assert_eq!(
+            parse_route("/homes/12/zones/3/readings?to=2024-03-01T13:00:00Z", now),
+            Ok(Route::Readings {
+                home_id: 12,
+                zone_id: 3,
+                from: from + Duration::hours(1) - DEFAULT_RANGE,
+                to: from + Duration::hours(1),
+                page: Page {
+                    limit: DEFAULT_LIMIT,
+                    offset: 0
+                },
+            })
+        );
+
+        assert_eq!(
+            parse_route(
+                "/homes/12/events?type=OPEN_WINDOW_DETECTED&from=2024-03-01T12:00:00Z",
+                now
+            ),
+            Ok(Route::Events {
+                home_id: 12,
+                event_type: "OPEN_WINDOW_DETECTED".to_string(),
+                from,
+                to: now,
+                page: Page {
+                    limit: DEFAULT_LIMIT,
+                    offset: 0
+                },
+            })
+        );
+        assert_eq!(
+            parse_route("/homes/12/events", now).unwrap_err().status,
+            "400 Bad Request"
+        );
+
+        // Defaults, and requests the API refuses
+        assert_eq!(
+            parse_route("/homes", now),
+            Ok(Route::Homes(Page {
+                limit: DEFAULT_LIMIT,
+                offset: 0
+            }))
+        );
+        assert_eq!(
+            parse_route("/homes/x/zones", now).unwrap_err().status,
+            "400 Bad Request"
+        );
+        assert_eq!(
+            parse_route("/homes?limit=5000", now).unwrap_err().status,
+            "400 Bad Request"
+        );
+        for offset in ["-1", "1000001", "9223372036854775807"] {
+            assert_eq!(
+                parse_route(&format!("/homes?offset={}", offset), now)
+                    .unwrap_err()
+                    .status,
+                "400 Bad Request",
+                "{offset}"
+            );
+        }
+        assert_eq!(
+            parse_route(
+                "/homes/12/zones/3/measurements?from=2000-01-01T00:00:00Z&to=2024-03-01T00:00:00Z",
+                now
+            )
+            .unwrap_err()
+            .status,
+            "400 Bad Request"
+        );
+        assert_eq!(parse_route("/devices", now).unwrap_err().status, "404 Not Found");
+    }
+}
@@ -1,36 +1,80 @@
-use crate::client::{TadoClient, TadoClientError};
-use crate::db::models::event_source;
-use crate::db::models::{NewClimateMeasurement, NewWeatherMeasurement};
+use crate::client::{self, RateLimiter, TadoClient};
+use crate::config::DisabledWeatherFields;
+use crate::db::models::{NewBackfillProgress, NewClimateMeasurement, NewWeatherMeasurement};
+use crate::db::models::{control_mode, event_source};
 use crate::models::tado::{self, HomeId, ZoneId};
 use crate::schema;
-use crate::services::ingest::{insert_climate_measurements, insert_weather_measurements};
-use crate::utils::{determine_zone_start_time, serde_enum_name};
+use crate::services::ingest::{
+    insert_climate_measurements, insert_weather_measurements, insert_zone_weather_measurements,
+};
+use crate::services::query::{self, Gap};
+use crate::services::report_cache::ReportCache;
+use crate::utils::{determine_zone_start_time, run_bounded, serde_enum_name, temperature_celsius};
 use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, SecondsFormat, Utc};
-use diesel::dsl::max;
-use diesel::prelude::*;
 use diesel::PgConnection;
+use diesel::prelude::*;
 use log::{debug, info, warn};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::num::NonZeroU32;
-use std::thread;
-use std::time::{Duration as StdDuration, Instant};
+use std::path::Path;
 
 const BOGUS_TEMP_C: f64 = 20.0;
 const BOGUS_HUMIDITY_FRACTION: f64 = 0.5; // as delivered by the API (UNIT_INTERVAL)
 const BOGUS_HUMIDITY_PERCENT: f64 = 50.0; // after we scale to percentages for inserts
 const FLOAT_EPSILON: f64 = 1e-6;
 
-fn approx_eq(lhs: f64, rhs: f64) -> bool {
-    (lhs - rhs).abs() <= FLOAT_EPSILON
+/// Where the backfill gets a zone's day report: the API, or the on-disk `ReportCache` wrapped around it.
+///
+/// Shared by the zone workers of a home, hence `Sync`.
+pub trait DayReportSource: Sync {
+    fn day_report(&self, home_id: HomeId, zone_id: ZoneId, day: NaiveDate) -> Result<tado::DayReport, String>;
+}
+
+impl<S: DayReportSource + ?Sized> DayReportSource for &S {
+    fn day_report(&self, home_id: HomeId, zone_id: ZoneId, day: NaiveDate) -> Result<tado::DayReport, String> {
+        (**self).day_report(home_id, zone_id, day)
+    }
+}
+
+/// Day reports from the API, limited to `BACKFILL_REQUESTS_PER_SECOND` on top of the client's own limiter. Both
+/// limiters are shared, so the rates hold for the whole home however many zone workers fetch at once.
+struct ApiDayReports<'a> {
+    client: &'a TadoClient,
+    limiter: Option<RateLimiter>,
+}
+
+impl DayReportSource for ApiDayReports<'_> {
+    fn day_report(&self, home_id: HomeId, zone_id: ZoneId, day: NaiveDate) -> Result<tado::DayReport, String> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire();
+        }
+        self.client
+            .get_zone_day_report(home_id, zone_id, Some(day))
+            .map_err(|e| {
+                format!(
+                    "get_zone_day_report({}, {}, {}) failed: {}",
+                    home_id.0, zone_id.0, day, e
+                )
+            })
+    }
 }
 
-#[derive(Debug, Clone)]
-struct Gap {
+/// A zone whose gaps are due for backfill, resolved before the zones fan out to workers.
+struct ZoneJob {
+    zone_id: ZoneId,
+    db_zone_id: i64,
+    /// Gap search start: the zone's creation (or `BACKFILL_FROM_DATE`), past any recorded progress.
     start: DateTime<Utc>,
-    end: DateTime<Utc>,
-    start_inclusive: bool,
+    zone_start_day: NaiveDate,
 }
 
+fn approx_eq(lhs: f64, rhs: f64) -> bool {
+    (lhs - rhs).abs() <= FLOAT_EPSILON
+}
+
+/// `[from, to)` bounds for historical weather inserts.
+type WeatherWindow = (DateTime<Utc>, DateTime<Utc>);
+
 fn format_gap_range(gap: &Gap) -> String {
     let start_bracket = if gap.start_inclusive { '[' } else { '(' };
     let start = gap.start.to_rfc3339_opts(SecondsFormat::Secs, true);
@@ -75,7 +119,7 @@ fn is_day_report_bogus(report: &tado::DayReport) -> bool {
             .and_then(|series| series.data_points.as_ref())
         {
             for point in points {
-                if let Some(value) = point.value.as_ref().and_then(|t| t.celsius) {
+                if let Some(value) = point.value.as_ref().and_then(temperature_celsius) {
                     indoor_had_data = true;
                     if !approx_eq(value, BOGUS_TEMP_C) {
                         indoor_has_real_signal = true;
@@ -117,7 +161,7 @@ fn is_day_report_bogus(report: &tado::DayReport) -> bool {
                 if interval.value.is_some() {
                     outdoor_had_data = true;
                     if let Some(value) = interval.value.as_ref() {
-                        let has_temp = value.temperature.as_ref().and_then(|temp| temp.celsius).is_some();
+                        let has_temp = value.temperature.as_ref().and_then(temperature_celsius).is_some();
                         let has_state = value.state.is_some();
                         if has_temp || has_state {
                             outdoor_has_real_signal = true;
@@ -179,6 +223,7 @@ fn remove_leading_bogus_rows(rows: &mut BTreeMap<DateTime<Utc>, NewClimateMeasur
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_for_home(
     conn: &mut PgConnection,
     client: &TadoClient,
@@ -187,6 +232,15 @@ pub fn run_for_home(
     backfill_requests_per_second: Option<NonZeroU32>,
     backfill_sample_rate: Option<NonZeroU32>,
     min_gap: Duration,
+    weather_disabled_fields: DisabledWeatherFields,
+    weather_per_zone: bool,
+    verify: bool,
+    ignore_progress: bool,
+    trim_leading_bogus: bool,
+    reports_dir: Option<&Path>,
+    database_url: &str,
+    concurrency: usize,
+    dry_run: bool,
 ) -> Result<(), String> {
     // Fetch zones to decide backfill per zone
     let zones = client
@@ -208,22 +262,16 @@ pub fn run_for_home(
 
     // Compute weather backfill window once per home (avoid extra API calls later),
     // clamping the start to the configured minimum date when provided.
-    let reference_zone = select_reference_zone_and_start(&zones);
-    if reference_zone.is_none() {
+    let reference_start = select_reference_zone_and_start(&zones).map(|(_, home_start)| home_start);
+    let weather_window = if reference_start.is_none() && min_start_dt_utc.is_none() {
         warn!(
-            "Backfill: home {} has no zone with a date_created timestamp; skipping weather backfill",
+            "Backfill: home {} has no zone with a date_created timestamp and no BACKFILL_FROM_DATE; skipping weather backfill",
             home_id.0
         );
-    }
-    let weather_window = reference_zone
-        .map(|(_, home_start)| {
-            let effective_start = match min_start_dt_utc {
-                Some(min_dt) if home_start < min_dt => min_dt,
-                _ => home_start,
-            };
-            compute_weather_backfill_window(conn, db_home_id, effective_start)
-        })
-        .transpose()?;
+        None
+    } else {
+        compute_weather_backfill_window(conn, db_home_id, reference_start, min_start_dt_utc)?
+    };
 
     for z in &zones {
         let Some(zid) = z.id else {
@@ -253,10 +301,28 @@ pub fn run_for_home(
         zone_id_map.len()
     );
 
-    let day_report_spacing =
-        backfill_requests_per_second.map(|limit| StdDuration::from_secs_f64(1.0 / limit.get() as f64));
+    // The backfill limiter only adds to the client's global limiter, so day reports run at the slower of the two
+    let day_report_rps = match (backfill_requests_per_second, client.effective_rps()) {
+        (Some(backfill), Some(global)) => Some(backfill.min(global)),
+        (backfill, global) => backfill.or(global),
+    };
+    if let Some(rps) = day_report_rps {
+        info!(
+            "Backfill: home {} day reports limited to {} request(s)/s",
+            home_id.0, rps
+        );
+    }
     let day_report_sample_rate = backfill_sample_rate;
+    let api_reports = ApiDayReports {
+        client,
+        limiter: backfill_requests_per_second.map(RateLimiter::new),
+    };
+    let reports: Box<dyn DayReportSource + '_> = match reports_dir {
+        Some(dir) => Box::new(ReportCache::new(dir, &api_reports)),
+        None => Box::new(&api_reports),
+    };
 
+    let mut jobs = Vec::new();
     for z in &zones {
         let Some(zone_id) = z.id else {
             continue;
@@ -274,7 +340,35 @@ pub fn run_for_home(
             Some(min_dt) if start < min_dt => min_dt,
             _ => start,
         };
-        let gaps_by_day = find_zone_gaps(conn, db_home_id, db_zone_id, start, min_gap)?;
+        let zone_start_day = start.date_naive();
+        let start = if ignore_progress {
+            start
+        } else {
+            let watermark = load_progress(conn, db_zone_id)?;
+            if let Some(day) = watermark {
+                debug!(
+                    "Backfill: zone {} completed through {}; resuming after it",
+                    zone_id.0, day
+                );
+            }
+            resume_start(start, watermark)
+        };
+        jobs.push(ZoneJob {
+            zone_id,
+            db_zone_id,
+            start,
+            zone_start_day,
+        });
+    }
+
+    let backfill_zone = |conn: &mut PgConnection, job: &ZoneJob| -> Result<(), String> {
+        let ZoneJob {
+            zone_id,
+            db_zone_id,
+            start,
+            zone_start_day,
+        } = *job;
+        let gaps_by_day = query::zone_gaps(conn, db_home_id, db_zone_id, start, min_gap)?;
         if gaps_by_day.is_empty() {
             debug!(
                 "Backfill: zone {} has no >={}min gaps after {}",
@@ -282,7 +376,7 @@ pub fn run_for_home(
                 min_gap.num_minutes(),
                 start
             );
-            continue;
+            return Ok(());
         }
 
         let total_gap_hours: f64 = gaps_by_day
@@ -303,167 +397,175 @@ pub fn run_for_home(
 
         backfill_zone_range(
             conn,
-            client,
+            &api_reports,
+            reports.as_ref(),
             home_id,
             db_home_id,
             zone_id,
             db_zone_id,
+            zone_start_day,
             weather_window,
-            day_report_spacing,
             day_report_sample_rate,
             &gaps_by_day,
-        )?;
-    }
-
-    Ok(())
-}
-
-fn find_zone_gaps(
-    conn: &mut PgConnection,
-    db_home_id: i64,
-    db_zone_id: i64,
-    start: DateTime<Utc>,
-    min_gap: Duration,
-) -> Result<BTreeMap<NaiveDate, Vec<Gap>>, String> {
-    use schema::climate_measurements::dsl as C;
-
-    let mut gaps: BTreeMap<NaiveDate, Vec<Gap>> = BTreeMap::new();
-    let now = Utc::now();
-    if start >= now {
-        return Ok(gaps);
-    }
-
-    let times: Vec<DateTime<Utc>> = C::climate_measurements
-        .filter(
-            C::home_id
-                .eq(db_home_id)
-                .and(C::zone_id.eq(db_zone_id))
-                .and(C::time.ge(start))
-                .and(C::time.lt(now)),
+            weather_disabled_fields,
+            weather_per_zone,
+            verify,
+            trim_leading_bogus,
+            dry_run,
         )
-        .select(C::time)
-        .order(C::time.asc())
-        .load(conn)
-        .map_err(|e| format!("query measurement timestamps failed: {}", e))?;
-
-    let mut cursor_date = start.date_naive();
-    let end_date = now.date_naive();
-    let mut idx = 0usize;
-
-    while cursor_date <= end_date {
-        let day_start = cursor_date.and_time(NaiveTime::MIN).and_utc();
-        let mut effective_start = day_start;
-        if cursor_date == start.date_naive() && start > day_start {
-            effective_start = start;
-        }
-
-        let day_end = if cursor_date == end_date {
-            now
-        } else {
-            cursor_date
-                .succ_opt()
-                .unwrap_or(NaiveDate::MAX)
-                .and_time(NaiveTime::MIN)
-                .and_utc()
-        };
-
-        if day_end <= effective_start {
-            if let Some(next) = cursor_date.succ_opt() {
-                cursor_date = next;
-                continue;
-            } else {
-                break;
-            }
-        }
+    };
 
-        while idx < times.len() && times[idx] < effective_start {
-            idx += 1;
+    if concurrency <= 1 || jobs.len() <= 1 {
+        for job in &jobs {
+            backfill_zone(conn, job)?;
         }
+        return Ok(());
+    }
 
-        let day_start_idx = idx;
-        while idx < times.len() && times[idx] < day_end {
-            idx += 1;
-        }
-        let day_times = &times[day_start_idx..idx];
+    // The weather window and both rate limiters were set up above, once for the home; workers only share them
+    info!(
+        "Backfill: home {} backfilling {} zone(s) on {} worker(s)",
+        home_id.0,
+        jobs.len(),
+        concurrency.min(jobs.len())
+    );
+    let results = run_bounded(
+        &jobs,
+        concurrency,
+        || PgConnection::establish(database_url).map_err(|e| format!("Backfill: DB connection failed: {}", e)),
+        backfill_zone,
+    );
+    results.into_iter().collect::<Result<Vec<()>, String>>()?;
 
-        let mut day_gaps: Vec<Gap> = Vec::new();
+    Ok(())
+}
 
-        if day_times.is_empty() {
-            if day_end - effective_start >= min_gap {
-                day_gaps.push(Gap {
-                    start: effective_start,
-                    end: day_end,
-                    start_inclusive: true,
-                });
-            }
-        } else {
-            let first = day_times[0];
-            if first - effective_start >= min_gap {
-                day_gaps.push(Gap {
-                    start: effective_start,
-                    end: first,
-                    start_inclusive: true,
-                });
-            }
+/// The zone's backfill watermark: the last day whose gaps were fully processed, if any.
+fn load_progress(conn: &mut PgConnection, db_zone_id: i64) -> Result<Option<NaiveDate>, String> {
+    use schema::backfill_progress::dsl as BP;
 
-            let mut prev = first;
-            for &ts in &day_times[1..] {
-                if ts - prev >= min_gap {
-                    day_gaps.push(Gap {
-                        start: prev,
-                        end: ts,
-                        start_inclusive: false,
-                    });
-                }
-                prev = ts;
-            }
+    BP::backfill_progress
+        .filter(BP::zone_id.eq(db_zone_id))
+        .select(BP::last_completed_day)
+        .first(conn)
+        .optional()
+        .map_err(|e| format!("fetch backfill progress for zone {} failed: {}", db_zone_id, e))
+}
 
-            if day_end - prev >= min_gap {
-                day_gaps.push(Gap {
-                    start: prev,
-                    end: day_end,
-                    start_inclusive: false,
-                });
-            }
-        }
+fn record_progress(conn: &mut PgConnection, db_home_id: i64, db_zone_id: i64, day: NaiveDate) -> Result<(), String> {
+    use schema::backfill_progress::dsl as BP;
 
-        if !day_gaps.is_empty() {
-            gaps.insert(cursor_date, day_gaps);
-        }
+    let row = NewBackfillProgress {
+        zone_id: db_zone_id,
+        home_id: db_home_id,
+        last_completed_day: day,
+        updated_at: Utc::now(),
+    };
+    diesel::insert_into(BP::backfill_progress)
+        .values(&row)
+        .on_conflict(BP::zone_id)
+        .do_update()
+        .set((
+            BP::last_completed_day.eq(row.last_completed_day),
+            BP::updated_at.eq(row.updated_at),
+        ))
+        .execute(conn)
+        .map(|_| ())
+        .map_err(|e| format!("record backfill progress for zone {} failed: {}", db_zone_id, e))
+}
 
-        match cursor_date.succ_opt() {
-            Some(next) => cursor_date = next,
-            None => break,
-        }
+/// Where the gap search starts given the recorded watermark: the start of the day after it, unless the zone's
+/// own start is already later.
+fn resume_start(start: DateTime<Utc>, watermark: Option<NaiveDate>) -> DateTime<Utc> {
+    match watermark.and_then(|day| day.succ_opt()) {
+        Some(next) => start.max(next.and_time(NaiveTime::MIN).and_utc()),
+        None => start,
     }
+}
 
-    Ok(gaps)
+/// Whether finishing `day` may advance the watermark. Today is still being recorded, and a sampled run skips
+/// days on purpose, so neither counts as fully processed.
+fn day_completes_progress(day: NaiveDate, today: NaiveDate, sample_rate: Option<NonZeroU32>) -> bool {
+    day < today && sample_rate.is_none_or(|rate| rate.get() == 1)
 }
 
 fn compute_weather_backfill_window(
     conn: &mut PgConnection,
     db_home_id: i64,
-    start: DateTime<Utc>,
-) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    reference_start: Option<DateTime<Utc>>,
+    min_start: Option<DateTime<Utc>>,
+) -> Result<Option<WeatherWindow>, String> {
     use schema::weather_measurements::dsl as W;
     let last_any: Option<DateTime<Utc>> = W::weather_measurements
         .filter(W::home_id.eq(db_home_id))
-        .select(max(W::time))
+        .select(diesel::dsl::max(W::time))
         .first(conn)
         .map_err(|e| format!("query last weather timestamp failed: {}", e))?;
-    let base_from = last_any.map(|t| t + chrono::Duration::seconds(1)).unwrap_or(start);
-    let from = base_from.max(start);
-    let to = Utc::now();
-    Ok((from, to))
+    Ok(weather_backfill_window(
+        reference_start,
+        min_start,
+        last_any,
+        Utc::now(),
+    ))
+}
+
+/// Resolve the `[from, to)` weather backfill window.
+///
+/// The lower bound is the later of the reference zone's creation and `BACKFILL_FROM_DATE`; either may be
+/// absent, so the configured date is honored even for homes without a usable zone. An already stored weather
+/// reading later than that bound moves `from` past it.
+fn weather_backfill_window(
+    reference_start: Option<DateTime<Utc>>,
+    min_start: Option<DateTime<Utc>>,
+    last_stored: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Option<WeatherWindow> {
+    let start = match (reference_start, min_start) {
+        (Some(reference), Some(min)) => reference.max(min),
+        (Some(reference), None) => reference,
+        (None, Some(min)) => min,
+        (None, None) => return None,
+    };
+    let from = last_stored
+        .map(|t| t + chrono::Duration::seconds(1))
+        .map_or(start, |after_last| after_last.max(start));
+    Some((from, now))
+}
+
+/// Earliest day at or after `first_day` that actually has gaps to fill.
+fn first_fillable_gap_day(gaps_by_day: &BTreeMap<NaiveDate, Vec<Gap>>, first_day: NaiveDate) -> Option<NaiveDate> {
+    gaps_by_day
+        .range(first_day..)
+        .find(|(_, gaps)| !gaps.is_empty())
+        .map(|(day, _)| *day)
+}
+
+/// Sampling keeps every N-th day of the year, plus the first fillable day so early gaps are never dropped.
+fn day_selected_by_sampling(day: NaiveDate, first_fillable_day: Option<NaiveDate>, rate: Option<NonZeroU32>) -> bool {
+    match rate {
+        None => true,
+        Some(rate) => Some(day) == first_fillable_day || day.ordinal().is_multiple_of(rate.get()),
+    }
+}
+
+/// Day range in which to look for the end of the placeholder period: from the zone's start (its `date_created`,
+/// already clamped to `BACKFILL_FROM_DATE`) through the last gap day. The placeholder period starts at creation,
+/// so anchoring at the first gap day would miss the transition whenever that gap opened long after it.
+fn bogus_search_range(
+    zone_start_day: NaiveDate,
+    gaps_by_day: &BTreeMap<NaiveDate, Vec<Gap>>,
+) -> Option<(NaiveDate, NaiveDate)> {
+    let first_gap_day = *gaps_by_day.keys().next()?;
+    let last_gap_day = *gaps_by_day.keys().next_back()?;
+    Some((zone_start_day.min(first_gap_day), last_gap_day))
 }
 
 fn find_first_non_bogus_day(
-    client: &TadoClient,
+    reports: &dyn DayReportSource,
     home_id: HomeId,
     zone_id: ZoneId,
     start: NaiveDate,
     end: NaiveDate,
-    min_spacing: Option<StdDuration>,
 ) -> Result<Option<NaiveDate>, String> {
     if start > end {
         return Ok(None);
@@ -483,12 +585,7 @@ fn find_first_non_bogus_day(
     while low <= high {
         let mid = low + (high - low) / 2;
         let day = start + Duration::days(mid);
-        let report = fetch_day_report_with_limit(client, home_id, zone_id, day, min_spacing).map_err(|e| {
-            format!(
-                "get_zone_day_report({}, {}, {}) failed: {}",
-                home_id.0, zone_id.0, day, e
-            )
-        })?;
+        let report = reports.day_report(home_id, zone_id, day)?;
 
         if is_day_report_bogus(&report) {
             low = mid + 1;
@@ -530,40 +627,44 @@ fn select_reference_zone_and_start(zones: &[tado::Zone]) -> Option<(ZoneId, Date
 #[allow(clippy::too_many_arguments)]
 fn backfill_zone_range(
     conn: &mut PgConnection,
-    client: &TadoClient,
+    api_reports: &dyn DayReportSource,
+    reports: &dyn DayReportSource,
     home_id: HomeId,
     db_home_id: i64,
     zone_id: ZoneId,
     db_zone_id: i64,
-    weather_window: Option<(DateTime<Utc>, DateTime<Utc>)>,
-    day_report_spacing: Option<StdDuration>,
+    zone_start_day: NaiveDate,
+    weather_window: Option<WeatherWindow>,
     day_report_sample_rate: Option<NonZeroU32>,
     gaps_by_day: &BTreeMap<NaiveDate, Vec<Gap>>,
+    weather_disabled_fields: DisabledWeatherFields,
+    weather_per_zone: bool,
+    verify: bool,
+    trim_leading_bogus: bool,
+    dry_run: bool,
 ) -> Result<(), String> {
     if gaps_by_day.is_empty() {
         return Ok(());
     }
 
-    let first_gap_day = *gaps_by_day.keys().next().unwrap();
-    let last_gap_day = *gaps_by_day.keys().next_back().unwrap();
+    let Some((search_start, search_end)) = bogus_search_range(zone_start_day, gaps_by_day) else {
+        return Ok(());
+    };
 
-    let first_valid_day = find_first_non_bogus_day(
-        client,
-        home_id,
-        zone_id,
-        first_gap_day,
-        last_gap_day,
-        day_report_spacing,
-    )?;
+    let first_valid_day = find_first_non_bogus_day(reports, home_id, zone_id, search_start, search_end)?;
 
     let Some(first_day) = first_valid_day else {
         info!(
             "Backfill: zone {} has only bogus historical data between {} and {}; skipping",
-            zone_id.0, first_gap_day, last_gap_day
+            zone_id.0, search_start, search_end
         );
         return Ok(());
     };
 
+    // `first_day` is the first non-bogus day, which need not have a gap itself. Exempt the first gap day
+    // at or after it from sampling instead, so the earliest fillable gap is never sampled out.
+    let first_fillable_day = first_fillable_gap_day(gaps_by_day, first_day);
+
     let mut inserted_total: usize = 0;
     let mut processed_days: u64 = 0;
 
@@ -575,199 +676,422 @@ fn backfill_zone_range(
             continue;
         }
 
-        if let Some(rate) = day_report_sample_rate
-            && *day != first_day
-            && day.ordinal() % rate.get() != 0
-        {
+        if !day_selected_by_sampling(*day, first_fillable_day, day_report_sample_rate) {
             continue;
         }
 
-        let report = fetch_day_report_with_limit(client, home_id, zone_id, *day, day_report_spacing).map_err(|e| {
+        let (report, request_id) = client::with_request_id(|| reports.day_report(home_id, zone_id, *day));
+        let report = report?;
+        processed_days += 1;
+
+        let (by_ts, weather_by_ts) = rows_from_day_report(
+            &report,
+            gaps,
+            db_home_id,
+            db_zone_id,
+            weather_window,
+            trim_leading_bogus,
+        );
+
+        let rows: Vec<NewClimateMeasurement> = by_ts.into_values().collect();
+        let inserted = insert_climate_measurements(conn, &rows, dry_run)?;
+        inserted_total += inserted;
+        match request_id {
+            Some(id) => info!(
+                "Backfill: request {} -> {} row(s) for zone {} on {}",
+                id, inserted, zone_id.0, day
+            ),
+            None => debug!(
+                "Backfill: cached report -> {} row(s) for zone {} on {}",
+                inserted, zone_id.0, day
+            ),
+        }
+
+        if verify {
+            // Always from the API: verification is about what Tado returns now, not what was cached
+            let refetched = api_reports
+                .day_report(home_id, zone_id, *day)
+                .map_err(|e| format!("verification re-fetch for zone {} on {} failed: {}", zone_id.0, day, e))?;
+            let expected: Vec<DateTime<Utc>> = rows_from_day_report(
+                &refetched,
+                gaps,
+                db_home_id,
+                db_zone_id,
+                weather_window,
+                trim_leading_bogus,
+            )
+            .0
+            .into_keys()
+            .collect();
+            let stored = stored_historical_times(conn, db_home_id, db_zone_id, &expected)?;
+            let missing = missing_timestamps(&expected, &stored);
+            if missing.is_empty() {
+                debug!(
+                    "Backfill: verified {} row(s) for zone {} on {}",
+                    expected.len(),
+                    zone_id.0,
+                    day
+                );
+            } else {
+                warn!(
+                    "Backfill: verification for zone {} on {} found {} of {} expected row(s) missing (first: {})",
+                    zone_id.0,
+                    day,
+                    missing.len(),
+                    expected.len(),
+                    missing[0].to_rfc3339_opts(SecondsFormat::Secs, true)
+                );
+            }
+        }
+
+        let weather_rows: Vec<NewWeatherMeasurement> = weather_by_ts
+            .into_values()
+            .map(|mut row| {
+                weather_disabled_fields.apply(&mut row);
+                row
+            })
+            .collect();
+        insert_weather_measurements(conn, &weather_rows, dry_run)?;
+        if weather_per_zone {
+            // Each zone's own day report carries the home weather, so the zone gets exactly its report's rows.
+            insert_zone_weather_measurements(conn, &weather_rows, &[db_zone_id], dry_run)?;
+        }
+
+        // A dry run wrote no rows, so the days it walked are not done
+        if !dry_run && day_completes_progress(*day, Utc::now().date_naive(), day_report_sample_rate) {
+            record_progress(conn, db_home_id, db_zone_id, *day)?;
+        }
+    }
+
+    info!(
+        "Backfill: zone {} complete ({} day(s), {} row(s) inserted)",
+        zone_id.0, processed_days, inserted_total
+    );
+
+    Ok(())
+}
+
+/// Fills the stretch between a zone's latest stored reading and now from day reports, so the realtime loop
+/// does not start next to a sparse tail. Bounded to `STARTUP_CATCHUP_WINDOW`, i.e. at most two day reports.
+/// Returns the number of inserted rows.
+#[allow(clippy::too_many_arguments)]
+pub fn catch_up_zone(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    home_id: HomeId,
+    db_home_id: i64,
+    zone_id: ZoneId,
+    db_zone_id: i64,
+    trim_leading_bogus: bool,
+    dry_run: bool,
+) -> Result<usize, String> {
+    use schema::climate_measurements::dsl as C;
+
+    let now = Utc::now();
+    let latest: Option<DateTime<Utc>> = C::climate_measurements
+        .filter(C::home_id.eq(db_home_id))
+        .filter(C::zone_id.eq(db_zone_id))
+        .filter(C::device_id.is_null())
+        .select(diesel::dsl::max(C::time))
+        .first(conn)
+        .map_err(|e| format!("query latest zone reading failed: {}", e))?;
+    let Some(gap) = startup_catchup_gap(latest, now) else {
+        debug!("Catch-up: zone {} is already current", zone_id.0);
+        return Ok(0);
+    };
+
+    let mut inserted = 0;
+    let mut day = gap.start.date_naive();
+    while day <= now.date_naive() {
+        let report = client.get_zone_day_report(home_id, zone_id, Some(day)).map_err(|e| {
             format!(
                 "get_zone_day_report({}, {}, {}) failed: {}",
                 home_id.0, zone_id.0, day, e
             )
         })?;
-        processed_days += 1;
+        let rows: Vec<NewClimateMeasurement> = rows_from_day_report(
+            &report,
+            std::slice::from_ref(&gap),
+            db_home_id,
+            db_zone_id,
+            None,
+            trim_leading_bogus,
+        )
+        .0
+        .into_values()
+        .collect();
+        inserted += insert_climate_measurements(conn, &rows, dry_run)?;
+        day = day.succ_opt().unwrap_or(NaiveDate::MAX);
+    }
+    info!(
+        "Catch-up: zone {} filled {} row(s) in {}",
+        zone_id.0,
+        inserted,
+        format_gap_range(&gap)
+    );
+    Ok(inserted)
+}
 
-        let mut by_ts: BTreeMap<DateTime<Utc>, NewClimateMeasurement> = BTreeMap::new();
-        let mut weather_by_ts: BTreeMap<DateTime<Utc>, NewWeatherMeasurement> = BTreeMap::new();
-
-        if let Some(md) = report.measured_data.as_ref() {
-            if let Some(temp_series) = md.inside_temperature.as_ref().and_then(|s| s.data_points.as_ref()) {
-                for dp in temp_series {
-                    if let (Some(ts), Some(val)) = (
-                        dp.timestamp.as_ref().cloned(),
-                        dp.value.as_ref().and_then(|t| t.celsius),
-                    ) {
-                        if !timestamp_in_any_gap(ts, gaps) {
-                            continue;
-                        }
-                        let entry = by_ts.entry(ts).or_insert_with(|| {
-                            NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
-                        });
-                        entry.inside_temp_c = Some(val);
-                    }
-                }
-            }
-            if let Some(h_series) = md.humidity.as_ref().and_then(|s| s.data_points.as_ref()) {
-                for dp in h_series {
-                    if let (Some(ts), Some(val)) = (dp.timestamp.as_ref().cloned(), dp.value) {
-                        if !timestamp_in_any_gap(ts, gaps) {
-                            continue;
-                        }
-                        let entry = by_ts.entry(ts).or_insert_with(|| {
-                            NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
-                        });
-                        entry.humidity_pct = Some(val * 100.0);
-                    }
-                }
-            }
-            if let Some(conn_series) = md
-                .measuring_device_connected
-                .as_ref()
-                .and_then(|s| s.data_intervals.as_ref())
-            {
-                for di in conn_series {
-                    if let (Some(ts), Some(val)) = (di.interval.from.as_ref().cloned(), di.value) {
-                        if !timestamp_in_any_gap(ts, gaps) {
-                            continue;
-                        }
-                        let entry = by_ts.entry(ts).or_insert_with(|| {
-                            NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
-                        });
-                        entry.connection_up = Some(val);
+/// Longest recent stretch the startup catch-up fills; anything older is left to the regular backfill.
+const STARTUP_CATCHUP_WINDOW: Duration = Duration::hours(6);
+/// Day reports carry one reading per 15 minutes, so shorter gaps have nothing to fill.
+const STARTUP_CATCHUP_MIN_GAP: Duration = Duration::minutes(15);
+
+/// The recent gap to fill on startup: after the latest stored reading, but no further back than the window.
+fn startup_catchup_gap(latest: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Option<Gap> {
+    let window_start = now - STARTUP_CATCHUP_WINDOW;
+    let gap = match latest {
+        Some(latest) if latest >= window_start => Gap {
+            start: latest,
+            end: now,
+            start_inclusive: false,
+        },
+        _ => Gap {
+            start: window_start,
+            end: now,
+            start_inclusive: true,
+        },
+    };
+    (gap.end - gap.start >= STARTUP_CATCHUP_MIN_GAP).then_some(gap)
+}
+
+/// `schedule` for the home-mode schedule, `manual` for an overlay and `away` for away mode; `None` for stripe
+/// types that do not say what drove the setting (e.g. `OPEN_WINDOW_DETECTED`).
+fn control_mode_of_stripe(stripe_type: &str) -> Option<&'static str> {
+    match stripe_type {
+        "HOME" => Some(control_mode::SCHEDULE),
+        "OVERLAY_ACTIVE" => Some(control_mode::MANUAL),
+        "AWAY" => Some(control_mode::AWAY),
+        _ => None,
+    }
+}
+
+/// Maps a day report onto climate and weather rows, keeping only timestamps inside `gaps` (and, for
+/// weather, inside `weather_window`) and, with `trim_leading_bogus`, dropping leading placeholder rows.
+fn rows_from_day_report(
+    report: &tado::DayReport,
+    gaps: &[Gap],
+    db_home_id: i64,
+    db_zone_id: i64,
+    weather_window: Option<WeatherWindow>,
+    trim_leading_bogus: bool,
+) -> (
+    BTreeMap<DateTime<Utc>, NewClimateMeasurement>,
+    BTreeMap<DateTime<Utc>, NewWeatherMeasurement>,
+) {
+    let mut by_ts: BTreeMap<DateTime<Utc>, NewClimateMeasurement> = BTreeMap::new();
+    let mut weather_by_ts: BTreeMap<DateTime<Utc>, NewWeatherMeasurement> = BTreeMap::new();
+
+    if let Some(md) = report.measured_data.as_ref() {
+        if let Some(temp_series) = md.inside_temperature.as_ref().and_then(|s| s.data_points.as_ref()) {
+            for dp in temp_series {
+                if let (Some(ts), Some(val)) = (
+                    dp.timestamp.as_ref().cloned(),
+                    dp.value.as_ref().and_then(temperature_celsius),
+                ) {
+                    if !timestamp_in_any_gap(ts, gaps) {
+                        continue;
                     }
+                    let entry = by_ts.entry(ts).or_insert_with(|| {
+                        NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
+                    });
+                    entry.inside_temp_c = Some(val);
                 }
             }
         }
-
-        if let Some(cf) = report.call_for_heat.as_ref().and_then(|s| s.data_intervals.as_ref()) {
-            for di in cf {
-                if let (Some(ts), Some(val)) = (di.interval.from.as_ref().cloned(), di.value) {
+        if let Some(h_series) = md.humidity.as_ref().and_then(|s| s.data_points.as_ref()) {
+            for dp in h_series {
+                if let (Some(ts), Some(val)) = (dp.timestamp.as_ref().cloned(), dp.value) {
                     if !timestamp_in_any_gap(ts, gaps) {
                         continue;
                     }
-                    let pct = match val {
-                        tado::CallForHeatValue::None_ => 0.0,
-                        tado::CallForHeatValue::Low => 33.0,
-                        tado::CallForHeatValue::Medium => 66.0,
-                        tado::CallForHeatValue::High => 100.0,
-                    };
                     let entry = by_ts.entry(ts).or_insert_with(|| {
                         NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
                     });
-                    entry.heating_power_pct = Some(pct);
+                    entry.humidity_pct = Some(val * 100.0);
                 }
             }
         }
-
-        if let Some(ac) = report.ac_activity.as_ref().and_then(|s| s.data_intervals.as_ref()) {
-            for di in ac {
+        if let Some(conn_series) = md
+            .measuring_device_connected
+            .as_ref()
+            .and_then(|s| s.data_intervals.as_ref())
+        {
+            for di in conn_series {
                 if let (Some(ts), Some(val)) = (di.interval.from.as_ref().cloned(), di.value) {
                     if !timestamp_in_any_gap(ts, gaps) {
                         continue;
                     }
-                    let on = matches!(val, tado::Power::On);
                     let entry = by_ts.entry(ts).or_insert_with(|| {
                         NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
                     });
-                    entry.ac_power_on = Some(on);
+                    entry.connection_up = Some(val);
                 }
             }
         }
+    }
 
-        if let Some(settings) = report.settings.as_ref().and_then(|s| s.data_intervals.as_ref()) {
-            for di in settings {
-                if let Some(ts) = di.interval.from.as_ref().cloned() {
-                    if !timestamp_in_any_gap(ts, gaps) {
-                        continue;
+    if let Some(cf) = report.call_for_heat.as_ref().and_then(|s| s.data_intervals.as_ref()) {
+        for di in cf {
+            if let (Some(ts), Some(val)) = (di.interval.from.as_ref().cloned(), di.value) {
+                if !timestamp_in_any_gap(ts, gaps) {
+                    continue;
+                }
+                let pct = match val {
+                    tado::CallForHeatValue::None_ => 0.0,
+                    tado::CallForHeatValue::Low => 33.0,
+                    tado::CallForHeatValue::Medium => 66.0,
+                    tado::CallForHeatValue::High => 100.0,
+                };
+                let entry = by_ts.entry(ts).or_insert_with(|| {
+                    NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
+                });
+                entry.heating_power_pct = Some(pct);
+            }
+        }
+    }
+
+    if let Some(ac) = report.ac_activity.as_ref().and_then(|s| s.data_intervals.as_ref()) {
+        for di in ac {
+            if let (Some(ts), Some(val)) = (di.interval.from.as_ref().cloned(), di.value) {
+                if !timestamp_in_any_gap(ts, gaps) {
+                    continue;
+                }
+                let on = matches!(val, tado::Power::On);
+                let entry = by_ts.entry(ts).or_insert_with(|| {
+                    NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
+                });
+                entry.ac_power_on = Some(on);
+            }
+        }
+    }
+
+    if let Some(settings) = report.settings.as_ref().and_then(|s| s.data_intervals.as_ref()) {
+        for di in settings {
+            if let Some(ts) = di.interval.from.as_ref().cloned() {
+                if !timestamp_in_any_gap(ts, gaps) {
+                    continue;
+                }
+                if let Some(val) = di.value.as_ref() {
+                    let setpoint = val.temperature.as_ref().and_then(temperature_celsius);
+                    let ac_mode = val.mode.as_ref().and_then(serde_enum_name);
+                    let ac_on = val.power.map(|p| matches!(p, tado::Power::On));
+                    let entry = by_ts.entry(ts).or_insert_with(|| {
+                        NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
+                    });
+                    if let Some(sp) = setpoint {
+                        entry.setpoint_temp_c = Some(sp);
                     }
-                    if let Some(val) = di.value.as_ref() {
-                        let setpoint = val.temperature.as_ref().and_then(|t| t.celsius);
-                        let ac_mode = val.mode.as_ref().and_then(serde_enum_name);
-                        let ac_on = val.power.map(|p| matches!(p, tado::Power::On));
-                        let entry = by_ts.entry(ts).or_insert_with(|| {
-                            NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::HISTORICAL)
-                        });
-                        if let Some(sp) = setpoint {
-                            entry.setpoint_temp_c = Some(sp);
-                        }
-                        if let Some(m) = ac_mode {
-                            entry.ac_mode = Some(m);
-                        }
-                        if let Some(on) = ac_on {
-                            entry.ac_power_on = Some(on);
-                        }
+                    if let Some(m) = ac_mode {
+                        entry.ac_mode = Some(m);
+                    }
+                    if let Some(on) = ac_on {
+                        entry.ac_power_on = Some(on);
                     }
                 }
             }
         }
+    }
 
-        if let Some((w_from, w_to)) = weather_window
-            && let Some(w) = report.weather.as_ref()
-            && let Some(cond) = w.condition.as_ref().and_then(|ts| ts.data_intervals.as_ref())
-        {
-            for di in cond {
-                if let Some(ts) = di.interval.from.as_ref().cloned() {
-                    if ts < w_from || ts >= w_to || !timestamp_in_any_gap(ts, gaps) {
-                        continue;
+    // HOME/AWAY stripes span the time the zone spent in that mode; other stripe types say nothing about it.
+    // Each stripe also tells why the setting was active, which becomes the rows' control mode.
+    // `OPEN_WINDOW_DETECTED` stripes mark the rows they cover as `window_open`; like realtime, rows outside
+    // one stay unknown rather than closed.
+    if let Some(stripes) = report.stripes.as_ref().and_then(|s| s.data_intervals.as_ref()) {
+        for di in stripes {
+            let Some(stripe_type) = di.value.as_ref().and_then(|v| v.stripe_type.as_deref()) else {
+                continue;
+            };
+            let (Some(from), Some(to)) = (di.interval.from, di.interval.to) else {
+                continue;
+            };
+            let mode = matches!(stripe_type, "HOME" | "AWAY").then_some(stripe_type);
+            let control = control_mode_of_stripe(stripe_type);
+            let window_open = stripe_type == "OPEN_WINDOW_DETECTED";
+            for (_, entry) in by_ts.range_mut(from..to) {
+                if window_open {
+                    entry.window_open = Some(true);
+                }
+                if let Some(mode) = mode {
+                    entry.tado_mode = Some(mode.to_string());
+                }
+                if let Some(control) = control {
+                    entry.control_mode = Some(control.to_string());
+                }
+            }
+        }
+    }
+
+    // Day reports carry only the condition (state and temperature) and a boolean `sunny` series, no solar
+    // intensity, and the API has no weather history to fetch it from: historical weather rows keep
+    // `solar_intensity_pct` empty rather than guessing it from `sunny`.
+    if let Some((w_from, w_to)) = weather_window
+        && let Some(w) = report.weather.as_ref()
+        && let Some(cond) = w.condition.as_ref().and_then(|ts| ts.data_intervals.as_ref())
+    {
+        for di in cond {
+            if let Some(ts) = di.interval.from.as_ref().cloned() {
+                if ts < w_from || ts >= w_to || !timestamp_in_any_gap(ts, gaps) {
+                    continue;
+                }
+                let entry = weather_by_ts.entry(ts).or_insert_with(|| {
+                    let mut row = NewWeatherMeasurement::new(ts, db_home_id, event_source::HISTORICAL);
+                    // Day report intervals carry their own start, so the bucket is the reported time
+                    row.reported_at = Some(ts);
+                    row
+                });
+                if let Some(v) = di.value.as_ref() {
+                    if let Some(temp) = v.temperature.as_ref().and_then(temperature_celsius) {
+                        entry.outside_temp_c = Some(temp);
                     }
-                    let entry = weather_by_ts
-                        .entry(ts)
-                        .or_insert_with(|| NewWeatherMeasurement::new(ts, db_home_id, event_source::HISTORICAL));
-                    if let Some(v) = di.value.as_ref() {
-                        if let Some(temp) = v.temperature.as_ref().and_then(|t| t.celsius) {
-                            entry.outside_temp_c = Some(temp);
-                        }
-                        if let Some(state) = v.state.as_ref().and_then(serde_enum_name) {
-                            entry.weather_state = Some(state);
-                        }
+                    if let Some(state) = v.state.as_ref().and_then(serde_enum_name) {
+                        entry.weather_state = Some(state);
                     }
                 }
             }
         }
+    }
 
+    if trim_leading_bogus {
         remove_leading_bogus_rows(&mut by_ts);
-
-        let rows: Vec<NewClimateMeasurement> = by_ts.into_values().collect();
-        let inserted = insert_climate_measurements(conn, &rows)?;
-        inserted_total += inserted;
-
-        let weather_rows: Vec<NewWeatherMeasurement> = weather_by_ts.into_values().collect();
-        insert_weather_measurements(conn, &weather_rows)?;
     }
 
-    info!(
-        "Backfill: zone {} complete ({} day(s), {} row(s) inserted)",
-        zone_id.0, processed_days, inserted_total
-    );
-
-    Ok(())
+    (by_ts, weather_by_ts)
 }
 
-fn fetch_day_report_with_limit(
-    client: &TadoClient,
-    home_id: HomeId,
-    zone_id: ZoneId,
-    day: NaiveDate,
-    min_spacing: Option<StdDuration>,
-) -> Result<tado::DayReport, TadoClientError> {
-    let start = Instant::now();
-    let result = client.get_zone_day_report(home_id, zone_id, Some(day));
-    if let Some(required) = min_spacing {
-        let elapsed = start.elapsed();
-        if elapsed < required {
-            thread::sleep(required - elapsed);
-        }
+/// Times among `expected` that already have a historical zone row.
+fn stored_historical_times(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    db_zone_id: i64,
+    expected: &[DateTime<Utc>],
+) -> Result<Vec<DateTime<Utc>>, String> {
+    use schema::climate_measurements::dsl as C;
+
+    if expected.is_empty() {
+        return Ok(Vec::new());
     }
-    result
+    C::climate_measurements
+        .filter(C::home_id.eq(db_home_id))
+        .filter(C::zone_id.eq(db_zone_id))
+        .filter(C::device_id.is_null())
+        .filter(C::source.eq(event_source::HISTORICAL))
+        .filter(C::time.eq_any(expected))
+        .select(C::time)
+        .load(conn)
+        .map_err(|e| format!("fetch stored rows for verification failed: {}", e))
+}
+
+/// Expected timestamps with no stored row, in order.
+fn missing_timestamps(expected: &[DateTime<Utc>], stored: &[DateTime<Utc>]) -> Vec<DateTime<Utc>> {
+    let stored: BTreeSet<&DateTime<Utc>> = stored.iter().collect();
+    expected.iter().filter(|ts| !stored.contains(ts)).copied().collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::TimeZone;
+    use chrono::{TimeZone, Timelike};
     use std::collections::BTreeMap;
 
     fn load_bogus_fixture() -> tado::DayReport {
@@ -819,6 +1143,187 @@ mod tests {
         assert!(rows.contains_key(&ts2));
     }
 
+    #[test]
+    fn leading_bogus_trimming_can_be_disabled() {
+        let report: tado::DayReport = serde_json::from_str(
+            r#"{"measuredData": {
+                "insideTemperature": {"dataPoints": [
+                    {"timestamp": "2024-03-01T00:00:00Z", "value": {"celsius": 20.0}},
+                    {"timestamp": "2024-03-01T00:15:00Z", "value": {"celsius": 20.0}},
+                    {"timestamp": "2024-03-01T00:30:00Z", "value": {"celsius": 20.0}}
+                ]},
+                "humidity": {"dataPoints": [
+                    {"timestamp": "2024-03-01T00:00:00Z", "value": 0.5},
+                    {"timestamp": "2024-03-01T00:15:00Z", "value": 0.5},
+                    {"timestamp": "2024-03-01T00:30:00Z", "value": 0.52}
+                ]}
+            }}"#,
+        )
+        .expect("parse day report");
+        let gaps = [Gap {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap(),
+            start_inclusive: true,
+        }];
+
+        let trimmed = rows_from_day_report(&report, &gaps, 1, 2, None, true).0;
+        assert_eq!(
+            trimmed.keys().copied().collect::<Vec<_>>(),
+            [Utc.with_ymd_and_hms(2024, 3, 1, 0, 30, 0).unwrap()]
+        );
+
+        let untouched = rows_from_day_report(&report, &gaps, 1, 2, None, false).0;
+        assert_eq!(untouched.len(), 3);
+        assert!(untouched.values().take(2).all(measurement_is_leading_bogus));
+    }
+
+    #[test]
+    fn weather_window_honors_from_date_without_zones() {
+        let from_date = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(weather_backfill_window(None, None, None, now), None);
+        assert_eq!(
+            weather_backfill_window(None, Some(from_date), None, now),
+            Some((from_date, now))
+        );
+
+        let created = Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            weather_backfill_window(Some(created), Some(from_date), None, now),
+            Some((from_date, now))
+        );
+    }
+
+    #[test]
+    fn weather_window_prefers_later_stored_weather() {
+        let from_date = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let last_stored = Utc.with_ymd_and_hms(2024, 3, 15, 8, 30, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+
+        let (from, to) = weather_backfill_window(None, Some(from_date), Some(last_stored), now).expect("window");
+        assert_eq!(from, last_stored + chrono::Duration::seconds(1));
+        assert_eq!(to, now);
+
+        let earlier = Utc.with_ymd_and_hms(2023, 12, 1, 0, 0, 0).unwrap();
+        let (from, _) = weather_backfill_window(None, Some(from_date), Some(earlier), now).expect("window");
+        assert_eq!(from, from_date);
+    }
+
+    #[test]
+    fn sampling_keeps_first_fillable_gap_day() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let gap_on = |d: NaiveDate| Gap {
+            start: d.and_hms_opt(10, 0, 0).unwrap().and_utc(),
+            end: d.and_hms_opt(12, 0, 0).unwrap().and_utc(),
+            start_inclusive: true,
+        };
+        // First non-bogus day (Jan 2) has no gap; the first real gap is on Jan 4 (ordinal 4, not a multiple of 3).
+        let mut gaps_by_day = BTreeMap::new();
+        gaps_by_day.insert(day(1), vec![gap_on(day(1))]);
+        gaps_by_day.insert(day(4), vec![gap_on(day(4))]);
+        gaps_by_day.insert(day(5), vec![gap_on(day(5))]);
+        gaps_by_day.insert(day(6), vec![gap_on(day(6))]);
+        let rate = NonZeroU32::new(3);
+
+        let first_fillable = first_fillable_gap_day(&gaps_by_day, day(2));
+        assert_eq!(first_fillable, Some(day(4)));
+
+        let selected: Vec<NaiveDate> = gaps_by_day
+            .keys()
+            .filter(|d| **d >= day(2))
+            .copied()
+            .filter(|d| day_selected_by_sampling(*d, first_fillable, rate))
+            .collect();
+        assert_eq!(selected, vec![day(4), day(6)]);
+    }
+
+    #[test]
+    fn zone_concurrency_leaves_request_count_unchanged() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Bogus until a zone-specific day, counting every request across workers.
+        struct ProbedReports {
+            requests: AtomicUsize,
+            bogus: tado::DayReport,
+        }
+
+        impl DayReportSource for ProbedReports {
+            fn day_report(&self, _: HomeId, zone_id: ZoneId, day: NaiveDate) -> Result<tado::DayReport, String> {
+                self.requests.fetch_add(1, Ordering::SeqCst);
+                let first_real = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + Duration::days(zone_id.0 * 9);
+                Ok(if day < first_real {
+                    self.bogus.clone()
+                } else {
+                    tado::DayReport::default()
+                })
+            }
+        }
+
+        let zones: Vec<ZoneId> = (1..=6).map(ZoneId).collect();
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let end = NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let probe = |concurrency: usize| {
+            let reports = ProbedReports {
+                requests: AtomicUsize::new(0),
+                bogus: load_bogus_fixture(),
+            };
+            let first_days = run_bounded(
+                &zones,
+                concurrency,
+                || Ok(()),
+                |_, zone_id| find_first_non_bogus_day(&reports, HomeId(1), *zone_id, start, end),
+            );
+            (first_days, reports.requests.into_inner())
+        };
+
+        let (sequential, sequential_requests) = probe(1);
+        let (parallel, parallel_requests) = probe(4);
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel_requests, sequential_requests);
+        assert_eq!(sequential[0], Ok(Some(NaiveDate::from_ymd_opt(2024, 1, 10).unwrap())));
+    }
+
+    #[test]
+    fn bogus_search_starts_at_zone_creation() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let gap_on = |d: NaiveDate| Gap {
+            start: d.and_hms_opt(10, 0, 0).unwrap().and_utc(),
+            end: d.and_hms_opt(12, 0, 0).unwrap().and_utc(),
+            start_inclusive: true,
+        };
+        // Zone created in January; the first gap only opened in June.
+        let mut gaps_by_day = BTreeMap::new();
+        gaps_by_day.insert(day(6, 10), vec![gap_on(day(6, 10))]);
+        gaps_by_day.insert(day(6, 20), vec![gap_on(day(6, 20))]);
+
+        assert_eq!(
+            bogus_search_range(day(1, 5), &gaps_by_day),
+            Some((day(1, 5), day(6, 20)))
+        );
+        assert_eq!(bogus_search_range(day(1, 5), &BTreeMap::new()), None);
+    }
+
+    #[test]
+    fn recorded_progress_moves_the_start_past_completed_days() {
+        let day = |m, d| NaiveDate::from_ymd_opt(2024, m, d).unwrap();
+        let start = Utc.with_ymd_and_hms(2024, 1, 5, 9, 30, 0).unwrap();
+
+        assert_eq!(resume_start(start, None), start);
+        assert_eq!(
+            resume_start(start, Some(day(3, 1))),
+            Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap()
+        );
+        // A watermark before the zone's own start never moves the start backwards
+        assert_eq!(resume_start(start, Some(day(1, 1))), start);
+
+        let today = day(3, 10);
+        assert!(day_completes_progress(day(3, 9), today, None));
+        assert!(day_completes_progress(day(3, 9), today, NonZeroU32::new(1)));
+        assert!(!day_completes_progress(today, today, None));
+        assert!(!day_completes_progress(day(3, 9), today, NonZeroU32::new(7)));
+    }
+
     #[test]
     fn timestamp_gap_inclusion_rules() {
         let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
@@ -847,4 +1352,160 @@ mod tests {
             std::slice::from_ref(&exclusive_gap)
         ));
     }
+
+    #[test]
+    fn verification_detects_under_insert() {
+        let report: tado::DayReport = serde_json::from_value(serde_json::json!({
+            "measuredData": {
+                "insideTemperature": {
+                    "dataPoints": [
+                        {"timestamp": "2024-03-01T00:00:00Z", "value": {"celsius": 21.3}},
+                        {"timestamp": "2024-03-01T00:15:00Z", "value": {"celsius": 21.4}},
+                        {"timestamp": "2024-03-01T00:30:00Z", "value": {"celsius": 21.6}}
+                    ]
+                }
+            }
+        }))
+        .expect("parse day report");
+        let gaps = vec![Gap {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 10, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 1, 1, 0, 0).unwrap(),
+            start_inclusive: true,
+        }];
+
+        let expected: Vec<DateTime<Utc>> = rows_from_day_report(&report, &gaps, 1, 2, None, true)
+            .0
+            .into_keys()
+            .collect();
+        assert_eq!(expected.len(), 2, "only in-gap timestamps are expected");
+
+        // Everything landed
+        assert!(missing_timestamps(&expected, &expected).is_empty());
+        // One row silently dropped on insert
+        let stored = vec![expected[0]];
+        assert_eq!(missing_timestamps(&expected, &stored), vec![expected[1]]);
+    }
+
+    #[test]
+    fn startup_catchup_fills_recent_gap_only() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 5, 0).unwrap();
+        let latest = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let points: Vec<serde_json::Value> = (0..96)
+            .map(|i| {
+                let ts = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap() + Duration::minutes(15 * i);
+                serde_json::json!({"timestamp": ts, "value": {"celsius": 21.0 + i as f64 / 100.0}})
+            })
+            .collect();
+        let report: tado::DayReport = serde_json::from_value(serde_json::json!({
+            "measuredData": {"insideTemperature": {"dataPoints": points}}
+        }))
+        .expect("parse day report");
+
+        let gap = startup_catchup_gap(Some(latest), now).expect("gap since latest reading");
+        let filled: Vec<DateTime<Utc>> = rows_from_day_report(&report, &[gap], 1, 2, None, true)
+            .0
+            .into_keys()
+            .collect();
+        let expected: Vec<DateTime<Utc>> = (1..=4).map(|i| latest + Duration::minutes(15 * i)).collect();
+        assert_eq!(filled, expected);
+
+        // Without recent data the gap is capped to the window; a fresh zone needs nothing.
+        let capped = startup_catchup_gap(None, now).expect("window-sized gap");
+        assert_eq!(capped.start, now - STARTUP_CATCHUP_WINDOW);
+        assert!(startup_catchup_gap(Some(now - Duration::minutes(5)), now).is_none());
+    }
+
+    #[test]
+    fn stripes_annotate_rows_with_control_mode() {
+        let json = std::fs::read_to_string("tests/data/day-report-stripes.json").expect("fixture present");
+        let report: tado::DayReport = serde_json::from_str(&json).expect("parse day report");
+        let gap = Gap {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 1, 2, 0, 0).unwrap(),
+            start_inclusive: true,
+        };
+
+        let rows: Vec<NewClimateMeasurement> = rows_from_day_report(&report, &[gap], 1, 2, None, true)
+            .0
+            .into_values()
+            .collect();
+        let modes: Vec<(Option<&str>, Option<&str>)> = rows
+            .iter()
+            .map(|r| (r.control_mode.as_deref(), r.tado_mode.as_deref()))
+            .collect();
+        assert_eq!(
+            modes,
+            vec![
+                (Some(control_mode::SCHEDULE), Some("HOME")),
+                (Some(control_mode::MANUAL), None),
+                (Some(control_mode::AWAY), Some("AWAY")),
+                // An open window says nothing about what drove the setting
+                (None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn day_report_weather_fills_temperature_and_state_but_not_solar_intensity() {
+        let json = std::fs::read_to_string("tests/data/day-report-weather.json").expect("fixture present");
+        let report: tado::DayReport = serde_json::from_str(&json).expect("parse day report");
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let end = Utc.with_ymd_and_hms(2024, 3, 1, 2, 0, 0).unwrap();
+        let gap = Gap {
+            start,
+            end,
+            start_inclusive: true,
+        };
+
+        let weather = rows_from_day_report(&report, &[gap], 1, 2, Some((start, end)), true).1;
+        let values: Vec<_> = weather
+            .iter()
+            .map(|(ts, row)| {
+                (
+                    ts.hour(),
+                    row.outside_temp_c,
+                    row.weather_state.as_deref(),
+                    row.solar_intensity_pct,
+                )
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (0, Some(3.5), Some("NIGHT_CLOUDY"), None),
+                (1, Some(2.75), Some("DRIZZLE"), None),
+            ]
+        );
+        assert!(weather.values().all(|row| row.source == event_source::HISTORICAL));
+    }
+
+    #[test]
+    fn open_window_stripe_marks_the_rows_it_covers() {
+        let json = std::fs::read_to_string("tests/data/day-report-open-window.json").expect("fixture present");
+        let report: tado::DayReport = serde_json::from_str(&json).expect("parse day report");
+        let gap = Gap {
+            start: Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2024, 3, 1, 1, 15, 0).unwrap(),
+            start_inclusive: true,
+        };
+
+        let gaps = [gap];
+        let rows = rows_from_day_report(&report, &gaps, 1, 2, None, true).0;
+        let window: Vec<(u32, Option<bool>)> = rows
+            .iter()
+            .map(|(ts, row)| (ts.minute() + 60 * ts.hour(), row.window_open))
+            .collect();
+        // The 00:20-00:50 window covers the 00:30 and 00:45 readings only
+        assert_eq!(
+            window,
+            vec![(0, None), (15, None), (30, Some(true)), (45, Some(true)), (60, None)]
+        );
+
+        let without_stripes = tado::DayReport {
+            stripes: None,
+            ..report
+        };
+        let rows = rows_from_day_report(&without_stripes, &gaps, 1, 2, None, true).0;
+        assert!(rows.values().all(|row| row.window_open.is_none()));
+    }
 }
@@ -0,0 +1,760 @@
+//! Portable snapshot export of recent data for bug reports and offline analysis.
+//!
+//! `--export-sqlite` writes a SQLite database file with an equivalent schema, filled in one transaction.
+//! Postgres is only read; rows are streamed one at a time so memory stays flat regardless of the range.
+//!
+//! `run_measurements` is the backup-oriented counterpart: only `climate_measurements` and
+//! `weather_measurements`, for the configured homes and an optional date range, as newline-delimited JSON or CSV.
+
+use crate::db::models::{ClimateMeasurement, WeatherMeasurement};
+use crate::db::sqlite::Sqlite;
+use crate::schema;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use diesel::PgConnection;
+use diesel::pg::PgRowByRowLoadingMode;
+use diesel::prelude::*;
+use diesel::sql_types::{Text, Timestamptz};
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+struct ExportTable {
+    name: &'static str,
+    /// Column name and SQLite type, in insert order.
+    columns: &'static [(&'static str, &'static str)],
+    /// Time column used to limit the export to the requested window; reference tables are copied whole.
+    time_column: Option<&'static str>,
+}
+
+const TABLES: &[ExportTable] = &[
+    ExportTable {
+        name: "users",
+        columns: &[
+            ("id", "INTEGER PRIMARY KEY"),
+            ("tado_user_id", "TEXT NOT NULL"),
+            ("email", "TEXT"),
+            ("username", "TEXT"),
+            ("name", "TEXT"),
+            ("locale", "TEXT"),
+            ("created_at", "TEXT NOT NULL"),
+            ("updated_at", "TEXT NOT NULL"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "homes",
+        columns: &[
+            ("id", "INTEGER PRIMARY KEY"),
+            ("tado_home_id", "INTEGER NOT NULL"),
+            ("name", "TEXT"),
+            ("timezone", "TEXT"),
+            ("temperature_unit", "TEXT"),
+            ("address_line1", "TEXT"),
+            ("address_line2", "TEXT"),
+            ("zip_code", "TEXT"),
+            ("city", "TEXT"),
+            ("state", "TEXT"),
+            ("country", "TEXT"),
+            ("latitude", "REAL"),
+            ("longitude", "REAL"),
+            ("created_at", "TEXT NOT NULL"),
+            ("updated_at", "TEXT NOT NULL"),
+            ("away_radius_m", "REAL"),
+            ("incident_detection_enabled", "INTEGER"),
+            ("feature_flags", "TEXT"),
+            ("incident_detection_supported", "INTEGER"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "user_homes",
+        columns: &[
+            ("user_id", "INTEGER NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("role", "TEXT"),
+            ("joined_at", "TEXT"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "zones",
+        columns: &[
+            ("id", "INTEGER PRIMARY KEY"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("tado_zone_id", "INTEGER NOT NULL"),
+            ("name", "TEXT"),
+            ("zone_type", "TEXT"),
+            ("date_created", "TEXT"),
+            ("created_at", "TEXT NOT NULL"),
+            ("updated_at", "TEXT NOT NULL"),
+            ("capabilities", "TEXT"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "devices",
+        columns: &[
+            ("id", "INTEGER PRIMARY KEY"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("tado_device_id", "TEXT NOT NULL"),
+            ("short_serial_no", "TEXT"),
+            ("device_type", "TEXT"),
+            ("firmware_version", "TEXT"),
+            ("orientation", "TEXT"),
+            ("battery_state", "TEXT"),
+            ("characteristics", "TEXT"),
+            ("created_at", "TEXT NOT NULL"),
+            ("updated_at", "TEXT NOT NULL"),
+            ("device_type_desc", "TEXT"),
+            ("temperature_offset_c", "REAL"),
+            ("mounting_state", "TEXT"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "zone_devices",
+        columns: &[
+            ("zone_id", "INTEGER NOT NULL"),
+            ("device_id", "INTEGER NOT NULL"),
+            ("linked_at", "TEXT"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "flow_temperature_optimization",
+        columns: &[
+            ("home_id", "INTEGER PRIMARY KEY"),
+            ("max_flow_temperature", "INTEGER"),
+            ("auto_adaptation_enabled", "INTEGER"),
+            ("auto_adaptation_max_flow_temperature", "INTEGER"),
+            ("max_flow_temperature_min", "INTEGER"),
+            ("max_flow_temperature_max", "INTEGER"),
+            ("open_therm_device_serial_number", "TEXT"),
+            ("updated_at", "TEXT NOT NULL"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "installations",
+        columns: &[
+            ("id", "INTEGER PRIMARY KEY"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("tado_installation_id", "INTEGER NOT NULL"),
+            ("installation_type", "TEXT"),
+            ("state", "TEXT"),
+            ("revision", "INTEGER"),
+            ("created_at", "TEXT NOT NULL"),
+            ("updated_at", "TEXT NOT NULL"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "mobile_devices",
+        columns: &[
+            ("id", "INTEGER PRIMARY KEY"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("tado_mobile_device_id", "INTEGER NOT NULL"),
+            ("name", "TEXT"),
+            ("platform", "TEXT"),
+            ("model", "TEXT"),
+            ("geo_tracking_enabled", "INTEGER"),
+            ("created_at", "TEXT NOT NULL"),
+            ("updated_at", "TEXT NOT NULL"),
+        ],
+        time_column: None,
+    },
+    ExportTable {
+        name: "climate_measurements",
+        columns: &[
+            ("id", "INTEGER"),
+            ("time", "TEXT NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("zone_id", "INTEGER"),
+            ("device_id", "INTEGER"),
+            ("source", "TEXT NOT NULL"),
+            ("inside_temp_c", "REAL"),
+            ("humidity_pct", "REAL"),
+            ("setpoint_temp_c", "REAL"),
+            ("heating_power_pct", "REAL"),
+            ("ac_power_on", "INTEGER"),
+            ("ac_mode", "TEXT"),
+            ("window_open", "INTEGER"),
+            ("battery_low", "INTEGER"),
+            ("connection_up", "INTEGER"),
+            ("ingest_lag_secs", "REAL"),
+            ("inside_temp_precision_c", "REAL"),
+            ("tado_mode", "TEXT"),
+            ("control_mode", "TEXT"),
+        ],
+        time_column: Some("time"),
+    },
+    ExportTable {
+        name: "weather_measurements",
+        columns: &[
+            ("id", "INTEGER"),
+            ("time", "TEXT NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("source", "TEXT NOT NULL"),
+            ("outside_temp_c", "REAL"),
+            ("solar_intensity_pct", "REAL"),
+            ("weather_state", "TEXT"),
+            ("ingest_lag_secs", "REAL"),
+            ("reported_at", "TEXT"),
+        ],
+        time_column: Some("time"),
+    },
+    ExportTable {
+        name: "zone_weather_measurements",
+        columns: &[
+            ("id", "INTEGER"),
+            ("time", "TEXT NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("zone_id", "INTEGER NOT NULL"),
+            ("source", "TEXT NOT NULL"),
+            ("outside_temp_c", "REAL"),
+            ("solar_intensity_pct", "REAL"),
+            ("weather_state", "TEXT"),
+        ],
+        time_column: Some("time"),
+    },
+    ExportTable {
+        name: "presence_measurements",
+        columns: &[
+            ("id", "INTEGER"),
+            ("time", "TEXT NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("mobile_device_id", "INTEGER NOT NULL"),
+            ("at_home", "INTEGER"),
+            ("stale", "INTEGER"),
+            ("relative_distance_from_home_fence", "REAL"),
+        ],
+        time_column: Some("time"),
+    },
+    ExportTable {
+        name: "air_comfort_measurements",
+        columns: &[
+            ("id", "INTEGER"),
+            ("time", "TEXT NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("zone_id", "INTEGER NOT NULL"),
+            ("temperature_level", "TEXT"),
+            ("humidity_level", "TEXT"),
+            ("freshness", "TEXT"),
+        ],
+        time_column: Some("time"),
+    },
+    ExportTable {
+        name: "events",
+        columns: &[
+            ("id", "INTEGER"),
+            ("time", "TEXT NOT NULL"),
+            ("home_id", "INTEGER NOT NULL"),
+            ("zone_id", "INTEGER"),
+            ("device_id", "INTEGER"),
+            ("source", "TEXT"),
+            ("event_type", "TEXT NOT NULL"),
+            ("payload", "TEXT"),
+        ],
+        time_column: Some("time"),
+    },
+];
+
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[diesel(sql_type = Text)]
+    row_json: String,
+}
+
+/// Writes the reference tables and the last `days` days of measurements and events to `path`.
+pub fn run(conn: &mut PgConnection, path: &Path, days: NonZeroU32) -> Result<(), String> {
+    let since = Utc::now() - ChronoDuration::days(i64::from(days.get()));
+    info!(
+        "Export: writing SQLite snapshot of data since {} to {}",
+        since,
+        path.display()
+    );
+
+    let mut db = Sqlite::create(path)?;
+    write_snapshot(&mut db, |db, table| export_table(conn, db, table, since))
+        .map_err(|e| format!("write {} failed: {}", path.display(), e))?;
+
+    info!("Export: snapshot complete: {}", path.display());
+    Ok(())
+}
+
+/// Creates every table and fills it with the rows `rows` inserts, all in one transaction.
+fn write_snapshot(
+    db: &mut Sqlite,
+    mut rows: impl FnMut(&mut Sqlite, &ExportTable) -> Result<usize, String>,
+) -> Result<(), String> {
+    db.execute("BEGIN TRANSACTION")?;
+    for table in TABLES {
+        db.execute(&table_ddl(table))?;
+        let count = rows(db, table)?;
+        info!("Export: {} row(s) from {}", count, table.name);
+    }
+    db.execute("COMMIT")
+}
+
+fn export_table(
+    conn: &mut PgConnection,
+    db: &mut Sqlite,
+    table: &ExportTable,
+    since: DateTime<Utc>,
+) -> Result<usize, String> {
+    let query = match table.time_column {
+        Some(column) => format!(
+            "select row_to_json(t)::text as row_json from {0} t where t.{1} >= $1 order by t.{1}",
+            table.name, column
+        ),
+        // `$1` keeps the bind list uniform; it is trivially true for reference tables.
+        None => format!(
+            "select row_to_json(t)::text as row_json from {} t where $1 is not null",
+            table.name
+        ),
+    };
+
+    let rows = diesel::sql_query(query)
+        .bind::<Timestamptz, _>(since)
+        .load_iter::<JsonRow, PgRowByRowLoadingMode>(conn)
+        .map_err(|e| format!("read {} failed: {}", table.name, e))?;
+
+    let mut count = 0;
+    for row in rows {
+        let row = row.map_err(|e| format!("read {} row failed: {}", table.name, e))?;
+        let value: Value =
+            serde_json::from_str(&row.row_json).map_err(|e| format!("decode {} row failed: {}", table.name, e))?;
+        db.execute(&insert_sql(table, &value))
+            .map_err(|e| format!("write {} row failed: {}", table.name, e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn table_ddl(table: &ExportTable) -> String {
+    let columns = table
+        .columns
+        .iter()
+        .map(|(name, ty)| format!("{} {}", name, ty))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("CREATE TABLE {} ({})", table.name, columns)
+}
+
+fn insert_sql(table: &ExportTable, row: &Value) -> String {
+    let names = table
+        .columns
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = table
+        .columns
+        .iter()
+        .map(|(name, _)| sqlite_literal(row.get(*name).unwrap_or(&Value::Null)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("INSERT INTO {} ({}) VALUES ({})", table.name, names, values)
+}
+
+fn sqlite_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Bool(b) => if *b { "1" } else { "0" }.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => quote(s),
+        // JSONB columns are stored as their JSON text
+        other => quote(&other.to_string()),
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Tables written by `run_measurements`, in output order.
+const MEASUREMENT_TABLES: [&str; 2] = ["climate_measurements", "weather_measurements"];
+
+/// Output format of the `--export` measurement dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeasurementFormat {
+    /// One JSON object per line, tagged with its `table`, all tables in one file.
+    #[default]
+    Ndjson,
+    /// One file per table with a header row, named `<stem>.<table>.csv` next to the requested path.
+    Csv,
+}
+
+impl MeasurementFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "ndjson" => Ok(MeasurementFormat::Ndjson),
+            "csv" => Ok(MeasurementFormat::Csv),
+            other => Err(format!("`--export-format` must be ndjson or csv (got '{}')", other)),
+        }
+    }
+}
+
+/// Inclusive UTC date bounds of the `--export` dump; either side may be open.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DateRange {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl DateRange {
+    fn start(&self) -> Option<DateTime<Utc>> {
+        self.from.map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc())
+    }
+
+    /// Exclusive upper bound: midnight after `to`, so the whole last day is included.
+    fn end(&self) -> Option<DateTime<Utc>> {
+        self.to
+            .and_then(|d| d.succ_opt())
+            .map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc())
+    }
+}
+
+/// Streams the climate and weather measurements of `tado_home_ids` (every home when `None`) within `range` to
+/// `path`, oldest first.
+pub fn run_measurements(
+    conn: &mut PgConnection,
+    path: &Path,
+    format: MeasurementFormat,
+    tado_home_ids: Option<&BTreeSet<i64>>,
+    range: DateRange,
+) -> Result<(), String> {
+    let home_ids = match tado_home_ids {
+        Some(ids) => Some(db_home_ids(conn, ids)?),
+        None => None,
+    };
+    info!(
+        "Export: writing {} measurements ({} to {}) to {}",
+        match format {
+            MeasurementFormat::Ndjson => "NDJSON",
+            MeasurementFormat::Csv => "CSV",
+        },
+        range.from.map_or("start".to_string(), |d| d.to_string()),
+        range.to.map_or("now".to_string(), |d| d.to_string()),
+        path.display()
+    );
+
+    let home_ids = home_ids.as_deref();
+    match format {
+        MeasurementFormat::Ndjson => {
+            let mut out = create_output(path)?;
+            for table in MEASUREMENT_TABLES {
+                let table = measurement_table(table);
+                let rows = export_measurements(conn, &mut out, table, format, home_ids, range)?;
+                info!("Export: {} row(s) from {}", rows, table.name);
+            }
+            out.flush()
+                .map_err(|e| format!("write {} failed: {}", path.display(), e))
+        }
+        MeasurementFormat::Csv => {
+            for table in MEASUREMENT_TABLES {
+                let table = measurement_table(table);
+                let table_path = csv_table_path(path, table.name);
+                let io_err = |e: std::io::Error| format!("write {} failed: {}", table_path.display(), e);
+                let mut out = create_output(&table_path)?;
+                write_csv_header(&mut out, table).map_err(io_err)?;
+                let rows = export_measurements(conn, &mut out, table, format, home_ids, range)?;
+                out.flush().map_err(io_err)?;
+                info!(
+                    "Export: {} row(s) from {} to {}",
+                    rows,
+                    table.name,
+                    table_path.display()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Database ids of the given Tado homes; ids without a stored home are skipped with a warning.
+fn db_home_ids(conn: &mut PgConnection, tado_home_ids: &BTreeSet<i64>) -> Result<Vec<i64>, String> {
+    use schema::homes::dsl as H;
+
+    let found: Vec<(i64, i64)> = H::homes
+        .filter(H::tado_home_id.eq_any(tado_home_ids.iter().copied().collect::<Vec<_>>()))
+        .select((H::id, H::tado_home_id))
+        .load(conn)
+        .map_err(|e| format!("fetch homes failed: {}", e))?;
+    for missing in tado_home_ids
+        .iter()
+        .filter(|id| !found.iter().any(|(_, tado_id)| tado_id == *id))
+    {
+        warn!(
+            "Export: home {} from TADO_HOME_IDS is not in the database; skipping it",
+            missing
+        );
+    }
+    Ok(found.into_iter().map(|(id, _)| id).collect())
+}
+
+fn export_measurements(
+    conn: &mut PgConnection,
+    out: &mut impl Write,
+    table: &ExportTable,
+    format: MeasurementFormat,
+    home_ids: Option<&[i64]>,
+    range: DateRange,
+) -> Result<usize, String> {
+    match table.name {
+        "climate_measurements" => export_climate(conn, out, table, format, home_ids, range),
+        _ => export_weather(conn, out, table, format, home_ids, range),
+    }
+}
+
+fn export_climate(
+    conn: &mut PgConnection,
+    out: &mut impl Write,
+    table: &ExportTable,
+    format: MeasurementFormat,
+    home_ids: Option<&[i64]>,
+    range: DateRange,
+) -> Result<usize, String> {
+    use schema::climate_measurements::dsl as C;
+
+    let mut query = C::climate_measurements.into_boxed();
+    if let Some(ids) = home_ids {
+        query = query.filter(C::home_id.eq_any(ids.to_vec()));
+    }
+    if let Some(start) = range.start() {
+        query = query.filter(C::time.ge(start));
+    }
+    if let Some(end) = range.end() {
+        query = query.filter(C::time.lt(end));
+    }
+    let rows = query
+        .order((C::time.asc(), C::id.asc()))
+        .select(ClimateMeasurement::as_select())
+        .load_iter::<ClimateMeasurement, PgRowByRowLoadingMode>(conn)
+        .map_err(|e| format!("read {} failed: {}", table.name, e))?;
+    write_rows(out, table, format, rows)
+}
+
+fn export_weather(
+    conn: &mut PgConnection,
+    out: &mut impl Write,
+    table: &ExportTable,
+    format: MeasurementFormat,
+    home_ids: Option<&[i64]>,
+    range: DateRange,
+) -> Result<usize, String> {
+    use schema::weather_measurements::dsl as W;
+
+    let mut query = W::weather_measurements.into_boxed();
+    if let Some(ids) = home_ids {
+        query = query.filter(W::home_id.eq_any(ids.to_vec()));
+    }
+    if let Some(start) = range.start() {
+        query = query.filter(W::time.ge(start));
+    }
+    if let Some(end) = range.end() {
+        query = query.filter(W::time.lt(end));
+    }
+    let rows = query
+        .order((W::time.asc(), W::id.asc()))
+        .select(WeatherMeasurement::as_select())
+        .load_iter::<WeatherMeasurement, PgRowByRowLoadingMode>(conn)
+        .map_err(|e| format!("read {} failed: {}", table.name, e))?;
+    write_rows(out, table, format, rows)
+}
+
+fn write_rows<T: Serialize>(
+    out: &mut impl Write,
+    table: &ExportTable,
+    format: MeasurementFormat,
+    rows: impl Iterator<Item = QueryResult<T>>,
+) -> Result<usize, String> {
+    let mut count = 0;
+    for row in rows {
+        let row = row.map_err(|e| format!("read {} row failed: {}", table.name, e))?;
+        match format {
+            MeasurementFormat::Ndjson => write_ndjson_line(out, table, &row),
+            MeasurementFormat::Csv => write_csv_row(out, table, &row),
+        }
+        .map_err(|e| format!("write {} row failed: {}", table.name, e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// A row tagged with its table, so both tables can share one file.
+#[derive(Serialize)]
+struct NdjsonLine<'a, T> {
+    table: &'static str,
+    #[serde(flatten)]
+    row: &'a T,
+}
+
+fn write_ndjson_line<T: Serialize>(out: &mut impl Write, table: &ExportTable, row: &T) -> Result<(), String> {
+    serde_json::to_writer(&mut *out, &NdjsonLine { table: table.name, row }).map_err(|e| e.to_string())?;
+    writeln!(out).map_err(|e| e.to_string())
+}
+
+fn write_csv_header(out: &mut impl Write, table: &ExportTable) -> std::io::Result<()> {
+    let names = table.columns.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+    writeln!(out, "{}", names.join(","))
+}
+
+/// Writes `row` in the column order of `table`; missing and null values are left empty.
+fn write_csv_row<T: Serialize>(out: &mut impl Write, table: &ExportTable, row: &T) -> Result<(), String> {
+    let value = serde_json::to_value(row).map_err(|e| e.to_string())?;
+    let fields = table
+        .columns
+        .iter()
+        .map(|(name, _)| csv_field(value.get(*name).unwrap_or(&Value::Null)))
+        .collect::<Vec<_>>();
+    writeln!(out, "{}", fields.join(",")).map_err(|e| e.to_string())
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn measurement_table(name: &str) -> &'static ExportTable {
+    TABLES
+        .iter()
+        .find(|t| t.name == name)
+        .expect("measurement table is listed in TABLES")
+}
+
+/// `backup.csv` becomes `backup.climate_measurements.csv` in the same directory.
+fn csv_table_path(path: &Path, table: &str) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("export");
+    path.with_file_name(format!("{}.{}.csv", stem, table))
+}
+
+fn create_output(path: &Path) -> Result<BufWriter<File>, String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("create {} failed: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn snapshot_is_a_sqlite_file_with_every_table_and_the_seeded_row() {
+        let seeded = json!({
+            "id": 1,
+            "time": "2024-03-01T12:00:00+00:00",
+            "home_id": 3,
+            "zone_id": 7,
+            "device_id": null,
+            "source": "realtime",
+            "inside_temp_c": 21.5,
+            "window_open": false,
+            "ac_mode": "O'HEAT",
+        });
+        let path = std::env::temp_dir().join(format!("tado-timescale-snapshot-{}.sqlite", std::process::id()));
+        {
+            let mut db = Sqlite::create(&path).expect("create database");
+            write_snapshot(&mut db, |db, table| {
+                if table.name != "climate_measurements" {
+                    return Ok(0);
+                }
+                db.execute(&insert_sql(table, &seeded))?;
+                Ok(1)
+            })
+            .expect("write snapshot");
+        }
+
+        let header = std::fs::read(&path).expect("read snapshot");
+        assert!(header.starts_with(b"SQLite format 3\0"));
+        let mut db = Sqlite::open(&path).expect("open snapshot");
+        let tables = db
+            .query("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .expect("list tables");
+        let climate = db
+            .query("SELECT id, time, device_id, inside_temp_c, window_open, ac_mode FROM climate_measurements")
+            .expect("read climate rows");
+        let events = db.query("SELECT count(*) FROM events").expect("count events");
+        drop(db);
+        std::fs::remove_file(&path).expect("cleanup");
+
+        let mut expected: Vec<&str> = TABLES.iter().map(|t| t.name).collect();
+        expected.sort_unstable();
+        assert_eq!(
+            tables
+                .iter()
+                .map(|row| row[0].as_deref().expect("name"))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        let text = |s: &str| Some(s.to_string());
+        assert_eq!(
+            climate,
+            vec![vec![
+                text("1"),
+                text("2024-03-01T12:00:00+00:00"),
+                None,
+                text("21.5"),
+                text("0"),
+                text("O'HEAT"),
+            ]]
+        );
+        assert_eq!(events, vec![vec![text("0")]]);
+    }
+
+    #[test]
+    fn measurement_rows_render_as_ndjson_and_csv() {
+        let table = measurement_table("weather_measurements");
+        let row = WeatherMeasurement {
+            id: 5,
+            time: "2024-03-01T12:00:00Z".parse().expect("time"),
+            home_id: 3,
+            source: "realtime".to_string(),
+            outside_temp_c: Some(4.5),
+            solar_intensity_pct: None,
+            weather_state: Some("CLOUDY, \"DRIZZLE\"".to_string()),
+            ingest_lag_secs: None,
+            reported_at: None,
+        };
+
+        let mut ndjson = Vec::new();
+        write_ndjson_line(&mut ndjson, table, &row).expect("ndjson");
+        let line: Value = serde_json::from_slice(&ndjson).expect("valid json");
+        assert_eq!(line["table"], "weather_measurements");
+        assert_eq!(line["outside_temp_c"], 4.5);
+        assert!(ndjson.ends_with(b"}\n"));
+
+        let mut csv = Vec::new();
+        write_csv_header(&mut csv, table).expect("header");
+        write_csv_row(&mut csv, table, &row).expect("row");
+        assert_eq!(
+            String::from_utf8(csv).expect("utf8"),
+            "id,time,home_id,source,outside_temp_c,solar_intensity_pct,weather_state,ingest_lag_secs,reported_at\n\
+             5,2024-03-01T12:00:00Z,3,realtime,4.5,,\"CLOUDY, \"\"DRIZZLE\"\"\",,\n"
+        );
+
+        assert_eq!(
+            csv_table_path(Path::new("/tmp/backup.csv"), "climate_measurements"),
+            PathBuf::from("/tmp/backup.climate_measurements.csv")
+        );
+        let range = DateRange {
+            from: NaiveDate::from_ymd_opt(2024, 3, 1),
+            to: NaiveDate::from_ymd_opt(2024, 3, 31),
+        };
+        assert_eq!(range.end(), Some("2024-04-01T00:00:00Z".parse().expect("end")));
+    }
+}
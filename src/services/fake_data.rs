@@ -1,17 +1,23 @@
-use crate::db::models::{event_source, NewClimateMeasurement, NewHome, NewWeatherMeasurement, NewZone};
+use crate::config::DisabledWeatherFields;
+use crate::db::models::{NewClimateMeasurement, NewHome, NewWeatherMeasurement, NewZone, event_source};
 use crate::schema;
-use crate::services::ingest::{insert_climate_measurements, insert_weather_measurements};
+use crate::services::ingest::{INSERT_BATCH_ROWS, insert_climate_measurements, insert_weather_measurements};
 use chrono::{DateTime, Datelike, Duration, Timelike, Utc, Weekday};
-use diesel::prelude::*;
 use diesel::PgConnection;
+use diesel::prelude::*;
 use log::info;
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use std::f64::consts::PI;
 
-const HOME_TADO_ID: i64 = 4_201_337;
-const STEP_MINUTES: i64 = 15;
-const ZONE_NAMES: [&str; 8] = [
+const DEFAULT_HOME_TADO_ID: i64 = 4_201_337;
+const DEFAULT_HISTORY_DAYS: u32 = 365 * 5;
+const DEFAULT_STEP_MINUTES: u32 = 15;
+const DEFAULT_RNG_SEED: u64 = 0x0420_1337_DEAD_BEEF;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+/// A century of history; far past any useful demo, and well inside the range `DateTime` arithmetic can reach.
+const MAX_HISTORY_DAYS: u32 = 36_525;
+const DEFAULT_ZONE_NAMES: [&str; 8] = [
     "Living Room",
     "Kitchen",
     "Bedroom 1",
@@ -22,33 +28,146 @@ const ZONE_NAMES: [&str; 8] = [
     "Nursery",
 ];
 
-pub fn run(conn: &mut PgConnection) -> Result<(), String> {
-    let db_home_id = ensure_home(conn)?;
+/// Shape of the synthetic dataset. The defaults reproduce the original demo home: eight zones, five years of
+/// history at 15-minute steps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeDataConfig {
+    pub tado_home_id: i64,
+    /// One zone per name, numbered from 1 in order.
+    pub zone_names: Vec<String>,
+    pub history_days: u32,
+    pub step_minutes: u32,
+    /// Same seed, same dataset: regenerating upserts over the existing rows instead of adding new ones.
+    pub rng_seed: u64,
+}
+
+impl Default for FakeDataConfig {
+    fn default() -> Self {
+        FakeDataConfig {
+            tado_home_id: DEFAULT_HOME_TADO_ID,
+            zone_names: DEFAULT_ZONE_NAMES.iter().map(|name| name.to_string()).collect(),
+            history_days: DEFAULT_HISTORY_DAYS,
+            step_minutes: DEFAULT_STEP_MINUTES,
+            rng_seed: DEFAULT_RNG_SEED,
+        }
+    }
+}
+
+impl FakeDataConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.history_days == 0 {
+            return Err("fake data history must be at least one day".to_string());
+        }
+        if self.history_days > MAX_HISTORY_DAYS {
+            return Err(format!(
+                "fake data history of {} days exceeds the maximum of {} days",
+                self.history_days, MAX_HISTORY_DAYS
+            ));
+        }
+        if self.step_minutes == 0 || !MINUTES_PER_DAY.is_multiple_of(self.step_minutes) {
+            return Err(format!(
+                "fake data step of {} minute(s) must divide evenly into a day ({} minutes)",
+                self.step_minutes, MINUTES_PER_DAY
+            ));
+        }
+        if self.zone_names.is_empty() {
+            return Err("fake data needs at least one zone name".to_string());
+        }
+        Ok(())
+    }
+
+    fn step(&self) -> Duration {
+        Duration::minutes(self.step_minutes.into())
+    }
+
+    fn samples_per_day(&self) -> usize {
+        (MINUTES_PER_DAY / self.step_minutes) as usize
+    }
+
+    fn align_to_step(&self, ts: DateTime<Utc>) -> DateTime<Utc> {
+        let step_seconds = i64::from(self.step_minutes) * 60;
+        let aligned = (ts.timestamp() / step_seconds) * step_seconds;
+        DateTime::<Utc>::from_timestamp(aligned, 0).expect("valid timestamp")
+    }
+}
+
+pub fn run(
+    conn: &mut PgConnection,
+    weather_disabled_fields: DisabledWeatherFields,
+    config: &FakeDataConfig,
+    dry_run: bool,
+) -> Result<(), String> {
+    config.validate()?;
+    let db_home_id = ensure_home(conn, config.tado_home_id)?;
     let now = Utc::now();
-    let start = align_to_step(now - Duration::days(365 * 5));
-    let end = align_to_step(now);
+    let start = config.align_to_step(now - Duration::days(config.history_days.into()));
+    let end = config.align_to_step(now);
     if start >= end {
         return Err("Fake data generator requires start earlier than end".to_string());
     }
 
-    let zone_ids = ensure_zones(conn, db_home_id, start)?;
-    let mut rng = SmallRng::seed_from_u64(0x0420_1337_DEAD_BEEFu64);
+    let zone_ids = ensure_zones(conn, db_home_id, &config.zone_names, start)?;
 
     info!(
-        "Fake data: generating synthetic history for home {} from {} to {} (zones={})",
-        HOME_TADO_ID,
+        "Fake data: generating synthetic history for home {} from {} to {} (zones={}, step={}min)",
+        config.tado_home_id,
         start,
         end,
-        zone_ids.len()
+        zone_ids.len(),
+        config.step_minutes
     );
 
-    let mut climate_batch = Vec::with_capacity(zone_ids.len() * samples_per_day());
-    let mut weather_batch = Vec::with_capacity(samples_per_day());
     let mut inserted_climate: usize = 0;
     let mut inserted_weather: usize = 0;
+    generate(
+        config,
+        start,
+        end,
+        db_home_id,
+        &zone_ids,
+        INSERT_BATCH_ROWS,
+        weather_disabled_fields,
+        |climate, weather| {
+            inserted_climate += insert_climate_measurements(conn, climate, dry_run)?;
+            inserted_weather += insert_weather_measurements(conn, weather, dry_run)?;
+            Ok(())
+        },
+    )?;
+
+    let total_days = (end - start).num_days();
+    info!(
+        "Fake data: complete (days={}, climate_inserts={}, weather_inserts={})",
+        total_days, inserted_climate, inserted_weather
+    );
+
+    Ok(())
+}
+
+/// Generates rows for `[start, end)` and hands them to `flush` in batches.
+///
+/// A batch is flushed at every UTC day boundary and whenever the pending climate rows reach
+/// `flush_threshold`, so memory stays at roughly one batch (a few MB) regardless of the span;
+/// multi-decade spans only cost time, not memory.
+#[allow(clippy::too_many_arguments)]
+fn generate<F>(
+    config: &FakeDataConfig,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    db_home_id: i64,
+    zone_ids: &[i64],
+    flush_threshold: usize,
+    weather_disabled_fields: DisabledWeatherFields,
+    mut flush: F,
+) -> Result<(), String>
+where
+    F: FnMut(&[NewClimateMeasurement], &[NewWeatherMeasurement]) -> Result<(), String>,
+{
+    let mut rng = SmallRng::seed_from_u64(config.rng_seed);
+    let mut climate_batch = Vec::with_capacity(flush_threshold.min(zone_ids.len() * config.samples_per_day()));
+    let mut weather_batch = Vec::with_capacity(config.samples_per_day());
     let mut ts = start;
     let mut current_day = start.date_naive();
-    let step = Duration::minutes(STEP_MINUTES);
+    let step = config.step();
     let mut last_logged_month: Option<(i32, u32)> = None;
 
     while ts < end {
@@ -64,14 +183,8 @@ pub fn run(conn: &mut PgConnection) -> Result<(), String> {
             }
         }
 
-        if ts.date_naive() != current_day {
-            flush_batches(
-                conn,
-                &mut climate_batch,
-                &mut weather_batch,
-                &mut inserted_climate,
-                &mut inserted_weather,
-            )?;
+        if ts.date_naive() != current_day || climate_batch.len() + zone_ids.len() > flush_threshold {
+            flush_batches(&mut climate_batch, &mut weather_batch, &mut flush)?;
             current_day = ts.date_naive();
         }
 
@@ -88,6 +201,7 @@ pub fn run(conn: &mut PgConnection) -> Result<(), String> {
         weather_row.outside_temp_c = Some(outside_temp);
         weather_row.solar_intensity_pct = Some(solar_intensity);
         weather_row.weather_state = Some(weather_state.clone());
+        weather_disabled_fields.apply(&mut weather_row);
         weather_batch.push(weather_row);
 
         for (index, zone_id) in zone_ids.iter().enumerate() {
@@ -112,28 +226,14 @@ pub fn run(conn: &mut PgConnection) -> Result<(), String> {
         ts += step;
     }
 
-    flush_batches(
-        conn,
-        &mut climate_batch,
-        &mut weather_batch,
-        &mut inserted_climate,
-        &mut inserted_weather,
-    )?;
-
-    let total_days = (end - start).num_days();
-    info!(
-        "Fake data: complete (days={}, climate_inserts={}, weather_inserts={})",
-        total_days, inserted_climate, inserted_weather
-    );
-
-    Ok(())
+    flush_batches(&mut climate_batch, &mut weather_batch, &mut flush)
 }
 
-fn ensure_home(conn: &mut PgConnection) -> Result<i64, String> {
+fn ensure_home(conn: &mut PgConnection, tado_home_id: i64) -> Result<i64, String> {
     use schema::homes::dsl as H;
 
     let new_home = NewHome {
-        tado_home_id: HOME_TADO_ID,
+        tado_home_id,
         name: Some("Chez Villa".to_string()),
         timezone: Some("Etc/UTC".to_string()),
         temperature_unit: Some("CELSIUS".to_string()),
@@ -145,6 +245,10 @@ fn ensure_home(conn: &mut PgConnection) -> Result<i64, String> {
         country: Some("Promised Land".to_string()),
         latitude: Some(51.5074),
         longitude: Some(-0.1278),
+        away_radius_m: Some(400.0),
+        incident_detection_enabled: Some(true),
+        incident_detection_supported: Some(true),
+        feature_flags: None,
     };
 
     diesel::insert_into(H::homes)
@@ -163,27 +267,35 @@ fn ensure_home(conn: &mut PgConnection) -> Result<i64, String> {
             H::country.eq(new_home.country.clone()),
             H::latitude.eq(new_home.latitude),
             H::longitude.eq(new_home.longitude),
+            H::away_radius_m.eq(new_home.away_radius_m),
+            H::incident_detection_enabled.eq(new_home.incident_detection_enabled),
+            H::incident_detection_supported.eq(new_home.incident_detection_supported),
             H::updated_at.eq(Utc::now()),
         ))
         .execute(conn)
         .map_err(|e| format!("insert home failed: {}", e))?;
 
     H::homes
-        .filter(H::tado_home_id.eq(HOME_TADO_ID))
+        .filter(H::tado_home_id.eq(tado_home_id))
         .select(H::id)
         .first(conn)
         .map_err(|e| format!("fetch home id failed: {}", e))
 }
 
-fn ensure_zones(conn: &mut PgConnection, db_home_id: i64, start: DateTime<Utc>) -> Result<Vec<i64>, String> {
+fn ensure_zones(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    zone_names: &[String],
+    start: DateTime<Utc>,
+) -> Result<Vec<i64>, String> {
     use schema::zones::dsl as Z;
 
-    for (index, name) in ZONE_NAMES.iter().enumerate() {
+    for (index, name) in zone_names.iter().enumerate() {
         let zone_tado_id = (index as i64) + 1;
         let new_zone = NewZone {
             home_id: db_home_id,
             tado_zone_id: zone_tado_id,
-            name: Some((*name).to_string()),
+            name: Some(name.clone()),
             zone_type: Some("HEATING".to_string()),
             date_created: Some(start),
         };
@@ -208,8 +320,8 @@ fn ensure_zones(conn: &mut PgConnection, db_home_id: i64, start: DateTime<Utc>)
         .load(conn)
         .map_err(|e| format!("fetch zones failed: {}", e))?;
 
-    let mut map = Vec::with_capacity(ZONE_NAMES.len());
-    for (index, _) in ZONE_NAMES.iter().enumerate() {
+    let mut map = Vec::with_capacity(zone_names.len());
+    for index in 0..zone_names.len() {
         let zone_tado_id = (index as i64) + 1;
         let db_id = rows
             .iter()
@@ -221,36 +333,23 @@ fn ensure_zones(conn: &mut PgConnection, db_home_id: i64, start: DateTime<Utc>)
     Ok(map)
 }
 
-fn flush_batches(
-    conn: &mut PgConnection,
+fn flush_batches<F>(
     climate_batch: &mut Vec<NewClimateMeasurement>,
     weather_batch: &mut Vec<NewWeatherMeasurement>,
-    inserted_climate: &mut usize,
-    inserted_weather: &mut usize,
-) -> Result<(), String> {
-    if !climate_batch.is_empty() {
-        let inserted = insert_climate_measurements(conn, climate_batch)?;
-        *inserted_climate += inserted;
-        climate_batch.clear();
-    }
-    if !weather_batch.is_empty() {
-        let inserted = insert_weather_measurements(conn, weather_batch)?;
-        *inserted_weather += inserted;
-        weather_batch.clear();
+    flush: &mut F,
+) -> Result<(), String>
+where
+    F: FnMut(&[NewClimateMeasurement], &[NewWeatherMeasurement]) -> Result<(), String>,
+{
+    if climate_batch.is_empty() && weather_batch.is_empty() {
+        return Ok(());
     }
+    flush(climate_batch, weather_batch)?;
+    climate_batch.clear();
+    weather_batch.clear();
     Ok(())
 }
 
-fn align_to_step(ts: DateTime<Utc>) -> DateTime<Utc> {
-    let step_seconds = STEP_MINUTES * 60;
-    let aligned = (ts.timestamp() / step_seconds) * step_seconds;
-    DateTime::<Utc>::from_timestamp(aligned, 0).expect("valid timestamp")
-}
-
-fn samples_per_day() -> usize {
-    (24 * 60 / STEP_MINUTES) as usize
-}
-
 fn compute_outside_temp(
     day_fraction: f64,
     annual_fraction: f64,
@@ -391,3 +490,68 @@ fn gaussian(x: f64, center: f64, width: f64) -> f64 {
 fn is_weekend(weekday: Weekday) -> bool {
     matches!(weekday, Weekday::Sat | Weekday::Sun)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn flushes_at_row_threshold_without_changing_totals() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 22, 0, 0).unwrap();
+        let end = start + Duration::hours(4);
+        let zone_ids = [1, 2, 3];
+        let mut batches: Vec<(usize, usize)> = Vec::new();
+
+        let config = FakeDataConfig::default();
+        generate(
+            &config,
+            start,
+            end,
+            1,
+            &zone_ids,
+            10,
+            DisabledWeatherFields::default(),
+            |climate, weather| {
+                batches.push((climate.len(), weather.len()));
+                Ok(())
+            },
+        )
+        .expect("generation succeeds");
+
+        let steps = (4 * 60 / config.step_minutes) as usize;
+        let total_climate: usize = batches.iter().map(|(c, _)| c).sum();
+        let total_weather: usize = batches.iter().map(|(_, w)| w).sum();
+        assert_eq!(total_climate, steps * zone_ids.len());
+        assert_eq!(total_weather, steps);
+        // Threshold flushes carry whole steps (9 rows); midnight and the end flush the 6-row remainders.
+        let climate_sizes: Vec<usize> = batches.iter().map(|(c, _)| *c).collect();
+        assert_eq!(climate_sizes, vec![9, 9, 6, 9, 9, 6]);
+    }
+
+    #[test]
+    fn config_rejects_uneven_steps_and_empty_history() {
+        assert_eq!(FakeDataConfig::default().validate(), Ok(()));
+        assert_eq!(FakeDataConfig::default().samples_per_day(), 96);
+
+        let config = |history_days, step_minutes| FakeDataConfig {
+            history_days,
+            step_minutes,
+            ..FakeDataConfig::default()
+        };
+        assert!(config(30, 60).validate().is_ok());
+        assert!(config(30, 7).validate().is_err());
+        assert!(config(30, 0).validate().is_err());
+        assert!(config(0, 15).validate().is_err());
+        assert!(config(MAX_HISTORY_DAYS, 15).validate().is_ok());
+        assert!(config(MAX_HISTORY_DAYS + 1, 15).validate().is_err());
+        assert!(config(u32::MAX, 15).validate().is_err());
+
+        let hourly = config(1, 60);
+        let ts = Utc.with_ymd_and_hms(2024, 3, 1, 10, 47, 12).unwrap();
+        assert_eq!(
+            hourly.align_to_step(ts),
+            Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap()
+        );
+    }
+}
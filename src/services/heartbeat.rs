@@ -0,0 +1,226 @@
+use crate::config::MaintenanceWindow;
+use crate::db::models::{CollectorInstance, NewCollectorInstance};
+use crate::schema;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::{debug, warn};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Heartbeats older than this belong to processes that are gone and get deleted.
+const STALE_AFTER_HOURS: i64 = 24;
+/// A heartbeat is never considered stale sooner than this, even with a very short realtime interval.
+const MIN_FRESH_WINDOW_MINUTES: i64 = 5;
+/// How often the background beater wakes to check whether a beat is due.
+const BACKGROUND_POLL: Duration = Duration::from_secs(1);
+
+/// Identity of this process in `collector_instances`.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    hostname: String,
+    pid: i32,
+    started_at: DateTime<Utc>,
+    beat_interval: Duration,
+    /// How recent another instance's `last_seen` must be to count as still running.
+    fresh_window: ChronoDuration,
+    /// `DRY_RUN`: read other instances' heartbeats but never write or expire any.
+    dry_run: bool,
+}
+
+impl Heartbeat {
+    /// `beat_interval` is how often this (and every other) instance refreshes its heartbeat.
+    pub fn new(beat_interval: Duration, dry_run: bool) -> Self {
+        Heartbeat {
+            hostname: current_hostname(),
+            pid: std::process::id() as i32,
+            started_at: Utc::now(),
+            beat_interval,
+            fresh_window: fresh_window(beat_interval),
+            dry_run,
+        }
+    }
+
+    /// Registers this process, warns about any other instance with a fresh heartbeat and drops stale ones.
+    pub fn register(&self, conn: &mut PgConnection) -> Result<(), String> {
+        use schema::collector_instances::dsl as CI;
+
+        let now = Utc::now();
+        let deleted = if self.dry_run {
+            0
+        } else {
+            diesel::delete(
+                CI::collector_instances.filter(CI::last_seen.lt(now - ChronoDuration::hours(STALE_AFTER_HOURS))),
+            )
+            .execute(conn)
+            .map_err(|e| format!("expire stale collector heartbeats failed: {}", e))?
+        };
+        if deleted > 0 {
+            debug!("Heartbeat: expired {} stale collector instance(s)", deleted);
+        }
+
+        let others: Vec<CollectorInstance> = CI::collector_instances
+            .select(CollectorInstance::as_select())
+            .load(conn)
+            .map_err(|e| format!("fetch collector heartbeats failed: {}", e))?;
+        for other in self.fresh_competitors(&others, now) {
+            warn!(
+                "Another collector instance appears to be running (host={}, pid={}, started_at={}, last_seen={}); \
+                 running two collectors doubles Tado API load",
+                other.hostname, other.pid, other.started_at, other.last_seen
+            );
+        }
+
+        self.beat(conn)
+    }
+
+    /// Upserts this process' heartbeat with the current time.
+    pub fn beat(&self, conn: &mut PgConnection) -> Result<(), String> {
+        use schema::collector_instances::dsl as CI;
+
+        if self.dry_run {
+            debug!(
+                "Dry run: would upsert collector heartbeat (host={}, pid={})",
+                self.hostname, self.pid
+            );
+            return Ok(());
+        }
+        let row = NewCollectorInstance {
+            hostname: self.hostname.clone(),
+            pid: self.pid,
+            started_at: self.started_at,
+            last_seen: Utc::now(),
+        };
+        diesel::insert_into(CI::collector_instances)
+            .values(&row)
+            .on_conflict((CI::hostname, CI::pid, CI::started_at))
+            .do_update()
+            .set(CI::last_seen.eq(row.last_seen))
+            .execute(conn)
+            .map(|_| ())
+            .map_err(|e| format!("upsert collector heartbeat failed: {}", e))
+    }
+
+    /// Beats every interval from its own thread and connection for the rest of the process, so a long backfill
+    /// or reference sync on the main connection never lets the heartbeat go stale. Like the realtime loop it
+    /// pauses during `maintenance_window`, and beats as soon as the window ends. A failed beat reconnects and is
+    /// retried on the next interval.
+    pub fn spawn_background(&self, database_url: &str, maintenance_window: Option<MaintenanceWindow>) {
+        let heartbeat = self.clone();
+        let database_url = database_url.to_string();
+        thread::spawn(move || {
+            let mut conn: Option<PgConnection> = None;
+            let mut next_beat = Instant::now() + heartbeat.beat_interval;
+            loop {
+                thread::sleep(BACKGROUND_POLL);
+                if Instant::now() < next_beat || maintenance_window.is_some_and(|w| w.contains(Utc::now())) {
+                    continue;
+                }
+                next_beat = Instant::now() + heartbeat.beat_interval;
+                let beat = match conn.as_mut() {
+                    Some(conn) => heartbeat.beat(conn),
+                    None => PgConnection::establish(&database_url)
+                        .map_err(|e| format!("heartbeat DB connection failed: {}", e))
+                        .and_then(|new_conn| heartbeat.beat(conn.insert(new_conn))),
+                };
+                if let Err(e) = beat {
+                    warn!("Collector heartbeat failed: {}", e);
+                    conn = None;
+                }
+            }
+        });
+    }
+
+    fn is_self(&self, instance: &CollectorInstance) -> bool {
+        instance.hostname == self.hostname && instance.pid == self.pid && instance.started_at == self.started_at
+    }
+
+    fn fresh_competitors<'a>(
+        &self,
+        instances: &'a [CollectorInstance],
+        now: DateTime<Utc>,
+    ) -> Vec<&'a CollectorInstance> {
+        instances
+            .iter()
+            .filter(|i| !self.is_self(i) && now - i.last_seen <= self.fresh_window)
+            .collect()
+    }
+}
+
+/// How recent a heartbeat must be to count as a running instance: three beats, but at least
+/// `MIN_FRESH_WINDOW_MINUTES`.
+fn fresh_window(beat_interval: Duration) -> ChronoDuration {
+    ChronoDuration::from_std(beat_interval)
+        .ok()
+        .and_then(|interval| interval.checked_mul(3))
+        .unwrap_or(ChronoDuration::MAX)
+        .max(ChronoDuration::minutes(MIN_FRESH_WINDOW_MINUTES))
+}
+
+/// Fails unless a collector on this host beat within the fresh window; used by `--healthcheck`, which runs
+/// next to the collector it probes. Returns the time of the latest heartbeat.
+pub fn check_fresh(conn: &mut PgConnection, beat_interval: Duration) -> Result<DateTime<Utc>, String> {
+    use schema::collector_instances::dsl as CI;
+
+    let hostname = current_hostname();
+    let last_seen: Option<DateTime<Utc>> = CI::collector_instances
+        .filter(CI::hostname.eq(&hostname))
+        .select(diesel::dsl::max(CI::last_seen))
+        .first(conn)
+        .map_err(|e| format!("fetch collector heartbeats failed: {}", e))?;
+    let last_seen = last_seen.ok_or_else(|| format!("no collector heartbeat recorded for host {}", hostname))?;
+    let age = Utc::now() - last_seen;
+    if age > fresh_window(beat_interval) {
+        return Err(format!(
+            "last collector heartbeat on host {} is {}s old ({})",
+            hostname,
+            age.num_seconds(),
+            last_seen
+        ));
+    }
+    Ok(last_seen)
+}
+
+fn current_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(id: i64, pid: i32, last_seen: DateTime<Utc>) -> CollectorInstance {
+        CollectorInstance {
+            id,
+            hostname: "collector-a".to_string(),
+            pid,
+            started_at: last_seen - ChronoDuration::hours(1),
+            last_seen,
+        }
+    }
+
+    #[test]
+    fn fresh_competing_heartbeat_is_reported() {
+        let mut me = Heartbeat::new(Duration::from_secs(60), false);
+        me.hostname = "collector-a".to_string();
+        me.pid = 1;
+        let now = me.started_at;
+
+        let mut own = instance(1, 1, now);
+        own.started_at = me.started_at;
+        let fresh = instance(2, 2, now - ChronoDuration::minutes(2));
+        let stale = instance(3, 3, now - ChronoDuration::minutes(30));
+        let instances = vec![own, fresh, stale];
+
+        let competitors = me.fresh_competitors(&instances, now);
+
+        assert_eq!(competitors.len(), 1);
+        assert_eq!(competitors[0].id, 2);
+    }
+}
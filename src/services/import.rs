@@ -0,0 +1,285 @@
+//! Restores measurements from newline-delimited JSON without touching the Tado API; the inverse of `--export`.
+//!
+//! Each line is one `NewClimateMeasurement` or `NewWeatherMeasurement`. Lines written by `--export` carry a
+//! `table` tag; untagged lines from other tools are weather rows when they have a weather-only field and climate
+//! rows otherwise. The whole file is validated before the first insert, so a malformed line leaves the database
+//! untouched. Rows already stored are skipped by the usual on-conflict rules, which makes re-running safe.
+
+use crate::db::models::{NewClimateMeasurement, NewWeatherMeasurement, event_source};
+use crate::schema;
+use crate::services::ingest::{INSERT_BATCH_ROWS, insert_climate_measurements, insert_weather_measurements};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::info;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Sources `--source` accepts; the same set the measurement tables allow.
+pub const SOURCES: &[&str] = &[event_source::HISTORICAL, event_source::REALTIME, event_source::DERIVED];
+
+/// Source stamped on imported rows unless `--source` says otherwise.
+pub const DEFAULT_SOURCE: &str = event_source::HISTORICAL;
+
+/// Fields only weather rows have, used to classify lines without a `table` tag.
+const WEATHER_FIELDS: &[&str] = &["outside_temp_c", "solar_intensity_pct", "weather_state", "reported_at"];
+
+/// Validates `--source`, returning the canonical constant.
+pub fn parse_source(value: &str) -> Result<&'static str, String> {
+    SOURCES
+        .iter()
+        .copied()
+        .find(|s| *s == value)
+        .ok_or_else(|| format!("`--source` must be one of {} (got '{}')", SOURCES.join(", "), value))
+}
+
+#[derive(Debug)]
+enum ImportRow {
+    Climate(NewClimateMeasurement),
+    Weather(NewWeatherMeasurement),
+}
+
+/// Database ids the imported rows may reference, loaded once up front.
+#[derive(Debug, Default)]
+struct KnownIds {
+    homes: BTreeSet<i64>,
+    /// zone id -> home id
+    zones: BTreeMap<i64, i64>,
+    /// device id -> home id
+    devices: BTreeMap<i64, i64>,
+}
+
+#[derive(Debug, Default)]
+struct ImportCounts {
+    climate_inserted: usize,
+    climate_skipped: usize,
+    weather_inserted: usize,
+    weather_skipped: usize,
+}
+
+/// Reads the NDJSON file at `path`, stamps every row with `source` and inserts it, skipping stored duplicates.
+/// Under `dry_run` the file is still validated in full, but the batches are only logged.
+pub fn run(conn: &mut PgConnection, path: &Path, source: &str, dry_run: bool) -> Result<(), String> {
+    let known = load_known_ids(conn)?;
+    let total = for_each_row(path, source, |row| validate(&known, row))?;
+    info!(
+        "Import: {} row(s) in {} are valid; inserting as {}",
+        total,
+        path.display(),
+        source
+    );
+
+    let mut counts = ImportCounts::default();
+    let mut climate = Vec::new();
+    let mut weather = Vec::new();
+    for_each_row(path, source, |row| {
+        match row {
+            ImportRow::Climate(row) => climate.push(row),
+            ImportRow::Weather(row) => weather.push(row),
+        }
+        if climate.len() >= INSERT_BATCH_ROWS {
+            flush_climate(conn, &mut climate, &mut counts, dry_run)?;
+        }
+        if weather.len() >= INSERT_BATCH_ROWS {
+            flush_weather(conn, &mut weather, &mut counts, dry_run)?;
+        }
+        Ok(())
+    })?;
+    flush_climate(conn, &mut climate, &mut counts, dry_run)?;
+    flush_weather(conn, &mut weather, &mut counts, dry_run)?;
+
+    info!(
+        "Import: climate {} inserted, {} skipped as duplicates; weather {} inserted, {} skipped as duplicates",
+        counts.climate_inserted, counts.climate_skipped, counts.weather_inserted, counts.weather_skipped
+    );
+    Ok(())
+}
+
+fn flush_climate(
+    conn: &mut PgConnection,
+    rows: &mut Vec<NewClimateMeasurement>,
+    counts: &mut ImportCounts,
+    dry_run: bool,
+) -> Result<(), String> {
+    let inserted = insert_climate_measurements(conn, rows, dry_run)?;
+    counts.climate_inserted += inserted;
+    counts.climate_skipped += rows.len() - inserted;
+    rows.clear();
+    Ok(())
+}
+
+fn flush_weather(
+    conn: &mut PgConnection,
+    rows: &mut Vec<NewWeatherMeasurement>,
+    counts: &mut ImportCounts,
+    dry_run: bool,
+) -> Result<(), String> {
+    let inserted = insert_weather_measurements(conn, rows, dry_run)?;
+    counts.weather_inserted += inserted;
+    counts.weather_skipped += rows.len() - inserted;
+    rows.clear();
+    Ok(())
+}
+
+fn load_known_ids(conn: &mut PgConnection) -> Result<KnownIds, String> {
+    use schema::devices::dsl as D;
+    use schema::homes::dsl as H;
+    use schema::zones::dsl as Z;
+
+    let homes = H::homes
+        .select(H::id)
+        .load::<i64>(conn)
+        .map_err(|e| format!("fetch homes failed: {}", e))?;
+    let zones = Z::zones
+        .select((Z::id, Z::home_id))
+        .load::<(i64, i64)>(conn)
+        .map_err(|e| format!("fetch zones failed: {}", e))?;
+    let devices = D::devices
+        .select((D::id, D::home_id))
+        .load::<(i64, i64)>(conn)
+        .map_err(|e| format!("fetch devices failed: {}", e))?;
+    Ok(KnownIds {
+        homes: homes.into_iter().collect(),
+        zones: zones.into_iter().collect(),
+        devices: devices.into_iter().collect(),
+    })
+}
+
+/// Calls `f` with each non-blank line's row and returns how many there were; the first failure stops the read
+/// and is reported with its line number.
+fn for_each_row(
+    path: &Path,
+    source: &str,
+    mut f: impl FnMut(ImportRow) -> Result<(), String>,
+) -> Result<usize, String> {
+    let file = File::open(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let mut count = 0;
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let at = |e: String| format!("{}:{}: {}", path.display(), index + 1, e);
+        let line = line.map_err(|e| at(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        f(parse_line(&line, source).map_err(at)?).map_err(at)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn parse_line(line: &str, source: &str) -> Result<ImportRow, String> {
+    let mut value: Value = serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| "expected a JSON object".to_string())?;
+    let weather = match object.remove("table") {
+        Some(Value::String(table)) if table == "climate_measurements" => false,
+        Some(Value::String(table)) if table == "weather_measurements" => true,
+        Some(other) => {
+            return Err(format!(
+                "`table` must be climate_measurements or weather_measurements (got {})",
+                other
+            ));
+        }
+        None => WEATHER_FIELDS.iter().any(|field| object.contains_key(*field)),
+    };
+    object.insert("source".to_string(), Value::from(source));
+
+    if weather {
+        deserialize(value).map(ImportRow::Weather)
+    } else {
+        deserialize(value).map(ImportRow::Climate)
+    }
+}
+
+fn deserialize<T: DeserializeOwned>(value: Value) -> Result<T, String> {
+    serde_path_to_error::deserialize(value).map_err(|err| format!("at path `{}`: {}", err.path(), err.inner()))
+}
+
+/// Rejects rows whose ids are not stored (the insert would fail on the foreign key halfway through) and
+/// percentages outside 0–100.
+fn validate(known: &KnownIds, row: ImportRow) -> Result<(), String> {
+    let (home_id, percentages) = match &row {
+        ImportRow::Climate(r) => (
+            r.home_id,
+            vec![
+                ("humidity_pct", r.humidity_pct),
+                ("heating_power_pct", r.heating_power_pct),
+            ],
+        ),
+        ImportRow::Weather(r) => (r.home_id, vec![("solar_intensity_pct", r.solar_intensity_pct)]),
+    };
+    if !known.homes.contains(&home_id) {
+        return Err(format!("home_id {} is not in the database", home_id));
+    }
+    if let ImportRow::Climate(r) = &row {
+        for (name, id, owners) in [
+            ("zone_id", r.zone_id, &known.zones),
+            ("device_id", r.device_id, &known.devices),
+        ] {
+            match id.map(|id| (id, owners.get(&id))) {
+                Some((id, None)) => return Err(format!("{} {} is not in the database", name, id)),
+                Some((id, Some(owner))) if *owner != home_id => {
+                    return Err(format!("{} {} belongs to home {}, not {}", name, id, owner, home_id));
+                }
+                _ => {}
+            }
+        }
+    }
+    for (name, value) in percentages {
+        if let Some(value) = value.filter(|v| !(0.0..=100.0).contains(v)) {
+            return Err(format!("{} {} is outside 0-100", name, value));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_parse_validate_and_take_the_source_override() {
+        let known = KnownIds {
+            homes: BTreeSet::from([3]),
+            zones: BTreeMap::from([(7, 3), (8, 4)]),
+            devices: BTreeMap::new(),
+        };
+
+        // as written by `--export`, with its id and original source
+        let exported = r#"{"table":"climate_measurements","id":1,"time":"2024-03-01T12:00:00Z","home_id":3,"zone_id":7,"device_id":null,"source":"realtime","inside_temp_c":21.5,"humidity_pct":40.0}"#;
+        match parse_line(exported, event_source::HISTORICAL).expect("climate line") {
+            ImportRow::Climate(row) => {
+                assert_eq!(row.source, event_source::HISTORICAL);
+                assert_eq!(row.inside_temp_c, Some(21.5));
+                validate(&known, ImportRow::Climate(row)).expect("valid");
+            }
+            other => panic!("expected climate row, got {other:?}"),
+        }
+
+        let untagged = r#"{"time":"2024-03-01T12:00:00Z","home_id":3,"outside_temp_c":4.5}"#;
+        assert!(matches!(
+            parse_line(untagged, event_source::HISTORICAL),
+            Ok(ImportRow::Weather(_))
+        ));
+
+        let err = parse_line(r#"{"home_id":3}"#, event_source::HISTORICAL).expect_err("missing time");
+        assert!(err.contains("time"), "{err}");
+        assert!(parse_line("[1]", event_source::HISTORICAL).is_err());
+        assert!(parse_line(r#"{"table":"events"}"#, event_source::HISTORICAL).is_err());
+
+        let foreign_zone = r#"{"time":"2024-03-01T12:00:00Z","home_id":3,"zone_id":8}"#;
+        let row = parse_line(foreign_zone, event_source::HISTORICAL).expect("parses");
+        assert_eq!(
+            validate(&known, row).expect_err("zone of another home"),
+            "zone_id 8 belongs to home 4, not 3"
+        );
+        let humid = r#"{"time":"2024-03-01T12:00:00Z","home_id":3,"humidity_pct":140.0}"#;
+        assert!(validate(&known, parse_line(humid, event_source::HISTORICAL).expect("parses")).is_err());
+
+        assert_eq!(parse_source("derived"), Ok(event_source::DERIVED));
+        assert!(parse_source("backup").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::db::models::{NewClimateMeasurement, NewWeatherMeasurement};
+use crate::utils::percent_encode;
+use log::{debug, warn};
+use std::fmt::Write as _;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Keeps each UDP datagram below a typical Ethernet MTU so InfluxDB's UDP listener never sees a truncated line.
+const MAX_DATAGRAM_BYTES: usize = 1400;
+
+enum Transport {
+    Udp {
+        socket: UdpSocket,
+        addr: String,
+    },
+    Http {
+        agent: ureq::Agent,
+        write_url: String,
+        token: Option<String>,
+    },
+}
+
+/// Mirrors realtime measurements into InfluxDB as line protocol, in addition to Timescale.
+///
+/// `udp://host:port` targets a UDP listener; `http(s)://` URLs use the v2 write API and need a bucket.
+/// Writes are best effort: failures are logged and never propagate into the realtime loop.
+pub struct InfluxSink {
+    transport: Transport,
+}
+
+/// Lines collected during one realtime tick and written in one go.
+#[derive(Debug, Default)]
+pub struct InfluxBatch {
+    lines: Vec<String>,
+}
+
+impl InfluxSink {
+    pub fn new(url: &str, bucket: Option<&str>, org: Option<&str>, token: Option<&str>) -> Result<Self, String> {
+        let transport = if let Some(addr) = url.strip_prefix("udp://") {
+            let socket =
+                UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("binding InfluxDB UDP socket failed: {}", e))?;
+            Transport::Udp {
+                socket,
+                addr: addr.trim_end_matches('/').to_string(),
+            }
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            let bucket = bucket.ok_or("INFLUXDB_BUCKET is required for an HTTP INFLUXDB_URL")?;
+            let agent = ureq::Agent::config_builder()
+                .timeout_global(Some(REQUEST_TIMEOUT))
+                .http_status_as_error(false)
+                .build()
+                .into();
+            Transport::Http {
+                agent,
+                write_url: write_url(url, bucket, org),
+                token: token.map(str::to_string),
+            }
+        } else {
+            return Err(format!(
+                "INFLUXDB_URL must start with udp://, http:// or https://, got '{}'",
+                url
+            ));
+        };
+        Ok(InfluxSink { transport })
+    }
+
+    /// Sends the tick's lines. Returns whether they were accepted; an empty batch counts as delivered.
+    pub fn write(&self, batch: &InfluxBatch) -> bool {
+        if batch.lines.is_empty() {
+            return true;
+        }
+        let delivered = match &self.transport {
+            Transport::Udp { socket, addr } => datagrams(&batch.lines).iter().all(|payload| {
+                socket
+                    .send_to(payload.as_bytes(), addr.as_str())
+                    .map_err(|e| warn!("InfluxDB: UDP write to {} failed: {}", addr, e))
+                    .is_ok()
+            }),
+            Transport::Http {
+                agent,
+                write_url,
+                token,
+            } => {
+                let mut request = agent
+                    .post(write_url)
+                    .header("Content-Type", "text/plain; charset=utf-8");
+                if let Some(token) = token {
+                    request = request.header("Authorization", &format!("Token {}", token));
+                }
+                match request.send(batch.lines.join("\n")) {
+                    Ok(res) if res.status().is_success() => true,
+                    Ok(res) => {
+                        warn!("InfluxDB: write rejected with status {}", res.status().as_u16());
+                        false
+                    }
+                    Err(e) => {
+                        warn!("InfluxDB: write failed: {}", e);
+                        false
+                    }
+                }
+            }
+        };
+        if delivered {
+            debug!("InfluxDB: wrote {} line(s)", batch.lines.len());
+        }
+        delivered
+    }
+}
+
+/// v2 write endpoint with the bucket and, when set, the organization percent-encoded into the query.
+fn write_url(url: &str, bucket: &str, org: Option<&str>) -> String {
+    let mut write_url = format!(
+        "{}/api/v2/write?bucket={}&precision=ns",
+        url.trim_end_matches('/'),
+        percent_encode(bucket)
+    );
+    if let Some(org) = org {
+        let _ = write!(write_url, "&org={}", percent_encode(org));
+    }
+    write_url
+}
+
+impl InfluxBatch {
+    /// Moves another batch's lines into this one, e.g. to merge the per-home batches of a tick.
+    pub fn append(&mut self, mut other: InfluxBatch) {
+        self.lines.append(&mut other.lines);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn push_climate(&mut self, row: &NewClimateMeasurement) {
+        let mut tags = vec![("home_id", row.home_id.to_string())];
+        tags.extend(row.zone_id.map(|id| ("zone_id", id.to_string())));
+        tags.extend(row.device_id.map(|id| ("device_id", id.to_string())));
+        tags.push(("source", row.source.clone()));
+        let fields = [
+            ("inside_temp_c", row.inside_temp_c.map(FieldValue::Float)),
+            ("humidity_pct", row.humidity_pct.map(FieldValue::Float)),
+            ("setpoint_temp_c", row.setpoint_temp_c.map(FieldValue::Float)),
+            ("heating_power_pct", row.heating_power_pct.map(FieldValue::Float)),
+            ("ac_power_on", row.ac_power_on.map(FieldValue::Bool)),
+            ("ac_mode", row.ac_mode.clone().map(FieldValue::Text)),
+            ("window_open", row.window_open.map(FieldValue::Bool)),
+            ("battery_low", row.battery_low.map(FieldValue::Bool)),
+            ("connection_up", row.connection_up.map(FieldValue::Bool)),
+            (
+                "inside_temp_precision_c",
+                row.inside_temp_precision_c.map(FieldValue::Float),
+            ),
+            ("tado_mode", row.tado_mode.clone().map(FieldValue::Text)),
+            ("control_mode", row.control_mode.clone().map(FieldValue::Text)),
+        ];
+        self.lines
+            .extend(line("climate", &tags, &fields, row.time.timestamp_nanos_opt()));
+    }
+
+    pub fn push_weather(&mut self, row: &NewWeatherMeasurement) {
+        let tags = [("home_id", row.home_id.to_string()), ("source", row.source.clone())];
+        let fields = [
+            ("outside_temp_c", row.outside_temp_c.map(FieldValue::Float)),
+            ("solar_intensity_pct", row.solar_intensity_pct.map(FieldValue::Float)),
+            ("weather_state", row.weather_state.clone().map(FieldValue::Text)),
+        ];
+        self.lines
+            .extend(line("weather", &tags, &fields, row.time.timestamp_nanos_opt()));
+    }
+}
+
+enum FieldValue {
+    Float(f64),
+    Bool(bool),
+    Text(String),
+}
+
+/// Formats one line-protocol record. Unset fields are omitted; a row with none left yields no line, since
+/// InfluxDB rejects field-less records.
+fn line(
+    measurement: &str,
+    tags: &[(&str, String)],
+    fields: &[(&str, Option<FieldValue>)],
+    timestamp_ns: Option<i64>,
+) -> Option<String> {
+    let fields: Vec<String> = fields
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value.as_ref()? {
+                FieldValue::Float(v) if v.is_finite() => format!("{:?}", v),
+                FieldValue::Float(_) => return None,
+                FieldValue::Bool(v) => v.to_string(),
+                FieldValue::Text(v) => format!("\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")),
+            };
+            Some(format!("{}={}", key, value))
+        })
+        .collect();
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut out = measurement.to_string();
+    for (key, value) in tags {
+        let _ = write!(out, ",{}={}", key, escape_tag(value));
+    }
+    let _ = write!(out, " {}", fields.join(","));
+    if let Some(ts) = timestamp_ns {
+        let _ = write!(out, " {}", ts);
+    }
+    Some(out)
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+/// Packs newline-separated lines into datagrams of at most `MAX_DATAGRAM_BYTES` (a longer line goes alone).
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for line in lines {
+        match out.last_mut() {
+            Some(current) if current.len() + 1 + line.len() <= MAX_DATAGRAM_BYTES => {
+                current.push('\n');
+                current.push_str(line);
+            }
+            _ => out.push(line.clone()),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::event_source;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn write_url_encodes_bucket_and_org() {
+        assert_eq!(
+            write_url("http://influx:8086/", "tado", None),
+            "http://influx:8086/api/v2/write?bucket=tado&precision=ns"
+        );
+        assert_eq!(
+            write_url("https://influx.example", "home/tado data", Some("Me & Co")),
+            "https://influx.example/api/v2/write?bucket=home%2Ftado%20data&precision=ns&org=Me%20%26%20Co"
+        );
+    }
+
+    #[test]
+    fn climate_row_formats_as_line_protocol() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut row = NewClimateMeasurement::new(time, 3, Some(7), None, event_source::REALTIME);
+        row.inside_temp_c = Some(21.5);
+        row.humidity_pct = Some(48.0);
+        row.ac_power_on = Some(false);
+        row.ac_mode = Some("COOL \"eco\"".to_string());
+
+        let mut batch = InfluxBatch::default();
+        batch.push_climate(&row);
+        // A row without any values is dropped
+        batch.push_climate(&NewClimateMeasurement::new(
+            time,
+            3,
+            None,
+            Some(9),
+            event_source::REALTIME,
+        ));
+
+        assert_eq!(
+            batch.lines,
+            [
+                "climate,home_id=3,zone_id=7,source=realtime inside_temp_c=21.5,humidity_pct=48.0,ac_power_on=false,ac_mode=\"COOL \\\"eco\\\"\" 1709294400000000000"
+            ]
+        );
+        assert_eq!(datagrams(&[batch.lines[0].clone(), "x".to_string()]).len(), 1);
+    }
+}
@@ -1,36 +1,432 @@
-use crate::db::models::{NewClimateMeasurement, NewWeatherMeasurement};
+use crate::db::models::{
+    NewClimateMeasurement, NewEvent, NewWeatherMeasurement, NewZoneWeatherMeasurement, event_source, event_types,
+};
 use crate::schema;
-use diesel::prelude::*;
+use crate::services::metrics::{self, RowKind};
+use chrono::NaiveDate;
 use diesel::PgConnection;
+use diesel::prelude::*;
+use log::{info, warn};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::num::NonZeroU32;
+
+/// Maximum rows per INSERT statement. Keeps every statement well below Postgres' 65535 bind parameter
+/// limit (roughly 16 columns per climate row) and bounds how much a caller needs to buffer.
+pub const INSERT_BATCH_ROWS: usize = 2_000;
+
+/// Rows logged per skipped insert under `DRY_RUN`.
+const DRY_RUN_SAMPLE_ROWS: usize = 3;
+
+/// Under `DRY_RUN` (`dry_run`), logs the rows a writer was about to insert into `table` and returns `true` so it
+/// skips the insert; otherwise returns `false`.
+pub fn skip_for_dry_run<T: Debug>(dry_run: bool, table: &str, rows: &[T]) -> bool {
+    if !dry_run {
+        return false;
+    }
+    info!(
+        "Dry run: would insert {} row(s) into {}; sample: {:?}",
+        rows.len(),
+        table,
+        &rows[..rows.len().min(DRY_RUN_SAMPLE_ROWS)]
+    );
+    true
+}
 
-pub fn insert_climate_measurements(conn: &mut PgConnection, rows: &[NewClimateMeasurement]) -> Result<usize, String> {
+/// Inserts climate rows, silently skipping ones already stored.
+///
+/// The conflict target matches `climate_measurements_dedupe_uq`, which is declared `NULLS NOT DISTINCT`
+/// (Postgres 15+): a zone row (`device_id` NULL) or device row (`zone_id` NULL) at the same time and source
+/// is a duplicate, rather than the default unique-index behaviour of treating every NULL as distinct.
+///
+/// Zone rows (`zone_id` set, `device_id` NULL) and device-health rows (`zone_id` NULL, `device_id` set) never
+/// collide, even at the same time and source: `NULLS NOT DISTINCT` only equates NULL with NULL, and each kind
+/// has a non-NULL id in the column where the other has NULL. Do not widen the key to a single nullable
+/// "entity" column, or a device row could silently drop a zone row with the same id.
+pub fn insert_climate_measurements(
+    conn: &mut PgConnection,
+    rows: &[NewClimateMeasurement],
+    dry_run: bool,
+) -> Result<usize, String> {
     if rows.is_empty() {
         return Ok(0);
     }
 
+    if skip_for_dry_run(dry_run, "climate_measurements", rows) {
+        return Ok(rows.len());
+    }
+
     use schema::climate_measurements::dsl as C;
 
-    diesel::insert_into(C::climate_measurements)
-        .values(rows)
-        .on_conflict((C::time, C::home_id, C::source, C::zone_id, C::device_id))
-        .do_nothing()
-        .execute(conn)
-        .map(|count| count as usize)
-        .map_err(|e| format!("insert climate rows failed: {}", e))
+    let mut inserted = 0;
+    for chunk in rows.chunks(INSERT_BATCH_ROWS) {
+        inserted += diesel::insert_into(C::climate_measurements)
+            .values(chunk)
+            .on_conflict((C::time, C::home_id, C::source, C::zone_id, C::device_id))
+            .do_nothing()
+            .execute(conn)
+            .map_err(|e| format!("insert climate rows failed: {}", e))?;
+    }
+    metrics::record_inserted(RowKind::Climate, inserted);
+    Ok(inserted)
 }
 
-pub fn insert_weather_measurements(conn: &mut PgConnection, rows: &[NewWeatherMeasurement]) -> Result<usize, String> {
+pub fn insert_weather_measurements(
+    conn: &mut PgConnection,
+    rows: &[NewWeatherMeasurement],
+    dry_run: bool,
+) -> Result<usize, String> {
     if rows.is_empty() {
         return Ok(0);
     }
 
+    if skip_for_dry_run(dry_run, "weather_measurements", rows) {
+        return Ok(rows.len());
+    }
+
     use schema::weather_measurements::dsl as W;
 
-    diesel::insert_into(W::weather_measurements)
+    let mut inserted = 0;
+    for chunk in rows.chunks(INSERT_BATCH_ROWS) {
+        inserted += diesel::insert_into(W::weather_measurements)
+            .values(chunk)
+            .on_conflict((W::home_id, W::time, W::source))
+            .do_nothing()
+            .execute(conn)
+            .map_err(|e| format!("insert weather rows failed: {}", e))?;
+    }
+    metrics::record_inserted(RowKind::Weather, inserted);
+    Ok(inserted)
+}
+
+/// Copies each home weather row onto every zone in `zone_ids` and inserts the copies, skipping stored ones.
+pub fn insert_zone_weather_measurements(
+    conn: &mut PgConnection,
+    rows: &[NewWeatherMeasurement],
+    zone_ids: &[i64],
+    dry_run: bool,
+) -> Result<usize, String> {
+    let rows = zone_weather_rows(rows, zone_ids);
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    if skip_for_dry_run(dry_run, "zone_weather_measurements", &rows) {
+        return Ok(rows.len());
+    }
+
+    use schema::zone_weather_measurements::dsl as ZW;
+
+    let mut inserted = 0;
+    for chunk in rows.chunks(INSERT_BATCH_ROWS) {
+        inserted += diesel::insert_into(ZW::zone_weather_measurements)
+            .values(chunk)
+            .on_conflict((ZW::zone_id, ZW::time, ZW::source))
+            .do_nothing()
+            .execute(conn)
+            .map_err(|e| format!("insert zone weather rows failed: {}", e))?;
+    }
+    metrics::record_inserted(RowKind::ZoneWeather, inserted);
+    Ok(inserted)
+}
+
+fn zone_weather_rows(rows: &[NewWeatherMeasurement], zone_ids: &[i64]) -> Vec<NewZoneWeatherMeasurement> {
+    zone_ids
+        .iter()
+        .flat_map(|zone_id| {
+            rows.iter()
+                .map(|row| NewZoneWeatherMeasurement::from_home(row, *zone_id))
+        })
+        .collect()
+}
+
+pub fn insert_events(conn: &mut PgConnection, rows: &[NewEvent], dry_run: bool) -> Result<usize, String> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    if skip_for_dry_run(dry_run, "events", rows) {
+        return Ok(rows.len());
+    }
+
+    use schema::events::dsl as E;
+
+    let inserted = diesel::insert_into(E::events)
         .values(rows)
-        .on_conflict((W::home_id, W::time, W::source))
-        .do_nothing()
         .execute(conn)
-        .map(|count| count as usize)
-        .map_err(|e| format!("insert weather rows failed: {}", e))
+        .map_err(|e| format!("insert event rows failed: {}", e))?;
+    metrics::record_inserted(RowKind::Event, inserted);
+    Ok(inserted)
+}
+
+/// Inserts one event subject to `EVENTS_MAX_PER_ZONE_PER_DAY` (`cap`, `None` for unlimited), counted in the
+/// home's `throttle`. The event only counts once it is stored, so a failed insert does not use up the cap.
+pub fn insert_capped_event(
+    conn: &mut PgConnection,
+    throttle: &mut EventThrottle,
+    cap: Option<NonZeroU32>,
+    event: &NewEvent,
+    dry_run: bool,
+) -> Result<usize, String> {
+    let Some(cap) = cap else {
+        return insert_events(conn, std::slice::from_ref(event), dry_run);
+    };
+    let Some(stored) = throttle.admit(cap, event) else {
+        return Ok(0);
+    };
+    let inserted = insert_events(conn, std::slice::from_ref(&stored), dry_run)?;
+    if stored.event_type != event.event_type {
+        warn!(
+            "Events: zone {} reached {} {} event(s) on {} (UTC); dropping the rest of the day",
+            event.zone_id.unwrap_or_default(),
+            cap,
+            event.event_type,
+            event.time.date_naive()
+        );
+    }
+    throttle.record(event);
+    Ok(inserted)
+}
+
+/// Counts of one home's stored zone events for the current UTC day, for `EVENTS_MAX_PER_ZONE_PER_DAY`.
+///
+/// Events without a zone, events from earlier days (e.g. rollups) and throttle markers themselves are
+/// never dropped. The first event over the cap is replaced by a single `EVENTS_THROTTLED` marker.
+#[derive(Debug, Clone, Default)]
+pub struct EventThrottle {
+    /// UTC day the counts belong to.
+    day: Option<NaiveDate>,
+    counts: BTreeMap<(i64, String), u32>,
+}
+
+impl EventThrottle {
+    /// Whether `event` is never counted against the cap.
+    fn exempt(&self, event: &NewEvent) -> bool {
+        event.zone_id.is_none()
+            || event.event_type == event_types::EVENTS_THROTTLED
+            || self.day.is_some_and(|current| event.time.date_naive() < current)
+    }
+
+    /// What to store for `event` given the counts so far: the event itself, the `EVENTS_THROTTLED` marker in its
+    /// place, or nothing once the day's marker for its zone and type is stored.
+    fn admit(&self, cap: NonZeroU32, event: &NewEvent) -> Option<NewEvent> {
+        if self.exempt(event) {
+            return Some(event.clone());
+        }
+        let day = event.time.date_naive();
+        let count = if self.day == Some(day) {
+            let key = (event.zone_id?, event.event_type.clone());
+            self.counts.get(&key).copied().unwrap_or(0)
+        } else {
+            0
+        };
+        if count < cap.get() {
+            Some(event.clone())
+        } else if count == cap.get() {
+            Some(NewEvent {
+                time: event.time,
+                home_id: event.home_id,
+                zone_id: event.zone_id,
+                device_id: None,
+                source: Some(event_source::DERIVED.to_string()),
+                event_type: event_types::EVENTS_THROTTLED.to_string(),
+                payload: Some(json!({
+                    "event_type": event.event_type,
+                    "day": day.to_string(),
+                    "cap": cap.get(),
+                })),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Counts `event` (as passed to `admit`, not the marker it may have become) once it has been stored.
+    fn record(&mut self, event: &NewEvent) {
+        let Some(zone_id) = event.zone_id.filter(|_| !self.exempt(event)) else {
+            return;
+        };
+        let day = event.time.date_naive();
+        if self.day != Some(day) {
+            self.day = Some(day);
+            self.counts.clear();
+        }
+        *self.counts.entry((zone_id, event.event_type.clone())).or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    const TIMESERIES_MIGRATION: &str = include_str!("../../migrations/002_create_timeseries/up.sql");
+
+    /// Without a database, guard the index definition that `on_conflict` above relies on: if it ever loses
+    /// `nulls not distinct`, two NULL-device rows at the same time would both be inserted.
+    #[test]
+    fn climate_dedupe_index_treats_null_zone_and_device_as_equal() {
+        let (columns, nulls_not_distinct) = climate_dedupe_index();
+        assert_eq!(columns, ["time", "home_id", "source", "zone_id", "device_id"]);
+        assert!(nulls_not_distinct);
+    }
+
+    /// Compressed chunks can only enforce the dedupe index when every one of its columns is a segmentby or
+    /// orderby column.
+    #[test]
+    fn climate_compression_covers_the_dedupe_index() {
+        let sql = include_str!("../../migrations/023_add_compression/up.sql").to_ascii_lowercase();
+        let start = sql
+            .find("alter table climate_measurements set (")
+            .expect("climate compression");
+        let statement = &sql[start..start + sql[start..].find(';').expect("statement end")];
+        let setting = |name: &str| {
+            let value = &statement[statement.find(name).expect(name) + name.len()..];
+            let value = &value[value.find('\'').expect("quoted value") + 1..];
+            value[..value.find('\'').expect("closing quote")]
+                .split(',')
+                .map(|c| c.split_whitespace().next().unwrap_or_default().to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut covered = setting("compress_segmentby");
+        covered.extend(setting("compress_orderby"));
+
+        for column in climate_dedupe_index().0 {
+            assert!(covered.contains(&column), "{column} missing from {covered:?}");
+        }
+    }
+
+    /// `climate_measurements_dedupe_uq` as the migration defines it: its column list and whether NULLs match.
+    fn climate_dedupe_index() -> (Vec<String>, bool) {
+        let sql = TIMESERIES_MIGRATION.to_ascii_lowercase();
+        let start = sql
+            .find("create unique index if not exists climate_measurements_dedupe_uq")
+            .expect("climate dedupe index");
+        let statement = &sql[start..start + sql[start..].find(';').expect("statement end")];
+        let columns = &statement[statement.find('(').expect("column list") + 1..statement.find(')').expect("list end")];
+        (
+            columns.split(',').map(|c| c.trim().to_string()).collect(),
+            statement.contains("nulls not distinct"),
+        )
+    }
+
+    /// Whether Postgres would reject `row` as a duplicate of one of `stored` under the migration's index.
+    fn conflicts(index: &(Vec<String>, bool), stored: &[&NewClimateMeasurement], row: &NewClimateMeasurement) -> bool {
+        let (columns, nulls_not_distinct) = index;
+        let key = |row: &NewClimateMeasurement| {
+            let value = serde_json::to_value(row).expect("serialize row");
+            columns
+                .iter()
+                .map(|c| value.get(c).cloned().unwrap_or_else(|| panic!("row has no column {c}")))
+                .collect::<Vec<_>>()
+        };
+        let new_key = key(row);
+        if !nulls_not_distinct && new_key.iter().any(serde_json::Value::is_null) {
+            return false;
+        }
+        stored.iter().any(|other| key(other) == new_key)
+    }
+
+    #[test]
+    fn zone_and_device_rows_at_same_time_both_persist() {
+        let index = climate_dedupe_index();
+        let time = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let zone_row = NewClimateMeasurement::new(time, 1, Some(3), None, event_source::REALTIME);
+        let device_row = NewClimateMeasurement::new(time, 1, None, Some(3), event_source::REALTIME);
+        let historical = NewClimateMeasurement::new(time, 1, Some(3), None, event_source::HISTORICAL);
+        let mut zone_again = zone_row.clone();
+        zone_again.inside_temp_c = Some(21.0);
+
+        assert!(
+            !conflicts(&index, &[&zone_row], &device_row),
+            "device row must not conflict"
+        );
+        assert!(!conflicts(&index, &[&zone_row], &historical), "sources are kept apart");
+        assert!(
+            conflicts(&index, &[&zone_row, &device_row], &zone_again),
+            "same zone row is a duplicate"
+        );
+    }
+
+    #[test]
+    fn every_zone_resolves_its_home_weather() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut weather = NewWeatherMeasurement::new(time, 1, event_source::REALTIME);
+        weather.outside_temp_c = Some(4.5);
+        weather.weather_state = Some("CLOUDY".to_string());
+
+        let rows = zone_weather_rows(std::slice::from_ref(&weather), &[7, 8]);
+
+        assert_eq!(rows.iter().map(|r| r.zone_id).collect::<Vec<_>>(), vec![7, 8]);
+        for row in &rows {
+            assert_eq!((row.time, row.home_id), (time, 1));
+            assert_eq!(row.source, event_source::REALTIME);
+            assert_eq!(row.outside_temp_c, Some(4.5));
+            assert_eq!(row.weather_state.as_deref(), Some("CLOUDY"));
+        }
+        assert!(zone_weather_rows(&[weather], &[]).is_empty());
+    }
+
+    #[test]
+    fn events_over_daily_zone_cap_are_replaced_by_one_marker() {
+        let cap = NonZeroU32::new(2).unwrap();
+        let mut throttle = EventThrottle::default();
+        let event = |hour, event_type: &str| NewEvent {
+            time: Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap(),
+            home_id: 1,
+            zone_id: Some(7),
+            device_id: None,
+            source: Some(event_source::REALTIME.to_string()),
+            event_type: event_type.to_string(),
+            payload: None,
+        };
+        // What `insert_capped_event` stores, assuming every insert succeeds
+        let store = |throttle: &mut EventThrottle, event: NewEvent| {
+            let stored = throttle.admit(cap, &event);
+            if stored.is_some() {
+                throttle.record(&event);
+            }
+            stored.map(|e| e.event_type)
+        };
+
+        assert!(store(&mut throttle, event(8, event_types::OVERLAY_SET)).is_some());
+        // A failed insert is not recorded, so it does not use up the cap
+        assert!(throttle.admit(cap, &event(9, event_types::OVERLAY_SET)).is_some());
+        assert!(store(&mut throttle, event(9, event_types::OVERLAY_SET)).is_some());
+
+        let marker = throttle
+            .admit(cap, &event(10, event_types::OVERLAY_SET))
+            .expect("marker replaces the third event");
+        assert_eq!(marker.event_type, event_types::EVENTS_THROTTLED);
+        assert_eq!(marker.payload.as_ref().unwrap()["event_type"], event_types::OVERLAY_SET);
+        assert_eq!(marker.payload.as_ref().unwrap()["day"], "2024-03-01");
+        throttle.record(&event(10, event_types::OVERLAY_SET));
+        assert_eq!(
+            store(&mut throttle, event(10, event_types::OVERLAY_CLEARED)).as_deref(),
+            Some(event_types::OVERLAY_CLEARED)
+        );
+        assert_eq!(
+            store(&mut throttle, event(11, event_types::OVERLAY_SET)),
+            None,
+            "marker is written only once per day"
+        );
+
+        let next_day = NewEvent {
+            time: Utc.with_ymd_and_hms(2024, 3, 2, 0, 5, 0).unwrap(),
+            ..event(0, event_types::OVERLAY_SET)
+        };
+        assert_eq!(
+            store(&mut throttle, next_day).as_deref(),
+            Some(event_types::OVERLAY_SET),
+            "counts reset at the UTC day boundary"
+        );
+    }
+
+    #[test]
+    fn dry_run_skips_inserts_only_while_enabled() {
+        let rows = [1, 2, 3, 4];
+        assert!(!skip_for_dry_run(false, "events", &rows));
+        assert!(skip_for_dry_run(true, "events", &rows));
+    }
 }
@@ -0,0 +1,511 @@
+//! Prometheus metrics, scraped on `METRICS_LISTEN_ADDR` and/or pushed via `PROM_REMOTE_WRITE_URL`.
+//!
+//! Counters live in a process-wide registry so the client and the services can record without threading a
+//! handle through every call. Recording is a no-op until an exporter enables it.
+
+use crate::client::TrafficCounters;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write as _};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+/// Upper bounds of the request latency buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+/// A scraper that connects but never sends its request must not block the endpoint for long.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry::new());
+
+thread_local! {
+    /// Insert counts held back while `defer_inserted` runs on this thread.
+    static DEFERRED_INSERTS: RefCell<Option<BTreeMap<RowKind, u64>>> = const { RefCell::new(None) };
+}
+
+/// Kinds of rows counted by `record_inserted`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RowKind {
+    Climate,
+    Weather,
+    ZoneWeather,
+    Event,
+}
+
+impl RowKind {
+    fn label(self) -> &'static str {
+        match self {
+            RowKind::Climate => "climate",
+            RowKind::Weather => "weather",
+            RowKind::ZoneWeather => "zone_weather",
+            RowKind::Event => "event",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Histogram {
+    /// Cumulative count per entry of `LATENCY_BUCKETS`.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Histogram {
+            buckets: [0; LATENCY_BUCKETS.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+#[derive(Debug)]
+struct Registry {
+    /// `(endpoint, status)` → requests; status is the HTTP code, or `error` when no response arrived.
+    requests: BTreeMap<(String, String), u64>,
+    latency: Histogram,
+    /// Tado home id → unix time of the last realtime tick in which every home was collected.
+    last_tick: BTreeMap<i64, i64>,
+    /// Seconds the most recent realtime tick took.
+    tick_duration: Option<f64>,
+    inserted: BTreeMap<RowKind, u64>,
+    /// Counters of every Tado client created so far; summed into the traffic totals.
+    traffic: Vec<Arc<TrafficCounters>>,
+}
+
+impl Registry {
+    const fn new() -> Self {
+        Registry {
+            requests: BTreeMap::new(),
+            latency: Histogram::new(),
+            last_tick: BTreeMap::new(),
+            tick_duration: None,
+            inserted: BTreeMap::new(),
+            traffic: Vec::new(),
+        }
+    }
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Counts one Tado request. `url` may carry ids and a query; they are folded into a per-endpoint label.
+pub fn record_request(url: &str, status: Option<u16>, latency: Duration) {
+    if !enabled() {
+        return;
+    }
+    let status = status.map_or_else(|| "error".to_string(), |s| s.to_string());
+    let mut registry = registry();
+    *registry.requests.entry((endpoint_label(url), status)).or_default() += 1;
+    registry.latency.observe(latency.as_secs_f64());
+}
+
+pub fn record_inserted(kind: RowKind, rows: usize) {
+    if rows == 0 {
+        return;
+    }
+    let deferred = DEFERRED_INSERTS.with_borrow_mut(|deferred| {
+        deferred
+            .as_mut()
+            .map(|counts| *counts.entry(kind).or_default() += rows as u64)
+            .is_some()
+    });
+    if deferred || !enabled() {
+        return;
+    }
+    *registry().inserted.entry(kind).or_default() += rows as u64;
+}
+
+/// Insert counts recorded inside `defer_inserted`, not yet added to the registry.
+#[derive(Debug, Default, PartialEq)]
+pub struct DeferredInserts(BTreeMap<RowKind, u64>);
+
+impl DeferredInserts {
+    /// Adds the held-back counts; called once the rows they describe are committed.
+    pub fn record(self) {
+        for (kind, rows) in self.0 {
+            record_inserted(kind, rows as usize);
+        }
+    }
+}
+
+/// Runs `f` with this thread's insert counts held back instead of recorded, so rows written inside a
+/// transaction that later rolls back are never counted. Drop the returned counts to discard them.
+pub fn defer_inserted<T>(f: impl FnOnce() -> T) -> (T, DeferredInserts) {
+    let outer = DEFERRED_INSERTS.replace(Some(BTreeMap::new()));
+    let value = f();
+    let deferred = DEFERRED_INSERTS.replace(outer).unwrap_or_default();
+    (value, DeferredInserts(deferred))
+}
+
+pub fn record_successful_tick(home_id: i64, at: DateTime<Utc>) {
+    if !enabled() {
+        return;
+    }
+    registry().last_tick.insert(home_id, at.timestamp());
+}
+
+pub fn record_tick_duration(duration: Duration) {
+    if !enabled() {
+        return;
+    }
+    registry().tick_duration = Some(duration.as_secs_f64());
+}
+
+/// Adds a client's traffic counters to the exported `tado_http_requests_total`/`tado_response_bytes_total`.
+pub fn register_traffic(traffic: Arc<TrafficCounters>) {
+    registry().traffic.push(traffic);
+}
+
+/// Turns recording on; called by whichever exporter starts first.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Starts the metrics endpoint on a background thread and enables recording.
+pub fn serve(addr: &str) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("binding metrics listener on {} failed: {}", addr, e))?;
+    enable();
+    info!("Metrics: serving Prometheus metrics on http://{}/metrics", addr);
+    thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = handle_connection(stream) {
+                            debug!("Metrics: connection failed: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Metrics: accepting connection failed: {}", e),
+                }
+            }
+        })
+        .map_err(|e| format!("spawning metrics thread failed: {}", e))?;
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers so the client sees a clean response instead of a reset
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("");
+    let (status, content_type, body) = if request_line.starts_with("GET ") && path == "/metrics" {
+        ("200 OK", "text/plain; version=0.0.4; charset=utf-8", render())
+    } else {
+        ("404 Not Found", "text/plain; charset=utf-8", "not found\n".to_string())
+    };
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// One metric family: its samples plus the `HELP`/`TYPE` metadata of the text format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Family {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: &'static str,
+    pub samples: Vec<Sample>,
+}
+
+/// One series value. Histogram families carry `_bucket`/`_sum`/`_count` samples under their own names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: Vec<(&'static str, String)>,
+    pub value: f64,
+}
+
+impl Sample {
+    fn new(name: &str, labels: Vec<(&'static str, String)>, value: f64) -> Self {
+        Sample {
+            name: name.to_string(),
+            labels,
+            value,
+        }
+    }
+}
+
+/// Current values of every metric, shared by the scrape endpoint and the remote-write push.
+pub fn snapshot() -> Vec<Family> {
+    let registry = registry();
+
+    let requests = registry
+        .requests
+        .iter()
+        .map(|((endpoint, status), count)| {
+            Sample::new(
+                "tado_requests_total",
+                vec![("endpoint", endpoint.clone()), ("status", status.clone())],
+                *count as f64,
+            )
+        })
+        .collect();
+
+    let latency = &registry.latency;
+    let mut latency_samples: Vec<Sample> = LATENCY_BUCKETS
+        .iter()
+        .zip(latency.buckets)
+        .map(|(bound, count)| {
+            Sample::new(
+                "tado_request_duration_seconds_bucket",
+                vec![("le", bound.to_string())],
+                count as f64,
+            )
+        })
+        .collect();
+    latency_samples.push(Sample::new(
+        "tado_request_duration_seconds_bucket",
+        vec![("le", "+Inf".to_string())],
+        latency.count as f64,
+    ));
+    latency_samples.push(Sample::new(
+        "tado_request_duration_seconds_sum",
+        Vec::new(),
+        latency.sum,
+    ));
+    latency_samples.push(Sample::new(
+        "tado_request_duration_seconds_count",
+        Vec::new(),
+        latency.count as f64,
+    ));
+
+    let last_tick = registry
+        .last_tick
+        .iter()
+        .map(|(home_id, at)| {
+            Sample::new(
+                "tado_last_successful_tick_timestamp_seconds",
+                vec![("home_id", home_id.to_string())],
+                *at as f64,
+            )
+        })
+        .collect();
+
+    let tick_duration = registry
+        .tick_duration
+        .map(|d| Sample::new("tado_tick_duration_seconds", Vec::new(), d))
+        .into_iter()
+        .collect();
+
+    let inserted = registry
+        .inserted
+        .iter()
+        .map(|(kind, count)| {
+            Sample::new(
+                "tado_inserted_rows_total",
+                vec![("kind", kind.label().to_string())],
+                *count as f64,
+            )
+        })
+        .collect();
+
+    let (http_requests, response_bytes) = registry.traffic.iter().fold((0, 0), |(requests, bytes), traffic| {
+        (requests + traffic.requests(), bytes + traffic.response_bytes())
+    });
+
+    vec![
+        Family {
+            name: "tado_requests_total",
+            help: "Tado API requests by endpoint and response status.",
+            kind: "counter",
+            samples: requests,
+        },
+        Family {
+            name: "tado_request_duration_seconds",
+            help: "Tado API request latency.",
+            kind: "histogram",
+            samples: latency_samples,
+        },
+        Family {
+            name: "tado_last_successful_tick_timestamp_seconds",
+            help: "Unix time of the last realtime tick in which every home, this one included, was collected.",
+            kind: "gauge",
+            samples: last_tick,
+        },
+        Family {
+            name: "tado_tick_duration_seconds",
+            help: "Duration of the last realtime tick.",
+            kind: "gauge",
+            samples: tick_duration,
+        },
+        Family {
+            name: "tado_inserted_rows_total",
+            help: "Rows inserted into the database by kind.",
+            kind: "counter",
+            samples: inserted,
+        },
+        Family {
+            name: "tado_http_requests_total",
+            help: "HTTP requests sent to Tado, including token grants and retries.",
+            kind: "counter",
+            samples: vec![Sample::new(
+                "tado_http_requests_total",
+                Vec::new(),
+                http_requests as f64,
+            )],
+        },
+        Family {
+            name: "tado_response_bytes_total",
+            help: "JSON response bytes read from Tado.",
+            kind: "counter",
+            samples: vec![Sample::new(
+                "tado_response_bytes_total",
+                Vec::new(),
+                response_bytes as f64,
+            )],
+        },
+    ]
+}
+
+/// Renders the registry in the Prometheus text exposition format.
+fn render() -> String {
+    let mut out = String::new();
+    for family in snapshot() {
+        let _ = writeln!(out, "# HELP {} {}", family.name, family.help);
+        let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind);
+        for sample in &family.samples {
+            out.push_str(&sample.name);
+            if !sample.labels.is_empty() {
+                let labels: Vec<String> = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+                    .collect();
+                let _ = write!(out, "{{{}}}", labels.join(","));
+            }
+            let _ = writeln!(out, " {}", sample.value);
+        }
+    }
+    out
+}
+
+/// Reduces a request URL to its path with numeric ids replaced by `:id`, e.g. `/homes/:id/zones/:id/state`,
+/// so label cardinality stays bounded.
+fn endpoint_label(url: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |idx| &rest[idx..]),
+        None => url,
+    };
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    path.split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                ":id"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_are_labelled_without_ids() {
+        assert_eq!(
+            endpoint_label("https://my.tado.com/api/v2/homes/123/zones/4/state"),
+            "/api/v2/homes/:id/zones/:id/state"
+        );
+        assert_eq!(
+            endpoint_label("https://login.tado.com/oauth2/token?x=1"),
+            "/oauth2/token"
+        );
+        assert_eq!(endpoint_label("/homes/42/weather"), "/homes/:id/weather");
+        assert_eq!(endpoint_label("http://127.0.0.1:8080"), "/");
+    }
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new();
+        histogram.observe(0.07);
+        histogram.observe(3.0);
+        histogram.observe(120.0);
+
+        assert_eq!(histogram.buckets[0], 0);
+        assert_eq!(histogram.buckets[1], 1);
+        assert_eq!(histogram.buckets[6], 2);
+        assert_eq!(histogram.buckets[LATENCY_BUCKETS.len() - 1], 2);
+        assert_eq!(histogram.count, 3);
+    }
+
+    #[test]
+    fn deferred_inserts_are_held_back_until_recorded() {
+        let ((), deferred) = defer_inserted(|| {
+            record_inserted(RowKind::Climate, 2);
+            record_inserted(RowKind::Climate, 1);
+            record_inserted(RowKind::Event, 0);
+        });
+        assert_eq!(deferred, DeferredInserts(BTreeMap::from([(RowKind::Climate, 3)])));
+        assert!(
+            DEFERRED_INSERTS.with_borrow(Option::is_none),
+            "deferral ends with the closure"
+        );
+    }
+
+    #[test]
+    fn traffic_counters_are_exported() {
+        register_traffic(Arc::new(TrafficCounters::default()));
+        let families = snapshot();
+
+        for name in ["tado_http_requests_total", "tado_response_bytes_total"] {
+            let family = families
+                .iter()
+                .find(|f| f.name == name)
+                .unwrap_or_else(|| panic!("{name} missing"));
+            assert_eq!(family.kind, "counter");
+            assert_eq!(family.samples.len(), 1, "{name}");
+            assert_eq!(family.samples[0].name, name);
+        }
+        let rendered = render();
+        assert!(rendered.contains("# TYPE tado_http_requests_total counter\ntado_http_requests_total "));
+        assert!(rendered.contains("# TYPE tado_response_bytes_total counter\ntado_response_bytes_total "));
+    }
+}
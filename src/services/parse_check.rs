@@ -0,0 +1,59 @@
+//! Offline replay of a saved Tado API response through the same deserializer the client uses.
+//!
+//! Reproduces parse failures without touching the API: on error the exact JSON path is reported, as in the
+//! client's own deserialization log.
+
+use crate::models::tado;
+use log::info;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// Model types accepted by `--as`, named as in `models::tado`.
+pub const SUPPORTED_TYPES: &[&str] = &["ZoneState", "DayReport", "Home", "Weather"];
+
+/// Parses the file at `path` as `type_name`; the error names the failing JSON path.
+pub fn run(path: &Path, type_name: &str) -> Result<(), String> {
+    let json = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    parse_as(type_name, &json).map_err(|e| format!("{} does not parse as {}: {}", path.display(), type_name, e))?;
+    info!("{} parses as {}", path.display(), type_name);
+    Ok(())
+}
+
+fn parse_as(type_name: &str, json: &str) -> Result<(), String> {
+    match type_name {
+        "ZoneState" => parse::<tado::ZoneState>(json),
+        "DayReport" => parse::<tado::DayReport>(json),
+        "Home" => parse::<tado::Home>(json),
+        "Weather" => parse::<tado::Weather>(json),
+        other => Err(format!(
+            "unsupported type '{}' (expected one of: {})",
+            other,
+            SUPPORTED_TYPES.join(", ")
+        )),
+    }
+}
+
+fn parse<T: DeserializeOwned>(json: &str) -> Result<(), String> {
+    let mut de = serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize::<_, T>(&mut de)
+        .map(|_| ())
+        .map_err(|err| format!("at path `{}`: {}", err.path(), err.inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_path_of_first_parse_failure() {
+        assert!(run(Path::new("tests/data/day-report.json"), "DayReport").is_ok());
+
+        let err = run(Path::new("tests/data/zone-state-invalid.json"), "ZoneState").expect_err("invalid fixture");
+        assert!(
+            err.contains("at path `sensorDataPoints.insideTemperature.celsius`"),
+            "{}",
+            err
+        );
+        assert!(parse_as("Zone", "{}").expect_err("unknown type").contains("ZoneState"));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::client::TadoClient;
+use crate::db::models::{NewMobileDevice, NewPresenceMeasurement};
+use crate::models::tado::{self, HomeId};
+use crate::schema;
+use crate::services::ingest;
+use chrono::{DateTime, Utc};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::{debug, info};
+
+/// Upserts the home's mobile devices and records one presence row per geo-tracked device.
+///
+/// The endpoint carries no reading time, so rows are stamped with `now`. Devices without geo tracking report
+/// no location and only get their reference row. Returns the number of inserted presence rows. Under `DRY_RUN`
+/// (`dry_run`) nothing is written; devices not stored yet are skipped since they have no id to reference.
+pub fn collect(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_home_id: i64,
+    home_id: i64,
+    now: DateTime<Utc>,
+    dry_run: bool,
+) -> Result<usize, String> {
+    use schema::mobile_devices::dsl as MD;
+    use schema::presence_measurements::dsl as P;
+
+    let devices = client
+        .get_mobile_devices(HomeId(home_id))
+        .map_err(|e| format!("get_mobile_devices({}) failed: {}", home_id, e))?;
+
+    let mut inserted = 0;
+    for device in &devices {
+        let Some(new_row) = mobile_device_row(device, db_home_id) else {
+            debug!("Presence: skipping mobile device without id in home {}", home_id);
+            continue;
+        };
+        let db_mobile_device_id: i64 = if dry_run {
+            info!(
+                "Dry run: would upsert mobile device {} in home {}",
+                new_row.tado_mobile_device_id, home_id
+            );
+            let existing = MD::mobile_devices
+                .filter(MD::home_id.eq(db_home_id))
+                .filter(MD::tado_mobile_device_id.eq(new_row.tado_mobile_device_id))
+                .select(MD::id)
+                .first(conn)
+                .optional()
+                .map_err(|e| format!("fetch mobile device failed: {}", e))?;
+            match existing {
+                Some(id) => id,
+                None => continue,
+            }
+        } else {
+            diesel::insert_into(MD::mobile_devices)
+                .values(&new_row)
+                .on_conflict((MD::home_id, MD::tado_mobile_device_id))
+                .do_update()
+                .set((
+                    MD::name.eq(new_row.name.clone()),
+                    MD::platform.eq(new_row.platform.clone()),
+                    MD::model.eq(new_row.model.clone()),
+                    MD::geo_tracking_enabled.eq(new_row.geo_tracking_enabled),
+                    MD::updated_at.eq(Utc::now()),
+                ))
+                .returning(MD::id)
+                .get_result(conn)
+                .map_err(|e| format!("upsert mobile device failed: {}", e))?
+        };
+
+        if let Some(row) = presence_row(device, db_home_id, db_mobile_device_id, now) {
+            if ingest::skip_for_dry_run(dry_run, "presence_measurements", std::slice::from_ref(&row)) {
+                continue;
+            }
+            inserted += diesel::insert_into(P::presence_measurements)
+                .values(&row)
+                .on_conflict((P::mobile_device_id, P::time))
+                .do_nothing()
+                .execute(conn)
+                .map_err(|e| format!("insert presence row failed: {}", e))?;
+        }
+    }
+    debug!(
+        "Presence: home {} has {} mobile device(s), {} presence row(s) inserted",
+        home_id,
+        devices.len(),
+        inserted
+    );
+    Ok(inserted)
+}
+
+fn mobile_device_row(device: &tado::MobileDevice, db_home_id: i64) -> Option<NewMobileDevice> {
+    let metadata = device.device_metadata.as_ref();
+    Some(NewMobileDevice {
+        home_id: db_home_id,
+        tado_mobile_device_id: device.id?.0,
+        name: device.name.clone(),
+        platform: metadata.and_then(|m| m.platform.clone()),
+        model: metadata.and_then(|m| m.model.clone()),
+        geo_tracking_enabled: device.settings.as_ref().and_then(|s| s.geo_tracking_enabled),
+    })
+}
+
+fn presence_row(
+    device: &tado::MobileDevice,
+    db_home_id: i64,
+    db_mobile_device_id: i64,
+    now: DateTime<Utc>,
+) -> Option<NewPresenceMeasurement> {
+    let location = device.location.as_ref()?;
+    Some(NewPresenceMeasurement {
+        time: now,
+        home_id: db_home_id,
+        mobile_device_id: db_mobile_device_id,
+        at_home: location.at_home,
+        stale: location.stale,
+        relative_distance_from_home_fence: location.relative_distance_from_home_fence,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn geo_tracked_devices_yield_presence_rows() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let devices: Vec<tado::MobileDevice> = serde_json::from_str(
+            r#"[
+                {"id": 11, "name": "Phone", "settings": {"geoTrackingEnabled": true},
+                 "location": {"stale": false, "atHome": false, "relativeDistanceFromHomeFence": 0.42,
+                              "bearingFromHome": {"degrees": 90.0, "radians": 1.5708}},
+                 "deviceMetadata": {"platform": "Android", "osVersion": "14", "model": "Pixel", "locale": "en"}},
+                {"id": 12, "name": "Tablet", "settings": {"geoTrackingEnabled": false}}
+            ]"#,
+        )
+        .expect("parse mobile devices");
+
+        let phone = mobile_device_row(&devices[0], 3).expect("phone has an id");
+        assert_eq!(phone.tado_mobile_device_id, 11);
+        assert_eq!(phone.platform.as_deref(), Some("Android"));
+        assert_eq!(phone.geo_tracking_enabled, Some(true));
+
+        let row = presence_row(&devices[0], 3, 5, now).expect("phone is geo-tracked");
+        assert_eq!(row.mobile_device_id, 5);
+        assert_eq!(row.at_home, Some(false));
+        assert_eq!(row.stale, Some(false));
+        assert_eq!(row.relative_distance_from_home_fence, Some(0.42));
+
+        assert!(presence_row(&devices[1], 3, 6, now).is_none());
+        assert!(mobile_device_row(&tado::MobileDevice::default(), 3).is_none());
+    }
+}
@@ -0,0 +1,624 @@
+use crate::db::models::{ClimateMeasurement, Event, Home, Zone, event_source};
+use crate::schema;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use diesel::PgConnection;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Double, Nullable, Text, Timestamptz};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Bucket width used to line up realtime and historical rows; matches the day report resolution.
+const MERGE_BUCKET: &str = "15 minutes";
+
+/// One zone reading per time bucket with realtime and historical values folded together.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MergedClimate {
+    pub time: DateTime<Utc>,
+    pub inside_temp_c: Option<f64>,
+    pub humidity_pct: Option<f64>,
+    pub setpoint_temp_c: Option<f64>,
+    pub heating_power_pct: Option<f64>,
+    pub ac_power_on: Option<bool>,
+    pub ac_mode: Option<String>,
+    pub window_open: Option<bool>,
+}
+
+/// Per-source aggregate of a single bucket, as returned by the bucketing query.
+#[derive(Debug, Clone, QueryableByName)]
+struct SourceBucket {
+    #[diesel(sql_type = Timestamptz)]
+    bucket: DateTime<Utc>,
+    #[diesel(sql_type = Text)]
+    source: String,
+    #[diesel(sql_type = Nullable<Double>)]
+    inside_temp_c: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    humidity_pct: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    setpoint_temp_c: Option<f64>,
+    #[diesel(sql_type = Nullable<Double>)]
+    heating_power_pct: Option<f64>,
+    #[diesel(sql_type = Nullable<Bool>)]
+    ac_power_on: Option<bool>,
+    #[diesel(sql_type = Nullable<Text>)]
+    ac_mode: Option<String>,
+    #[diesel(sql_type = Nullable<Bool>)]
+    window_open: Option<bool>,
+}
+
+/// Returns a gap-minimized climate series for a zone in `[from, to)`.
+///
+/// Rows are grouped with `time_bucket` per source, then merged column by column: a non-null
+/// historical value wins, and realtime fills whatever historical left empty. Nothing is materialized.
+pub fn merged_climate(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    db_zone_id: i64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<MergedClimate>, String> {
+    let query = format!(
+        "select time_bucket(interval '{MERGE_BUCKET}', time) as bucket, source, \
+                avg(inside_temp_c) as inside_temp_c, avg(humidity_pct) as humidity_pct, \
+                avg(setpoint_temp_c) as setpoint_temp_c, avg(heating_power_pct) as heating_power_pct, \
+                bool_or(ac_power_on) as ac_power_on, max(ac_mode) as ac_mode, bool_or(window_open) as window_open \
+         from climate_measurements \
+         where home_id = $1 and zone_id = $2 and device_id is null and time >= $3 and time < $4 \
+           and source in ('{}', '{}') \
+         group by bucket, source \
+         order by bucket",
+        event_source::HISTORICAL,
+        event_source::REALTIME,
+    );
+
+    let rows: Vec<SourceBucket> = diesel::sql_query(query)
+        .bind::<BigInt, _>(db_home_id)
+        .bind::<BigInt, _>(db_zone_id)
+        .bind::<Timestamptz, _>(from)
+        .bind::<Timestamptz, _>(to)
+        .load(conn)
+        .map_err(|e| format!("fetch merged climate rows failed: {}", e))?;
+
+    Ok(merge_source_buckets(rows))
+}
+
+/// Returns one page of the stored homes, ordered by id.
+pub fn homes(conn: &mut PgConnection, limit: i64, offset: i64) -> Result<Vec<Home>, String> {
+    use schema::homes::dsl as H;
+
+    H::homes
+        .order(H::id.asc())
+        .limit(limit)
+        .offset(offset)
+        .load(conn)
+        .map_err(|e| format!("fetch homes failed: {}", e))
+}
+
+pub fn home_by_tado_id(conn: &mut PgConnection, tado_home_id: i64) -> Result<Option<Home>, String> {
+    use schema::homes::dsl as H;
+
+    H::homes
+        .filter(H::tado_home_id.eq(tado_home_id))
+        .first(conn)
+        .optional()
+        .map_err(|e| format!("fetch home {} failed: {}", tado_home_id, e))
+}
+
+/// Returns one page of a home's zones, ordered by Tado zone id.
+pub fn zones(conn: &mut PgConnection, db_home_id: i64, limit: i64, offset: i64) -> Result<Vec<Zone>, String> {
+    use schema::zones::dsl as Z;
+
+    Z::zones
+        .filter(Z::home_id.eq(db_home_id))
+        .order(Z::tado_zone_id.asc())
+        .limit(limit)
+        .offset(offset)
+        .load(conn)
+        .map_err(|e| format!("fetch zones failed: {}", e))
+}
+
+pub fn zone_by_tado_id(conn: &mut PgConnection, db_home_id: i64, tado_zone_id: i64) -> Result<Option<Zone>, String> {
+    use schema::zones::dsl as Z;
+
+    Z::zones
+        .filter(Z::home_id.eq(db_home_id))
+        .filter(Z::tado_zone_id.eq(tado_zone_id))
+        .first(conn)
+        .optional()
+        .map_err(|e| format!("fetch zone {} failed: {}", tado_zone_id, e))
+}
+
+/// The most recent zone-level reading (no device) from `source`, or of either source when `None`, if the zone
+/// has any.
+pub fn latest_climate_for_zone(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    db_zone_id: i64,
+    source: Option<&str>,
+) -> Result<Option<ClimateMeasurement>, String> {
+    use schema::climate_measurements::dsl as C;
+
+    let mut query = zone_climate_query(db_home_id, db_zone_id);
+    if let Some(source) = source {
+        query = query.filter(C::source.eq(source.to_string()));
+    }
+    query
+        .order((C::time.desc(), C::id.desc()))
+        .select(ClimateMeasurement::as_select())
+        .first(conn)
+        .optional()
+        .map_err(|e| format!("fetch latest climate row for zone {} failed: {}", db_zone_id, e))
+}
+
+/// One page of zone-level readings (no device) of both sources in `[from, to)`, oldest first; unlike
+/// `merged_climate`, every stored row is returned as is.
+pub fn climate_between(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    db_zone_id: i64,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ClimateMeasurement>, String> {
+    climate_between_query(db_home_id, db_zone_id, from, to, limit, offset)
+        .select(ClimateMeasurement::as_select())
+        .load(conn)
+        .map_err(|e| format!("fetch climate rows for zone {} failed: {}", db_zone_id, e))
+}
+
+fn zone_climate_query<'a>(db_home_id: i64, db_zone_id: i64) -> schema::climate_measurements::BoxedQuery<'a, Pg> {
+    use schema::climate_measurements::dsl as C;
+
+    C::climate_measurements
+        .filter(C::home_id.eq(db_home_id))
+        .filter(C::zone_id.eq(db_zone_id))
+        .filter(C::device_id.is_null())
+        .into_boxed()
+}
+
+fn climate_between_query<'a>(
+    db_home_id: i64,
+    db_zone_id: i64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: i64,
+    offset: i64,
+) -> schema::climate_measurements::BoxedQuery<'a, Pg> {
+    use schema::climate_measurements::dsl as C;
+
+    zone_climate_query(db_home_id, db_zone_id)
+        .filter(C::time.ge(from))
+        .filter(C::time.lt(to))
+        .order((C::time.asc(), C::id.asc()))
+        .limit(limit)
+        .offset(offset)
+}
+
+/// A stretch without zone readings. Gaps between two readings exclude the reading they start at; a gap that
+/// starts at a day boundary or the requested start includes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub start_inclusive: bool,
+}
+
+/// Gaps of at least `min_gap` in a zone's readings (of any source or device) between `start` and now, keyed by
+/// UTC day; a gap spanning midnight is split at it.
+pub fn zone_gaps(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    db_zone_id: i64,
+    start: DateTime<Utc>,
+    min_gap: Duration,
+) -> Result<BTreeMap<NaiveDate, Vec<Gap>>, String> {
+    use schema::climate_measurements::dsl as C;
+
+    let now = Utc::now();
+    if start >= now {
+        return Ok(BTreeMap::new());
+    }
+
+    let times: Vec<DateTime<Utc>> = C::climate_measurements
+        .filter(
+            C::home_id
+                .eq(db_home_id)
+                .and(C::zone_id.eq(db_zone_id))
+                .and(C::time.ge(start))
+                .and(C::time.lt(now)),
+        )
+        .select(C::time)
+        .order(C::time.asc())
+        .load(conn)
+        .map_err(|e| format!("query measurement timestamps failed: {}", e))?;
+
+    Ok(compute_gaps(&times, start, now, min_gap))
+}
+
+/// Gaps of at least `min_gap` around the sorted `times` within `[start, end)`, per UTC day.
+pub fn compute_gaps(
+    times: &[DateTime<Utc>],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    min_gap: Duration,
+) -> BTreeMap<NaiveDate, Vec<Gap>> {
+    let mut gaps: BTreeMap<NaiveDate, Vec<Gap>> = BTreeMap::new();
+    if start >= end {
+        return gaps;
+    }
+
+    let mut cursor_date = start.date_naive();
+    let end_date = end.date_naive();
+    let mut idx = 0usize;
+
+    while cursor_date <= end_date {
+        let day_start = cursor_date.and_time(NaiveTime::MIN).and_utc();
+        let mut effective_start = day_start;
+        if cursor_date == start.date_naive() && start > day_start {
+            effective_start = start;
+        }
+
+        let day_end = if cursor_date == end_date {
+            end
+        } else {
+            cursor_date
+                .succ_opt()
+                .unwrap_or(NaiveDate::MAX)
+                .and_time(NaiveTime::MIN)
+                .and_utc()
+        };
+
+        if day_end <= effective_start {
+            if let Some(next) = cursor_date.succ_opt() {
+                cursor_date = next;
+                continue;
+            } else {
+                break;
+            }
+        }
+
+        while idx < times.len() && times[idx] < effective_start {
+            idx += 1;
+        }
+
+        let day_start_idx = idx;
+        while idx < times.len() && times[idx] < day_end {
+            idx += 1;
+        }
+        let day_times = &times[day_start_idx..idx];
+
+        let mut day_gaps: Vec<Gap> = Vec::new();
+
+        if day_times.is_empty() {
+            if day_end - effective_start >= min_gap {
+                day_gaps.push(Gap {
+                    start: effective_start,
+                    end: day_end,
+                    start_inclusive: true,
+                });
+            }
+        } else {
+            let first = day_times[0];
+            if first - effective_start >= min_gap {
+                day_gaps.push(Gap {
+                    start: effective_start,
+                    end: first,
+                    start_inclusive: true,
+                });
+            }
+
+            let mut prev = first;
+            for &ts in &day_times[1..] {
+                if ts - prev >= min_gap {
+                    day_gaps.push(Gap {
+                        start: prev,
+                        end: ts,
+                        start_inclusive: false,
+                    });
+                }
+                prev = ts;
+            }
+
+            if day_end - prev >= min_gap {
+                day_gaps.push(Gap {
+                    start: prev,
+                    end: day_end,
+                    start_inclusive: false,
+                });
+            }
+        }
+
+        if !day_gaps.is_empty() {
+            gaps.insert(cursor_date, day_gaps);
+        }
+
+        match cursor_date.succ_opt() {
+            Some(next) => cursor_date = next,
+            None => break,
+        }
+    }
+
+    gaps
+}
+
+/// Returns one page of a home's events of one type in `[from, to)`, oldest first.
+///
+/// Filters on `event_type` and `time` so `events_type_time_idx` can serve the range scan; the hypertable
+/// additionally prunes chunks outside the range.
+pub fn events_by_type(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    event_type: &str,
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Event>, String> {
+    events_by_type_query(db_home_id, event_type, from, to, limit, offset)
+        .load::<Event>(conn)
+        .map_err(|e| format!("fetch {} events failed: {}", event_type, e))
+}
+
+fn events_by_type_query<'a>(
+    db_home_id: i64,
+    event_type: &'a str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    limit: i64,
+    offset: i64,
+) -> schema::events::BoxedQuery<'a, Pg> {
+    use schema::events::dsl as E;
+
+    E::events
+        .filter(E::event_type.eq(event_type))
+        .filter(E::home_id.eq(db_home_id))
+        .filter(E::time.ge(from))
+        .filter(E::time.lt(to))
+        .order((E::time.asc(), E::id.asc()))
+        .limit(limit)
+        .offset(offset)
+        .into_boxed()
+}
+
+fn merge_source_buckets(rows: Vec<SourceBucket>) -> Vec<MergedClimate> {
+    // bucket -> (historical, realtime)
+    let mut by_bucket: BTreeMap<DateTime<Utc>, (Option<SourceBucket>, Option<SourceBucket>)> = BTreeMap::new();
+    for row in rows {
+        let slot = by_bucket.entry(row.bucket).or_default();
+        if row.source == event_source::HISTORICAL {
+            slot.0 = Some(row);
+        } else {
+            slot.1 = Some(row);
+        }
+    }
+
+    by_bucket
+        .into_iter()
+        .map(|(time, (historical, realtime))| {
+            let h = historical.as_ref();
+            let r = realtime.as_ref();
+            let pick = |f: fn(&SourceBucket) -> Option<f64>| h.and_then(f).or_else(|| r.and_then(f));
+            let pick_bool = |f: fn(&SourceBucket) -> Option<bool>| h.and_then(f).or_else(|| r.and_then(f));
+            MergedClimate {
+                time,
+                inside_temp_c: pick(|b| b.inside_temp_c),
+                humidity_pct: pick(|b| b.humidity_pct),
+                setpoint_temp_c: pick(|b| b.setpoint_temp_c),
+                heating_power_pct: pick(|b| b.heating_power_pct),
+                ac_power_on: pick_bool(|b| b.ac_power_on),
+                ac_mode: h
+                    .and_then(|b| b.ac_mode.clone())
+                    .or_else(|| r.and_then(|b| b.ac_mode.clone())),
+                window_open: pick_bool(|b| b.window_open),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::event_types;
+    use chrono::TimeZone;
+
+    fn bucket(minute: u32, source: &str) -> SourceBucket {
+        SourceBucket {
+            bucket: Utc.with_ymd_and_hms(2024, 3, 1, 12, minute, 0).unwrap(),
+            source: source.to_string(),
+            inside_temp_c: None,
+            humidity_pct: None,
+            setpoint_temp_c: None,
+            heating_power_pct: None,
+            ac_power_on: None,
+            ac_mode: None,
+            window_open: None,
+        }
+    }
+
+    #[test]
+    fn events_query_filters_type_and_range_in_time_order() {
+        let from = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let query = events_by_type_query(7, event_types::OPEN_WINDOW_DETECTED, from, to, 50, 100);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        for predicate in [
+            r#""events"."event_type" = $1"#,
+            r#""events"."home_id" = $2"#,
+            r#""events"."time" >= $3"#,
+            r#""events"."time" < $4"#,
+        ] {
+            assert!(sql.contains(predicate), "{sql}");
+        }
+        assert!(
+            sql.contains(r#"ORDER BY "events"."time" ASC, "events"."id" ASC"#),
+            "{sql}"
+        );
+        assert!(
+            sql.contains(r#"LIMIT $5 OFFSET $6"#)
+                && sql.contains(
+                    r#"binds: ["OPEN_WINDOW_DETECTED", 7, 2024-03-01T00:00:00Z, 2024-03-02T00:00:00Z, 50, 100]"#
+                ),
+            "{sql}"
+        );
+    }
+
+    #[test]
+    fn sparse_sources_merge_into_full_series() {
+        let mut h0 = bucket(0, event_source::HISTORICAL);
+        h0.inside_temp_c = Some(21.0);
+        h0.setpoint_temp_c = Some(22.0);
+        let mut r0 = bucket(0, event_source::REALTIME);
+        r0.inside_temp_c = Some(20.5);
+        r0.humidity_pct = Some(45.0);
+        r0.heating_power_pct = Some(30.0);
+        r0.window_open = Some(false);
+
+        let mut h15 = bucket(15, event_source::HISTORICAL);
+        h15.humidity_pct = Some(47.0);
+        h15.heating_power_pct = Some(0.0);
+        h15.window_open = Some(true);
+        let mut r15 = bucket(15, event_source::REALTIME);
+        r15.inside_temp_c = Some(21.2);
+        r15.setpoint_temp_c = Some(18.0);
+        r15.humidity_pct = Some(44.0);
+
+        let merged = merge_source_buckets(vec![r15, h0, r0, h15]);
+
+        assert_eq!(merged.len(), 2);
+        for row in &merged {
+            assert!(row.inside_temp_c.is_some());
+            assert!(row.humidity_pct.is_some());
+            assert!(row.setpoint_temp_c.is_some());
+            assert!(row.heating_power_pct.is_some());
+            assert!(row.window_open.is_some());
+        }
+        // Historical wins where both are present
+        assert_eq!(merged[0].inside_temp_c, Some(21.0));
+        assert_eq!(merged[1].humidity_pct, Some(47.0));
+        // Realtime fills historical gaps
+        assert_eq!(merged[0].humidity_pct, Some(45.0));
+        assert_eq!(merged[1].inside_temp_c, Some(21.2));
+        assert_eq!(merged[1].setpoint_temp_c, Some(18.0));
+    }
+
+    #[test]
+    fn climate_between_filters_zone_rows_in_range() {
+        let from = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 3, 2, 0, 0, 0).unwrap();
+        let query = climate_between_query(3, 5, from, to, 50, 100);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+
+        for predicate in [
+            r#""climate_measurements"."home_id" = $1"#,
+            r#""climate_measurements"."zone_id" = $2"#,
+            r#""climate_measurements"."device_id" IS NULL"#,
+            r#""climate_measurements"."time" >= $3"#,
+            r#""climate_measurements"."time" < $4"#,
+        ] {
+            assert!(sql.contains(predicate), "{sql}");
+        }
+        assert!(
+            sql.contains(r#"ORDER BY "climate_measurements"."time" ASC, "climate_measurements"."id" ASC"#),
+            "{sql}"
+        );
+        assert!(sql.contains("LIMIT $5 OFFSET $6"), "{sql}");
+        assert!(sql.ends_with(", 50, 100]"), "{sql}");
+    }
+
+    #[test]
+    fn gaps_split_at_midnight_and_respect_min_gap() {
+        let at = |d: u32, h: u32, m: u32| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+        let times = [at(1, 0, 0), at(1, 0, 15), at(1, 6, 0), at(1, 6, 15), at(2, 1, 0)];
+
+        let gaps = compute_gaps(&times, at(1, 0, 0), at(2, 3, 0), Duration::hours(1));
+
+        let day1 = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let day2 = NaiveDate::from_ymd_opt(2024, 3, 2).unwrap();
+        assert_eq!(
+            gaps[&day1],
+            vec![
+                Gap {
+                    start: at(1, 0, 15),
+                    end: at(1, 6, 0),
+                    start_inclusive: false,
+                },
+                Gap {
+                    start: at(1, 6, 15),
+                    end: at(2, 0, 0),
+                    start_inclusive: false,
+                },
+            ]
+        );
+        assert_eq!(
+            gaps[&day2],
+            vec![
+                Gap {
+                    start: at(2, 0, 0),
+                    end: at(2, 1, 0),
+                    start_inclusive: true,
+                },
+                Gap {
+                    start: at(2, 1, 0),
+                    end: at(2, 3, 0),
+                    start_inclusive: false,
+                },
+            ]
+        );
+        // Readings closer together than the minimum gap leave nothing to fill
+        assert!(compute_gaps(&times[..2], at(1, 0, 0), at(1, 0, 20), Duration::minutes(20)).is_empty());
+    }
+
+    #[test]
+    fn compute_gaps_edge_cases() {
+        let at = |d: u32, h: u32, m: u32| Utc.with_ymd_and_hms(2024, 3, d, h, m, 0).unwrap();
+        let day1 = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let hour = Duration::hours(1);
+
+        // No readings: the whole window is one gap per day, each including its start
+        let empty = compute_gaps(&[], at(1, 12, 0), at(2, 6, 0), hour);
+        assert_eq!(empty.len(), 2);
+        assert_eq!(
+            empty[&day1],
+            vec![Gap {
+                start: at(1, 12, 0),
+                end: at(2, 0, 0),
+                start_inclusive: true,
+            }]
+        );
+
+        // A single reading splits its day into a gap before (inclusive) and after (exclusive) it
+        let single = compute_gaps(&[at(1, 10, 0)], at(1, 6, 0), at(1, 14, 0), hour);
+        assert_eq!(
+            single[&day1],
+            vec![
+                Gap {
+                    start: at(1, 6, 0),
+                    end: at(1, 10, 0),
+                    start_inclusive: true,
+                },
+                Gap {
+                    start: at(1, 10, 0),
+                    end: at(1, 14, 0),
+                    start_inclusive: false,
+                },
+            ]
+        );
+
+        // Readings outside the window are ignored; an inverted or empty window has no gaps
+        let outside = compute_gaps(&[at(1, 3, 0)], at(1, 6, 0), at(1, 14, 0), hour);
+        assert_eq!(
+            outside,
+            BTreeMap::from([(
+                day1,
+                vec![Gap {
+                    start: at(1, 6, 0),
+                    end: at(1, 14, 0),
+                    start_inclusive: true,
+                }],
+            )])
+        );
+        assert!(compute_gaps(&[], at(2, 0, 0), at(1, 0, 0), hour).is_empty());
+        assert!(compute_gaps(&[], at(1, 0, 0), at(1, 0, 0), hour).is_empty());
+    }
+}
@@ -1,36 +1,607 @@
-use crate::client::TadoClient;
-use crate::db::models::event_source;
-use crate::db::models::{NewClimateMeasurement, NewWeatherMeasurement};
+use crate::client::{self, TadoClient};
+use crate::config::{DisabledWeatherFields, MaintenanceWindow, OutOfOrderCheck};
+use crate::db::models::{ClimateMeasurement, NewClimateMeasurement, NewEvent, NewWeatherMeasurement};
+use crate::db::models::{event_source, event_types};
 use crate::models::tado::{self, HomeId};
 use crate::schema;
-use crate::utils::serde_enum_name;
-use chrono::Utc;
-use diesel::prelude::*;
+use crate::services::accounts::Accounts;
+use crate::services::influx::{InfluxBatch, InfluxSink};
+use crate::services::ingest::{
+    EventThrottle, insert_capped_event, insert_climate_measurements, insert_events, insert_weather_measurements,
+    insert_zone_weather_measurements,
+};
+use crate::services::metrics;
+use crate::services::retention::{self, RetentionPolicy};
+use crate::services::webhook::WeatherWebhook;
+use crate::services::{air_comfort, backfill, presence, query, refs, rollup, shutdown};
+use crate::utils::{data_point_celsius, serde_enum_name, temperature_celsius};
+use chrono::{DateTime, NaiveDate, Utc};
 use diesel::PgConnection;
+use diesel::connection::SimpleConnection;
+use diesel::prelude::*;
 use log::{debug, info, warn};
-use std::collections::BTreeMap;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroU32;
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// Optional behaviours of the realtime loop, all driven by config.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealtimeOptions {
+    pub store_ingest_lag: bool,
+    pub maintenance_window: Option<MaintenanceWindow>,
+    pub daily_runtime_rollup: bool,
+    /// Per-source measurement retention, pruned once per UTC day.
+    pub retention: RetentionPolicy,
+    pub track_geolocation_override: bool,
+    /// Emit `SENSOR_PRECISION_CHANGED` when a zone's reported temperature precision changes.
+    pub track_sensor_precision: bool,
+    /// Fetch each home's weather on every tick.
+    pub collect_weather: bool,
+    /// Fetch each zone's state (climate rows and the events derived from it) on every tick.
+    pub collect_zones: bool,
+    /// List each home's devices on every tick for connectivity and battery events.
+    pub collect_devices: bool,
+    /// Record mobile device presence for each home on every tick.
+    pub collect_presence: bool,
+    /// Record each zone's air comfort levels on every tick.
+    pub collect_air_comfort: bool,
+    /// Back-to-back overrunning ticks allowed before a recovery sleep is forced.
+    pub max_catchup_ticks: u32,
+    /// How long a changed battery state must persist before `DEVICE_BATTERY_LOW`/`NORMAL` is emitted.
+    pub battery_event_debounce: Duration,
+    /// Flag zone readings older than the latest one seen for the zone; `None` skips the check.
+    pub out_of_order_check: Option<OutOfOrderCheck>,
+    /// Flag schedule changes further than this from the predicted block start; `None` skips the check.
+    pub schedule_transition_tolerance: Option<Duration>,
+    /// Failed home collections in a row after which the loop gives up and returns an error.
+    pub max_consecutive_failures: u32,
+    /// Fill each zone's recent gap from day reports once before the first tick.
+    pub startup_catchup: bool,
+    /// Re-run the full reference sync this often; `None` keeps the startup sync only.
+    pub refs_sync_every: Option<Duration>,
+    pub refs_sync_options: refs::SyncOptions,
+    pub weather_disabled_fields: DisabledWeatherFields,
+    /// Also copy the home's weather onto every zone in `zone_weather_measurements`.
+    pub weather_per_zone: bool,
+    /// Write each home's tick in one transaction, so a failure mid-tick leaves none of its rows behind.
+    pub tx_per_tick: bool,
+    /// Attempts at re-establishing a lost database connection before the loop gives up.
+    pub db_reconnect_max_retries: u32,
+    /// `EVENTS_MAX_PER_ZONE_PER_DAY`: cap on each zone's realtime events of one type per UTC day.
+    pub events_max_per_zone_per_day: Option<NonZeroU32>,
+    /// `BACKFILL_TRIM_LEADING_BOGUS`, applied to the day reports the catch-up after startup or a pause reads.
+    pub trim_leading_bogus: bool,
+    /// `DRY_RUN`: log measurement and event inserts, retention deletes and heartbeats instead of writing them.
+    pub dry_run: bool,
+    /// `REALTIME_DEDUP`: skip a zone's climate row when every value matches the last one stored for the zone,
+    /// unless that one is at least this old. `None` stores every row.
+    pub dedup: Option<chrono::Duration>,
+}
+
+/// Names of the per-tick data categories `options` collects, in collection order.
+fn collected_categories(options: &RealtimeOptions) -> Vec<&'static str> {
+    [
+        (options.collect_weather, "weather"),
+        (options.collect_zones, "zones"),
+        (options.collect_devices, "devices"),
+        (options.collect_presence, "presence"),
+        (options.collect_air_comfort, "air comfort"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, name)| enabled.then_some(name))
+    .collect()
+}
+
+fn log_collected_categories(options: &RealtimeOptions) {
+    let categories = collected_categories(options);
+    if categories.is_empty() {
+        warn!("Realtime: every collection category is disabled; ticks will fetch nothing");
+    } else {
+        info!("Realtime: collecting {}", categories.join(", "));
+    }
+}
+
+/// Cache: tado_home_id -> (tado_zone_id -> db_zone_id)
+type ZoneMaps = BTreeMap<i64, BTreeMap<i64, i64>>;
+
+#[allow(clippy::too_many_arguments)]
 pub fn run_loop(
     conn: &mut PgConnection,
-    client: &TadoClient,
-    home_ids: &[i64],
+    database_url: &str,
+    accounts: &Accounts,
     interval: Duration,
+    weather_webhook: Option<&WeatherWebhook>,
+    influx: Option<&InfluxSink>,
+    options: RealtimeOptions,
 ) -> Result<(), String> {
+    let RealtimeOptions {
+        store_ingest_lag,
+        maintenance_window,
+        daily_runtime_rollup,
+        retention,
+        track_geolocation_override,
+        max_catchup_ticks,
+        max_consecutive_failures,
+        startup_catchup,
+        refs_sync_every,
+        refs_sync_options,
+        db_reconnect_max_retries,
+        ..
+    } = options;
+    let home_ids = &accounts.home_ids()[..];
+    log_collected_categories(&options);
     info!(
-        "Realtime loop started (homes={}, interval={}s)",
+        "Realtime loop started (homes={}, interval={}s, store_ingest_lag={}, maintenance_window={}, daily_runtime_rollup={}, track_geolocation_override={})",
         home_ids.len(),
-        interval.as_secs()
+        interval.as_secs(),
+        store_ingest_lag,
+        maintenance_window
+            .map(|w| w.to_string())
+            .unwrap_or_else(|| "-".to_string()),
+        daily_runtime_rollup,
+        track_geolocation_override
     );
-    // Build caches for DB identifiers used every tick
+    let (mut home_db_ids, mut zone_maps) = load_id_caches(conn, home_ids)?;
+
+    if startup_catchup {
+        catch_up_recent_gaps(conn, accounts, &home_db_ids, &zone_maps, &options);
+    }
+
+    // With several homes each one gets its own connection and is collected on its own thread, so a slow
+    // home no longer delays the others; the tick then lasts as long as the slowest home.
+    let mut home_conns: BTreeMap<i64, PgConnection> = BTreeMap::new();
+    if home_ids.len() > 1 {
+        for home_id in home_ids {
+            let home_conn = PgConnection::establish(database_url)
+                .map_err(|e| format!("DB connection for home {} failed: {}", home_id, e))?;
+            home_conns.insert(*home_id, home_conn);
+        }
+    }
+
+    // Per-home zone state observed on the previous tick
+    let mut tracking: BTreeMap<i64, ZoneTracking> = home_ids
+        .iter()
+        .map(|home_id| (*home_id, ZoneTracking::default()))
+        .collect();
+    let mut paused = false;
+    // Set when a maintenance window ends, so the next tick first fills the paused stretch
+    let mut catch_up_pending = false;
+    let mut pacer = TickPacer::new(interval, max_catchup_ticks);
+    // Per-home failure streaks, so a healthy home neither resets nor masks a failing one
+    let mut failures: BTreeMap<i64, FailureStreak> = home_ids
+        .iter()
+        .map(|home_id| (*home_id, FailureStreak::new(max_consecutive_failures)))
+        .collect();
+    // The startup sync just ran
+    let mut last_refs_sync = Instant::now();
+    // UTC day for which the daily rollup last ran in this process
+    let mut rolled_up_day: Option<NaiveDate> = None;
+    // UTC day for which retention pruning last ran in this process
+    let mut pruned_day: Option<NaiveDate> = None;
+    let mut tick: u64 = 0;
+
+    loop {
+        if shutdown::requested() {
+            break;
+        }
+        // Nothing runs while the maintenance window is active, not even a reconnect; the gap it leaves is filled
+        // from day reports as soon as the window ends. The heartbeat thread pauses for the window on its own.
+        let in_maintenance = maintenance_window.is_some_and(|w| w.contains(Utc::now()));
+        if in_maintenance != paused {
+            if let (true, Some(window)) = (in_maintenance, maintenance_window) {
+                info!("Realtime: maintenance window {} active; pausing collection", window);
+            } else {
+                info!("Realtime: maintenance window over; resuming collection");
+                catch_up_pending = true;
+            }
+            paused = in_maintenance;
+        }
+        if paused {
+            shutdown::sleep(interval);
+            continue;
+        }
+
+        let tick_start = Instant::now();
+        let webhook = weather_webhook.filter(|w| w.due(tick));
+        tick += 1;
+
+        // The ID caches and zone tracking above are plain locals, so they survive a reconnect unchanged
+        let reconnected = ensure_connected(conn, database_url, db_reconnect_max_retries, "main").and_then(|_| {
+            home_conns.iter_mut().try_for_each(|(home_id, home_conn)| {
+                ensure_connected(
+                    home_conn,
+                    database_url,
+                    db_reconnect_max_retries,
+                    &format!("home {}", home_id),
+                )
+            })
+        });
+        if let Err(e) = reconnected {
+            if shutdown::requested() {
+                break;
+            }
+            return Err(e);
+        }
+
+        if std::mem::take(&mut catch_up_pending) {
+            catch_up_recent_gaps(conn, accounts, &home_db_ids, &zone_maps, &options);
+        }
+
+        // Runs on the first tick and again whenever the UTC day rolls over; the rollup itself skips
+        // days that already have events, so restarts do not duplicate them.
+        let today = Utc::now().date_naive();
+        if daily_runtime_rollup && rolled_up_day != Some(today) {
+            for db_home_id in home_db_ids.values() {
+                if let Err(e) = rollup::roll_up_previous_day(conn, *db_home_id, today, options.dry_run) {
+                    warn!("Rollup: heating runtime for home {} failed: {}", db_home_id, e);
+                }
+            }
+            rolled_up_day = Some(today);
+        }
+        if retention.is_enabled() && pruned_day != Some(today) {
+            if let Err(e) = retention::prune(conn, retention, Utc::now(), options.dry_run) {
+                warn!("Retention: pruning expired measurements failed: {}", e);
+            }
+            pruned_day = Some(today);
+        }
+
+        let mut influx_batch = influx.map(|_| InfluxBatch::default());
+        let results = collect_homes(
+            conn,
+            &mut home_conns,
+            accounts,
+            home_ids,
+            (&home_db_ids, &zone_maps),
+            &mut tracking,
+            webhook,
+            influx_batch.as_mut(),
+            &options,
+        );
+        let tick_succeeded = results.iter().all(|(_, result)| result.is_ok());
+        for (home_id, result) in results {
+            let failures = failures
+                .entry(home_id)
+                .or_insert_with(|| FailureStreak::new(max_consecutive_failures));
+            if let Err(e) = result {
+                warn!(
+                    "Realtime: collecting home {} failed ({} consecutive failure(s)): {}",
+                    home_id,
+                    failures.count + 1,
+                    e
+                );
+                if failures.record_failure() {
+                    return Err(format!(
+                        "Realtime: giving up after {} consecutive collection failures for home {}; last error: {}",
+                        failures.count, home_id, e
+                    ));
+                }
+            } else {
+                failures.record_success();
+                // A home that failed this tick makes the whole tick a failure for the liveness gauge
+                if tick_succeeded {
+                    metrics::record_successful_tick(home_id, Utc::now());
+                }
+            }
+        }
+        // One write per tick; the rows are already in Timescale, so a failed write only loses the mirror copy
+        if let (Some(sink), Some(batch)) = (influx, influx_batch.as_ref()) {
+            sink.write(batch);
+        }
+        if shutdown::requested() {
+            break;
+        }
+
+        // Periodic reference sync runs after collection so it only delays the next tick, never splits one.
+        if refs_sync_due(last_refs_sync, Instant::now(), refs_sync_every) {
+            info!("Realtime: running scheduled reference sync");
+            match resync_refs(conn, database_url, accounts, home_ids, refs_sync_options) {
+                Ok((homes, zones)) => {
+                    home_db_ids = homes;
+                    zone_maps = zones;
+                }
+                Err(e) => warn!("Realtime: scheduled reference sync failed: {}", e),
+            }
+            last_refs_sync = Instant::now();
+        }
+
+        metrics::record_tick_duration(tick_start.elapsed());
+
+        // Maintain steady cadence
+        let was_throttling = pacer.throttling();
+        let sleep = pacer.sleep_after(tick_start.elapsed());
+        if pacer.throttling() && !was_throttling {
+            warn!(
+                "Realtime: {} consecutive ticks overran the {}s interval (paused VM or slow API?); throttling to one tick per {} ms",
+                pacer.overruns,
+                interval.as_secs(),
+                sleep.as_millis()
+            );
+        } else if was_throttling && !pacer.throttling() {
+            info!("Realtime: tick cadence recovered; throttling lifted");
+        }
+        if !sleep.is_zero() {
+            shutdown::sleep(sleep);
+        }
+        debug!("Realtime tick completed in {} ms", tick_start.elapsed().as_millis());
+    }
+
+    info!("Received shutdown signal, exiting realtime loop");
+    Ok(())
+}
+
+/// One collection pass over all homes, then return; for cron-style deployments that poll instead of looping.
+///
+/// Every home is attempted even if an earlier one fails; the pass fails if any home did. Transition events
+/// need a previous observation, so a single pass only emits the ones seeded from the database (device health).
+pub fn run_single(
+    conn: &mut PgConnection,
+    accounts: &Accounts,
+    weather_webhook: Option<&WeatherWebhook>,
+    influx: Option<&InfluxSink>,
+    options: RealtimeOptions,
+) -> Result<(), String> {
+    if let Some(window) = options.maintenance_window.filter(|w| w.contains(Utc::now())) {
+        info!(
+            "Realtime: maintenance window {} active; skipping single collection",
+            window
+        );
+        return Ok(());
+    }
+    let home_ids = &accounts.home_ids()[..];
+    log_collected_categories(&options);
+    let (home_db_ids, zone_maps) = load_id_caches(conn, home_ids)?;
+    if options.startup_catchup {
+        catch_up_recent_gaps(conn, accounts, &home_db_ids, &zone_maps, &options);
+    }
+
+    let mut tracking = ZoneTracking::default();
+    let mut influx_batch = influx.map(|_| InfluxBatch::default());
+    let mut collected: Vec<i64> = Vec::new();
+    let mut failed: Vec<i64> = Vec::new();
+    for home_id in home_ids {
+        let result = collect_cached_home(
+            conn,
+            accounts,
+            *home_id,
+            &home_db_ids,
+            &zone_maps,
+            &mut tracking,
+            weather_webhook,
+            influx_batch.as_mut(),
+            &options,
+        );
+        match result {
+            Some(Ok(())) => collected.push(*home_id),
+            Some(Err(e)) => {
+                warn!("Realtime: collecting home {} failed: {}", home_id, e);
+                failed.push(*home_id);
+            }
+            None => {}
+        }
+    }
+    if let (Some(sink), Some(batch)) = (influx, influx_batch.as_ref()) {
+        sink.write(batch);
+    }
+    if options.retention.is_enabled()
+        && let Err(e) = retention::prune(conn, options.retention, Utc::now(), options.dry_run)
+    {
+        warn!("Retention: pruning expired measurements failed: {}", e);
+    }
+
+    if failed.is_empty() {
+        for home_id in collected {
+            metrics::record_successful_tick(home_id, Utc::now());
+        }
+        info!("Realtime: single collection completed for {} home(s)", home_ids.len());
+        Ok(())
+    } else {
+        Err(format!(
+            "Realtime: single collection failed for {} of {} home(s): {:?}",
+            failed.len(),
+            home_ids.len(),
+            failed
+        ))
+    }
+}
+
+/// Collect the tick's homes and return each collected home's outcome, ordered by home id.
+///
+/// Homes with a dedicated connection in `home_conns` run concurrently on scoped threads; otherwise they are
+/// collected one after another on `conn`, stopping early when shutdown is requested.
+#[allow(clippy::too_many_arguments)]
+fn collect_homes(
+    conn: &mut PgConnection,
+    home_conns: &mut BTreeMap<i64, PgConnection>,
+    accounts: &Accounts,
+    homes: &[i64],
+    (home_db_ids, zone_maps): (&BTreeMap<i64, i64>, &ZoneMaps),
+    tracking: &mut BTreeMap<i64, ZoneTracking>,
+    weather_webhook: Option<&WeatherWebhook>,
+    mut influx_batch: Option<&mut InfluxBatch>,
+    options: &RealtimeOptions,
+) -> Vec<(i64, Result<(), String>)> {
+    if home_conns.is_empty() {
+        let mut results = Vec::new();
+        for home_id in homes {
+            let result = collect_cached_home(
+                conn,
+                accounts,
+                *home_id,
+                home_db_ids,
+                zone_maps,
+                tracking.entry(*home_id).or_default(),
+                weather_webhook,
+                influx_batch.as_deref_mut(),
+                options,
+            );
+            results.extend(result.map(|r| (*home_id, r)));
+            if shutdown::requested() {
+                break;
+            }
+        }
+        return results;
+    }
+
+    let collect_influx = influx_batch.is_some();
+    thread::scope(|scope| {
+        let handles: Vec<_> = home_conns
+            .iter_mut()
+            .filter(|(home_id, _)| homes.contains(home_id))
+            .map(|(home_id, home_conn)| {
+                let home_id = *home_id;
+                let home_tracking = tracking.remove(&home_id).unwrap_or_default();
+                let handle = scope.spawn(move || {
+                    let mut home_tracking = home_tracking;
+                    let mut batch = collect_influx.then(InfluxBatch::default);
+                    let result = collect_cached_home(
+                        home_conn,
+                        accounts,
+                        home_id,
+                        home_db_ids,
+                        zone_maps,
+                        &mut home_tracking,
+                        weather_webhook,
+                        batch.as_mut(),
+                        options,
+                    );
+                    (result, batch, home_tracking)
+                });
+                (home_id, handle)
+            })
+            .collect();
+        let mut results = Vec::new();
+        for (home_id, handle) in handles {
+            match handle.join() {
+                Ok((result, batch, home_tracking)) => {
+                    tracking.insert(home_id, home_tracking);
+                    if let (Some(target), Some(batch)) = (influx_batch.as_deref_mut(), batch) {
+                        target.append(batch);
+                    }
+                    results.extend(result.map(|r| (home_id, r)));
+                }
+                Err(_) => results.push((home_id, Err("collection thread panicked".to_string()))),
+            }
+        }
+        results
+    })
+}
+
+/// Collect one home with its account's client, using the ID caches; `None` when the home is not in them
+/// (nothing to collect).
+#[allow(clippy::too_many_arguments)]
+fn collect_cached_home(
+    conn: &mut PgConnection,
+    accounts: &Accounts,
+    home_id: i64,
+    home_db_ids: &BTreeMap<i64, i64>,
+    zone_maps: &ZoneMaps,
+    tracking: &mut ZoneTracking,
+    weather_webhook: Option<&WeatherWebhook>,
+    influx_batch: Option<&mut InfluxBatch>,
+    options: &RealtimeOptions,
+) -> Option<Result<(), String>> {
+    let client = accounts.client_for(home_id)?;
+    let db_home_id = home_db_ids.get(&home_id).copied()?;
+    let zone_map = zone_maps.get(&home_id)?;
+    debug!("Realtime: collecting home {} ({} zones)", home_id, zone_map.len());
+    let mut webhook_rows = weather_webhook.map(|_| Vec::new());
+    if !options.tx_per_tick {
+        let result = collect_home(
+            conn,
+            client,
+            db_home_id,
+            home_id,
+            zone_map,
+            tracking,
+            webhook_rows.as_mut(),
+            influx_batch,
+            options,
+        );
+        // Each row was committed on its own, so even a failed tick's weather reading goes out
+        notify_weather(weather_webhook, home_id, webhook_rows);
+        return Some(result);
+    }
+    let result = collect_atomically(tracking, influx_batch, |tracking, influx_batch| {
+        conn.transaction(|conn| {
+            collect_home(
+                conn,
+                client,
+                db_home_id,
+                home_id,
+                zone_map,
+                tracking,
+                webhook_rows.as_mut(),
+                influx_batch,
+                options,
+            )
+            .map_err(TickFailed)
+        })
+        .map_err(|TickFailed(e)| format!("{} (tick rolled back)", e))
+    });
+    if result.is_ok() {
+        notify_weather(weather_webhook, home_id, webhook_rows);
+    }
+    Some(result)
+}
+
+/// Sends the weather rows a tick wrote to the webhook; only called once those rows are committed.
+fn notify_weather(webhook: Option<&WeatherWebhook>, home_id: i64, rows: Option<Vec<NewWeatherMeasurement>>) {
+    if let (Some(webhook), Some(rows)) = (webhook, rows) {
+        for row in &rows {
+            webhook.notify(home_id, row);
+        }
+    }
+}
+
+/// Error type of the per-tick transaction; diesel needs one it can convert its own errors into.
+struct TickFailed(String);
+
+impl From<diesel::result::Error> for TickFailed {
+    fn from(e: diesel::result::Error) -> Self {
+        TickFailed(format!("tick transaction failed: {}", e))
+    }
+}
+
+/// Runs `collect` against copies of the home's tracking state and a separate InfluxDB batch, keeping them and
+/// its insert counts only when it succeeds. With `collect` wrapping the home's tick in a transaction, a failed
+/// tick leaves nothing behind: no rows, no mirrored lines, no counted inserts and no remembered transitions, so
+/// the next tick detects them again.
+fn collect_atomically(
+    tracking: &mut ZoneTracking,
+    influx_batch: Option<&mut InfluxBatch>,
+    collect: impl FnOnce(&mut ZoneTracking, Option<&mut InfluxBatch>) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut staged_tracking = tracking.clone();
+    let mut staged_batch = influx_batch.is_some().then(InfluxBatch::default);
+    let (result, inserted) = metrics::defer_inserted(|| collect(&mut staged_tracking, staged_batch.as_mut()));
+    result?;
+    inserted.record();
+    *tracking = staged_tracking;
+    if let (Some(target), Some(batch)) = (influx_batch, staged_batch) {
+        target.append(batch);
+    }
+    Ok(())
+}
+
+/// Logs a failed write so the tick carries on, except under `REALTIME_TX_PER_TICK`: the failure has aborted
+/// the tick's transaction, so it fails the tick instead.
+fn write_failed(tx_per_tick: bool, message: String) -> Result<(), String> {
+    if tx_per_tick {
+        return Err(message);
+    }
+    warn!("{}", message);
+    Ok(())
+}
+
+/// Build caches for DB identifiers used every tick: tado_home_id -> db_home_id and the per-home zone maps.
+fn load_id_caches(conn: &mut PgConnection, home_ids: &[i64]) -> Result<(BTreeMap<i64, i64>, ZoneMaps), String> {
     use schema::homes::dsl as H;
     use schema::zones::dsl as Z;
 
-    // Cache: tado_home_id -> db_home_id
     let mut home_db_ids: BTreeMap<i64, i64> = BTreeMap::new();
-    // Cache: tado_home_id -> (tado_zone_id -> db_zone_id)
-    let mut zone_maps: BTreeMap<i64, BTreeMap<i64, i64>> = BTreeMap::new();
+    let mut zone_maps: ZoneMaps = BTreeMap::new();
 
     for home_id in home_ids {
         let db_home_id: i64 = H::homes
@@ -40,7 +611,7 @@ pub fn run_loop(
             .map_err(|e| format!("fetch db_home_id failed: {}", e))?;
         home_db_ids.insert(*home_id, db_home_id);
 
-        // Build zone map once per home from DB state
+        // Zone map per home from DB state
         let rows: Vec<(i64, i64)> = Z::zones
             .filter(Z::home_id.eq(db_home_id))
             .select((Z::tado_zone_id, Z::id))
@@ -50,154 +621,1655 @@ pub fn run_loop(
         zone_maps.insert(*home_id, zmap);
     }
 
-    loop {
-        let tick_start = Instant::now();
+    Ok((home_db_ids, zone_maps))
+}
 
-        for home_id in home_ids {
-            let db_home_id = match home_db_ids.get(home_id).copied() {
-                Some(id) => id,
-                None => continue,
-            };
-            let Some(zone_map) = zone_maps.get(home_id) else {
-                continue;
-            };
-            debug!("Realtime: collecting home {} ({} zones)", home_id, zone_map.len());
-            collect_home(conn, client, db_home_id, *home_id, zone_map)?;
+/// Smooths the handoff from backfill or a maintenance pause: fills the gap between each zone's latest stored
+/// reading and now. Failures only cost the smoothing, so they are logged and the loop carries on regardless.
+fn catch_up_recent_gaps(
+    conn: &mut PgConnection,
+    accounts: &Accounts,
+    home_db_ids: &BTreeMap<i64, i64>,
+    zone_maps: &ZoneMaps,
+    options: &RealtimeOptions,
+) {
+    for (home_id, db_home_id) in home_db_ids {
+        let (Some(client), Some(zone_map)) = (accounts.client_for(*home_id), zone_maps.get(home_id)) else {
+            continue;
+        };
+        for (&tado_zone_id, &db_zone_id) in zone_map {
+            if let Err(e) = backfill::catch_up_zone(
+                conn,
+                client,
+                HomeId(*home_id),
+                *db_home_id,
+                tado::ZoneId(tado_zone_id),
+                db_zone_id,
+                options.trim_leading_bogus,
+                options.dry_run,
+            ) {
+                warn!(
+                    "Realtime: catch-up failed for home {}, zone {}: {}",
+                    home_id, tado_zone_id, e
+                );
+            }
         }
+    }
+}
 
-        // Maintain steady cadence
-        let elapsed = tick_start.elapsed();
-        if elapsed < interval {
-            thread::sleep(interval - elapsed);
-        }
-        debug!("Realtime tick completed in {} ms", tick_start.elapsed().as_millis());
+/// Whether the periodic reference sync is due. `every = None` disables it.
+fn refs_sync_due(last_sync: Instant, now: Instant, every: Option<Duration>) -> bool {
+    every.is_some_and(|every| now.saturating_duration_since(last_sync) >= every)
+}
+
+/// Re-runs the reference sync so renamed zones, new devices and membership changes are picked up,
+/// then reloads the ID caches (new zones get collected from the next tick on).
+fn resync_refs(
+    conn: &mut PgConnection,
+    database_url: &str,
+    accounts: &Accounts,
+    home_ids: &[i64],
+    options: refs::SyncOptions,
+) -> Result<(BTreeMap<i64, i64>, ZoneMaps), String> {
+    accounts.sync_refs(conn, database_url, options)?;
+    load_id_caches(conn, home_ids)
+}
+
+/// Ties rows written in a tick to the `Tado API request N` log line of the call they came from.
+fn log_request_rows(request_id: Option<u64>, inserted: usize, what: &str, home_id: i64) {
+    if let Some(id) = request_id {
+        info!(
+            "Realtime: request {} -> {} {} row(s) for home {}",
+            id, inserted, what, home_id
+        );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn collect_home(
     conn: &mut PgConnection,
     client: &TadoClient,
     db_home_id: i64,
     home_id: i64,
     zone_id_map: &BTreeMap<i64, i64>,
+    tracking: &mut ZoneTracking,
+    webhook_rows: Option<&mut Vec<NewWeatherMeasurement>>,
+    mut influx_batch: Option<&mut InfluxBatch>,
+    options: &RealtimeOptions,
 ) -> Result<(), String> {
-    use schema::climate_measurements::dsl as C;
-    use schema::weather_measurements::dsl as W;
-
     // Weather (home-scoped)
-    if let Ok(weather) = client.get_weather(HomeId(home_id)) {
-        let now_ts = Utc::now();
-        let ts = weather
-            .outside_temperature
-            .as_ref()
-            .and_then(|t| t.timestamp)
-            .or_else(|| weather.solar_intensity.as_ref().and_then(|s| s.timestamp))
-            .unwrap_or(now_ts);
-        let weather_state = weather
-            .weather_state
-            .as_ref()
-            .and_then(|ws| ws.value.as_ref())
-            .and_then(serde_enum_name);
-
-        let mut row = NewWeatherMeasurement::new(ts, db_home_id, event_source::REALTIME);
-        row.outside_temp_c = weather.outside_temperature.as_ref().and_then(|t| t.celsius);
-        row.solar_intensity_pct = weather.solar_intensity.as_ref().and_then(|s| s.percentage);
-        row.weather_state = weather_state;
-        if let Err(e) = diesel::insert_into(W::weather_measurements)
-            .values(&row)
-            .on_conflict((W::home_id, W::time, W::source))
-            .do_nothing()
-            .execute(conn)
-        {
-            warn!("Realtime: insert weather row failed for home {}: {}", home_id, e);
+    if options.collect_weather {
+        let (weather, request_id) = client::with_request_id(|| client.get_weather(HomeId(home_id)));
+        if let Ok(weather) = weather {
+            let mut row = weather_row_from_report(&weather, db_home_id, Utc::now());
+            options.weather_disabled_fields.apply(&mut row);
+            if options.store_ingest_lag {
+                row.ingest_lag_secs = Some(ingest_lag_secs(row.time, Utc::now()));
+            }
+            match insert_weather_measurements(conn, std::slice::from_ref(&row), options.dry_run) {
+                Ok(inserted) => log_request_rows(request_id, inserted, "weather", home_id),
+                Err(e) => write_failed(
+                    options.tx_per_tick,
+                    format!("Realtime: insert weather row failed for home {}: {}", home_id, e),
+                )?,
+            }
+            if options.weather_per_zone {
+                let zone_ids: Vec<i64> = zone_id_map.values().copied().collect();
+                if let Err(e) =
+                    insert_zone_weather_measurements(conn, std::slice::from_ref(&row), &zone_ids, options.dry_run)
+                {
+                    write_failed(
+                        options.tx_per_tick,
+                        format!("Realtime: insert zone weather rows failed for home {}: {}", home_id, e),
+                    )?;
+                }
+            }
+            if let Some(batch) = influx_batch.as_deref_mut() {
+                batch.push_weather(&row);
+            }
+            // Sent by the caller once the row is committed
+            if let Some(rows) = webhook_rows {
+                rows.push(row);
+            }
         }
     }
 
     // Zones realtime
-    for (&tado_zone_id, &db_zone_id) in zone_id_map {
-        let zone_id = tado::ZoneId(tado_zone_id);
-        let state = client
-            .get_zone_state(HomeId(home_id), zone_id)
-            .map_err(|e| {
+    if options.collect_zones {
+        for (&tado_zone_id, &db_zone_id) in zone_id_map {
+            let zone_id = tado::ZoneId(tado_zone_id);
+            let (state, request_id) = client::with_request_id(|| client.get_zone_state(HomeId(home_id), zone_id));
+            let state = state.map_err(|e| {
                 format!(
                     "Realtime: get_zone_state({}, {}) failed (zones assumed static; restart the service if the set of zones changed): {}",
                     home_id, tado_zone_id, e
                 )
             })?;
 
-        let now_ts = Utc::now();
-        // pick the most precise timestamp available
-        let ts = state
-            .sensor_data_points
-            .as_ref()
-            .and_then(|s| s.inside_temperature.as_ref().and_then(|t| t.timestamp))
-            .or_else(|| {
-                state
-                    .sensor_data_points
-                    .as_ref()
-                    .and_then(|s| s.humidity.as_ref().and_then(|h| h.timestamp))
-            })
-            .or_else(|| {
-                state
-                    .activity_data_points
-                    .as_ref()
-                    .and_then(|a| a.heating_power.as_ref().and_then(|p| p.timestamp))
-            })
-            .or_else(|| {
-                state
-                    .activity_data_points
-                    .as_ref()
-                    .and_then(|a| a.ac_power.as_ref().and_then(|p| p.timestamp))
-            })
-            .unwrap_or(now_ts);
+            let now_ts = Utc::now();
+            // pick the most precise timestamp available
+            let ts = state
+                .sensor_data_points
+                .as_ref()
+                .and_then(|s| s.inside_temperature.as_ref().and_then(|t| t.timestamp))
+                .or_else(|| {
+                    state
+                        .sensor_data_points
+                        .as_ref()
+                        .and_then(|s| s.humidity.as_ref().and_then(|h| h.timestamp))
+                })
+                .or_else(|| {
+                    state
+                        .activity_data_points
+                        .as_ref()
+                        .and_then(|a| a.heating_power.as_ref().and_then(|p| p.timestamp))
+                })
+                .or_else(|| {
+                    state
+                        .activity_data_points
+                        .as_ref()
+                        .and_then(|a| a.ac_power.as_ref().and_then(|p| p.timestamp))
+                })
+                .unwrap_or(now_ts);
 
-        // Extract values without moving the state
-        let inside_temp_c = state
-            .sensor_data_points
-            .as_ref()
-            .and_then(|s| s.inside_temperature.as_ref().and_then(|t| t.celsius));
-        let humidity_pct = state
-            .sensor_data_points
-            .as_ref()
-            .and_then(|s| s.humidity.as_ref().and_then(|h| h.percentage));
-        let setpoint_temp_c = state
-            .setting
-            .as_ref()
-            .and_then(|set| set.temperature.as_ref().and_then(|t| t.celsius));
-        let heating_power_pct = state
-            .activity_data_points
+            let mut row = climate_row_from_state(&state, ts, db_home_id, db_zone_id);
+            if options.store_ingest_lag {
+                row.ingest_lag_secs = Some(ingest_lag_secs(ts, Utc::now()));
+            }
+            let reading = ClimateReading::from(&row);
+            let duplicate = match options.dedup {
+                Some(keepalive) => {
+                    let last = last_climate_reading(
+                        conn,
+                        &mut tracking.last_climate,
+                        db_home_id,
+                        db_zone_id,
+                        options.tx_per_tick,
+                    )?;
+                    is_duplicate_reading(last, &reading, ts, keepalive)
+                }
+                None => false,
+            };
+            if duplicate {
+                debug!(
+                    "Realtime: zone {} of home {} unchanged since its last stored reading; skipping climate row",
+                    zone_id.0, home_id
+                );
+            } else {
+                match insert_climate_measurements(conn, std::slice::from_ref(&row), options.dry_run) {
+                    Ok(inserted) => {
+                        log_request_rows(request_id, inserted, &format!("zone {} climate", zone_id.0), home_id);
+                        tracking.last_climate.insert(db_zone_id, (ts, reading));
+                    }
+                    Err(e) => write_failed(
+                        options.tx_per_tick,
+                        format!(
+                            "Realtime: insert climate row failed for home {}, zone {}: {}",
+                            home_id, zone_id.0, e
+                        ),
+                    )?,
+                }
+                if let Some(batch) = influx_batch.as_deref_mut() {
+                    batch.push_climate(&row);
+                }
+            }
+
+            let mut events = Vec::new();
+            if let Some(check) = options.out_of_order_check
+                && let Some(latest) = check_reading_order(&mut tracking.latest_reading_times, db_zone_id, ts)
+            {
+                warn!(
+                    "Realtime: zone {} of home {} reported a reading at {} older than the latest seen ({})",
+                    zone_id.0, home_id, ts, latest
+                );
+                if check == OutOfOrderCheck::Event {
+                    events.push(out_of_order_event(db_home_id, db_zone_id, ts, latest, now_ts));
+                }
+            }
+            events.extend(track_overlay(
+                &mut tracking.overlays,
+                db_home_id,
+                db_zone_id,
+                state.overlay.as_ref(),
+                now_ts,
+            ));
+            events.extend(track_open_window(
+                &mut tracking.open_windows,
+                db_home_id,
+                db_zone_id,
+                state.open_window.as_ref(),
+                now_ts,
+            ));
+            if let Some(tolerance) = options.schedule_transition_tolerance {
+                let tolerance = chrono::Duration::from_std(tolerance).unwrap_or(chrono::Duration::MAX);
+                events.extend(track_schedule_transition(
+                    &mut tracking.schedules,
+                    db_home_id,
+                    db_zone_id,
+                    &state,
+                    tolerance,
+                    now_ts,
+                ));
+            }
+            if options.track_sensor_precision {
+                events.extend(track_sensor_precision(
+                    &mut tracking.sensor_precisions,
+                    db_home_id,
+                    db_zone_id,
+                    row.inside_temp_precision_c,
+                    now_ts,
+                ));
+            }
+            if options.track_geolocation_override {
+                events.extend(track_geolocation_override(
+                    &mut tracking.geolocation_overrides,
+                    db_home_id,
+                    db_zone_id,
+                    &state,
+                    now_ts,
+                ));
+            }
+            for event in &events {
+                if let Err(e) = insert_capped_event(
+                    conn,
+                    &mut tracking.event_throttle,
+                    options.events_max_per_zone_per_day,
+                    event,
+                    options.dry_run,
+                ) {
+                    write_failed(
+                        options.tx_per_tick,
+                        format!(
+                            "Realtime: insert {} event failed for home {}, zone {}: {}",
+                            event.event_type, home_id, zone_id.0, e
+                        ),
+                    )?;
+                }
+            }
+        }
+    }
+
+    // Device connectivity and battery; a failed device listing only costs this tick's lifecycle events
+    if options.collect_devices
+        && let Err(e) = collect_device_health(conn, client, db_home_id, home_id, &mut tracking.device_health, options)
+    {
+        write_failed(
+            options.tx_per_tick,
+            format!("Realtime: device health for home {} failed: {}", home_id, e),
+        )?;
+    }
+
+    // Mobile device presence; like device health, a failure only costs this tick's rows
+    if options.collect_presence
+        && let Err(e) = presence::collect(conn, client, db_home_id, home_id, Utc::now(), options.dry_run)
+    {
+        write_failed(
+            options.tx_per_tick,
+            format!("Realtime: presence for home {} failed: {}", home_id, e),
+        )?;
+    }
+
+    // Air comfort; likewise only costs this tick's rows
+    if options.collect_air_comfort
+        && let Err(e) = air_comfort::collect(
+            conn,
+            client,
+            db_home_id,
+            home_id,
+            zone_id_map,
+            Utc::now(),
+            options.dry_run,
+        )
+    {
+        write_failed(
+            options.tx_per_tick,
+            format!("Realtime: air comfort for home {} failed: {}", home_id, e),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn collect_device_health(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_home_id: i64,
+    home_id: i64,
+    health: &mut BTreeMap<i64, DeviceHealth>,
+    options: &RealtimeOptions,
+) -> Result<(), String> {
+    use schema::devices::dsl as D;
+
+    let battery_debounce = chrono::Duration::from_std(options.battery_event_debounce).unwrap_or(chrono::Duration::MAX);
+
+    let devices = client
+        .get_devices(HomeId(home_id))
+        .map_err(|e| format!("get_devices failed: {}", e))?;
+    let device_ids: BTreeMap<String, i64> = D::devices
+        .filter(D::home_id.eq(db_home_id))
+        .select((D::tado_device_id, D::id))
+        .load::<(String, i64)>(conn)
+        .map_err(|e| format!("fetch device map failed: {}", e))?
+        .into_iter()
+        .collect();
+
+    let now = Utc::now();
+    for device in &devices {
+        // Devices added since the last reference sync are picked up once it reruns
+        let Some(db_device_id) = device.serial_no.as_ref().and_then(|s| device_ids.get(&s.0).copied()) else {
+            continue;
+        };
+        if let std::collections::btree_map::Entry::Vacant(entry) = health.entry(db_device_id) {
+            entry.insert(load_device_health(conn, db_device_id)?);
+        }
+        for event in track_device_health(health, db_home_id, db_device_id, device, battery_debounce, now) {
+            if let Err(e) = insert_events(conn, std::slice::from_ref(&event), options.dry_run) {
+                write_failed(
+                    options.tx_per_tick,
+                    format!(
+                        "Realtime: insert {} event failed for home {}, device {}: {}",
+                        event.event_type, home_id, db_device_id, e
+                    ),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Last known device state as recorded by the most recent lifecycle events, so a restart continues where the
+/// previous process stopped instead of re-seeding from the first poll.
+fn load_device_health(conn: &mut PgConnection, db_device_id: i64) -> Result<DeviceHealth, String> {
+    use schema::events::dsl as E;
+
+    let latest: Vec<(String, DateTime<Utc>)> = E::events
+        .filter(E::device_id.eq(db_device_id))
+        .filter(E::event_type.eq_any(DEVICE_HEALTH_EVENT_TYPES))
+        .order((E::event_type, E::time.desc()))
+        .distinct_on(E::event_type)
+        .select((E::event_type, E::time))
+        .load(conn)
+        .map_err(|e| format!("fetch latest device events failed: {}", e))?;
+    Ok(device_health_from_latest_events(&latest))
+}
+
+/// Seconds between a reading's own timestamp and the moment it is written; growing values mean polling lags behind.
+fn ingest_lag_secs(reading_time: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    (now - reading_time).num_milliseconds() as f64 / 1000.0
+}
+
+/// Computes the sleep between ticks. A tick that overruns the interval is normally followed immediately
+/// by the next one; after `max_catchup_ticks` such ticks in a row (e.g. right after a VM resume) a
+/// minimum recovery sleep is inserted so the loop does not fire a burst of API calls.
+#[derive(Debug)]
+struct TickPacer {
+    interval: Duration,
+    max_catchup_ticks: u32,
+    overruns: u32,
+}
+
+impl TickPacer {
+    fn new(interval: Duration, max_catchup_ticks: u32) -> Self {
+        TickPacer {
+            interval,
+            max_catchup_ticks,
+            overruns: 0,
+        }
+    }
+
+    fn throttling(&self) -> bool {
+        self.overruns > self.max_catchup_ticks
+    }
+
+    fn sleep_after(&mut self, elapsed: Duration) -> Duration {
+        if elapsed < self.interval {
+            self.overruns = 0;
+            return self.interval - elapsed;
+        }
+        self.overruns = self.overruns.saturating_add(1);
+        if self.throttling() {
+            (self.interval / 2).max(Duration::from_secs(1))
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Counts one home's failed collections in a row; its next success resets the streak. A transient API outage
+/// is sat out, while one that outlasts `max` failures ends the loop instead of logging forever.
+#[derive(Debug)]
+struct FailureStreak {
+    max: u32,
+    count: u32,
+}
+
+impl FailureStreak {
+    fn new(max: u32) -> Self {
+        FailureStreak { max, count: 0 }
+    }
+
+    fn record_success(&mut self) {
+        self.count = 0;
+    }
+
+    /// Returns whether the streak has reached the limit.
+    fn record_failure(&mut self) -> bool {
+        self.count = self.count.saturating_add(1);
+        self.count >= self.max
+    }
+}
+
+/// Wait before the first reconnect retry; doubles per retry up to `DB_RECONNECT_MAX_DELAY`.
+const DB_RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const DB_RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Pings `conn` and, when the database has gone away (restart, dropped TCP connection), replaces it with a
+/// fresh one. Called at the start of every tick, so a connection lost mid-tick costs that tick only.
+fn ensure_connected(conn: &mut PgConnection, database_url: &str, max_retries: u32, name: &str) -> Result<(), String> {
+    let Err(e) = conn.batch_execute("select 1") else {
+        return Ok(());
+    };
+    warn!("Realtime: {} database connection lost ({}); reconnecting", name, e);
+    *conn = reconnect_with_backoff(
+        max_retries,
+        || PgConnection::establish(database_url).map_err(|e| e.to_string()),
+        shutdown::sleep,
+    )
+    .map_err(|e| format!("Realtime: reconnecting the {} database connection failed: {}", name, e))?;
+    info!("Realtime: {} database connection re-established", name);
+    Ok(())
+}
+
+/// Calls `connect` up to `max_retries` times, sleeping an exponentially growing delay in between. `sleep`
+/// returns whether it was cut short by shutdown, which abandons the remaining attempts.
+fn reconnect_with_backoff<C>(
+    max_retries: u32,
+    mut connect: impl FnMut() -> Result<C, String>,
+    mut sleep: impl FnMut(Duration) -> bool,
+) -> Result<C, String> {
+    let mut attempt: u32 = 0;
+    loop {
+        attempt += 1;
+        let e = match connect() {
+            Ok(conn) => return Ok(conn),
+            Err(e) => e,
+        };
+        if attempt >= max_retries {
+            return Err(format!("giving up after {} attempt(s): {}", attempt, e));
+        }
+        let factor = 2u32.checked_pow(attempt - 1).unwrap_or(u32::MAX);
+        let delay = DB_RECONNECT_BASE_DELAY
+            .checked_mul(factor)
+            .map_or(DB_RECONNECT_MAX_DELAY, |d| d.min(DB_RECONNECT_MAX_DELAY));
+        warn!(
+            "Realtime: database reconnect attempt {}/{} failed ({}); retrying in {}s",
+            attempt,
+            max_retries,
+            e,
+            delay.as_secs()
+        );
+        if sleep(delay) {
+            return Err("shutdown requested".to_string());
+        }
+    }
+}
+
+/// Zone state remembered between ticks to detect transitions, keyed by db_zone_id.
+#[derive(Debug, Clone, Default)]
+struct ZoneTracking {
+    overlays: BTreeMap<i64, OverlayObservation>,
+    geolocation_overrides: BTreeMap<i64, bool>,
+    /// Last recorded open window per zone; `None` once it closed.
+    open_windows: BTreeMap<i64, Option<tado::ZoneOpenWindow>>,
+    /// Keyed by db_device_id rather than zone: a device can serve several zones.
+    device_health: BTreeMap<i64, DeviceHealth>,
+    /// Latest reading time seen per zone, for the out-of-order check.
+    latest_reading_times: BTreeMap<i64, DateTime<Utc>>,
+    schedules: BTreeMap<i64, ScheduleObservation>,
+    /// Last reported inside temperature precision (°C) per zone.
+    sensor_precisions: BTreeMap<i64, f64>,
+    /// Time and values of the last stored realtime climate row per zone, for `REALTIME_DEDUP`; seeded from the
+    /// database on a miss.
+    last_climate: BTreeMap<i64, (DateTime<Utc>, ClimateReading)>,
+    /// The home's zone event counts for `EVENTS_MAX_PER_ZONE_PER_DAY`.
+    event_throttle: EventThrottle,
+}
+
+/// A zone's setting and Home/Away mode on the previous tick, and the block starts predicted for it since.
+#[derive(Debug, Clone, Default)]
+struct ScheduleObservation {
+    setting: Option<tado::ZoneSetting>,
+    tado_mode: Option<tado::HomePresence>,
+    /// `next_time_block.start` values not yet matched by a setting change. Several are kept because a block
+    /// boundary may pass without any change (two blocks with the same setting) while the API already predicts
+    /// the next one.
+    predicted_starts: BTreeSet<DateTime<Utc>>,
+}
+
+/// Predicted block starts older than this are dropped; a change that late is no longer attributable to them.
+const MAX_PREDICTION_AGE: chrono::Duration = chrono::Duration::days(1);
+
+const DEVICE_HEALTH_EVENT_TYPES: [&str; 4] = [
+    event_types::DEVICE_CONNECTED,
+    event_types::DEVICE_DISCONNECTED,
+    event_types::DEVICE_BATTERY_LOW,
+    event_types::DEVICE_BATTERY_NORMAL,
+];
+
+/// Last known connectivity and battery of a device; `None` until first observed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct DeviceHealth {
+    connected: Option<bool>,
+    battery_low: Option<bool>,
+    /// Battery state that differs from `battery_low` but has not yet outlasted the debounce, and since when.
+    battery_pending: Option<(bool, DateTime<Utc>)>,
+}
+
+/// Fold the latest event time per lifecycle type into the state it leaves the device in.
+fn device_health_from_latest_events(latest: &[(String, DateTime<Utc>)]) -> DeviceHealth {
+    let time_of = |event_type: &str| latest.iter().find(|(t, _)| t == event_type).map(|(_, time)| *time);
+    // The later of each pair wins; one without the other is still decisive
+    let later_is = |yes: &str, no: &str| match (time_of(yes), time_of(no)) {
+        (Some(a), Some(b)) => Some(a > b),
+        (Some(_), None) => Some(true),
+        (None, Some(_)) => Some(false),
+        (None, None) => None,
+    };
+    DeviceHealth {
+        connected: later_is(event_types::DEVICE_CONNECTED, event_types::DEVICE_DISCONNECTED),
+        battery_low: later_is(event_types::DEVICE_BATTERY_LOW, event_types::DEVICE_BATTERY_NORMAL),
+        battery_pending: None,
+    }
+}
+
+/// Record the device's connectivity and battery and return an event for each change.
+///
+/// Fields the device does not report (battery on wired devices) are left alone, and a field without a known
+/// previous value only seeds the cache, as with the zone trackers. A battery change is only reported once the
+/// new state has been seen on consecutive polls for `battery_debounce`; flipping back in between cancels it.
+fn track_device_health(
+    health: &mut BTreeMap<i64, DeviceHealth>,
+    db_home_id: i64,
+    db_device_id: i64,
+    device: &tado::Device,
+    battery_debounce: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Vec<NewEvent> {
+    let entry = health.entry(db_device_id).or_default();
+    let tado_device_id = device.serial_no.as_ref().map(|s| s.0.clone());
+    let event = |time: DateTime<Utc>, event_type: &str, payload: serde_json::Value| NewEvent {
+        time,
+        home_id: db_home_id,
+        zone_id: None,
+        device_id: Some(db_device_id),
+        source: Some(event_source::REALTIME.to_string()),
+        event_type: event_type.to_string(),
+        payload: Some(payload),
+    };
+    let mut events = Vec::new();
+
+    let connection = device.connection_state.as_ref();
+    if let Some(connected) = connection.and_then(|c| c.value) {
+        if entry.connected.is_some_and(|prev| prev != connected) {
+            let connection_time = connection.and_then(|c| c.timestamp);
+            let event_type = if connected {
+                event_types::DEVICE_CONNECTED
+            } else {
+                event_types::DEVICE_DISCONNECTED
+            };
+            events.push(event(
+                connection_time.unwrap_or(now),
+                event_type,
+                json!({ "tado_device_id": tado_device_id, "connection_timestamp": connection_time }),
+            ));
+        }
+        entry.connected = Some(connected);
+    }
+
+    if let Some(battery) = device.battery_state {
+        let low = battery == tado::BatteryState::Low;
+        match entry.battery_low {
+            None => entry.battery_low = Some(low),
+            Some(prev) if prev == low => entry.battery_pending = None,
+            Some(_) => {
+                let since = match entry.battery_pending {
+                    Some((pending, since)) if pending == low => since,
+                    _ => now,
+                };
+                if now - since >= battery_debounce {
+                    let event_type = if low {
+                        event_types::DEVICE_BATTERY_LOW
+                    } else {
+                        event_types::DEVICE_BATTERY_NORMAL
+                    };
+                    events.push(event(
+                        now,
+                        event_type,
+                        json!({
+                            "tado_device_id": tado_device_id,
+                            "battery_state": serde_enum_name(&battery),
+                            "observed_since": since,
+                        }),
+                    ));
+                    entry.battery_low = Some(low);
+                    entry.battery_pending = None;
+                } else {
+                    entry.battery_pending = Some((low, since));
+                }
+            }
+        }
+    }
+
+    events
+}
+
+/// Overlay last observed for a zone, used to derive overlay lifecycle events between ticks.
+#[derive(Debug, Clone, Default)]
+struct OverlayObservation {
+    overlay: Option<tado::ZoneOverlay>,
+    /// When this process first saw the current overlay (not when Tado applied it).
+    observed_since: Option<DateTime<Utc>>,
+}
+
+/// Record the zone's current overlay and return the lifecycle event for the transition, if any.
+///
+/// The first observation of a zone only seeds the cache; without a prior state a restart would otherwise
+/// report every active overlay as freshly set.
+fn track_overlay(
+    overlays: &mut BTreeMap<i64, OverlayObservation>,
+    db_home_id: i64,
+    db_zone_id: i64,
+    current: Option<&tado::ZoneOverlay>,
+    now: DateTime<Utc>,
+) -> Option<NewEvent> {
+    let Some(previous) = overlays.get(&db_zone_id).cloned() else {
+        overlays.insert(
+            db_zone_id,
+            OverlayObservation {
+                overlay: current.cloned(),
+                observed_since: current.map(|_| now),
+            },
+        );
+        return None;
+    };
+
+    let (event_type, payload) = match (previous.overlay.as_ref(), current) {
+        (None, Some(cur)) => (event_types::OVERLAY_SET, overlay_payload(cur)),
+        (Some(prev), None) => {
+            let mut payload = overlay_payload(prev);
+            payload["observed_since"] = json!(previous.observed_since);
+            payload["observed_duration_secs"] = json!(previous.observed_since.map(|since| (now - since).num_seconds()));
+            (event_types::OVERLAY_CLEARED, payload)
+        }
+        (Some(prev), Some(cur)) if !same_overlay(prev, cur) => {
+            let mut payload = overlay_payload(cur);
+            payload["previous"] = overlay_payload(prev);
+            (event_types::OVERLAY_UPDATED, payload)
+        }
+        _ => return None,
+    };
+
+    let observed_since = match current {
+        Some(_) if event_type == event_types::OVERLAY_SET => Some(now),
+        Some(_) => previous.observed_since.or(Some(now)),
+        None => None,
+    };
+    overlays.insert(
+        db_zone_id,
+        OverlayObservation {
+            overlay: current.cloned(),
+            observed_since,
+        },
+    );
+
+    Some(NewEvent {
+        time: now,
+        home_id: db_home_id,
+        zone_id: Some(db_zone_id),
+        device_id: None,
+        source: Some(event_source::REALTIME.to_string()),
+        event_type: event_type.to_string(),
+        payload: Some(payload),
+    })
+}
+
+/// Record the zone's open window and return `OPEN_WINDOW_DETECTED`/`OPEN_WINDOW_CLOSED` on transitions.
+///
+/// A window counts as newly detected when its `detected_time` differs from the last recorded one, so the
+/// countdown fields changing between polls never repeat the event. As with overlays, the first observation
+/// of a zone only seeds the cache.
+fn track_open_window(
+    open_windows: &mut BTreeMap<i64, Option<tado::ZoneOpenWindow>>,
+    db_home_id: i64,
+    db_zone_id: i64,
+    current: Option<&tado::ZoneOpenWindow>,
+    now: DateTime<Utc>,
+) -> Option<NewEvent> {
+    let previous = open_windows.insert(db_zone_id, current.cloned())?;
+
+    let (event_type, window) = match (previous.as_ref(), current) {
+        (None, Some(cur)) => (event_types::OPEN_WINDOW_DETECTED, cur),
+        (Some(prev), Some(cur)) if prev.detected_time != cur.detected_time => (event_types::OPEN_WINDOW_DETECTED, cur),
+        (Some(prev), None) => (event_types::OPEN_WINDOW_CLOSED, prev),
+        _ => return None,
+    };
+
+    Some(NewEvent {
+        time: now,
+        home_id: db_home_id,
+        zone_id: Some(db_zone_id),
+        device_id: None,
+        source: Some(event_source::REALTIME.to_string()),
+        event_type: event_type.to_string(),
+        payload: Some(json!({
+            "detected_time": window.detected_time,
+            "expiry": window.expiry,
+            "duration_in_seconds": window.duration_in_seconds,
+            "remaining_time_in_seconds": window.remaining_time_in_seconds,
+        })),
+    })
+}
+
+/// Compare a schedule-driven setting change with the block start predicted closest to it and return a
+/// `SCHEDULE_TRANSITION_DELAYED` event when the two are more than `tolerance` apart (`delay_secs` is negative
+/// for an early change). Changes under an overlay or alongside a Home/Away switch are not the schedule's and
+/// are never compared; the first observation of a zone only seeds the tracking.
+fn track_schedule_transition(
+    schedules: &mut BTreeMap<i64, ScheduleObservation>,
+    db_home_id: i64,
+    db_zone_id: i64,
+    state: &tado::ZoneState,
+    tolerance: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Option<NewEvent> {
+    let observation = schedules.entry(db_zone_id).or_default();
+    let current = state.setting.clone()?;
+    let previous = observation.setting.replace(current.clone());
+    let previous_mode = std::mem::replace(&mut observation.tado_mode, state.tado_mode);
+    observation
+        .predicted_starts
+        .retain(|start| now - *start <= MAX_PREDICTION_AGE);
+
+    let mut event = None;
+    let schedule_driven = state.overlay.is_none() && previous_mode == state.tado_mode;
+    if previous.is_some_and(|previous| previous != current) && schedule_driven {
+        let predicted = observation
+            .predicted_starts
+            .iter()
+            .copied()
+            .min_by_key(|start| (now - *start).abs());
+        if let Some(predicted) = predicted {
+            // The change consumes its boundary and any that passed before it
+            observation
+                .predicted_starts
+                .retain(|start| *start > now && *start != predicted);
+            let delay = now - predicted;
+            if delay.abs() > tolerance {
+                event = Some(NewEvent {
+                    time: now,
+                    home_id: db_home_id,
+                    zone_id: Some(db_zone_id),
+                    device_id: None,
+                    source: Some(event_source::REALTIME.to_string()),
+                    event_type: event_types::SCHEDULE_TRANSITION_DELAYED.to_string(),
+                    payload: Some(json!({
+                        "predicted_start": predicted,
+                        "observed_at": now,
+                        "delay_secs": delay.num_seconds(),
+                    })),
+                });
+            }
+        }
+    }
+
+    if let Some(start) = state.next_time_block.as_ref().and_then(|b| b.start) {
+        observation.predicted_starts.insert(start);
+    }
+    event
+}
+
+/// Remember the zone's latest reading time and return it when `reading_time` is older. Only the cached latest
+/// time is consulted, so the check never queries the database; a zone's first reading after startup always passes.
+fn check_reading_order(
+    latest_times: &mut BTreeMap<i64, DateTime<Utc>>,
+    db_zone_id: i64,
+    reading_time: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let latest = latest_times.entry(db_zone_id).or_insert(reading_time);
+    if reading_time < *latest {
+        return Some(*latest);
+    }
+    *latest = reading_time;
+    None
+}
+
+fn out_of_order_event(
+    db_home_id: i64,
+    db_zone_id: i64,
+    reading_time: DateTime<Utc>,
+    latest: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> NewEvent {
+    NewEvent {
+        time: now,
+        home_id: db_home_id,
+        zone_id: Some(db_zone_id),
+        device_id: None,
+        source: Some(event_source::REALTIME.to_string()),
+        event_type: event_types::OUT_OF_ORDER_MEASUREMENT.to_string(),
+        payload: Some(json!({
+            "reading_time": reading_time,
+            "latest_time": latest,
+        })),
+    }
+}
+
+/// Maps the home weather onto its realtime row. `time` is the API's reading time, or `now` when it sent none;
+/// `reported_at` keeps only the former so fallbacks stay distinguishable.
+fn weather_row_from_report(weather: &tado::Weather, db_home_id: i64, now: DateTime<Utc>) -> NewWeatherMeasurement {
+    let reported_at = weather
+        .outside_temperature
+        .as_ref()
+        .and_then(|t| t.timestamp)
+        .or_else(|| weather.solar_intensity.as_ref().and_then(|s| s.timestamp));
+    let weather_state = weather
+        .weather_state
+        .as_ref()
+        .and_then(|ws| ws.value.as_ref())
+        .and_then(serde_enum_name);
+
+    let mut row = NewWeatherMeasurement::new(reported_at.unwrap_or(now), db_home_id, event_source::REALTIME);
+    row.outside_temp_c = weather.outside_temperature.as_ref().and_then(data_point_celsius);
+    row.solar_intensity_pct = weather.solar_intensity.as_ref().and_then(|s| s.percentage);
+    row.weather_state = weather_state;
+    row.reported_at = reported_at;
+    row
+}
+
+/// Maps a zone state onto its realtime climate row; ingest lag is left to the caller since it depends on `now`.
+fn climate_row_from_state(
+    state: &tado::ZoneState,
+    ts: DateTime<Utc>,
+    db_home_id: i64,
+    db_zone_id: i64,
+) -> NewClimateMeasurement {
+    let inside_temp_c = state
+        .sensor_data_points
+        .as_ref()
+        .and_then(|s| s.inside_temperature.as_ref().and_then(data_point_celsius));
+    let humidity_pct = state
+        .sensor_data_points
+        .as_ref()
+        .and_then(|s| s.humidity.as_ref().and_then(|h| h.percentage));
+    let setpoint_temp_c = state
+        .setting
+        .as_ref()
+        .and_then(|set| set.temperature.as_ref().and_then(temperature_celsius));
+    let heating_power_pct = state
+        .activity_data_points
+        .as_ref()
+        .and_then(|a| a.heating_power.as_ref().and_then(|p| p.percentage));
+    let ac_power_on = state.activity_data_points.as_ref().and_then(|a| {
+        a.ac_power
             .as_ref()
-            .and_then(|a| a.heating_power.as_ref().and_then(|p| p.percentage));
-        let ac_power_on = state.activity_data_points.as_ref().and_then(|a| {
-            a.ac_power
-                .as_ref()
-                .and_then(|p| p.value.map(|v| matches!(v, tado::Power::On)))
-        });
-        let ac_mode = state
-            .setting
+            .and_then(|p| p.value.map(|v| matches!(v, tado::Power::On)))
+    });
+    let ac_mode = state
+        .setting
+        .as_ref()
+        .and_then(|set| set.mode.as_ref().and_then(serde_enum_name));
+
+    let mut row = NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::REALTIME);
+    row.inside_temp_c = inside_temp_c;
+    row.inside_temp_precision_c = inside_temp_precision_c(state);
+    row.humidity_pct = humidity_pct;
+    row.setpoint_temp_c = setpoint_temp_c;
+    row.heating_power_pct = heating_power_pct;
+    row.ac_power_on = ac_power_on;
+    row.ac_mode = ac_mode;
+    row.window_open = state.open_window.as_ref().map(|_| true);
+    row.tado_mode = state.tado_mode.as_ref().and_then(serde_enum_name);
+    row
+}
+
+/// A climate row's values apart from its time and ingest lag, compared by `REALTIME_DEDUP`.
+#[derive(Debug, Clone, PartialEq)]
+struct ClimateReading {
+    home_id: i64,
+    zone_id: Option<i64>,
+    device_id: Option<i64>,
+    source: String,
+    inside_temp_c: Option<f64>,
+    humidity_pct: Option<f64>,
+    setpoint_temp_c: Option<f64>,
+    heating_power_pct: Option<f64>,
+    ac_power_on: Option<bool>,
+    ac_mode: Option<String>,
+    window_open: Option<bool>,
+    battery_low: Option<bool>,
+    connection_up: Option<bool>,
+    inside_temp_precision_c: Option<f64>,
+    tado_mode: Option<String>,
+    control_mode: Option<String>,
+}
+
+impl From<&NewClimateMeasurement> for ClimateReading {
+    fn from(row: &NewClimateMeasurement) -> Self {
+        Self {
+            home_id: row.home_id,
+            zone_id: row.zone_id,
+            device_id: row.device_id,
+            source: row.source.clone(),
+            inside_temp_c: row.inside_temp_c,
+            humidity_pct: row.humidity_pct,
+            setpoint_temp_c: row.setpoint_temp_c,
+            heating_power_pct: row.heating_power_pct,
+            ac_power_on: row.ac_power_on,
+            ac_mode: row.ac_mode.clone(),
+            window_open: row.window_open,
+            battery_low: row.battery_low,
+            connection_up: row.connection_up,
+            inside_temp_precision_c: row.inside_temp_precision_c,
+            tado_mode: row.tado_mode.clone(),
+            control_mode: row.control_mode.clone(),
+        }
+    }
+}
+
+impl From<&ClimateMeasurement> for ClimateReading {
+    fn from(row: &ClimateMeasurement) -> Self {
+        Self {
+            home_id: row.home_id,
+            zone_id: row.zone_id,
+            device_id: row.device_id,
+            source: row.source.clone(),
+            inside_temp_c: row.inside_temp_c,
+            humidity_pct: row.humidity_pct,
+            setpoint_temp_c: row.setpoint_temp_c,
+            heating_power_pct: row.heating_power_pct,
+            ac_power_on: row.ac_power_on,
+            ac_mode: row.ac_mode.clone(),
+            window_open: row.window_open,
+            battery_low: row.battery_low,
+            connection_up: row.connection_up,
+            inside_temp_precision_c: row.inside_temp_precision_c,
+            tado_mode: row.tado_mode.clone(),
+            control_mode: row.control_mode.clone(),
+        }
+    }
+}
+
+/// The zone's last stored realtime reading, read from the database only when the cache has none yet (first
+/// tick after startup). A failed read counts as no reading, so the row is written rather than lost, except
+/// under `REALTIME_TX_PER_TICK` where it has aborted the transaction and fails the tick.
+fn last_climate_reading<'a>(
+    conn: &mut PgConnection,
+    last_climate: &'a mut BTreeMap<i64, (DateTime<Utc>, ClimateReading)>,
+    db_home_id: i64,
+    db_zone_id: i64,
+    tx_per_tick: bool,
+) -> Result<Option<&'a (DateTime<Utc>, ClimateReading)>, String> {
+    match last_climate.entry(db_zone_id) {
+        std::collections::btree_map::Entry::Occupied(cached) => Ok(Some(cached.into_mut())),
+        std::collections::btree_map::Entry::Vacant(slot) => {
+            match query::latest_climate_for_zone(conn, db_home_id, db_zone_id, Some(event_source::REALTIME)) {
+                Ok(Some(stored)) => return Ok(Some(slot.insert((stored.time, ClimateReading::from(&stored))))),
+                Ok(None) => {}
+                Err(e) => write_failed(tx_per_tick, format!("Realtime: dedup lookup failed: {}", e))?,
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Whether `reading`, taken at `time`, repeats `last` closely enough to skip. A repeat is still stored once the
+/// last row is `keepalive` old, so unchanged zones never leave a hole that gap detection would refill.
+fn is_duplicate_reading(
+    last: Option<&(DateTime<Utc>, ClimateReading)>,
+    reading: &ClimateReading,
+    time: DateTime<Utc>,
+    keepalive: chrono::Duration,
+) -> bool {
+    last.is_some_and(|(at, last)| last == reading && time - *at < keepalive)
+}
+
+/// Step in which Tado reports the zone's inside temperature, in Celsius. Day reports do not carry it.
+fn inside_temp_precision_c(state: &tado::ZoneState) -> Option<f64> {
+    state
+        .sensor_data_points
+        .as_ref()
+        .and_then(|s| s.inside_temperature.as_ref())
+        .and_then(|t| t.precision.as_ref())
+        .and_then(|p| p.celsius)
+}
+
+/// Record the zone's reported temperature precision and return `SENSOR_PRECISION_CHANGED` when it differs from
+/// the cached one. The first observation only seeds the cache; readings without a precision leave it alone.
+fn track_sensor_precision(
+    sensor_precisions: &mut BTreeMap<i64, f64>,
+    db_home_id: i64,
+    db_zone_id: i64,
+    precision_c: Option<f64>,
+    now: DateTime<Utc>,
+) -> Option<NewEvent> {
+    let precision_c = precision_c?;
+    let previous = sensor_precisions.insert(db_zone_id, precision_c)?;
+    if previous == precision_c {
+        return None;
+    }
+    Some(NewEvent {
+        time: now,
+        home_id: db_home_id,
+        zone_id: Some(db_zone_id),
+        device_id: None,
+        source: Some(event_source::REALTIME.to_string()),
+        event_type: event_types::SENSOR_PRECISION_CHANGED.to_string(),
+        payload: Some(json!({ "previous_precision_c": previous, "precision_c": precision_c })),
+    })
+}
+
+/// Record whether a manual presence (geolocation) override affects the zone and return
+/// `GEO_OVERRIDE_ON`/`GEO_OVERRIDE_OFF` when it flips. As with overlays, the first observation only seeds the cache.
+fn track_geolocation_override(
+    geolocation_overrides: &mut BTreeMap<i64, bool>,
+    db_home_id: i64,
+    db_zone_id: i64,
+    state: &tado::ZoneState,
+    now: DateTime<Utc>,
+) -> Option<NewEvent> {
+    let active = state.geolocation_override.unwrap_or(false);
+    let previous = geolocation_overrides.insert(db_zone_id, active)?;
+    if previous == active {
+        return None;
+    }
+
+    let event_type = if active {
+        event_types::GEO_OVERRIDE_ON
+    } else {
+        event_types::GEO_OVERRIDE_OFF
+    };
+    Some(NewEvent {
+        time: now,
+        home_id: db_home_id,
+        zone_id: Some(db_zone_id),
+        device_id: None,
+        source: Some(event_source::REALTIME.to_string()),
+        event_type: event_type.to_string(),
+        payload: Some(json!({
+            "disable_time": state.geolocation_override_disable_time,
+            "tado_mode": state.tado_mode.as_ref().and_then(serde_enum_name),
+        })),
+    })
+}
+
+/// Compare two overlays, ignoring the countdown fields that change on every poll.
+fn same_overlay(a: &tado::ZoneOverlay, b: &tado::ZoneOverlay) -> bool {
+    let termination_key = |o: &tado::ZoneOverlay| {
+        o.termination
             .as_ref()
-            .and_then(|set| set.mode.as_ref().and_then(serde_enum_name));
-
-        let mut row = NewClimateMeasurement::new(ts, db_home_id, Some(db_zone_id), None, event_source::REALTIME);
-        row.inside_temp_c = inside_temp_c;
-        row.humidity_pct = humidity_pct;
-        row.setpoint_temp_c = setpoint_temp_c;
-        row.heating_power_pct = heating_power_pct;
-        row.ac_power_on = ac_power_on;
-        row.ac_mode = ac_mode;
-        row.window_open = state.open_window.as_ref().map(|_| true);
-        if let Err(e) = diesel::insert_into(C::climate_measurements)
-            .values(&row)
-            .on_conflict((C::time, C::home_id, C::source, C::zone_id, C::device_id))
-            .do_nothing()
-            .execute(conn)
-        {
-            warn!(
-                "Realtime: insert climate row failed for home {}, zone {}: {}",
-                home_id, zone_id.0, e
-            );
+            .map(|t| (t.r#type, t.type_skill_based_app, t.duration_in_seconds, t.expiry))
+    };
+    a.r#type == b.r#type && a.setting == b.setting && termination_key(a) == termination_key(b)
+}
+
+fn overlay_payload(overlay: &tado::ZoneOverlay) -> serde_json::Value {
+    json!({
+        "overlay_type": overlay.r#type.as_ref().map(|t| t.0.clone()),
+        "setting": overlay.setting,
+        "termination": overlay.termination.as_ref().map(termination_payload),
+    })
+}
+
+/// Termination details tell apart timer, manual, and next-time-block overlays, and how long they were meant to last.
+fn termination_payload(termination: &tado::ZoneOverlayTermination) -> serde_json::Value {
+    json!({
+        "type": termination.r#type.as_ref().and_then(serde_enum_name),
+        "type_skill_based_app": termination.type_skill_based_app.as_ref().and_then(serde_enum_name),
+        "duration_in_seconds": termination.duration_in_seconds,
+        "remaining_time_in_seconds": termination.remaining_time_in_seconds,
+        "expiry": termination.expiry,
+        "projected_expiry": termination.projected_expiry,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const TIMER_OVERLAY_JSON: &str = r#"{
+        "type": "MANUAL",
+        "setting": {"type": "HEATING", "power": "ON", "temperature": {"celsius": 22.0, "fahrenheit": 71.6}},
+        "termination": {
+            "type": "TIMER",
+            "typeSkillBasedApp": "TIMER",
+            "durationInSeconds": 3600,
+            "remainingTimeInSeconds": 1800,
+            "expiry": "2024-03-01T12:00:00Z",
+            "projectedExpiry": "2024-03-01T12:00:00Z"
         }
+    }"#;
+
+    #[test]
+    fn collected_categories_follow_the_toggles() {
+        let options = RealtimeOptions {
+            collect_weather: true,
+            collect_zones: true,
+            collect_devices: true,
+            ..RealtimeOptions::default()
+        };
+        assert_eq!(collected_categories(&options), ["weather", "zones", "devices"]);
+
+        let climate_only = RealtimeOptions {
+            collect_zones: true,
+            collect_air_comfort: true,
+            ..RealtimeOptions::default()
+        };
+        assert_eq!(collected_categories(&climate_only), ["zones", "air comfort"]);
+        assert!(collected_categories(&RealtimeOptions::default()).is_empty());
     }
 
-    Ok(())
+    #[test]
+    fn ingest_lag_reflects_past_reading() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let reading = now - chrono::Duration::seconds(95);
+
+        let mut row = NewClimateMeasurement::new(reading, 1, Some(1), None, event_source::REALTIME);
+        row.ingest_lag_secs = Some(ingest_lag_secs(row.time, now));
+
+        assert_eq!(row.ingest_lag_secs, Some(95.0));
+    }
+
+    #[test]
+    fn inside_temp_precision_is_captured() {
+        let state: tado::ZoneState = serde_json::from_str(
+            r#"{"sensorDataPoints": {"insideTemperature": {
+                "celsius": 20.0, "fahrenheit": 68.0, "timestamp": "2024-03-01T12:00:00Z",
+                "type": "TEMPERATURE", "precision": {"celsius": 0.1, "fahrenheit": 0.1}
+            }}}"#,
+        )
+        .expect("parse zone state");
+
+        assert_eq!(inside_temp_precision_c(&state), Some(0.1));
+        assert_eq!(inside_temp_precision_c(&tado::ZoneState::default()), None);
+    }
+
+    #[test]
+    fn weather_row_keeps_reported_time_apart_from_fallback() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 3, 17).unwrap();
+        let weather: tado::Weather = serde_json::from_str(
+            r#"{"outsideTemperature": {"celsius": 7.5, "fahrenheit": 45.5,
+                "timestamp": "2024-03-01T11:45:00Z", "type": "TEMPERATURE"}}"#,
+        )
+        .expect("parse weather");
+        let reported = Utc.with_ymd_and_hms(2024, 3, 1, 11, 45, 0).unwrap();
+
+        let row = weather_row_from_report(&weather, 3, now);
+        assert_eq!(row.time, reported);
+        assert_eq!(row.reported_at, Some(reported));
+        assert_eq!(row.outside_temp_c, Some(7.5));
+
+        let row = weather_row_from_report(&tado::Weather::default(), 3, now);
+        assert_eq!(row.time, now);
+        assert_eq!(row.reported_at, None);
+    }
+
+    #[test]
+    fn failed_tick_keeps_none_of_its_state() {
+        let ts = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let state: tado::ZoneState = serde_json::from_str(
+            r#"{"sensorDataPoints": {"insideTemperature": {"celsius": 20.5, "fahrenheit": 68.9}}}"#,
+        )
+        .expect("parse zone state");
+        let row = climate_row_from_state(&state, ts, 3, 7);
+        let mut tracking = ZoneTracking::default();
+        tracking.latest_reading_times.insert(7, ts);
+        let mut batch = InfluxBatch::default();
+
+        // Zone 7 is written, then the tick fails on zone 8
+        let result = collect_atomically(&mut tracking, Some(&mut batch), |tracking, batch| {
+            check_reading_order(&mut tracking.latest_reading_times, 7, ts + chrono::Duration::minutes(1));
+            tracking.open_windows.insert(7, None);
+            batch.expect("influx enabled").push_climate(&row);
+            Err("Realtime: get_zone_state(1, 8) failed".to_string())
+        });
+        assert!(result.is_err());
+        assert_eq!(tracking.latest_reading_times, BTreeMap::from([(7, ts)]));
+        assert!(tracking.open_windows.is_empty());
+        assert!(batch.is_empty());
+
+        let result = collect_atomically(&mut tracking, Some(&mut batch), |tracking, batch| {
+            tracking.open_windows.insert(7, None);
+            batch.expect("influx enabled").push_climate(&row);
+            Ok(())
+        });
+        assert!(result.is_ok());
+        assert_eq!(tracking.open_windows.len(), 1);
+        assert!(!batch.is_empty());
+
+        assert!(write_failed(true, "insert failed".to_string()).is_err());
+        assert!(write_failed(false, "insert failed".to_string()).is_ok());
+    }
+
+    #[test]
+    fn climate_row_carries_tado_mode() {
+        let ts = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let state: tado::ZoneState = serde_json::from_str(
+            r#"{"tadoMode": "AWAY", "setting": {"type": "HEATING", "power": "ON",
+                "temperature": {"celsius": 16.0, "fahrenheit": 60.8}}}"#,
+        )
+        .expect("parse zone state");
+
+        let row = climate_row_from_state(&state, ts, 3, 7);
+        assert_eq!(row.tado_mode.as_deref(), Some("AWAY"));
+        assert_eq!(row.setpoint_temp_c, Some(16.0));
+        assert_eq!(row.source, event_source::REALTIME);
+        assert_eq!(
+            climate_row_from_state(&tado::ZoneState::default(), ts, 3, 7).tado_mode,
+            None
+        );
+    }
+
+    #[test]
+    fn dedup_reading_ignores_time_and_ingest_lag_only() {
+        let ts = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut first = NewClimateMeasurement::new(ts, 1, Some(2), None, event_source::REALTIME);
+        first.inside_temp_c = Some(20.5);
+        first.humidity_pct = Some(41.0);
+
+        let mut repeat = first.clone();
+        repeat.time = ts + chrono::Duration::minutes(1);
+        repeat.ingest_lag_secs = Some(3.0);
+        assert_eq!(ClimateReading::from(&first), ClimateReading::from(&repeat));
+
+        let mut changed = repeat.clone();
+        changed.humidity_pct = Some(42.0);
+        assert_ne!(ClimateReading::from(&first), ClimateReading::from(&changed));
+        let mut window = repeat.clone();
+        window.window_open = Some(true);
+        assert_ne!(ClimateReading::from(&first), ClimateReading::from(&window));
+
+        let keepalive = chrono::Duration::minutes(120);
+        let last = (ts, ClimateReading::from(&first));
+        let reading = ClimateReading::from(&repeat);
+        assert!(!is_duplicate_reading(None, &reading, repeat.time, keepalive));
+        assert!(is_duplicate_reading(Some(&last), &reading, repeat.time, keepalive));
+        assert!(is_duplicate_reading(
+            Some(&last),
+            &reading,
+            ts + chrono::Duration::minutes(119),
+            keepalive
+        ));
+        // Unchanged for the whole keepalive: stored again so no gap opens up
+        assert!(!is_duplicate_reading(Some(&last), &reading, ts + keepalive, keepalive));
+        assert!(!is_duplicate_reading(
+            Some(&last),
+            &ClimateReading::from(&changed),
+            repeat.time,
+            keepalive
+        ));
+    }
+
+    #[test]
+    fn out_of_order_reading_is_detected() {
+        let at = |minute| Utc.with_ymd_and_hms(2024, 3, 1, 12, minute, 0).unwrap();
+        let mut latest = BTreeMap::new();
+
+        assert_eq!(check_reading_order(&mut latest, 7, at(10)), None);
+        assert_eq!(check_reading_order(&mut latest, 7, at(15)), None);
+        // Re-polling the same reading is not out of order
+        assert_eq!(check_reading_order(&mut latest, 7, at(15)), None);
+        assert_eq!(check_reading_order(&mut latest, 7, at(5)), Some(at(15)));
+        // The stale reading does not lower the watermark, and zones are tracked independently
+        assert_eq!(check_reading_order(&mut latest, 7, at(12)), Some(at(15)));
+        assert_eq!(check_reading_order(&mut latest, 8, at(5)), None);
+
+        let event = out_of_order_event(3, 7, at(5), at(15), at(20));
+        assert_eq!(event.event_type, event_types::OUT_OF_ORDER_MEASUREMENT);
+        assert_eq!(event.zone_id, Some(7));
+        assert_eq!(event.payload.expect("payload")["latest_time"], json!(at(15)));
+    }
+
+    #[test]
+    fn overlay_payload_carries_timer_termination() {
+        let overlay: tado::ZoneOverlay = serde_json::from_str(TIMER_OVERLAY_JSON).expect("parse overlay");
+        let payload = overlay_payload(&overlay);
+
+        assert_eq!(payload["overlay_type"], "MANUAL");
+        assert_eq!(payload["termination"]["type"], "TIMER");
+        assert_eq!(payload["termination"]["type_skill_based_app"], "TIMER");
+        assert_eq!(payload["termination"]["duration_in_seconds"], 3600);
+        assert_eq!(payload["termination"]["expiry"], "2024-03-01T12:00:00Z");
+    }
+
+    #[test]
+    fn cleared_overlay_reports_observed_duration() {
+        let overlay: tado::ZoneOverlay = serde_json::from_str(TIMER_OVERLAY_JSON).expect("parse overlay");
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let mut overlays = BTreeMap::new();
+
+        assert!(track_overlay(&mut overlays, 1, 7, None, t0).is_none());
+        let set = track_overlay(&mut overlays, 1, 7, Some(&overlay), t0).expect("set event");
+        assert_eq!(set.event_type, event_types::OVERLAY_SET);
+
+        let cleared = track_overlay(&mut overlays, 1, 7, None, t0 + chrono::Duration::minutes(40)).expect("cleared");
+        assert_eq!(cleared.event_type, event_types::OVERLAY_CLEARED);
+        let payload = cleared.payload.expect("payload");
+        assert_eq!(payload["observed_duration_secs"], 2400);
+        assert_eq!(payload["termination"]["expiry"], "2024-03-01T12:00:00Z");
+    }
+
+    #[test]
+    fn overlay_sequence_emits_set_updated_cleared() {
+        let overlay: serde_json::Value = serde_json::from_str(TIMER_OVERLAY_JSON).expect("parse overlay");
+        let warmer = {
+            let mut o = overlay.clone();
+            o["setting"]["temperature"] = json!({"celsius": 23.0, "fahrenheit": 73.4});
+            o
+        };
+        let countdown = {
+            let mut o = overlay.clone();
+            o["termination"]["remainingTimeInSeconds"] = json!(1200);
+            o
+        };
+        let states: Vec<tado::ZoneState> = [json!({}), json!({"overlay": overlay}), json!({"overlay": countdown})]
+            .into_iter()
+            .chain([json!({"overlay": warmer}), json!({}), json!({})])
+            .map(|v| serde_json::from_value(v).expect("parse zone state"))
+            .collect();
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let mut overlays = BTreeMap::new();
+
+        let emitted: Vec<String> = states
+            .iter()
+            .filter_map(|state| track_overlay(&mut overlays, 1, 7, state.overlay.as_ref(), t0))
+            .map(|event| event.event_type)
+            .collect();
+        assert_eq!(
+            emitted,
+            [
+                event_types::OVERLAY_SET,
+                event_types::OVERLAY_UPDATED,
+                event_types::OVERLAY_CLEARED
+            ]
+        );
+    }
+
+    #[test]
+    fn open_window_transitions_emit_one_event_each() {
+        let window = |remaining: i64| -> tado::ZoneOpenWindow {
+            serde_json::from_value(json!({
+                "detectedTime": "2024-03-01T11:00:00Z",
+                "durationInSeconds": 900,
+                "expiry": "2024-03-01T11:15:00Z",
+                "remainingTimeInSeconds": remaining,
+            }))
+            .expect("parse open window")
+        };
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 10, 59, 0).unwrap();
+        let mut cache = BTreeMap::new();
+
+        assert!(track_open_window(&mut cache, 1, 7, None, t0).is_none());
+        let detected = track_open_window(&mut cache, 1, 7, Some(&window(840)), t0).expect("detected event");
+        assert_eq!(detected.event_type, event_types::OPEN_WINDOW_DETECTED);
+        let payload = detected.payload.expect("payload");
+        assert_eq!(payload["detected_time"], "2024-03-01T11:00:00Z");
+        assert_eq!(payload["expiry"], "2024-03-01T11:15:00Z");
+
+        // Same detection, only the countdown moved
+        assert!(track_open_window(&mut cache, 1, 7, Some(&window(780)), t0).is_none());
+        let closed = track_open_window(&mut cache, 1, 7, None, t0).expect("closed event");
+        assert_eq!(closed.event_type, event_types::OPEN_WINDOW_CLOSED);
+        assert_eq!(
+            closed.payload.expect("payload")["detected_time"],
+            "2024-03-01T11:00:00Z"
+        );
+        assert!(track_open_window(&mut cache, 1, 7, None, t0).is_none());
+    }
+
+    #[test]
+    fn pacer_throttles_after_consecutive_overruns() {
+        let interval = Duration::from_secs(60);
+        let mut pacer = TickPacer::new(interval, 2);
+
+        assert_eq!(pacer.sleep_after(Duration::from_secs(10)), Duration::from_secs(50));
+        // Two overrunning ticks may catch up immediately
+        assert_eq!(pacer.sleep_after(Duration::from_secs(600)), Duration::ZERO);
+        assert_eq!(pacer.sleep_after(Duration::from_secs(90)), Duration::ZERO);
+        assert!(!pacer.throttling());
+        // The third forces a recovery sleep
+        assert_eq!(pacer.sleep_after(Duration::from_secs(90)), Duration::from_secs(30));
+        assert!(pacer.throttling());
+        // A tick inside the interval resets the throttle
+        assert_eq!(pacer.sleep_after(Duration::from_secs(5)), Duration::from_secs(55));
+        assert!(!pacer.throttling());
+    }
+
+    #[test]
+    fn failure_streak_gives_up_only_after_consecutive_failures() {
+        let mut failures = FailureStreak::new(3);
+
+        assert!(!failures.record_failure());
+        assert!(!failures.record_failure());
+        failures.record_success();
+        assert!(!failures.record_failure());
+        assert!(!failures.record_failure());
+        assert!(failures.record_failure());
+    }
+
+    #[test]
+    fn reconnect_backs_off_and_gives_up_after_max_retries() {
+        let mut sleeps = Vec::new();
+        let mut calls = 0;
+        let connected = reconnect_with_backoff(
+            5,
+            || {
+                calls += 1;
+                if calls < 3 {
+                    Err("refused".to_string())
+                } else {
+                    Ok(calls)
+                }
+            },
+            |d| {
+                sleeps.push(d);
+                false
+            },
+        );
+        assert_eq!(connected, Ok(3));
+        assert_eq!(sleeps, [Duration::from_secs(1), Duration::from_secs(2)]);
+
+        let mut sleeps = Vec::new();
+        let failed = reconnect_with_backoff(
+            8,
+            || Err::<(), _>("refused".to_string()),
+            |d| {
+                sleeps.push(d);
+                false
+            },
+        );
+        assert_eq!(failed, Err("giving up after 8 attempt(s): refused".to_string()));
+        assert_eq!(sleeps.len(), 7);
+        assert_eq!(sleeps[6], DB_RECONNECT_MAX_DELAY);
+
+        // Shutdown during a backoff sleep abandons the remaining attempts
+        let aborted = reconnect_with_backoff(8, || Err::<(), _>("refused".to_string()), |_| true);
+        assert_eq!(aborted, Err("shutdown requested".to_string()));
+    }
+
+    #[test]
+    fn refs_sync_schedule() {
+        let start = Instant::now();
+        let hour = Duration::from_secs(3600);
+
+        assert!(!refs_sync_due(start, start + hour * 5, None));
+        assert!(!refs_sync_due(start, start + hour * 5, Some(hour * 6)));
+        assert!(refs_sync_due(start, start + hour * 6, Some(hour * 6)));
+    }
+
+    #[test]
+    fn geolocation_override_toggles_emit_events() {
+        let zone_state = |json: &str| -> tado::ZoneState { serde_json::from_str(json).expect("parse zone state") };
+        let off = zone_state(r#"{"geolocationOverride": false}"#);
+        let on =
+            zone_state(r#"{"geolocationOverride": true, "geolocationOverrideDisableTime": "2024-03-01T18:00:00Z"}"#);
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let mut cache = BTreeMap::new();
+
+        assert!(track_geolocation_override(&mut cache, 1, 7, &off, t0).is_none());
+        let switched_on = track_geolocation_override(&mut cache, 1, 7, &on, t0).expect("on event");
+        assert_eq!(switched_on.event_type, event_types::GEO_OVERRIDE_ON);
+        assert_eq!(
+            switched_on.payload.expect("payload")["disable_time"],
+            "2024-03-01T18:00:00Z"
+        );
+        assert!(track_geolocation_override(&mut cache, 1, 7, &on, t0).is_none());
+        let switched_off = track_geolocation_override(&mut cache, 1, 7, &off, t0).expect("off event");
+        assert_eq!(switched_off.event_type, event_types::GEO_OVERRIDE_OFF);
+    }
+
+    #[test]
+    fn sensor_precision_change_emits_event() {
+        let state: tado::ZoneState = serde_json::from_str(
+            r#"{"sensorDataPoints": {"insideTemperature": {"celsius": 20.5, "fahrenheit": 68.9,
+                "precision": {"celsius": 0.5, "fahrenheit": 0.9}}}}"#,
+        )
+        .expect("parse zone state");
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let mut cache = BTreeMap::new();
+
+        assert!(track_sensor_precision(&mut cache, 1, 7, Some(0.1), t0).is_none());
+        assert!(track_sensor_precision(&mut cache, 1, 7, Some(0.1), t0).is_none());
+        // A reading without precision neither fires nor forgets the cached one
+        assert!(track_sensor_precision(&mut cache, 1, 7, None, t0).is_none());
+        let coarser = track_sensor_precision(&mut cache, 1, 7, inside_temp_precision_c(&state), t0).expect("event");
+        assert_eq!(coarser.event_type, event_types::SENSOR_PRECISION_CHANGED);
+        assert_eq!(coarser.zone_id, Some(7));
+        assert_eq!(
+            coarser.payload,
+            Some(json!({ "previous_precision_c": 0.1, "precision_c": 0.5 }))
+        );
+        assert!(track_sensor_precision(&mut cache, 1, 7, Some(0.5), t0).is_none());
+    }
+
+    #[test]
+    fn schedule_change_far_from_predicted_start_is_flagged() {
+        let at = |hour, minute| Utc.with_ymd_and_hms(2024, 3, 1, hour, minute, 0).unwrap();
+        let zone_state = |celsius: f64, next_start: &str| -> tado::ZoneState {
+            serde_json::from_str(&format!(
+                r#"{{"tadoMode": "HOME", "setting": {{"type": "HEATING", "power": "ON",
+                     "temperature": {{"celsius": {}}}}}, "nextTimeBlock": {{"start": "{}"}}}}"#,
+                celsius, next_start
+            ))
+            .expect("parse zone state")
+        };
+        let tolerance = chrono::Duration::minutes(5);
+        let mut schedules = BTreeMap::new();
+        let mut track =
+            |state: &tado::ZoneState, now| track_schedule_transition(&mut schedules, 1, 7, state, tolerance, now);
+
+        // Predicted at 07:00, but the setting only changes at 07:20
+        assert!(track(&zone_state(18.0, "2024-03-01T07:00:00Z"), at(6, 50)).is_none());
+        assert!(track(&zone_state(18.0, "2024-03-01T22:00:00Z"), at(7, 10)).is_none());
+        let delayed = track(&zone_state(21.0, "2024-03-01T22:00:00Z"), at(7, 20)).expect("delayed event");
+        assert_eq!(delayed.event_type, event_types::SCHEDULE_TRANSITION_DELAYED);
+        let payload = delayed.payload.expect("payload");
+        assert_eq!(payload["predicted_start"], json!(at(7, 0)));
+        assert_eq!(payload["delay_secs"], 20 * 60);
+
+        // A change within the tolerance of the 22:00 prediction is on time
+        assert!(track(&zone_state(18.0, "2024-03-02T07:00:00Z"), at(22, 2)).is_none());
+    }
+
+    #[test]
+    fn device_health_changes_emit_lifecycle_events() {
+        let device = |connected: bool, battery: &str| -> tado::Device {
+            serde_json::from_value(json!({
+                "serialNo": "VA123",
+                "connectionState": {"value": connected, "timestamp": "2024-03-01T10:58:00Z"},
+                "batteryState": battery,
+            }))
+            .expect("parse device")
+        };
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let no_debounce = chrono::Duration::zero();
+        let mut cache = BTreeMap::new();
+
+        assert!(track_device_health(&mut cache, 1, 9, &device(true, "NORMAL"), no_debounce, t0).is_empty());
+        assert!(track_device_health(&mut cache, 1, 9, &device(true, "NORMAL"), no_debounce, t0).is_empty());
+
+        let events = track_device_health(&mut cache, 1, 9, &device(false, "LOW"), no_debounce, t0);
+        let types: Vec<&str> = events.iter().map(|e| e.event_type.as_str()).collect();
+        assert_eq!(
+            types,
+            [event_types::DEVICE_DISCONNECTED, event_types::DEVICE_BATTERY_LOW]
+        );
+        assert_eq!(events[0].device_id, Some(9));
+        assert_eq!(events[0].time, Utc.with_ymd_and_hms(2024, 3, 1, 10, 58, 0).unwrap());
+        assert_eq!(events[1].time, t0);
+        assert_eq!(events[0].payload.as_ref().expect("payload")["tado_device_id"], "VA123");
+
+        let events = track_device_health(&mut cache, 1, 9, &device(true, "LOW"), no_debounce, t0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, event_types::DEVICE_CONNECTED);
+    }
+
+    #[test]
+    fn battery_flapping_is_debounced() {
+        let device = |battery: &str| -> tado::Device {
+            serde_json::from_value(json!({"serialNo": "VA123", "batteryState": battery})).expect("parse device")
+        };
+        let t0 = Utc.with_ymd_and_hms(2024, 3, 1, 11, 0, 0).unwrap();
+        let minutes = |m: i64| t0 + chrono::Duration::minutes(m);
+        let debounce = chrono::Duration::minutes(30);
+        let mut cache = BTreeMap::new();
+
+        let emitted: Vec<(String, DateTime<Utc>)> = [
+            ("NORMAL", 0),
+            ("LOW", 10),
+            ("NORMAL", 20),
+            ("LOW", 30),
+            ("NORMAL", 40),
+            // Stays low from here on
+            ("LOW", 50),
+            ("LOW", 60),
+            ("LOW", 70),
+            ("LOW", 80),
+            ("LOW", 90),
+        ]
+        .into_iter()
+        .flat_map(|(battery, m)| track_device_health(&mut cache, 1, 9, &device(battery), debounce, minutes(m)))
+        .map(|event| (event.event_type, event.time))
+        .collect();
+
+        assert_eq!(emitted, [(event_types::DEVICE_BATTERY_LOW.to_string(), minutes(80))]);
+        assert_eq!(cache[&9].battery_low, Some(true));
+    }
+
+    #[test]
+    fn device_health_seeds_from_latest_events() {
+        let at = |hour: u32| Utc.with_ymd_and_hms(2024, 3, 1, hour, 0, 0).unwrap();
+        let latest = vec![
+            (event_types::DEVICE_CONNECTED.to_string(), at(8)),
+            (event_types::DEVICE_DISCONNECTED.to_string(), at(9)),
+            (event_types::DEVICE_BATTERY_LOW.to_string(), at(7)),
+        ];
+
+        let seeded = device_health_from_latest_events(&latest);
+        assert_eq!(
+            seeded,
+            DeviceHealth {
+                connected: Some(false),
+                battery_low: Some(true),
+                battery_pending: None,
+            }
+        );
+        assert_eq!(device_health_from_latest_events(&[]), DeviceHealth::default());
+
+        // A restart that sees the device still disconnected must not repeat the event
+        let device: tado::Device =
+            serde_json::from_value(json!({"connectionState": {"value": false}})).expect("parse device");
+        let mut cache = BTreeMap::from([(9, seeded)]);
+        assert!(track_device_health(&mut cache, 1, 9, &device, chrono::Duration::zero(), at(10)).is_empty());
+    }
 }
@@ -2,47 +2,126 @@ use crate::client::TadoClient;
 use crate::db::models as dbm;
 use crate::models::tado;
 use crate::schema;
-use crate::utils::{describe_device_type, serde_enum_name};
-use chrono::Utc;
-use diesel::prelude::*;
+use crate::services::ingest::insert_events;
+use crate::utils::{describe_device_type, run_bounded, serde_enum_name};
+use chrono::{DateTime, Utc};
 use diesel::PgConnection;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::QueryFragment;
+use diesel::query_dsl::methods::ExecuteDsl;
 use log::{debug, info, warn};
-use std::collections::BTreeMap;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Optional change tracking performed while syncing reference data, all driven by config.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncOptions {
+    pub track_zone_type_changes: bool,
+    pub track_device_characteristics: bool,
+    pub track_zone_capabilities: bool,
+    pub track_installations: bool,
+    /// Homes synced in parallel, each worker on its own database connection; 0 or 1 syncs them one by one.
+    pub threads: usize,
+    /// `DRY_RUN`: log the change events instead of inserting them. The reference rows themselves are still
+    /// upserted, since collection needs their ids.
+    pub dry_run: bool,
+}
 
-pub fn sync_all(conn: &mut PgConnection, client: &TadoClient, me: &tado::User, home_ids: &[i64]) -> Result<(), String> {
+/// Syncs the user, then every home. With `options.threads > 1` the homes are spread over that many workers,
+/// each with its own connection, sharing `client` (and so its token and retry budget).
+pub fn sync_all(
+    conn: &mut PgConnection,
+    database_url: &str,
+    client: &TadoClient,
+    me: &tado::User,
+    home_ids: &[i64],
+    options: SyncOptions,
+) -> Result<(), String> {
     info!("Syncing references for {} home(s)", home_ids.len());
     let db_user_id = upsert_user(conn, me)?;
-    for home_id in home_ids {
-        info!("Refs: syncing home {}", home_id);
-        let home = client
-            .get_home(tado::HomeId(*home_id))
-            .map_err(|e| format!("get_home({home_id}) failed: {}", e))?;
-        let db_home_id = upsert_home(conn, &home)?;
-        upsert_user_home(conn, db_user_id, db_home_id)?;
-
-        let zones = client
-            .get_zones(tado::HomeId(*home_id))
-            .map_err(|e| format!("get_zones({home_id}) failed: {}", e))?;
-        let zone_map = upsert_zones(conn, db_home_id, &zones)?;
-
-        let devices = client
-            .get_devices(tado::HomeId(*home_id))
-            .map_err(|e| format!("get_devices({home_id}) failed: {}", e))?;
-        let device_map = upsert_devices(conn, db_home_id, &devices)?;
+    if options.threads <= 1 || home_ids.len() <= 1 {
+        for home_id in home_ids {
+            sync_home(conn, client, db_user_id, *home_id, options)?;
+        }
+        return Ok(());
+    }
+
+    let results = run_bounded(
+        home_ids,
+        options.threads,
+        || PgConnection::establish(database_url).map_err(|e| format!("Refs: DB connection failed: {}", e)),
+        |worker_conn, home_id| sync_home(worker_conn, client, db_user_id, *home_id, options),
+    );
+    results.into_iter().collect()
+}
+
+fn sync_home(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_user_id: i64,
+    home_id: i64,
+    options: SyncOptions,
+) -> Result<(), String> {
+    info!("Refs: syncing home {}", home_id);
+    let home = client
+        .get_home(tado::HomeId(home_id))
+        .map_err(|e| format!("get_home({home_id}) failed: {}", e))?;
+    let incident_detection = fetch_incident_detection(client, &home, home_id);
+    let db_home_id = upsert_home(conn, &home, incident_detection.as_ref(), options.dry_run)?;
+    upsert_user_home(conn, db_user_id, db_home_id)?;
+
+    let zones = client
+        .get_zones(tado::HomeId(home_id))
+        .map_err(|e| format!("get_zones({home_id}) failed: {}", e))?;
+    let zone_map = upsert_zones(
+        conn,
+        db_home_id,
+        &zones,
+        options.track_zone_type_changes,
+        options.dry_run,
+    )?;
+    if options.track_zone_capabilities {
+        sync_zone_capabilities(conn, client, db_home_id, home_id, &zone_map, options.dry_run)?;
+    }
+
+    let devices = client
+        .get_devices(tado::HomeId(home_id))
+        .map_err(|e| format!("get_devices({home_id}) failed: {}", e))?;
+    let known_devices = load_known_devices(conn, db_home_id)?;
+    let device_map = upsert_devices(
+        conn,
+        client,
+        db_home_id,
+        &devices,
+        options.track_device_characteristics,
+        options.dry_run,
+    )?;
+    record_device_membership(conn, db_home_id, &known_devices, &devices, &device_map, options.dry_run)?;
 
+    debug!(
+        "Refs: fetched home {} (zones={}, devices={})",
+        home_id,
+        zones.len(),
+        devices.len()
+    );
+
+    let device_list = client
+        .get_device_list(tado::HomeId(home_id))
+        .map_err(|e| format!("get_device_list({home_id}) failed: {}", e))?;
+    upsert_zone_devices(conn, &zone_map, &device_map, device_list)?;
+    if options.track_installations {
+        sync_installations(conn, client, db_home_id, home_id, options.dry_run)?;
+    }
+    if home.supports_flow_temperature_optimization == Some(true) {
+        sync_flow_temperature_optimization(conn, client, db_home_id, home_id)?;
+    } else {
         debug!(
-            "Refs: fetched home {} (zones={}, devices={})",
-            home_id,
-            zones.len(),
-            devices.len()
+            "Refs: home {} does not support flow temperature optimization; skipping",
+            home_id
         );
-
-        let device_list = client
-            .get_device_list(tado::HomeId(*home_id))
-            .map_err(|e| format!("get_device_list({home_id}) failed: {}", e))?;
-        upsert_zone_devices(conn, &zone_map, &device_map, device_list)?;
-        info!("Refs: home {} complete", home_id);
     }
+    info!("Refs: home {} complete", home_id);
     Ok(())
 }
 
@@ -82,14 +161,47 @@ fn upsert_user(conn: &mut PgConnection, me: &tado::User) -> Result<i64, String>
     Ok(user.id)
 }
 
-fn upsert_home(conn: &mut PgConnection, home: &tado::Home) -> Result<i64, String> {
+/// Fetches the home's incident detection state, only for homes whose `get_home` payload says it is supported.
+/// A failed fetch falls back to the `get_home` payload.
+fn fetch_incident_detection(client: &TadoClient, home: &tado::Home, home_id: i64) -> Option<tado::IncidentDetection> {
+    if home.incident_detection.as_ref().and_then(|i| i.supported) != Some(true) {
+        debug!("Refs: home {} does not support incident detection; skipping", home_id);
+        return None;
+    }
+    match client.get_incident_detection(tado::HomeId(home_id)) {
+        Ok(incident_detection) => Some(incident_detection),
+        Err(e) => {
+            warn!("Refs: get_incident_detection({home_id}) failed: {}", e);
+            None
+        }
+    }
+}
+
+/// `(enabled, supported)`, preferring the dedicated endpoint over the summary embedded in `get_home`.
+fn incident_detection_state(
+    home: &tado::Home,
+    fetched: Option<&tado::IncidentDetection>,
+) -> (Option<bool>, Option<bool>) {
+    let embedded = home.incident_detection.as_ref();
+    let pick =
+        |field: fn(&tado::IncidentDetection) -> Option<bool>| fetched.and_then(field).or(embedded.and_then(field));
+    (pick(|i| i.enabled), pick(|i| i.supported))
+}
+
+fn upsert_home(
+    conn: &mut PgConnection,
+    home: &tado::Home,
+    incident_detection: Option<&tado::IncidentDetection>,
+    dry_run: bool,
+) -> Result<i64, String> {
     use schema::homes::dsl as H;
 
     let (tado_home_id, name) = (
         home.details.base.id.map(|h| h.0).unwrap_or_default(),
         home.details.base.name.clone(),
     );
-    let new_row = dbm::NewHome {
+    let (incident_detection_enabled, incident_detection_supported) = incident_detection_state(home, incident_detection);
+    let mut new_row = dbm::NewHome {
         tado_home_id,
         name,
         timezone: home.date_time_zone.clone(),
@@ -102,7 +214,21 @@ fn upsert_home(conn: &mut PgConnection, home: &tado::Home) -> Result<i64, String
         country: home.details.address.as_ref().and_then(|a| a.country.clone()),
         latitude: home.details.geolocation.as_ref().and_then(|g| g.latitude),
         longitude: home.details.geolocation.as_ref().and_then(|g| g.longitude),
+        away_radius_m: home.away_radius_in_meters,
+        incident_detection_enabled,
+        feature_flags: Some(home_feature_flags(home)),
+        incident_detection_supported,
     };
+    let stored: Option<StoredHomeSettings> = H::homes
+        .filter(H::tado_home_id.eq(tado_home_id))
+        .select((H::away_radius_m, H::incident_detection_enabled, H::feature_flags))
+        .first(conn)
+        .optional()
+        .map_err(|e| format!("fetch stored home settings failed: {}", e))?;
+    // A sync that could not read incident detection keeps the last known state instead of clearing it
+    if new_row.incident_detection_enabled.is_none() {
+        new_row.incident_detection_enabled = stored.as_ref().and_then(|(_, incident, _)| *incident);
+    }
 
     diesel::insert_into(H::homes)
         .values(&new_row)
@@ -120,6 +246,10 @@ fn upsert_home(conn: &mut PgConnection, home: &tado::Home) -> Result<i64, String
             H::country.eq(new_row.country.clone()),
             H::latitude.eq(new_row.latitude),
             H::longitude.eq(new_row.longitude),
+            H::away_radius_m.eq(new_row.away_radius_m),
+            H::incident_detection_enabled.eq(new_row.incident_detection_enabled),
+            H::feature_flags.eq(new_row.feature_flags.clone()),
+            H::incident_detection_supported.eq(new_row.incident_detection_supported),
             H::updated_at.eq(Utc::now()),
         ))
         .execute(conn)
@@ -129,9 +259,71 @@ fn upsert_home(conn: &mut PgConnection, home: &tado::Home) -> Result<i64, String
         .filter(H::tado_home_id.eq(new_row.tado_home_id))
         .first(conn)
         .map_err(|e| format!("fetch home failed: {}", e))?;
+
+    if let Some(stored) = stored {
+        let events = home_setting_changes(row.id, stored, &new_row, Utc::now());
+        insert_events(conn, &events, dry_run)?;
+    }
     Ok(row.id)
 }
 
+/// `(away_radius_m, incident_detection_enabled, feature_flags)` as stored by the previous sync.
+type StoredHomeSettings = (Option<f64>, Option<bool>, Option<serde_json::Value>);
+
+/// Boolean home toggles tracked together in `homes.feature_flags`.
+fn home_feature_flags(home: &tado::Home) -> serde_json::Value {
+    json!({
+        "christmas_mode_enabled": home.christmas_mode_enabled,
+        "show_auto_assist_reminders": home.show_auto_assist_reminders,
+        "simple_smart_schedule_enabled": home.simple_smart_schedule_enabled,
+    })
+}
+
+/// Events for home settings that differ from what the previous sync stored.
+/// A setting that was never stored (e.g. right after the columns were added) is recorded silently, and an
+/// incident detection state missing from this sync is unknown rather than a change.
+fn home_setting_changes(
+    db_home_id: i64,
+    (stored_radius, stored_incident, stored_flags): StoredHomeSettings,
+    current: &dbm::NewHome,
+    now: DateTime<Utc>,
+) -> Vec<dbm::NewEvent> {
+    let event = |event_type: &str, payload: serde_json::Value| dbm::NewEvent {
+        time: now,
+        home_id: db_home_id,
+        zone_id: None,
+        device_id: None,
+        source: None,
+        event_type: event_type.to_string(),
+        payload: Some(payload),
+    };
+
+    let mut events = Vec::new();
+    if stored_radius.is_some() && stored_radius != current.away_radius_m {
+        events.push(event(
+            dbm::event_types::AWAY_RADIUS_CHANGED,
+            json!({ "previous_m": stored_radius, "current_m": current.away_radius_m }),
+        ));
+    }
+    if let (Some(stored), Some(current)) = (stored_incident, current.incident_detection_enabled)
+        && stored != current
+    {
+        events.push(event(
+            dbm::event_types::INCIDENT_DETECTION_CHANGED,
+            json!({ "previous": stored, "current": current }),
+        ));
+    }
+    if let (Some(stored), Some(current)) = (stored_flags.as_ref(), current.feature_flags.as_ref())
+        && let Some(changed) = changed_flags(stored, current)
+    {
+        events.push(event(
+            dbm::event_types::HOME_FLAGS_CHANGED,
+            json!({ "changed": changed }),
+        ));
+    }
+    events
+}
+
 fn upsert_user_home(conn: &mut PgConnection, user_id: i64, home_id: i64) -> Result<(), String> {
     use schema::user_homes::dsl as UH;
 
@@ -151,7 +343,13 @@ fn upsert_user_home(conn: &mut PgConnection, user_id: i64, home_id: i64) -> Resu
     Ok(())
 }
 
-fn upsert_zones(conn: &mut PgConnection, db_home_id: i64, zones: &[tado::Zone]) -> Result<BTreeMap<i64, i64>, String> {
+fn upsert_zones(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    zones: &[tado::Zone],
+    track_type_changes: bool,
+    dry_run: bool,
+) -> Result<BTreeMap<i64, i64>, String> {
     use schema::zones::dsl as Z;
     let mut map = BTreeMap::new();
 
@@ -164,16 +362,17 @@ fn upsert_zones(conn: &mut PgConnection, db_home_id: i64, zones: &[tado::Zone])
             zone_type: z.r#type.as_ref().and_then(serde_enum_name),
             date_created: z.date_created,
         };
-        diesel::insert_into(Z::zones)
-            .values(&new_row)
-            .on_conflict((Z::home_id, Z::tado_zone_id))
-            .do_update()
-            .set((
-                Z::name.eq(new_row.name.clone()),
-                Z::zone_type.eq(new_row.zone_type.clone()),
-                Z::date_created.eq(new_row.date_created),
-                Z::updated_at.eq(Utc::now()),
-            ))
+        let stored_type: Option<Option<String>> = if track_type_changes {
+            Z::zones
+                .filter(Z::home_id.eq(db_home_id).and(Z::tado_zone_id.eq(tado_zone_id)))
+                .select(Z::zone_type)
+                .first(conn)
+                .optional()
+                .map_err(|e| format!("fetch stored zone type failed: {}", e))?
+        } else {
+            None
+        };
+        zone_upsert(&new_row, Utc::now())
             .execute(conn)
             .map_err(|e| format!("upsert zone failed: {}", e))?;
 
@@ -182,14 +381,393 @@ fn upsert_zones(conn: &mut PgConnection, db_home_id: i64, zones: &[tado::Zone])
             .first(conn)
             .map_err(|e| format!("fetch zone failed: {}", e))?;
         map.insert(tado_zone_id, row.id);
+
+        if let Some(change) = zone_type_change(stored_type.as_ref(), &new_row.zone_type, row.id, Utc::now()) {
+            record_zone_type_change(conn, db_home_id, tado_zone_id, change, dry_run)?;
+        }
     }
     Ok(map)
 }
 
+/// Inserts the zone or, when it is already stored, overwrites its name, type and creation date with `new_row`'s,
+/// so renames in the Tado app reach the database on the next sync.
+fn zone_upsert(
+    new_row: &dbm::NewZone,
+    now: DateTime<Utc>,
+) -> impl RunQueryDsl<PgConnection> + ExecuteDsl<PgConnection> + QueryFragment<Pg> + '_ {
+    use schema::zones::dsl as Z;
+
+    diesel::insert_into(Z::zones)
+        .values(new_row)
+        .on_conflict((Z::home_id, Z::tado_zone_id))
+        .do_update()
+        .set((
+            Z::name.eq(new_row.name.clone()),
+            Z::zone_type.eq(new_row.zone_type.clone()),
+            Z::date_created.eq(new_row.date_created),
+            Z::updated_at.eq(now),
+        ))
+}
+
+/// Per-flag `{previous, current}` for every key whose value differs, or `None` when nothing changed.
+fn changed_flags(stored: &serde_json::Value, current: &serde_json::Value) -> Option<serde_json::Value> {
+    let null = serde_json::Value::Null;
+    let keys: BTreeSet<&String> = stored
+        .as_object()
+        .into_iter()
+        .chain(current.as_object())
+        .flat_map(|flags| flags.keys())
+        .collect();
+    let changed: serde_json::Map<String, serde_json::Value> = keys
+        .into_iter()
+        .filter_map(|key| {
+            let (previous, now) = (stored.get(key).unwrap_or(&null), current.get(key).unwrap_or(&null));
+            (previous != now).then(|| (key.clone(), json!({ "previous": previous, "current": now })))
+        })
+        .collect();
+    (!changed.is_empty()).then_some(serde_json::Value::Object(changed))
+}
+
+/// Fetches and stores each zone's capabilities, emitting `ZONE_CAPABILITIES_CHANGED` when they differ from the
+/// stored copy. A failed fetch only skips that zone; capabilities are informative, not required.
+fn sync_zone_capabilities(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_home_id: i64,
+    home_id: i64,
+    zone_map: &BTreeMap<i64, i64>,
+    dry_run: bool,
+) -> Result<(), String> {
+    use schema::zones::dsl as Z;
+
+    for (tado_zone_id, db_zone_id) in zone_map {
+        let capabilities = match client.get_zone_capabilities(tado::HomeId(home_id), tado::ZoneId(*tado_zone_id)) {
+            Ok(c) => c,
+            Err(e) => {
+                warn!("Refs: get_zone_capabilities({home_id}, {tado_zone_id}) failed: {}", e);
+                continue;
+            }
+        };
+        let current = normalize_json(serde_json::to_value(&capabilities).unwrap_or(serde_json::Value::Null));
+
+        let stored: Option<serde_json::Value> = Z::zones
+            .find(db_zone_id)
+            .select(Z::capabilities)
+            .first(conn)
+            .map_err(|e| format!("fetch stored zone capabilities failed: {}", e))?;
+        diesel::update(Z::zones.find(db_zone_id))
+            .set(Z::capabilities.eq(Some(&current)))
+            .execute(conn)
+            .map_err(|e| format!("store zone capabilities failed: {}", e))?;
+
+        if let Some(diff) = capabilities_diff(stored.as_ref(), &current) {
+            info!("Refs: zone {} capabilities changed", tado_zone_id);
+            let event = dbm::NewEvent {
+                time: Utc::now(),
+                home_id: db_home_id,
+                zone_id: Some(*db_zone_id),
+                device_id: None,
+                source: None,
+                event_type: dbm::event_types::ZONE_CAPABILITIES_CHANGED.to_string(),
+                payload: Some(diff),
+            };
+            insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+        }
+    }
+    Ok(())
+}
+
+/// Stores the home's current flow temperature optimization settings. A failed fetch only skips this sync.
+fn sync_flow_temperature_optimization(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_home_id: i64,
+    home_id: i64,
+) -> Result<(), String> {
+    use schema::flow_temperature_optimization::dsl as F;
+
+    let settings = match client.get_flow_temperature_optimization(tado::HomeId(home_id)) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("Refs: get_flow_temperature_optimization({home_id}) failed: {}", e);
+            return Ok(());
+        }
+    };
+    let row = flow_temperature_optimization_row(&settings, db_home_id, Utc::now());
+    diesel::insert_into(F::flow_temperature_optimization)
+        .values(&row)
+        .on_conflict(F::home_id)
+        .do_update()
+        .set((
+            F::max_flow_temperature.eq(row.max_flow_temperature),
+            F::auto_adaptation_enabled.eq(row.auto_adaptation_enabled),
+            F::auto_adaptation_max_flow_temperature.eq(row.auto_adaptation_max_flow_temperature),
+            F::max_flow_temperature_min.eq(row.max_flow_temperature_min),
+            F::max_flow_temperature_max.eq(row.max_flow_temperature_max),
+            F::open_therm_device_serial_number.eq(row.open_therm_device_serial_number.clone()),
+            F::updated_at.eq(row.updated_at),
+        ))
+        .execute(conn)
+        .map_err(|e| format!("upsert flow temperature optimization failed: {}", e))?;
+    Ok(())
+}
+
+fn flow_temperature_optimization_row(
+    settings: &tado::FlowTemperatureOptimization,
+    db_home_id: i64,
+    now: DateTime<Utc>,
+) -> dbm::NewFlowTemperatureOptimization {
+    let auto_adaptation = settings.auto_adaptation.as_ref();
+    let constraints = settings.max_flow_temperature_constraints.as_ref();
+    dbm::NewFlowTemperatureOptimization {
+        home_id: db_home_id,
+        max_flow_temperature: settings.max_flow_temperature,
+        auto_adaptation_enabled: auto_adaptation.and_then(|a| a.enabled),
+        auto_adaptation_max_flow_temperature: auto_adaptation.and_then(|a| a.max_flow_temperature),
+        max_flow_temperature_min: constraints.and_then(|c| c.min),
+        max_flow_temperature_max: constraints.and_then(|c| c.max),
+        open_therm_device_serial_number: settings.open_therm_device_serial_number.clone(),
+        updated_at: now,
+    }
+}
+
+/// Fetches and stores the home's installations, emitting `INSTALLATION_STATE_CHANGED` when one's state or
+/// revision differs from the stored copy. A failed fetch only skips this sync, like zone capabilities.
+fn sync_installations(
+    conn: &mut PgConnection,
+    client: &TadoClient,
+    db_home_id: i64,
+    home_id: i64,
+    dry_run: bool,
+) -> Result<(), String> {
+    use schema::installations::dsl as I;
+
+    let installations = match client.get_installations(tado::HomeId(home_id)) {
+        Ok(i) => i,
+        Err(e) => {
+            warn!("Refs: get_installations({home_id}) failed: {}", e);
+            return Ok(());
+        }
+    };
+
+    for installation in &installations {
+        let Some(tado_installation_id) = installation.id.map(|id| id.0) else {
+            continue;
+        };
+        let new_row = dbm::NewInstallation {
+            home_id: db_home_id,
+            tado_installation_id,
+            installation_type: installation.r#type.clone(),
+            state: installation.state.clone(),
+            revision: installation.revision,
+        };
+        let stored: Option<(Option<String>, Option<i64>)> = I::installations
+            .filter(
+                I::home_id
+                    .eq(db_home_id)
+                    .and(I::tado_installation_id.eq(tado_installation_id)),
+            )
+            .select((I::state, I::revision))
+            .first(conn)
+            .optional()
+            .map_err(|e| format!("fetch stored installation failed: {}", e))?;
+        diesel::insert_into(I::installations)
+            .values(&new_row)
+            .on_conflict((I::home_id, I::tado_installation_id))
+            .do_update()
+            .set((
+                I::installation_type.eq(new_row.installation_type.clone()),
+                I::state.eq(new_row.state.clone()),
+                I::revision.eq(new_row.revision),
+                I::updated_at.eq(Utc::now()),
+            ))
+            .execute(conn)
+            .map_err(|e| format!("upsert installation failed: {}", e))?;
+
+        if let Some(payload) = installation_change(stored.as_ref(), &new_row) {
+            info!(
+                "Refs: installation {} changed state from {} to {}",
+                tado_installation_id,
+                stored.as_ref().and_then(|(state, _)| state.as_deref()).unwrap_or("-"),
+                new_row.state.as_deref().unwrap_or("-")
+            );
+            let event = dbm::NewEvent {
+                time: Utc::now(),
+                home_id: db_home_id,
+                zone_id: None,
+                device_id: None,
+                source: None,
+                event_type: dbm::event_types::INSTALLATION_STATE_CHANGED.to_string(),
+                payload: Some(payload),
+            };
+            insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+        }
+    }
+    Ok(())
+}
+
+/// Payload for an installation whose stored `(state, revision)` differs from the current one; `None` on the
+/// first sync and when both are unchanged.
+fn installation_change(
+    stored: Option<&(Option<String>, Option<i64>)>,
+    current: &dbm::NewInstallation,
+) -> Option<serde_json::Value> {
+    let (previous_state, previous_revision) = stored?;
+    if *previous_state == current.state && *previous_revision == current.revision {
+        return None;
+    }
+    Some(json!({
+        "installation_id": current.tado_installation_id,
+        "type": current.installation_type,
+        "previous_state": previous_state,
+        "current_state": current.state,
+        "previous_revision": previous_revision,
+        "current_revision": current.revision,
+    }))
+}
+
+/// Summarizes setpoint range and mode differences between stored and current zone capabilities.
+///
+/// Returns `None` on the first sync (nothing stored yet) and when the normalized blobs are equal.
+fn capabilities_diff(stored: Option<&serde_json::Value>, current: &serde_json::Value) -> Option<serde_json::Value> {
+    let previous = normalize_json(stored?.clone());
+    let current = normalize_json(current.clone());
+    if previous == current {
+        return None;
+    }
+
+    let range = |v: &serde_json::Value| v.pointer("/temperatures/celsius").cloned();
+    let modes = |v: &serde_json::Value| -> Vec<&'static str> {
+        ["HEAT", "COOL", "DRY", "FAN", "AUTO"]
+            .into_iter()
+            .filter(|m| v.get(*m).is_some_and(|c| !c.is_null()))
+            .collect()
+    };
+    let (modes_before, modes_after) = (modes(&previous), modes(&current));
+
+    Some(json!({
+        "previous_range_c": range(&previous),
+        "current_range_c": range(&current),
+        "modes_added": modes_after.iter().filter(|m| !modes_before.contains(m)).collect::<Vec<_>>(),
+        "modes_removed": modes_before.iter().filter(|m| !modes_after.contains(m)).collect::<Vec<_>>(),
+        "previous": previous,
+        "current": current,
+    }))
+}
+
+/// Summarizes how device characteristics changed, or `None` when they are equivalent.
+///
+/// Both sides are normalized first: arrays are sorted (capability lists carry no meaningful order) and
+/// JSON `null` equals a missing value, both for the whole column and for any object member, so reordering or a
+/// member flipping between `null` and absent never reports a change.
+fn characteristics_diff(
+    stored: Option<&serde_json::Value>,
+    current: Option<&serde_json::Value>,
+) -> Option<serde_json::Value> {
+    let previous = normalize_json(stored.cloned().unwrap_or(serde_json::Value::Null));
+    let current = normalize_json(current.cloned().unwrap_or(serde_json::Value::Null));
+    if previous == current {
+        return None;
+    }
+
+    let capabilities = |v: &serde_json::Value| -> Vec<String> {
+        v.get("capabilities")
+            .and_then(|c| c.as_array())
+            .map(|c| c.iter().filter_map(|s| s.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    };
+    let (before, after) = (capabilities(&previous), capabilities(&current));
+    let added: Vec<&String> = after.iter().filter(|c| !before.contains(c)).collect();
+    let removed: Vec<&String> = before.iter().filter(|c| !after.contains(c)).collect();
+
+    Some(json!({
+        "capabilities_added": added,
+        "capabilities_removed": removed,
+        "previous": previous,
+        "current": current,
+    }))
+}
+
+fn normalize_json(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(items) => {
+            let mut items: Vec<serde_json::Value> = items.into_iter().map(normalize_json).collect();
+            items.sort_by_key(|item| item.to_string());
+            serde_json::Value::Array(items)
+        }
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, normalize_json(v)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Returns the change to record when an already-known zone comes back with a different type.
+fn zone_type_change(
+    stored_type: Option<&Option<String>>,
+    current_type: &Option<String>,
+    db_zone_id: i64,
+    now: DateTime<Utc>,
+) -> Option<dbm::NewZoneTypeChange> {
+    let previous = stored_type?;
+    if previous == current_type {
+        return None;
+    }
+    Some(dbm::NewZoneTypeChange {
+        zone_id: db_zone_id,
+        changed_at: now,
+        previous_type: previous.clone(),
+        new_type: current_type.clone(),
+    })
+}
+
+fn record_zone_type_change(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    tado_zone_id: i64,
+    change: dbm::NewZoneTypeChange,
+    dry_run: bool,
+) -> Result<(), String> {
+    use schema::zone_type_history::dsl as ZTH;
+
+    warn!(
+        "Refs: zone {} changed type from {} to {}",
+        tado_zone_id,
+        change.previous_type.as_deref().unwrap_or("-"),
+        change.new_type.as_deref().unwrap_or("-")
+    );
+    diesel::insert_into(ZTH::zone_type_history)
+        .values(&change)
+        .execute(conn)
+        .map_err(|e| format!("insert zone type history failed: {}", e))?;
+
+    let event = dbm::NewEvent {
+        time: change.changed_at,
+        home_id: db_home_id,
+        zone_id: Some(change.zone_id),
+        device_id: None,
+        source: None,
+        event_type: dbm::event_types::ZONE_TYPE_CHANGED.to_string(),
+        payload: Some(json!({
+            "tado_zone_id": tado_zone_id,
+            "previous_type": change.previous_type,
+            "new_type": change.new_type,
+        })),
+    };
+    insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+    Ok(())
+}
+
 fn upsert_devices(
     conn: &mut PgConnection,
+    client: &TadoClient,
     db_home_id: i64,
     devices: &[tado::Device],
+    track_characteristics: bool,
+    dry_run: bool,
 ) -> Result<BTreeMap<String, i64>, String> {
     use schema::devices::dsl as D;
     let mut map = BTreeMap::new();
@@ -201,6 +779,36 @@ fn upsert_devices(
                 continue;
             }
         };
+        let (fetched_offset, stored_offset) = if has_temperature_offset(d) {
+            let stored: Option<f64> = D::devices
+                .filter(D::home_id.eq(db_home_id).and(D::tado_device_id.eq(&tado_device_id)))
+                .select(D::temperature_offset_c)
+                .first::<Option<f64>>(conn)
+                .optional()
+                .map_err(|e| format!("fetch stored temperature offset failed: {}", e))?
+                .flatten();
+            // A failed fetch keeps the stored offset rather than clearing it
+            let fetched = match client.get_temperature_offset(tado::DeviceId(tado_device_id.clone())) {
+                Ok(offset) => offset.celsius,
+                Err(e) => {
+                    warn!(
+                        "Refs: temperature offset of device {} unavailable: {}",
+                        tado_device_id, e
+                    );
+                    None
+                }
+            };
+            (fetched, stored)
+        } else {
+            (None, None)
+        };
+        let (stored_firmware, stored_mounting_state): (Option<String>, Option<String>) = D::devices
+            .filter(D::home_id.eq(db_home_id).and(D::tado_device_id.eq(&tado_device_id)))
+            .select((D::firmware_version, D::mounting_state))
+            .first(conn)
+            .optional()
+            .map_err(|e| format!("fetch stored firmware version and mounting state failed: {}", e))?
+            .unwrap_or_default();
         let new_row = dbm::NewDevice {
             home_id: db_home_id,
             tado_device_id: tado_device_id.clone(),
@@ -214,6 +822,18 @@ fn upsert_devices(
             orientation: d.orientation.as_ref().and_then(serde_enum_name),
             battery_state: d.battery_state.as_ref().and_then(serde_enum_name),
             characteristics: serde_json::to_value(&d.characteristics).ok(),
+            temperature_offset_c: fetched_offset.or(stored_offset),
+            mounting_state: d.mounting_state.as_ref().and_then(|m| m.value.clone()),
+        };
+        let stored_characteristics: Option<Option<serde_json::Value>> = if track_characteristics {
+            D::devices
+                .filter(D::home_id.eq(db_home_id).and(D::tado_device_id.eq(&tado_device_id)))
+                .select(D::characteristics)
+                .first(conn)
+                .optional()
+                .map_err(|e| format!("fetch stored device characteristics failed: {}", e))?
+        } else {
+            None
         };
         diesel::insert_into(D::devices)
             .values(&new_row)
@@ -227,6 +847,8 @@ fn upsert_devices(
                 D::orientation.eq(new_row.orientation.clone()),
                 D::battery_state.eq(new_row.battery_state.clone()),
                 D::characteristics.eq(new_row.characteristics.clone()),
+                D::temperature_offset_c.eq(new_row.temperature_offset_c),
+                D::mounting_state.eq(new_row.mounting_state.clone()),
                 D::updated_at.eq(Utc::now()),
             ))
             .execute(conn)
@@ -237,11 +859,227 @@ fn upsert_devices(
             .select(dbm::Device::as_select())
             .first(conn)
             .map_err(|e| format!("fetch device failed: {}", e))?;
+
+        if let Some(stored) = stored_characteristics
+            && let Some(diff) = characteristics_diff(stored.as_ref(), new_row.characteristics.as_ref())
+        {
+            info!("Refs: device {} characteristics changed", tado_device_id);
+            let event = dbm::NewEvent {
+                time: Utc::now(),
+                home_id: db_home_id,
+                zone_id: None,
+                device_id: Some(row.id),
+                source: None,
+                event_type: dbm::event_types::DEVICE_CHARACTERISTICS_CHANGED.to_string(),
+                payload: Some(diff),
+            };
+            insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+        }
+        if let Some(change) = firmware_version_change(stored_firmware.as_deref(), new_row.firmware_version.as_deref()) {
+            info!("Refs: device {} firmware updated", tado_device_id);
+            let event = dbm::NewEvent {
+                time: Utc::now(),
+                home_id: db_home_id,
+                zone_id: None,
+                device_id: Some(row.id),
+                source: None,
+                event_type: dbm::event_types::DEVICE_FIRMWARE_UPDATED.to_string(),
+                payload: Some(change),
+            };
+            insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+        }
+        if let Some(change) = mounting_state_change(stored_mounting_state.as_deref(), new_row.mounting_state.as_deref())
+        {
+            info!("Refs: device {} mounting state changed", tado_device_id);
+            let event = dbm::NewEvent {
+                // Tado stamps the transition itself; the sync may run long after it
+                time: d
+                    .mounting_state
+                    .as_ref()
+                    .and_then(|m| m.timestamp)
+                    .unwrap_or_else(Utc::now),
+                home_id: db_home_id,
+                zone_id: None,
+                device_id: Some(row.id),
+                source: None,
+                event_type: dbm::event_types::DEVICE_MOUNTING_STATE_CHANGED.to_string(),
+                payload: Some(change),
+            };
+            insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+        }
+        if let Some(change) = temperature_offset_change(stored_offset, fetched_offset) {
+            info!("Refs: device {} temperature offset changed", tado_device_id);
+            let event = dbm::NewEvent {
+                time: Utc::now(),
+                home_id: db_home_id,
+                zone_id: None,
+                device_id: Some(row.id),
+                source: None,
+                event_type: dbm::event_types::DEVICE_TEMPERATURE_OFFSET_CHANGED.to_string(),
+                payload: Some(change),
+            };
+            insert_events(conn, std::slice::from_ref(&event), dry_run)?;
+        }
         map.insert(tado_device_id, row.id);
     }
     Ok(map)
 }
 
+/// A device already stored for the home, with whether its latest membership event marked it removed.
+#[derive(Debug, Clone, PartialEq)]
+struct KnownDevice {
+    id: i64,
+    serial_no: String,
+    device_type: Option<String>,
+    removed: bool,
+}
+
+/// Devices stored for the home before this sync. Rows are never deleted, so a vanished device stays known and
+/// its last `DEVICE_ADDED`/`DEVICE_REMOVED` event tells whether it is currently part of the home.
+fn load_known_devices(conn: &mut PgConnection, db_home_id: i64) -> Result<Vec<KnownDevice>, String> {
+    use schema::devices::dsl as D;
+    use schema::events::dsl as E;
+
+    let devices: Vec<(i64, String, Option<String>)> = D::devices
+        .filter(D::home_id.eq(db_home_id))
+        .select((D::id, D::tado_device_id, D::device_type))
+        .load(conn)
+        .map_err(|e| format!("fetch stored devices failed: {}", e))?;
+    let membership: Vec<(Option<i64>, String)> = E::events
+        .filter(E::home_id.eq(db_home_id))
+        .filter(E::event_type.eq_any([dbm::event_types::DEVICE_ADDED, dbm::event_types::DEVICE_REMOVED]))
+        .order(E::time.asc())
+        .select((E::device_id, E::event_type))
+        .load(conn)
+        .map_err(|e| format!("fetch device membership events failed: {}", e))?;
+    let latest: BTreeMap<i64, String> = membership
+        .into_iter()
+        .filter_map(|(device_id, event_type)| Some((device_id?, event_type)))
+        .collect();
+    Ok(devices
+        .into_iter()
+        .map(|(id, serial_no, device_type)| KnownDevice {
+            removed: latest.get(&id).map(String::as_str) == Some(dbm::event_types::DEVICE_REMOVED),
+            id,
+            serial_no,
+            device_type,
+        })
+        .collect())
+}
+
+/// Records `DEVICE_ADDED` for reported devices that were unknown or marked removed, and `DEVICE_REMOVED` for
+/// known devices Tado no longer reports. The rows themselves are kept.
+fn record_device_membership(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    known: &[KnownDevice],
+    devices: &[tado::Device],
+    device_map: &BTreeMap<String, i64>,
+    dry_run: bool,
+) -> Result<(), String> {
+    let reported: BTreeMap<String, Option<String>> = devices
+        .iter()
+        .filter_map(|d| {
+            let serial_no = d.serial_no.as_ref().map(|s| s.0.clone()).filter(|s| !s.is_empty())?;
+            Some((serial_no, d.device_type.as_ref().map(|t| t.0.clone())))
+        })
+        .collect();
+    let now = Utc::now();
+    let events: Vec<dbm::NewEvent> = device_membership_changes(known, &reported)
+        .into_iter()
+        .filter_map(|(event_type, serial_no, device_type)| {
+            let device_id = device_map
+                .get(&serial_no)
+                .copied()
+                .or_else(|| known.iter().find(|k| k.serial_no == serial_no).map(|k| k.id))?;
+            info!("Refs: device {} {}", serial_no, event_type);
+            Some(dbm::NewEvent {
+                time: now,
+                home_id: db_home_id,
+                zone_id: None,
+                device_id: Some(device_id),
+                source: None,
+                event_type: event_type.to_string(),
+                payload: Some(json!({ "serial_no": serial_no, "device_type": device_type })),
+            })
+        })
+        .collect();
+    insert_events(conn, &events, dry_run)?;
+    Ok(())
+}
+
+/// Membership changes between the stored devices and the reported `serial -> device type` set. A home without
+/// stored devices is being synced for the first time, which adds nothing worth an audit entry.
+fn device_membership_changes(
+    known: &[KnownDevice],
+    reported: &BTreeMap<String, Option<String>>,
+) -> Vec<(&'static str, String, Option<String>)> {
+    if known.is_empty() {
+        return Vec::new();
+    }
+    let present: BTreeMap<&str, &KnownDevice> = known.iter().map(|k| (k.serial_no.as_str(), k)).collect();
+    let added = reported
+        .iter()
+        .filter(|(serial_no, _)| present.get(serial_no.as_str()).is_none_or(|k| k.removed))
+        .map(|(serial_no, device_type)| (dbm::event_types::DEVICE_ADDED, serial_no.clone(), device_type.clone()));
+    let removed = known
+        .iter()
+        .filter(|k| !k.removed && !reported.contains_key(&k.serial_no))
+        .map(|k| {
+            (
+                dbm::event_types::DEVICE_REMOVED,
+                k.serial_no.clone(),
+                k.device_type.clone(),
+            )
+        });
+    added.chain(removed).collect()
+}
+
+/// Only devices that measure the inside temperature carry a calibration offset; asking any other device
+/// (bridges, receivers) for one just returns 404.
+fn has_temperature_offset(device: &tado::Device) -> bool {
+    device
+        .characteristics
+        .as_ref()
+        .and_then(|c| c.capabilities.as_deref())
+        .is_some_and(|capabilities| capabilities.iter().any(|c| c == "INSIDE_TEMPERATURE_MEASUREMENT"))
+}
+
+/// Payload of `DEVICE_FIRMWARE_UPDATED`, when the stored version differs from the reported one. A device seen for
+/// the first time has nothing to compare against, and one that stops reporting a version has not been updated.
+fn firmware_version_change(stored: Option<&str>, current: Option<&str>) -> Option<serde_json::Value> {
+    match (stored, current) {
+        (Some(previous), Some(current)) if previous != current => Some(json!({
+            "previous_version": previous,
+            "version": current,
+        })),
+        _ => None,
+    }
+}
+
+/// Payload of `DEVICE_MOUNTING_STATE_CHANGED`, e.g. `CALIBRATION_ERROR` -> `COMPLETED`; as for firmware, only
+/// between two known states.
+fn mounting_state_change(stored: Option<&str>, current: Option<&str>) -> Option<serde_json::Value> {
+    match (stored, current) {
+        (Some(previous), Some(current)) if previous != current => Some(json!({
+            "previous_state": previous,
+            "state": current,
+        })),
+        _ => None,
+    }
+}
+
+/// Payload of `DEVICE_TEMPERATURE_OFFSET_CHANGED`, when both runs know the offset and it differs.
+fn temperature_offset_change(stored: Option<f64>, current: Option<f64>) -> Option<serde_json::Value> {
+    match (stored, current) {
+        (Some(previous), Some(current)) if previous != current => Some(json!({
+            "previous_offset_c": previous,
+            "offset_c": current,
+        })),
+        _ => None,
+    }
+}
+
 fn upsert_zone_devices(
     conn: &mut PgConnection,
     zone_map: &BTreeMap<i64, i64>,
@@ -278,3 +1116,353 @@ fn upsert_zone_devices(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn installation_state_change_is_detected_between_syncs() {
+        let sync = |state: &str, revision: i64| dbm::NewInstallation {
+            home_id: 3,
+            tado_installation_id: 42,
+            installation_type: Some("ACTIVE_THERMOSTAT_WIRED".to_string()),
+            state: Some(state.to_string()),
+            revision: Some(revision),
+        };
+        let stored_after = |row: &dbm::NewInstallation| (row.state.clone(), row.revision);
+
+        // First sync: nothing stored yet
+        let first = sync("INSTALLING", 4);
+        assert!(installation_change(None, &first).is_none());
+        // Unchanged second sync
+        assert!(installation_change(Some(&stored_after(&first)), &sync("INSTALLING", 4)).is_none());
+
+        let payload = installation_change(Some(&stored_after(&first)), &sync("COMPLETED", 5)).expect("state changed");
+        assert_eq!(payload["previous_state"], "INSTALLING");
+        assert_eq!(payload["current_state"], "COMPLETED");
+        assert_eq!(payload["previous_revision"], 4);
+        assert_eq!(payload["current_revision"], 5);
+        assert_eq!(payload["installation_id"], 42);
+    }
+
+    #[test]
+    fn renamed_zone_is_updated_on_sync() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let renamed = dbm::NewZone {
+            home_id: 3,
+            tado_zone_id: 7,
+            name: Some("Living room".to_string()),
+            zone_type: Some("HEATING".to_string()),
+            date_created: None,
+        };
+        let upsert = zone_upsert(&renamed, now);
+        let sql = diesel::debug_query::<Pg, _>(&upsert).to_string();
+
+        assert!(
+            sql.contains(r#"ON CONFLICT ("home_id", "tado_zone_id") DO UPDATE SET "name" = $5"#),
+            "{sql}"
+        );
+        // `$5` binds the new name, so an already stored zone takes it over
+        assert!(
+            sql.contains(r#"binds: [3, 7, "Living room", "HEATING", Some("Living room"),"#),
+            "{sql}"
+        );
+    }
+
+    #[test]
+    fn second_sync_with_new_type_records_change() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let heating = Some("HEATING".to_string());
+        let hot_water = Some("HOT_WATER".to_string());
+
+        // First sync: zone not stored yet
+        assert!(zone_type_change(None, &heating, 7, now).is_none());
+        // Second sync, unchanged
+        assert!(zone_type_change(Some(&heating), &heating, 7, now).is_none());
+        // Second sync, reconfigured
+        let change = zone_type_change(Some(&heating), &hot_water, 7, now).expect("type change");
+        assert_eq!(change.zone_id, 7);
+        assert_eq!(change.previous_type.as_deref(), Some("HEATING"));
+        assert_eq!(change.new_type.as_deref(), Some("HOT_WATER"));
+    }
+
+    #[test]
+    fn away_radius_change_emits_event() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let home = dbm::NewHome {
+            tado_home_id: 1,
+            name: None,
+            timezone: None,
+            temperature_unit: None,
+            address_line1: None,
+            address_line2: None,
+            zip_code: None,
+            city: None,
+            state: None,
+            country: None,
+            latitude: None,
+            longitude: None,
+            away_radius_m: Some(750.0),
+            incident_detection_enabled: Some(true),
+            feature_flags: None,
+            incident_detection_supported: Some(true),
+        };
+
+        // Columns not populated yet: first sync only records the values
+        assert!(home_setting_changes(3, (None, None, None), &home, now).is_empty());
+        assert!(home_setting_changes(3, (Some(750.0), Some(true), None), &home, now).is_empty());
+
+        let events = home_setting_changes(3, (Some(400.0), Some(true), None), &home, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, dbm::event_types::AWAY_RADIUS_CHANGED);
+        assert_eq!(events[0].payload.as_ref().expect("payload")["previous_m"], 400.0);
+        assert_eq!(events[0].payload.as_ref().expect("payload")["current_m"], 750.0);
+
+        let unknown = dbm::NewHome {
+            incident_detection_enabled: None,
+            ..home.clone()
+        };
+        assert!(home_setting_changes(3, (Some(750.0), Some(true), None), &unknown, now).is_empty());
+        let events = home_setting_changes(3, (Some(750.0), Some(false), None), &home, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, dbm::event_types::INCIDENT_DETECTION_CHANGED);
+    }
+
+    #[test]
+    fn characteristics_change_reports_capability_diff() {
+        let first = json!({"capabilities": ["INSIDE_TEMPERATURE_MEASUREMENT", "IDENTIFY"]});
+        let reordered = json!({"capabilities": ["IDENTIFY", "INSIDE_TEMPERATURE_MEASUREMENT"]});
+        let upgraded =
+            json!({"capabilities": ["IDENTIFY", "INSIDE_TEMPERATURE_MEASUREMENT", "RADIO_ENCRYPTION_KEY_ACCESS"]});
+
+        assert!(characteristics_diff(Some(&first), Some(&reordered)).is_none());
+        let with_null = json!({"capabilities": ["IDENTIFY", "INSIDE_TEMPERATURE_MEASUREMENT"], "firmware": null});
+        assert!(characteristics_diff(Some(&first), Some(&with_null)).is_none());
+        assert!(characteristics_diff(None, Some(&serde_json::Value::Null)).is_none());
+
+        let diff = characteristics_diff(Some(&reordered), Some(&upgraded)).expect("capabilities changed");
+        assert_eq!(diff["capabilities_added"], json!(["RADIO_ENCRYPTION_KEY_ACCESS"]));
+        assert_eq!(diff["capabilities_removed"], json!([]));
+    }
+
+    #[test]
+    fn second_sync_with_new_temperature_range_reports_change() {
+        let sync = |min, max, cool: bool| {
+            let capabilities = tado::ZoneCapabilities {
+                temperatures: Some(tado::TemperatureCapability {
+                    celsius: Some(tado::TemperatureRange {
+                        min: Some(min),
+                        max: Some(max),
+                        step: Some(1.0),
+                    }),
+                    fahrenheit: None,
+                }),
+                cool: cool.then(tado::AirConditioningModeCapabilities::default),
+                ..Default::default()
+            };
+            normalize_json(serde_json::to_value(&capabilities).expect("serialize capabilities"))
+        };
+
+        // First sync: nothing stored yet, so nothing to compare against
+        let first = sync(5, 25, false);
+        assert!(capabilities_diff(None, &first).is_none());
+        assert!(capabilities_diff(Some(&first), &sync(5, 25, false)).is_none());
+
+        // Second sync after the AC was reconfigured
+        let diff = capabilities_diff(Some(&first), &sync(16, 30, true)).expect("capabilities changed");
+        assert_eq!(diff["previous_range_c"], json!({"min": 5, "max": 25, "step": 1.0}));
+        assert_eq!(diff["current_range_c"], json!({"min": 16, "max": 30, "step": 1.0}));
+        assert_eq!(diff["modes_added"], json!(["COOL"]));
+        assert_eq!(diff["modes_removed"], json!([]));
+    }
+
+    #[test]
+    fn second_sync_toggling_christmas_mode_emits_flags_event() {
+        let now = Utc.with_ymd_and_hms(2024, 12, 1, 12, 0, 0).unwrap();
+        let sync = |christmas: bool| {
+            let home = tado::Home {
+                christmas_mode_enabled: Some(christmas),
+                show_auto_assist_reminders: Some(true),
+                ..Default::default()
+            };
+            dbm::NewHome {
+                tado_home_id: 1,
+                name: None,
+                timezone: None,
+                temperature_unit: None,
+                address_line1: None,
+                address_line2: None,
+                zip_code: None,
+                city: None,
+                state: None,
+                country: None,
+                latitude: None,
+                longitude: None,
+                away_radius_m: None,
+                incident_detection_enabled: None,
+                feature_flags: Some(home_feature_flags(&home)),
+                incident_detection_supported: None,
+            }
+        };
+
+        // First sync: nothing stored yet
+        let first = sync(false);
+        assert!(home_setting_changes(3, (None, None, None), &first, now).is_empty());
+
+        // Second sync with christmas mode switched on
+        let second = sync(true);
+        let events = home_setting_changes(3, (None, None, first.feature_flags.clone()), &second, now);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, dbm::event_types::HOME_FLAGS_CHANGED);
+        assert_eq!(
+            events[0].payload.as_ref().expect("payload")["changed"],
+            json!({ "christmas_mode_enabled": { "previous": false, "current": true } })
+        );
+
+        // Third sync, unchanged
+        assert!(home_setting_changes(3, (None, None, second.feature_flags.clone()), &second, now).is_empty());
+    }
+
+    #[test]
+    fn temperature_offsets_are_fetched_for_measuring_devices_only() {
+        let devices: Vec<tado::Device> = serde_json::from_str(
+            r#"[
+                {"deviceType": "VA02", "serialNo": "VA1", "characteristics": {"capabilities": ["INSIDE_TEMPERATURE_MEASUREMENT", "IDENTIFY"]}},
+                {"deviceType": "IB01", "serialNo": "IB1", "characteristics": {"capabilities": []}}
+            ]"#,
+        )
+        .expect("parse devices");
+        assert!(has_temperature_offset(&devices[0]));
+        assert!(!has_temperature_offset(&devices[1]));
+
+        assert_eq!(
+            temperature_offset_change(Some(0.0), Some(-1.5)),
+            Some(json!({ "previous_offset_c": 0.0, "offset_c": -1.5 }))
+        );
+        assert_eq!(temperature_offset_change(Some(-1.5), Some(-1.5)), None);
+        // First run after the column appeared, and a failed fetch, are not changes
+        assert_eq!(temperature_offset_change(None, Some(-1.5)), None);
+        assert_eq!(temperature_offset_change(Some(-1.5), None), None);
+    }
+
+    #[test]
+    fn firmware_update_is_reported_only_for_a_changed_known_version() {
+        assert_eq!(firmware_version_change(Some("215.1"), Some("215.1")), None);
+        // First sync of a device, or the first version it ever reports
+        assert_eq!(firmware_version_change(None, Some("215.1")), None);
+        assert_eq!(
+            firmware_version_change(Some("215.1"), Some("216.3")),
+            Some(json!({ "previous_version": "215.1", "version": "216.3" }))
+        );
+        assert_eq!(firmware_version_change(Some("215.1"), None), None);
+    }
+
+    #[test]
+    fn device_membership_changes_are_reported_once() {
+        let known = |serial_no: &str, removed| KnownDevice {
+            id: 0,
+            serial_no: serial_no.to_string(),
+            device_type: Some("VA02".to_string()),
+            removed,
+        };
+        let reported = |serials: &[&str]| -> BTreeMap<String, Option<String>> {
+            serials
+                .iter()
+                .map(|s| (s.to_string(), Some("VA02".to_string())))
+                .collect()
+        };
+
+        // First sync of a home, and an unchanged home
+        assert!(device_membership_changes(&[], &reported(&["VA1", "VA2"])).is_empty());
+        assert!(device_membership_changes(&[known("VA1", false)], &reported(&["VA1"])).is_empty());
+
+        let changes =
+            device_membership_changes(&[known("VA1", false), known("VA2", false)], &reported(&["VA1", "VA3"]));
+        assert_eq!(
+            changes,
+            [
+                ("DEVICE_ADDED", "VA3".to_string(), Some("VA02".to_string())),
+                ("DEVICE_REMOVED", "VA2".to_string(), Some("VA02".to_string())),
+            ]
+        );
+
+        // A device already marked removed is not removed again, and comes back as added
+        assert!(device_membership_changes(&[known("VA1", false), known("VA2", true)], &reported(&["VA1"])).is_empty());
+        assert_eq!(
+            device_membership_changes(&[known("VA1", false), known("VA2", true)], &reported(&["VA1", "VA2"])),
+            [("DEVICE_ADDED", "VA2".to_string(), Some("VA02".to_string()))]
+        );
+    }
+
+    #[test]
+    fn mounting_state_transitions_are_detected_between_known_states() {
+        assert_eq!(
+            mounting_state_change(Some("CALIBRATION_ERROR"), Some("COMPLETED")),
+            Some(json!({ "previous_state": "CALIBRATION_ERROR", "state": "COMPLETED" }))
+        );
+        assert_eq!(
+            mounting_state_change(Some("COMPLETED"), Some("CALIBRATION_ERROR")),
+            Some(json!({ "previous_state": "COMPLETED", "state": "CALIBRATION_ERROR" }))
+        );
+        assert_eq!(mounting_state_change(Some("COMPLETED"), Some("COMPLETED")), None);
+        // First sync after the column appeared, and devices without a mounting state
+        assert_eq!(mounting_state_change(None, Some("COMPLETED")), None);
+        assert_eq!(mounting_state_change(Some("COMPLETED"), None), None);
+
+        let device: tado::Device = serde_json::from_str(
+            r#"{"serialNo": "VA1", "mountingState": {"value": "CALIBRATION_ERROR", "timestamp": "2024-03-01T08:15:00Z"}}"#,
+        )
+        .expect("parse device");
+        let mounting = device.mounting_state.expect("mounting state");
+        assert_eq!(mounting.value.as_deref(), Some("CALIBRATION_ERROR"));
+        assert_eq!(
+            mounting.timestamp,
+            Some(Utc.with_ymd_and_hms(2024, 3, 1, 8, 15, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn flow_temperature_optimization_flattens_into_one_row() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let settings: tado::FlowTemperatureOptimization = serde_json::from_str(
+            r#"{"hasMultipleBoilerControlDevices": false, "maxFlowTemperature": 55,
+                "maxFlowTemperatureConstraints": {"min": 30, "max": 80},
+                "autoAdaptation": {"enabled": true, "maxFlowTemperature": 50},
+                "openThermDeviceSerialNumber": "BR1"}"#,
+        )
+        .expect("parse flow temperature optimization");
+        let row = flow_temperature_optimization_row(&settings, 3, now);
+        assert_eq!(row.home_id, 3);
+        assert_eq!(row.max_flow_temperature, Some(55));
+        assert_eq!(row.auto_adaptation_enabled, Some(true));
+        assert_eq!(row.auto_adaptation_max_flow_temperature, Some(50));
+        assert_eq!(
+            (row.max_flow_temperature_min, row.max_flow_temperature_max),
+            (Some(30), Some(80))
+        );
+        assert_eq!(row.open_therm_device_serial_number.as_deref(), Some("BR1"));
+
+        let empty = flow_temperature_optimization_row(&tado::FlowTemperatureOptimization::default(), 3, now);
+        assert_eq!(empty.auto_adaptation_enabled, None);
+        assert_eq!(empty.max_flow_temperature_min, None);
+    }
+
+    #[test]
+    fn incident_detection_prefers_the_dedicated_endpoint() {
+        let home: tado::Home = serde_json::from_str(r#"{"incidentDetection": {"supported": true, "enabled": false}}"#)
+            .expect("parse home");
+        let fetched = tado::IncidentDetection {
+            enabled: Some(true),
+            supported: None,
+        };
+        assert_eq!(
+            incident_detection_state(&home, Some(&fetched)),
+            (Some(true), Some(true))
+        );
+        // Skipped or failed fetch
+        assert_eq!(incident_detection_state(&home, None), (Some(false), Some(true)));
+        assert_eq!(incident_detection_state(&tado::Home::default(), None), (None, None));
+    }
+}
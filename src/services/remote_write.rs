@@ -0,0 +1,192 @@
+//! Pushes the metrics registry to `PROM_REMOTE_WRITE_URL` with the Prometheus remote-write 1.0 protocol, for
+//! deployments that cannot be scraped.
+//!
+//! The payload is a protobuf `WriteRequest` in a Snappy block. Both encodings are small enough to write by
+//! hand: the message has four fixed types, and a Snappy block made of literals only is valid (just not smaller).
+
+use crate::services::metrics::{self, Sample};
+use chrono::Utc;
+use log::{debug, info, warn};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Added to every series so pushed metrics are attributable like scraped ones.
+const JOB_LABEL: &str = "tado-timescale";
+/// Longest literal written as one Snappy element; any length is legal, this just keeps tags small.
+const MAX_LITERAL: usize = 1 << 16;
+
+struct RemoteWrite {
+    agent: ureq::Agent,
+    url: String,
+}
+
+static TARGET: OnceLock<RemoteWrite> = OnceLock::new();
+
+/// Enables metrics recording and pushes the registry every `interval` on a background thread.
+pub fn start(url: &str, interval: Duration) -> Result<(), String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!(
+            "PROM_REMOTE_WRITE_URL must start with http:// or https://, got '{}'",
+            url
+        ));
+    }
+    let agent = ureq::Agent::config_builder()
+        .timeout_global(Some(REQUEST_TIMEOUT))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let target = RemoteWrite {
+        agent,
+        url: url.to_string(),
+    };
+    if TARGET.set(target).is_err() {
+        return Err("Prometheus remote write already started".to_string());
+    }
+    metrics::enable();
+    info!(
+        "Metrics: pushing to {} every {}s via remote write",
+        url,
+        interval.as_secs()
+    );
+    thread::Builder::new()
+        .name("remote-write".to_string())
+        .spawn(move || {
+            loop {
+                thread::sleep(interval);
+                flush();
+            }
+        })
+        .map_err(|e| format!("spawning remote write thread failed: {}", e))?;
+    Ok(())
+}
+
+/// Pushes the current registry once; a no-op unless `start` ran. Failures are logged and never propagate.
+///
+/// Called on exit as well, so `--once` runs still deliver their counters.
+pub fn flush() {
+    let Some(target) = TARGET.get() else {
+        return;
+    };
+    let samples: Vec<Sample> = metrics::snapshot().into_iter().flat_map(|f| f.samples).collect();
+    let body = snappy_block(&write_request(&samples, Utc::now().timestamp_millis()));
+    let result = target
+        .agent
+        .post(&target.url)
+        .header("Content-Type", "application/x-protobuf")
+        .header("Content-Encoding", "snappy")
+        .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+        .send(&body[..]);
+    match result {
+        Ok(res) if res.status().is_success() => debug!("Metrics: pushed {} series", samples.len()),
+        Ok(res) => warn!("Metrics: remote write rejected with status {}", res.status().as_u16()),
+        Err(e) => warn!("Metrics: remote write failed: {}", e),
+    }
+}
+
+/// Encodes `prometheus.WriteRequest`: one `TimeSeries` per sample, all stamped with `timestamp_ms`.
+fn write_request(samples: &[Sample], timestamp_ms: i64) -> Vec<u8> {
+    let mut out = Vec::new();
+    for sample in samples {
+        let mut labels: Vec<(&str, &str)> = vec![("__name__", sample.name.as_str()), ("job", JOB_LABEL)];
+        labels.extend(sample.labels.iter().map(|(name, value)| (*name, value.as_str())));
+        // Remote write requires labels sorted by name
+        labels.sort_unstable();
+
+        let mut series = Vec::new();
+        for (name, value) in labels {
+            let mut label = Vec::new();
+            put_bytes(&mut label, 1, name.as_bytes());
+            put_bytes(&mut label, 2, value.as_bytes());
+            put_bytes(&mut series, 1, &label);
+        }
+        let mut point = Vec::new();
+        put_key(&mut point, 1, 1);
+        point.extend_from_slice(&sample.value.to_le_bytes());
+        put_key(&mut point, 2, 0);
+        put_varint(&mut point, timestamp_ms as u64);
+        put_bytes(&mut series, 2, &point);
+
+        put_bytes(&mut out, 1, &series);
+    }
+    out
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_key(out: &mut Vec<u8>, field: u64, wire_type: u64) {
+    put_varint(out, (field << 3) | wire_type);
+}
+
+/// Writes a length-delimited field (strings and embedded messages).
+fn put_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_key(out, field, 2);
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+/// Wraps `data` in a Snappy block (the raw format, not the framed one) made of literal elements.
+fn snappy_block(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_LITERAL * 5 + 10);
+    put_varint(&mut out, data.len() as u64);
+    for chunk in data.chunks(MAX_LITERAL) {
+        let n = chunk.len() - 1;
+        if n < 60 {
+            out.push((n as u8) << 2);
+        } else {
+            // Tags 60..=63 say the length-1 follows in 1..=4 little-endian bytes
+            let width = (usize::BITS - n.leading_zeros()).div_ceil(8) as usize;
+            out.push(((59 + width) as u8) << 2);
+            out.extend_from_slice(&n.to_le_bytes()[..width]);
+        }
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_batch_encodes_as_write_request() {
+        let samples = [Sample {
+            name: "tado_inserted_rows_total".to_string(),
+            labels: vec![("kind", "climate".to_string())],
+            value: 3.0,
+        }];
+        let encoded = write_request(&samples, 1_700_000_000_000);
+
+        let label = |name: &str, value: &str| {
+            let mut out = vec![0x0a, (4 + name.len() + value.len()) as u8, 0x0a, name.len() as u8];
+            out.extend_from_slice(name.as_bytes());
+            out.extend([0x12, value.len() as u8]);
+            out.extend_from_slice(value.as_bytes());
+            out
+        };
+        let mut series = Vec::new();
+        series.extend(label("__name__", "tado_inserted_rows_total"));
+        series.extend(label("job", "tado-timescale"));
+        series.extend(label("kind", "climate"));
+        series.extend([0x12, 16, 0x09]);
+        series.extend_from_slice(&3.0f64.to_le_bytes());
+        series.extend([0x10, 0x80, 0xd0, 0x95, 0xff, 0xbc, 0x31]);
+        let mut expected = vec![0x0a, series.len() as u8];
+        expected.extend(series);
+        assert_eq!(encoded, expected);
+
+        let block = snappy_block(&encoded);
+        assert_eq!(block[0] as usize, encoded.len());
+        assert_eq!(block[1], 60 << 2);
+        assert_eq!(block[2] as usize, encoded.len() - 1);
+        assert_eq!(&block[3..], &encoded[..]);
+        assert_eq!(snappy_block(b"abc"), [3, 2 << 2, b'a', b'b', b'c']);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::client::write_atomically;
+use crate::models::tado::{self, HomeId, ZoneId};
+use crate::services::backfill::DayReportSource;
+use chrono::{Duration, NaiveDate, Utc};
+use log::{debug, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Day reports persisted under `BACKFILL_PERSIST_REPORTS_DIR`, one JSON file per `(home, zone, day)`.
+///
+/// Cached days are served from disk; every other day is fetched from `inner` and written out once it is final,
+/// so a later full reprocess only has to fetch the days an earlier sampled run skipped. Cache problems are
+/// logged and fall back to `inner`: the cache saves requests, it is never a source of errors.
+pub struct ReportCache<S> {
+    dir: PathBuf,
+    inner: S,
+}
+
+impl<S: DayReportSource> ReportCache<S> {
+    pub fn new(dir: &Path, inner: S) -> Self {
+        ReportCache {
+            dir: dir.to_path_buf(),
+            inner,
+        }
+    }
+
+    fn path(&self, home_id: HomeId, zone_id: ZoneId, day: NaiveDate) -> PathBuf {
+        self.dir.join(format!(
+            "home{}_zone{}_{}.json",
+            home_id.0,
+            zone_id.0,
+            day.format("%Y-%m-%d")
+        ))
+    }
+
+    fn read(&self, path: &Path) -> Option<tado::DayReport> {
+        let contents = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(report) => Some(report),
+            Err(e) => {
+                warn!(
+                    "Backfill: ignoring unreadable cached day report {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn write(&self, path: &Path, report: &tado::DayReport) {
+        let result = fs::create_dir_all(&self.dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_json::to_string(report).map_err(|e| e.to_string()))
+            .and_then(|json| write_atomically(path, &json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("Backfill: caching day report to {} failed: {}", path.display(), e);
+        }
+    }
+}
+
+impl<S: DayReportSource> DayReportSource for ReportCache<S> {
+    fn day_report(&self, home_id: HomeId, zone_id: ZoneId, day: NaiveDate) -> Result<tado::DayReport, String> {
+        let path = self.path(home_id, zone_id, day);
+        if let Some(report) = self.read(&path) {
+            debug!("Backfill: using cached day report {}", path.display());
+            return Ok(report);
+        }
+        let report = self.inner.day_report(home_id, zone_id, day)?;
+        if report_is_final(day, Utc::now().date_naive()) {
+            self.write(&path, &report);
+        }
+        Ok(report)
+    }
+}
+
+/// Whether a day's report can no longer change. Reports follow the home's time zone, so the UTC day before
+/// today may still be running somewhere; only older days are cached.
+fn report_is_final(day: NaiveDate, today: NaiveDate) -> bool {
+    day < today - Duration::days(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct CountingSource {
+        fetches: AtomicUsize,
+    }
+
+    impl DayReportSource for CountingSource {
+        fn day_report(&self, _: HomeId, _: ZoneId, _: NaiveDate) -> Result<tado::DayReport, String> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            Ok(tado::DayReport {
+                hours_in_day: Some(24),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn cached_report_is_reused_instead_of_fetched() {
+        let dir = std::env::temp_dir().join(format!("tado-timescale-report-cache-{}", std::process::id()));
+        let day = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let cache = ReportCache::new(
+            &dir,
+            CountingSource {
+                fetches: AtomicUsize::new(0),
+            },
+        );
+
+        let first = cache.day_report(HomeId(3), ZoneId(7), day);
+        let second = cache.day_report(HomeId(3), ZoneId(7), day);
+        let other_zone = cache.day_report(HomeId(3), ZoneId(8), day);
+        let cached_file = cache.path(HomeId(3), ZoneId(7), day);
+        let file_name = cached_file.file_name().map(|n| n.to_string_lossy().into_owned());
+        fs::remove_dir_all(&dir).expect("cleanup");
+
+        assert_eq!(first, second);
+        assert!(other_zone.is_ok());
+        assert_eq!(cache.inner.fetches.load(Ordering::Relaxed), 2);
+        assert_eq!(file_name.as_deref(), Some("home3_zone7_2024-03-01.json"));
+
+        let today = NaiveDate::from_ymd_opt(2024, 3, 3).unwrap();
+        assert!(report_is_final(day, today));
+        assert!(!report_is_final(day, day.succ_opt().unwrap()));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::db::models::event_source;
+use crate::schema;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::info;
+use std::num::NonZeroU32;
+
+/// Width of one delete batch. Day-sized slices line up with chunk boundaries and keep each lock short.
+const BATCH_SPAN: ChronoDuration = ChronoDuration::days(1);
+
+/// How long measurements of each source are kept; `None` keeps them forever.
+///
+/// Timescale's drop policies work on whole chunks, which mix both sources, so pruning is a batched DELETE
+/// filtered by `source` instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionPolicy {
+    pub realtime_days: Option<NonZeroU32>,
+    pub historical_days: Option<NonZeroU32>,
+}
+
+/// Rows removed per table for one source.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PrunedRows {
+    pub climate: usize,
+    pub weather: usize,
+    pub zone_weather: usize,
+}
+
+impl RetentionPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.realtime_days.is_some() || self.historical_days.is_some()
+    }
+
+    /// `(source, cutoff)` for every source with a retention window; rows strictly before the cutoff expire.
+    fn cutoffs(&self, now: DateTime<Utc>) -> Vec<(&'static str, DateTime<Utc>)> {
+        [
+            (event_source::REALTIME, self.realtime_days),
+            (event_source::HISTORICAL, self.historical_days),
+        ]
+        .into_iter()
+        .filter_map(|(source, days)| days.map(|d| (source, now - ChronoDuration::days(i64::from(d.get())))))
+        .collect()
+    }
+}
+
+/// Deletes expired climate, weather and per-zone weather rows for each configured source, logging the totals.
+/// Under `DRY_RUN` (`dry_run`) only the cutoffs are logged and nothing is deleted.
+pub fn prune(
+    conn: &mut PgConnection,
+    policy: RetentionPolicy,
+    now: DateTime<Utc>,
+    dry_run: bool,
+) -> Result<(), String> {
+    for (source, cutoff) in policy.cutoffs(now) {
+        if dry_run {
+            info!(
+                "Dry run: would prune measurements with source={} older than {}",
+                source, cutoff
+            );
+            continue;
+        }
+        let pruned = prune_source(conn, source, cutoff)?;
+        info!(
+            "Retention: pruned {} climate, {} weather and {} zone weather row(s) with source={} older than {}",
+            pruned.climate, pruned.weather, pruned.zone_weather, source, cutoff
+        );
+    }
+    Ok(())
+}
+
+fn prune_source(conn: &mut PgConnection, source: &str, cutoff: DateTime<Utc>) -> Result<PrunedRows, String> {
+    use schema::climate_measurements::dsl as C;
+    use schema::weather_measurements::dsl as W;
+    use schema::zone_weather_measurements::dsl as ZW;
+
+    let mut pruned = PrunedRows::default();
+
+    let oldest: Option<DateTime<Utc>> = C::climate_measurements
+        .filter(C::source.eq(source))
+        .select(diesel::dsl::min(C::time))
+        .first(conn)
+        .map_err(|e| format!("fetch oldest {} climate row failed: {}", source, e))?;
+    for (from, to) in batch_windows(oldest, cutoff) {
+        pruned.climate += diesel::delete(
+            C::climate_measurements
+                .filter(C::source.eq(source))
+                .filter(C::time.ge(from))
+                .filter(C::time.lt(to)),
+        )
+        .execute(conn)
+        .map_err(|e| format!("delete expired {} climate rows failed: {}", source, e))?;
+    }
+
+    let oldest: Option<DateTime<Utc>> = W::weather_measurements
+        .filter(W::source.eq(source))
+        .select(diesel::dsl::min(W::time))
+        .first(conn)
+        .map_err(|e| format!("fetch oldest {} weather row failed: {}", source, e))?;
+    for (from, to) in batch_windows(oldest, cutoff) {
+        pruned.weather += diesel::delete(
+            W::weather_measurements
+                .filter(W::source.eq(source))
+                .filter(W::time.ge(from))
+                .filter(W::time.lt(to)),
+        )
+        .execute(conn)
+        .map_err(|e| format!("delete expired {} weather rows failed: {}", source, e))?;
+    }
+
+    let oldest: Option<DateTime<Utc>> = ZW::zone_weather_measurements
+        .filter(ZW::source.eq(source))
+        .select(diesel::dsl::min(ZW::time))
+        .first(conn)
+        .map_err(|e| format!("fetch oldest {} zone weather row failed: {}", source, e))?;
+    for (from, to) in batch_windows(oldest, cutoff) {
+        pruned.zone_weather += diesel::delete(
+            ZW::zone_weather_measurements
+                .filter(ZW::source.eq(source))
+                .filter(ZW::time.ge(from))
+                .filter(ZW::time.lt(to)),
+        )
+        .execute(conn)
+        .map_err(|e| format!("delete expired {} zone weather rows failed: {}", source, e))?;
+    }
+
+    Ok(pruned)
+}
+
+/// Half-open `[from, to)` slices of at most `BATCH_SPAN` covering `oldest..cutoff`; empty when nothing expired.
+fn batch_windows(oldest: Option<DateTime<Utc>>, cutoff: DateTime<Utc>) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut windows = Vec::new();
+    let Some(mut from) = oldest else {
+        return windows;
+    };
+    while from < cutoff {
+        let to = (from + BATCH_SPAN).min(cutoff);
+        windows.push((from, to));
+        from = to;
+    }
+    windows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn expired_rows_are_selected_per_source() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let policy = RetentionPolicy {
+            realtime_days: NonZeroU32::new(30),
+            historical_days: NonZeroU32::new(365),
+        };
+        let days_ago = |d: i64| now - ChronoDuration::days(d) + ChronoDuration::hours(6);
+        let seeded = [
+            (event_source::REALTIME, days_ago(10)),
+            (event_source::REALTIME, days_ago(40)),
+            (event_source::REALTIME, days_ago(42)),
+            (event_source::HISTORICAL, days_ago(40)),
+            (event_source::HISTORICAL, days_ago(400)),
+            (event_source::DERIVED, days_ago(1000)),
+        ];
+
+        // Apply the delete predicate batch by batch, as prune_source does
+        let mut deleted: Vec<&(&str, DateTime<Utc>)> = Vec::new();
+        for (source, cutoff) in policy.cutoffs(now) {
+            let oldest = seeded.iter().filter(|(s, _)| *s == source).map(|(_, t)| *t).min();
+            for (from, to) in batch_windows(oldest, cutoff) {
+                assert!(to - from <= BATCH_SPAN);
+                deleted.extend(seeded.iter().filter(|(s, t)| *s == source && *t >= from && *t < to));
+            }
+        }
+        deleted.sort();
+
+        assert_eq!(
+            deleted,
+            [
+                &(event_source::HISTORICAL, days_ago(400)),
+                &(event_source::REALTIME, days_ago(42)),
+                &(event_source::REALTIME, days_ago(40)),
+            ]
+        );
+        assert!(batch_windows(Some(now), now - ChronoDuration::days(1)).is_empty());
+        assert!(!RetentionPolicy::default().is_enabled());
+    }
+}
@@ -0,0 +1,141 @@
+use crate::db::models::{NewEvent, event_source, event_types};
+use crate::schema;
+use crate::services::ingest::insert_events;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use diesel::PgConnection;
+use diesel::prelude::*;
+use log::{debug, info};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Longest stretch a single sample is allowed to represent. Anything beyond this is treated as a
+/// collection gap rather than as the heating having stayed in the last observed state.
+const MAX_SAMPLE_SPAN_MINUTES: i64 = 15;
+
+/// `(zone_id, time, heating_power_pct, ac_power_on)` as loaded for the rollup.
+type RuntimeRow = (Option<i64>, DateTime<Utc>, Option<f64>, Option<bool>);
+
+/// Writes one `DAILY_HEATING_RUNTIME` event per zone for the UTC day before `today`.
+///
+/// The events themselves are the checkpoint: each one is stamped at the start of the day it covers,
+/// so a day that already has rollup events for this home is never computed twice, even across restarts.
+pub fn roll_up_previous_day(
+    conn: &mut PgConnection,
+    db_home_id: i64,
+    today: NaiveDate,
+    dry_run: bool,
+) -> Result<usize, String> {
+    use schema::climate_measurements::dsl as C;
+    use schema::events::dsl as E;
+
+    let Some(day) = today.pred_opt() else {
+        return Ok(0);
+    };
+    let day_start = day.and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc();
+    let day_end = day_start + ChronoDuration::days(1);
+
+    let last_rolled_up: Option<DateTime<Utc>> = E::events
+        .filter(E::home_id.eq(db_home_id))
+        .filter(E::event_type.eq(event_types::DAILY_HEATING_RUNTIME))
+        .select(diesel::dsl::max(E::time))
+        .first(conn)
+        .map_err(|e| format!("fetch last heating runtime rollup failed: {}", e))?;
+    if last_rolled_up.is_some_and(|t| t >= day_start) {
+        debug!(
+            "Rollup: heating runtime for home {} on {} already recorded",
+            db_home_id, day
+        );
+        return Ok(0);
+    }
+
+    let rows: Vec<RuntimeRow> = C::climate_measurements
+        .filter(C::home_id.eq(db_home_id))
+        .filter(C::zone_id.is_not_null())
+        .filter(C::time.ge(day_start))
+        .filter(C::time.lt(day_end))
+        .filter(C::heating_power_pct.is_not_null().or(C::ac_power_on.is_not_null()))
+        .order(C::time.asc())
+        .select((C::zone_id, C::time, C::heating_power_pct, C::ac_power_on))
+        .load(conn)
+        .map_err(|e| format!("fetch climate rows for heating runtime failed: {}", e))?;
+
+    let mut samples_by_zone: BTreeMap<i64, Vec<(DateTime<Utc>, bool)>> = BTreeMap::new();
+    for (zone_id, time, heating_power_pct, ac_power_on) in rows {
+        let Some(zone_id) = zone_id else { continue };
+        let active = heating_power_pct.is_some_and(|p| p > 0.0) || ac_power_on == Some(true);
+        samples_by_zone.entry(zone_id).or_default().push((time, active));
+    }
+
+    let events: Vec<NewEvent> = samples_by_zone
+        .iter()
+        .map(|(zone_id, samples)| NewEvent {
+            time: day_start,
+            home_id: db_home_id,
+            zone_id: Some(*zone_id),
+            device_id: None,
+            source: Some(event_source::DERIVED.to_string()),
+            event_type: event_types::DAILY_HEATING_RUNTIME.to_string(),
+            payload: Some(json!({
+                "day": day.to_string(),
+                "runtime_minutes": heating_runtime_minutes(samples, day_end),
+                "samples": samples.len(),
+            })),
+        })
+        .collect();
+
+    let inserted = insert_events(conn, &events, dry_run)?;
+    info!(
+        "Rollup: recorded heating runtime for {} zone(s) of home {} on {}",
+        inserted, db_home_id, day
+    );
+    Ok(inserted)
+}
+
+/// Minutes during which the zone was heating (or cooling), given time-ordered `(time, active)` samples.
+///
+/// Each sample holds until the next one (or `day_end` for the last), but never for longer than
+/// `MAX_SAMPLE_SPAN_MINUTES`, so outages do not inflate the runtime.
+fn heating_runtime_minutes(samples: &[(DateTime<Utc>, bool)], day_end: DateTime<Utc>) -> f64 {
+    let max_span = ChronoDuration::minutes(MAX_SAMPLE_SPAN_MINUTES);
+    let mut active_secs = 0i64;
+
+    for (idx, (time, active)) in samples.iter().enumerate() {
+        if !active {
+            continue;
+        }
+        let next = samples.get(idx + 1).map(|(t, _)| *t).unwrap_or(day_end);
+        let span = (next - *time).min(max_span);
+        if span > ChronoDuration::zero() {
+            active_secs += span.num_seconds();
+        }
+    }
+
+    active_secs as f64 / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn seeded_day_runtime_counts_only_active_spans() {
+        let at = |h, m| Utc.with_ymd_and_hms(2024, 1, 10, h, m, 0).unwrap();
+        let day_end = Utc.with_ymd_and_hms(2024, 1, 11, 0, 0, 0).unwrap();
+
+        let mut samples = Vec::new();
+        // 06:00-08:00 heating in 15 minute steps, off until 18:00.
+        for step in 0..8 {
+            samples.push((at(6, 0) + ChronoDuration::minutes(15 * step), true));
+        }
+        samples.push((at(8, 0), false));
+        // 18:00-18:30 heating, then the collector goes quiet for the rest of the day.
+        samples.push((at(18, 0), true));
+        samples.push((at(18, 15), true));
+
+        let minutes = heating_runtime_minutes(&samples, day_end);
+
+        // 120 minutes in the morning, 30 in the evening; the last sample is capped at 15 minutes.
+        assert!((minutes - 150.0).abs() < 1e-9, "got {minutes}");
+    }
+}
@@ -0,0 +1,74 @@
+//! Cooperative shutdown on SIGTERM/SIGINT.
+//!
+//! The signal handler only flips an atomic flag (the one thing that is async-signal-safe); the realtime loop
+//! polls it between units of work, so an in-flight insert batch always completes before the process exits.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// How often an interruptible sleep re-checks the flag.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+extern "C" fn on_signal(_signal: libc::c_int) {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Routes SIGTERM (Docker, systemd) and SIGINT (Ctrl-C) to the shutdown flag instead of killing the process.
+pub fn install_handlers() -> Result<(), String> {
+    for (signal, name) in [(libc::SIGTERM, "SIGTERM"), (libc::SIGINT, "SIGINT")] {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only performs an atomic store.
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            return Err(format!(
+                "installing {} handler failed: {}",
+                name,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for `duration` unless shutdown is requested first; returns whether it was cut short.
+pub fn sleep(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if requested() {
+            return true;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return false;
+        }
+        thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sigterm_interrupts_sleep() {
+        install_handlers().expect("handlers install");
+        assert!(!sleep(Duration::from_millis(10)));
+
+        let raiser = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(100));
+            // SAFETY: raising a signal we installed a handler for.
+            unsafe { libc::raise(libc::SIGTERM) };
+        });
+        let started = Instant::now();
+        assert!(sleep(Duration::from_secs(30)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        raiser.join().expect("raiser thread");
+        assert!(requested());
+    }
+}
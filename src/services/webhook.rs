@@ -0,0 +1,111 @@
+use crate::db::models::NewWeatherMeasurement;
+use log::{debug, warn};
+use serde_json::{Value, json};
+use std::num::NonZeroU32;
+use std::thread;
+use std::time::Duration;
+
+/// Attempts per delivery before the payload is dropped; collection never waits longer than this.
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pushes the latest per-home weather reading to an external HTTP endpoint.
+///
+/// Delivery is best effort: failures are logged and never propagate into the realtime loop.
+pub struct WeatherWebhook {
+    agent: ureq::Agent,
+    url: String,
+    every_ticks: NonZeroU32,
+}
+
+impl WeatherWebhook {
+    pub fn new(url: impl Into<String>, every_ticks: NonZeroU32) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .http_status_as_error(false)
+            .build()
+            .into();
+        WeatherWebhook {
+            agent,
+            url: url.into(),
+            every_ticks,
+        }
+    }
+
+    /// Whether the realtime tick with this zero-based index should post weather.
+    pub fn due(&self, tick: u64) -> bool {
+        tick.is_multiple_of(u64::from(self.every_ticks.get()))
+    }
+
+    /// POSTs the reading as JSON, retrying a bounded number of times. Returns whether it was delivered.
+    pub fn notify(&self, tado_home_id: i64, row: &NewWeatherMeasurement) -> bool {
+        let payload = weather_payload(tado_home_id, row);
+        for attempt in 1..=MAX_ATTEMPTS {
+            match self.agent.post(&self.url).send_json(&payload) {
+                Ok(res) if res.status().is_success() => {
+                    debug!("Webhook: weather for home {} delivered", tado_home_id);
+                    return true;
+                }
+                Ok(res) => warn!(
+                    "Webhook: weather for home {} rejected with status {} (attempt {} of {})",
+                    tado_home_id,
+                    res.status().as_u16(),
+                    attempt,
+                    MAX_ATTEMPTS
+                ),
+                Err(e) => warn!(
+                    "Webhook: weather for home {} failed: {} (attempt {} of {})",
+                    tado_home_id, e, attempt, MAX_ATTEMPTS
+                ),
+            }
+            if attempt < MAX_ATTEMPTS {
+                thread::sleep(RETRY_DELAY);
+            }
+        }
+        false
+    }
+}
+
+fn weather_payload(tado_home_id: i64, row: &NewWeatherMeasurement) -> Value {
+    json!({
+        "home_id": tado_home_id,
+        "time": row.time,
+        "source": row.source,
+        "outside_temp_c": row.outside_temp_c,
+        "solar_intensity_pct": row.solar_intensity_pct,
+        "weather_state": row.weather_state,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::event_source;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn payload_shape_and_failed_delivery_is_non_fatal() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let mut row = NewWeatherMeasurement::new(time, 3, event_source::REALTIME);
+        row.outside_temp_c = Some(7.5);
+        row.weather_state = Some("CLOUDY".to_string());
+
+        assert_eq!(
+            weather_payload(12345, &row),
+            json!({
+                "home_id": 12345,
+                "time": "2024-03-01T12:00:00Z",
+                "source": "realtime",
+                "outside_temp_c": 7.5,
+                "solar_intensity_pct": null,
+                "weather_state": "CLOUDY",
+            })
+        );
+
+        // Nothing listens on the discard port; delivery gives up after its retries instead of erroring out.
+        let webhook = WeatherWebhook::new("http://127.0.0.1:9/weather", NonZeroU32::new(2).unwrap());
+        assert!(!webhook.notify(12345, &row));
+        assert!(webhook.due(0) && !webhook.due(1) && webhook.due(2));
+    }
+}
@@ -4,6 +4,9 @@ use core::fmt;
 use serde::Serialize;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
 /// Errors that can occur while determining a zone's historical start time.
 #[derive(Debug)]
@@ -27,6 +30,19 @@ impl Display for StartTimeError {
 
 impl Error for StartTimeError {}
 
+/// Percent-encodes everything but RFC 3986 unreserved characters.
+pub fn percent_encode(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
 /// Determine the earliest timestamp to begin historical backfill for a zone.
 ///
 /// Policy (per requirements):
@@ -45,8 +61,32 @@ pub fn determine_zone_start_time(zones: &[tado::Zone], zone_id: ZoneId) -> Resul
 }
 
 /// Serialize a serde-backed enum into its string name (e.g. SCREAMING_SNAKE_CASE).
+///
+/// Enums that serialize as integers on the wire come back as their numeric code instead of `None`;
+/// currently that is only `TimetableTypeId` (`0`/`1`/`2` for one/three/seven day timetables).
 pub fn serde_enum_name<T: Serialize>(val: &T) -> Option<String> {
-    serde_json::to_value(val).ok()?.as_str().map(|s| s.to_string())
+    match serde_json::to_value(val).ok()? {
+        serde_json::Value::String(s) => Some(s),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// A temperature in Celsius, converted from `fahrenheit` when Tado sent only that (homes set to Fahrenheit).
+///
+/// A reported `celsius` always wins. Converted values are rounded to two decimals so e.g. 71.6°F is stored as
+/// 22.0 rather than 21.999999999999996.
+pub fn temperature_celsius(temperature: &tado::Temperature) -> Option<f64> {
+    celsius_or_converted(temperature.celsius, temperature.fahrenheit)
+}
+
+/// [`temperature_celsius`] for readings that carry a timestamp alongside the temperature.
+pub fn data_point_celsius(point: &tado::TemperatureDataPoint) -> Option<f64> {
+    celsius_or_converted(point.celsius, point.fahrenheit)
+}
+
+fn celsius_or_converted(celsius: Option<f64>, fahrenheit: Option<f64>) -> Option<f64> {
+    celsius.or_else(|| fahrenheit.map(|f| ((f - 32.0) * 5.0 / 9.0 * 100.0).round() / 100.0))
 }
 
 /// Map Tado device type codes to human-friendly descriptions.
@@ -73,3 +113,123 @@ pub fn describe_device_type(code: &str) -> Option<&'static str> {
         _ => None,
     }
 }
+
+/// Runs `work` over `items` on at most `threads` scoped workers, each with a context from `open` (e.g. its own
+/// connection). Results come back in input order; a worker whose `open` fails reports that error for every item
+/// it picks up.
+pub fn run_bounded<T, C, R>(
+    items: &[T],
+    threads: usize,
+    open: impl Fn() -> Result<C, String> + Sync,
+    work: impl Fn(&mut C, &T) -> Result<R, String> + Sync,
+) -> Vec<Result<R, String>>
+where
+    T: Sync,
+    R: Send,
+{
+    let next = AtomicUsize::new(0);
+    let slots: Vec<Mutex<Option<Result<R, String>>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                let mut ctx = open();
+                loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(idx) else {
+                        break;
+                    };
+                    let result = match ctx.as_mut() {
+                        Ok(ctx) => work(ctx, item),
+                        Err(e) => Err(e.clone()),
+                    };
+                    *slots[idx].lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+                }
+            });
+        }
+    });
+    slots
+        .into_iter()
+        .map(|slot| {
+            slot.into_inner()
+                .unwrap_or_else(|e| e.into_inner())
+                .unwrap_or_else(|| Err("worker exited before processing the item".to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn fahrenheit_only_temperatures_are_converted_to_celsius() {
+        let reading = |celsius, fahrenheit| tado::Temperature { celsius, fahrenheit };
+        assert_eq!(temperature_celsius(&reading(None, Some(71.6))), Some(22.0));
+        assert_eq!(temperature_celsius(&reading(None, Some(68.9))), Some(20.5));
+        assert_eq!(temperature_celsius(&reading(None, Some(-40.0))), Some(-40.0));
+        // Celsius wins even when the two disagree, and nothing comes from nothing
+        assert_eq!(temperature_celsius(&reading(Some(21.0), Some(32.0))), Some(21.0));
+        assert_eq!(temperature_celsius(&reading(None, None)), None);
+
+        let point: tado::TemperatureDataPoint =
+            serde_json::from_str(r#"{"fahrenheit": 50.0, "timestamp": "2024-03-01T12:00:00Z"}"#).expect("parse");
+        assert_eq!(data_point_celsius(&point), Some(10.0));
+    }
+
+    #[test]
+    fn enums_stored_as_text_have_a_name() {
+        let names = [
+            serde_enum_name(&tado::AirConditioningMode::Cool),
+            serde_enum_name(&tado::BatteryState::Low),
+            serde_enum_name(&tado::HomePresence::Away),
+            serde_enum_name(&tado::Orientation::Vertical),
+            serde_enum_name(&tado::TemperatureUnit::Celsius),
+            serde_enum_name(&tado::WeatherState::CloudyPartly),
+            serde_enum_name(&tado::ZoneOverlayTerminationType::Timer),
+            serde_enum_name(&tado::ZoneOverlayTerminationTypeSkillBasedApp::NextTimeBlock),
+            serde_enum_name(&tado::ZoneType::HotWater),
+            serde_enum_name(&tado::TimetableTypeType::SevenDay),
+        ];
+        assert!(names.iter().all(Option::is_some), "{names:?}");
+
+        assert_eq!(serde_enum_name(&tado::ZoneType::HotWater).as_deref(), Some("HOT_WATER"));
+        assert_eq!(serde_enum_name(&tado::TimetableTypeId::OneDay).as_deref(), Some("0"));
+        assert_eq!(serde_enum_name(&tado::TimetableTypeId::SevenDay).as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn run_bounded_runs_items_concurrently_on_their_own_contexts() {
+        use std::sync::Barrier;
+
+        let homes = [101_i64, 202];
+        let opened = AtomicUsize::new(0);
+        // Each item waits here until the other arrives, so a sequential run never gets past it
+        let both_in_flight = Barrier::new(homes.len());
+        // Stands in for the reference tables: home -> stored zone rows
+        let stored: Mutex<BTreeMap<i64, Vec<String>>> = Mutex::new(BTreeMap::new());
+
+        let results = run_bounded(
+            &homes,
+            4,
+            || Ok(opened.fetch_add(1, Ordering::SeqCst)),
+            |_conn, home_id| {
+                both_in_flight.wait();
+                let zones = vec![format!("{home_id}/1"), format!("{home_id}/2")];
+                stored.lock().unwrap().insert(*home_id, zones);
+                Ok(*home_id)
+            },
+        );
+
+        assert_eq!(results, vec![Ok(101), Ok(202)]);
+        // One context per worker, never more workers than homes
+        assert_eq!(opened.load(Ordering::SeqCst), 2);
+        let stored = stored.into_inner().unwrap();
+        assert_eq!(stored[&101], ["101/1", "101/2"]);
+        assert_eq!(stored[&202], ["202/1", "202/2"]);
+
+        let failed: Vec<Result<(), String>> =
+            run_bounded(&homes, 2, || Err::<(), _>("no db".to_string()), |_, _| Ok(()));
+        assert_eq!(failed, vec![Err("no db".to_string()), Err("no db".to_string())]);
+    }
+}